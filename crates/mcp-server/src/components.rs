@@ -118,14 +118,32 @@ pub(crate) async fn handle_component_call(
         .await;
 
     let result = lifecycle_manager
-        .execute_component_call(&component_id, &method_name, &serde_json::to_string(&args)?)
+        .execute_component_call_with_output(
+            &component_id,
+            &method_name,
+            &serde_json::to_string(&args)?,
+        )
         .await;
 
+    // Refresh the values to hide in case the secrets changed since the component
+    // was loaded; a failure here means the call couldn't have seen them either
+    let redactor = lifecycle_manager.secret_redactor();
+    if redactor.is_enabled(&component_id) {
+        let _ = lifecycle_manager
+            .load_component_secrets(&component_id)
+            .await;
+    }
+    let redact = |text: String| match redactor.redact(&component_id, &text) {
+        Cow::Borrowed(_) => text,
+        Cow::Owned(redacted) => redacted,
+    };
+
     match result {
-        Ok(result_str) => {
+        Ok(output) => {
             debug!("Component call successful");
 
-            let parsed_value = parse_structured_result(&result_str);
+            let mut parsed_value = parse_structured_result(&output.result);
+            redactor.redact_value(&component_id, &mut parsed_value);
             let display_value = unwrap_result_wrapper(&parsed_value);
             let response_text = value_to_text(&display_value)?;
 
@@ -138,7 +156,14 @@ pub(crate) async fn handle_component_call(
                 align_structured_result_with_schema(Some(schema), parsed_value.clone())
             });
 
-            let contents = vec![Content::text(response_text)];
+            let mut contents = vec![Content::text(response_text)];
+            if let Some(stdout) = output.stdout {
+                contents.push(Content::text(format!("stdout:\n{}", redact(stdout))));
+            }
+            if let Some(stderr) = output.stderr {
+                contents.push(Content::text(format!("stderr:\n{}", redact(stderr))));
+            }
+            contents.extend(output.notices.into_iter().map(Content::text));
 
             Ok(CallToolResult {
                 content: Some(contents),
@@ -147,8 +172,9 @@ pub(crate) async fn handle_component_call(
             })
         }
         Err(e) => {
-            error!(error = %e, "Component call failed");
-            Err(anyhow::anyhow!(e.to_string()))
+            let message = redact(e.to_string());
+            error!(error = %message, "Component call failed");
+            Err(anyhow::anyhow!(message))
         }
     }
 }
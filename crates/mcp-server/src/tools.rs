@@ -55,6 +55,10 @@ fn is_builtin_tool(name: &str) -> bool {
             | "revoke-storage-permission"
             | "revoke-network-permission"
             | "revoke-environment-variable-permission"
+            | "deny-storage-permission"
+            | "deny-network-permission"
+            | "revoke-deny-storage-permission"
+            | "revoke-deny-network-permission"
             | "search-components"
             | "reset-permission"
     )
@@ -106,6 +110,18 @@ pub async fn handle_tools_call(
             "revoke-environment-variable-permission" if !disable_builtin_tools => {
                 handle_revoke_environment_variable_permission(&req, lifecycle_manager).await
             }
+            "deny-storage-permission" if !disable_builtin_tools => {
+                handle_deny_storage_permission(&req, lifecycle_manager).await
+            }
+            "deny-network-permission" if !disable_builtin_tools => {
+                handle_deny_network_permission(&req, lifecycle_manager).await
+            }
+            "revoke-deny-storage-permission" if !disable_builtin_tools => {
+                handle_revoke_deny_storage_permission(&req, lifecycle_manager).await
+            }
+            "revoke-deny-network-permission" if !disable_builtin_tools => {
+                handle_revoke_deny_network_permission(&req, lifecycle_manager).await
+            }
             "search-components" if !disable_builtin_tools => {
                 handle_search_component(&req, lifecycle_manager).await
             }
@@ -230,7 +246,7 @@ fn get_builtin_tools() -> Vec<Tool> {
                         "properties": {
                           "uri": { 
                             "type": "string",
-                            "description": "URI of the storage resource to grant access to. e.g. fs:///tmp/test"
+                            "description": "URI of the storage resource to grant access to. e.g. fs:///tmp/test, or mem://name for an in-memory directory"
                           },
                           "access": {
                             "type": "array",
@@ -239,6 +255,41 @@ fn get_builtin_tools() -> Vec<Tool> {
                               "enum": ["read", "write"]
                             },
                             "description": "Access type for the storage resource, this must be an array of strings with values 'read' or 'write'"
+                          },
+                          "mount": {
+                            "type": "string",
+                            "description": "Path the component sees the directory at, e.g. /notes. Defaults to the path in the URI"
+                          },
+                          "quota": {
+                            "type": "object",
+                            "properties": {
+                              "bytes": {
+                                "type": "string",
+                                "description": "Total size the component may store below the directory, e.g. 100Mi"
+                              },
+                              "files": {
+                                "type": "integer",
+                                "description": "Number of files and directories the component may store below the directory"
+                              }
+                            },
+                            "additionalProperties": false,
+                            "description": "Disk quota for a writable grant"
+                          },
+                          "seed": {
+                            "type": "object",
+                            "properties": {
+                              "tarball": {
+                                "type": "string",
+                                "description": "Path of a .tar or .tar.gz archive to unpack into the directory"
+                              },
+                              "files": {
+                                "type": "object",
+                                "additionalProperties": { "type": "string" },
+                                "description": "File contents by path inside the directory"
+                              }
+                            },
+                            "additionalProperties": false,
+                            "description": "Initial contents of a mem:// directory"
                           }
                         },
                         "required": ["uri", "access"],
@@ -255,7 +306,7 @@ fn get_builtin_tools() -> Vec<Tool> {
         Tool {
             name: Cow::Borrowed("grant-network-permission"),
             description: Some(Cow::Borrowed(
-                "Grants network access permission to a component, allowing it to make network requests to specific hosts."
+                "Grants network access permission to a component, allowing it to make network requests to specific hosts or IP address ranges."
             )),
             input_schema: Arc::new(
                 serde_json::from_value(json!({
@@ -270,10 +321,32 @@ fn get_builtin_tools() -> Vec<Tool> {
                         "properties": {
                           "host": { 
                             "type": "string",
-                            "description": "Host to grant network access to"
+                            "description": "Host to grant network access to, optionally with a method, port and path (e.g. GET https://api.github.com/repos/our-org/**)"
+                          },
+                          "methods": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "HTTP methods the host may be called with, all methods if omitted"
+                          },
+                          "ports": {
+                            "type": "array",
+                            "items": { "type": "integer", "minimum": 1, "maximum": 65535 },
+                            "description": "Ports the host may be reached on, all ports if omitted"
+                          },
+                          "paths": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "URL path prefixes or globs (e.g. /repos/our-org/**), all paths if omitted"
+                          },
+                          "cidr": {
+                            "type": "string",
+                            "description": "IP address range to grant access to instead of a host (e.g. 10.0.0.0/8 or 127.0.0.1/32); the only way to reach private and loopback addresses"
                           }
                         },
-                        "required": ["host"],
+                        "oneOf": [
+                          { "required": ["host"] },
+                          { "required": ["cidr"] }
+                        ],
                         "additionalProperties": false
                       }
                     },
@@ -351,7 +424,7 @@ fn get_builtin_tools() -> Vec<Tool> {
         Tool {
             name: Cow::Borrowed("revoke-network-permission"),
             description: Some(Cow::Borrowed(
-                "Revokes network access permission from a component, removing its ability to make network requests to specific hosts."
+                "Revokes network access permission from a component, removing its ability to make network requests to specific hosts or IP address ranges."
             )),
             input_schema: Arc::new(
                 serde_json::from_value(json!({
@@ -366,10 +439,32 @@ fn get_builtin_tools() -> Vec<Tool> {
                         "properties": {
                           "host": { 
                             "type": "string",
-                            "description": "Host to revoke network access from"
+                            "description": "Host to revoke network access from; without methods, ports or paths every grant for the host is revoked"
+                          },
+                          "methods": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "HTTP methods the host may be called with, all methods if omitted"
+                          },
+                          "ports": {
+                            "type": "array",
+                            "items": { "type": "integer", "minimum": 1, "maximum": 65535 },
+                            "description": "Ports the host may be reached on, all ports if omitted"
+                          },
+                          "paths": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "URL path prefixes or globs (e.g. /repos/our-org/**), all paths if omitted"
+                          },
+                          "cidr": {
+                            "type": "string",
+                            "description": "IP address range to revoke access from instead of a host (e.g. 10.0.0.0/8)"
                           }
                         },
-                        "required": ["host"],
+                        "oneOf": [
+                          { "required": ["host"] },
+                          { "required": ["cidr"] }
+                        ],
                         "additionalProperties": false
                       }
                     },
@@ -412,6 +507,186 @@ fn get_builtin_tools() -> Vec<Tool> {
             output_schema: None,
             annotations: None,
         },
+        Tool {
+            name: Cow::Borrowed("deny-storage-permission"),
+            description: Some(Cow::Borrowed(
+                "Denies a component storage access below a path, even inside a directory it has been granted. Deny rules take precedence over grants."
+            )),
+            input_schema: Arc::new(
+                serde_json::from_value(json!({
+                    "type": "object",
+                    "properties": {
+                      "component_id": {
+                        "type": "string",
+                        "description": "ID of the component to deny storage access for"
+                      },
+                      "details": {
+                        "type": "object",
+                        "properties": {
+                          "uri": {
+                            "type": "string",
+                            "description": "URI of the storage resource to deny access to, optionally ending in /* or /**. e.g. fs:///work/secrets"
+                          },
+                          "access": {
+                            "type": "array",
+                            "items": {
+                              "type": "string",
+                              "enum": ["read", "write"]
+                            },
+                            "description": "Access types to deny, both read and write if omitted"
+                          }
+                        },
+                        "required": ["uri"],
+                        "additionalProperties": false
+                      }
+                    },
+                    "required": ["component_id", "details"]
+                  }))
+                .unwrap_or_default(),
+            ),
+            output_schema: None,
+            annotations: None,
+        },
+        Tool {
+            name: Cow::Borrowed("deny-network-permission"),
+            description: Some(Cow::Borrowed(
+                "Denies a component network access to a host or IP address range, even if a granted host pattern matches it. Deny rules take precedence over grants."
+            )),
+            input_schema: Arc::new(
+                serde_json::from_value(json!({
+                    "type": "object",
+                    "properties": {
+                      "component_id": {
+                        "type": "string",
+                        "description": "ID of the component to deny network access for"
+                      },
+                      "details": {
+                        "type": "object",
+                        "properties": {
+                          "host": {
+                            "type": "string",
+                            "description": "Host or host pattern to deny access to (e.g. metadata.internal or *.internal); * denies every host"
+                          },
+                          "methods": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "HTTP methods the rule applies to, all methods if omitted"
+                          },
+                          "ports": {
+                            "type": "array",
+                            "items": { "type": "integer", "minimum": 1, "maximum": 65535 },
+                            "description": "Ports the rule applies to, all ports if omitted"
+                          },
+                          "paths": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "URL path prefixes or globs the rule applies to, all paths if omitted"
+                          },
+                          "cidr": {
+                            "type": "string",
+                            "description": "IP address range to deny access to instead of a host (e.g. 169.254.169.254/32)"
+                          }
+                        },
+                        "oneOf": [
+                          { "required": ["host"] },
+                          { "required": ["cidr"] }
+                        ],
+                        "additionalProperties": false
+                      }
+                    },
+                    "required": ["component_id", "details"]
+                  }))
+                .unwrap_or_default(),
+            ),
+            output_schema: None,
+            annotations: None,
+        },
+        Tool {
+            name: Cow::Borrowed("revoke-deny-storage-permission"),
+            description: Some(Cow::Borrowed(
+                "Removes a storage deny rule from a component, by the URI it was added for."
+            )),
+            input_schema: Arc::new(
+                serde_json::from_value(json!({
+                    "type": "object",
+                    "properties": {
+                      "component_id": {
+                        "type": "string",
+                        "description": "ID of the component to remove the deny rule from"
+                      },
+                      "details": {
+                        "type": "object",
+                        "properties": {
+                          "uri": {
+                            "type": "string",
+                            "description": "URI the deny rule was added for. e.g. fs:///work/secrets"
+                          }
+                        },
+                        "required": ["uri"],
+                        "additionalProperties": false
+                      }
+                    },
+                    "required": ["component_id", "details"]
+                  }))
+                .unwrap_or_default(),
+            ),
+            output_schema: None,
+            annotations: None,
+        },
+        Tool {
+            name: Cow::Borrowed("revoke-deny-network-permission"),
+            description: Some(Cow::Borrowed(
+                "Removes a network deny rule from a component. The details must match the rule as it was added."
+            )),
+            input_schema: Arc::new(
+                serde_json::from_value(json!({
+                    "type": "object",
+                    "properties": {
+                      "component_id": {
+                        "type": "string",
+                        "description": "ID of the component to remove the deny rule from"
+                      },
+                      "details": {
+                        "type": "object",
+                        "properties": {
+                          "host": {
+                            "type": "string",
+                            "description": "Host or host pattern the deny rule was added for"
+                          },
+                          "methods": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "HTTP methods the rule applies to, all methods if omitted"
+                          },
+                          "ports": {
+                            "type": "array",
+                            "items": { "type": "integer", "minimum": 1, "maximum": 65535 },
+                            "description": "Ports the rule applies to, all ports if omitted"
+                          },
+                          "paths": {
+                            "type": "array",
+                            "items": { "type": "string" },
+                            "description": "URL path prefixes or globs the rule applies to, all paths if omitted"
+                          },
+                          "cidr": {
+                            "type": "string",
+                            "description": "IP address range the deny rule was added for"
+                          }
+                        },
+                        "oneOf": [
+                          { "required": ["host"] },
+                          { "required": ["cidr"] }
+                        ],
+                        "additionalProperties": false
+                      }
+                    },
+                    "required": ["component_id", "details"]
+                  }))
+                .unwrap_or_default(),
+            ),
+            output_schema: None,
+            annotations: None,
+        },
         Tool {
             name: Cow::Borrowed("reset-permission"),
             description: Some(Cow::Borrowed(
@@ -515,6 +790,7 @@ pub async fn handle_get_policy(
     let policy_info = lifecycle_manager.get_policy_info(component_id).await;
 
     let status_text = if let Some(info) = policy_info {
+        let storage_usage = lifecycle_manager.storage_usage(component_id).await?;
         serde_json::to_string(&json!({
             "status": "policy found",
             "component_id": component_id,
@@ -524,7 +800,8 @@ pub async fn handle_get_policy(
                 "local_path": info.local_path,
                 "created_at": info.created_at.duration_since(std::time::UNIX_EPOCH)
                     .unwrap_or_default().as_secs()
-            }
+            },
+            "storage_usage": storage_usage
         }))?
     } else {
         serde_json::to_string(&json!({
@@ -945,6 +1222,128 @@ pub async fn handle_revoke_environment_variable_permission(
     }
 }
 
+#[instrument(skip(lifecycle_manager))]
+pub async fn handle_deny_storage_permission(
+    req: &CallToolRequestParam,
+    lifecycle_manager: &LifecycleManager,
+) -> Result<CallToolResult> {
+    handle_deny_rule(req, lifecycle_manager, "storage", DenyRuleChange::Add).await
+}
+
+#[instrument(skip(lifecycle_manager))]
+pub async fn handle_deny_network_permission(
+    req: &CallToolRequestParam,
+    lifecycle_manager: &LifecycleManager,
+) -> Result<CallToolResult> {
+    handle_deny_rule(req, lifecycle_manager, "network", DenyRuleChange::Add).await
+}
+
+#[instrument(skip(lifecycle_manager))]
+pub async fn handle_revoke_deny_storage_permission(
+    req: &CallToolRequestParam,
+    lifecycle_manager: &LifecycleManager,
+) -> Result<CallToolResult> {
+    handle_deny_rule(req, lifecycle_manager, "storage", DenyRuleChange::Remove).await
+}
+
+#[instrument(skip(lifecycle_manager))]
+pub async fn handle_revoke_deny_network_permission(
+    req: &CallToolRequestParam,
+    lifecycle_manager: &LifecycleManager,
+) -> Result<CallToolResult> {
+    handle_deny_rule(req, lifecycle_manager, "network", DenyRuleChange::Remove).await
+}
+
+/// Whether a deny tool adds or removes a rule
+#[derive(Debug, Clone, Copy)]
+enum DenyRuleChange {
+    Add,
+    Remove,
+}
+
+/// Shared body of the deny and revoke-deny tools, which only differ in the
+/// permission type and the policy manager call
+async fn handle_deny_rule(
+    req: &CallToolRequestParam,
+    lifecycle_manager: &LifecycleManager,
+    permission_type: &str,
+    change: DenyRuleChange,
+) -> Result<CallToolResult> {
+    let args = extract_args_from_request(req)?;
+
+    let component_id = args
+        .get("component_id")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| anyhow::anyhow!("Missing required argument: 'component_id'"))?;
+
+    let details = args
+        .get("details")
+        .ok_or_else(|| anyhow::anyhow!("Missing required argument: 'details'"))?;
+
+    lifecycle_manager
+        .ensure_component_loaded(component_id)
+        .await
+        .map_err(|e| anyhow::anyhow!("Component not found: {} ({})", component_id, e))?;
+
+    let (result, status, action) = match change {
+        DenyRuleChange::Add => {
+            info!(
+                "Adding {} deny rule to component {}",
+                permission_type, component_id
+            );
+            (
+                lifecycle_manager
+                    .deny_permission(component_id, permission_type, details)
+                    .await,
+                "deny rule added",
+                "add",
+            )
+        }
+        DenyRuleChange::Remove => {
+            info!(
+                "Removing {} deny rule from component {}",
+                permission_type, component_id
+            );
+            (
+                lifecycle_manager
+                    .revoke_deny_permission(component_id, permission_type, details)
+                    .await,
+                "deny rule removed",
+                "remove",
+            )
+        }
+    };
+
+    match result {
+        Ok(()) => {
+            let status_text = serde_json::to_string(&json!({
+                "status": status,
+                "component_id": component_id,
+                "permission_type": permission_type,
+                "details": details
+            }))?;
+
+            let contents = vec![Content::text(status_text)];
+
+            Ok(CallToolResult {
+                content: Some(contents),
+                structured_content: None,
+                is_error: None,
+            })
+        }
+        Err(e) => {
+            error!("Failed to {} {} deny rule: {}", action, permission_type, e);
+            Err(anyhow::anyhow!(
+                "Failed to {} {} deny rule for component {}: {}",
+                action,
+                permission_type,
+                component_id,
+                e
+            ))
+        }
+    }
+}
+
 #[instrument(skip(lifecycle_manager))]
 pub async fn handle_reset_permission(
     req: &CallToolRequestParam,
@@ -999,7 +1398,7 @@ mod tests {
     #[test]
     fn test_get_builtin_tools() {
         let tools = get_builtin_tools();
-        assert_eq!(tools.len(), 12);
+        assert_eq!(tools.len(), 16);
         assert!(tools.iter().any(|t| t.name == "load-component"));
         assert!(tools.iter().any(|t| t.name == "unload-component"));
         assert!(tools.iter().any(|t| t.name == "list-components"));
@@ -1014,10 +1413,35 @@ mod tests {
         assert!(tools
             .iter()
             .any(|t| t.name == "revoke-environment-variable-permission"));
+        assert!(tools.iter().any(|t| t.name == "deny-storage-permission"));
+        assert!(tools.iter().any(|t| t.name == "deny-network-permission"));
+        assert!(tools
+            .iter()
+            .any(|t| t.name == "revoke-deny-storage-permission"));
+        assert!(tools
+            .iter()
+            .any(|t| t.name == "revoke-deny-network-permission"));
         assert!(tools.iter().any(|t| t.name == "reset-permission"));
         assert!(tools.iter().any(|t| t.name == "search-components"));
     }
 
+    #[test]
+    fn test_grant_network_permission_schema_accepts_cidr() {
+        let tools = get_builtin_tools();
+        let tool = tools
+            .iter()
+            .find(|t| t.name == "grant-network-permission")
+            .unwrap();
+        let details = &tool.input_schema["properties"]["details"];
+
+        assert!(details["properties"]["cidr"].is_object());
+        assert!(details.get("required").is_none());
+        assert_eq!(
+            details["oneOf"],
+            json!([{ "required": ["host"] }, { "required": ["cidr"] }])
+        );
+    }
+
     #[tokio::test]
     async fn test_grant_network_permission_integration() -> Result<()> {
         // Create a test lifecycle manager
@@ -1324,4 +1748,49 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_deny_permission_integration() -> Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let lifecycle_manager = wassette::LifecycleManager::new(&tempdir).await?;
+
+        let mut args = serde_json::Map::new();
+        args.insert("component_id".to_string(), json!("test-component"));
+        args.insert("details".to_string(), json!({"host": "metadata.internal"}));
+        let req = CallToolRequestParam {
+            name: "deny-network-permission".into(),
+            arguments: Some(args),
+        };
+
+        // Deny rules go through the same component lookup as grants
+        let result = handle_deny_network_permission(&req, &lifecycle_manager).await;
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Component not found"));
+        let result = handle_revoke_deny_network_permission(&req, &lifecycle_manager).await;
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Component not found"));
+
+        let mut args = serde_json::Map::new();
+        args.insert("component_id".to_string(), json!("test-component"));
+        let req = CallToolRequestParam {
+            name: "deny-storage-permission".into(),
+            arguments: Some(args),
+        };
+        let result = handle_deny_storage_permission(&req, &lifecycle_manager).await;
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Missing required argument: 'details'"));
+        let result = handle_revoke_deny_storage_permission(&req, &lifecycle_manager).await;
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Missing required argument: 'details'"));
+
+        Ok(())
+    }
 }
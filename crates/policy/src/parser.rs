@@ -176,6 +176,9 @@ invalid: yaml: content
                 allow: Some(vec![StoragePermission {
                     uri: "fs://work/agent/**".to_string(),
                     access: vec![AccessType::Read, AccessType::Write],
+                    mount: None,
+                    quota: None,
+                    seed: None,
                 }]),
                 deny: None,
             }),
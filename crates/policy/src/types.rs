@@ -3,9 +3,10 @@
 
 //! Type definitions
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fmt::Display;
 use std::sync::OnceLock;
+use std::time::Duration;
 
 use anyhow::bail;
 use serde::{Deserialize, Serialize};
@@ -23,21 +24,83 @@ pub enum AccessType {
 
 /// uri: URI pattern for the resource (e.g. fs://work/agent/**)
 /// access: Access types allowed (read, write)
+/// mount: Path the component sees the directory at (e.g. /notes)
+///
+/// Relative `fs://` paths are below the plugin directory, while absolute ones
+/// (`fs:///srv/data`) and home paths (`fs://~/notes`) are used as written.
+///
+/// A `mem://name` URI mounts a virtual directory instead of a host path. It
+/// starts out with the contents of `seed` and every call gets a fresh copy, so
+/// writes are thrown away when the call ends.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct StoragePermission {
     /// URI pattern for the resource
     pub uri: String,
     /// Access types allowed
     pub access: Vec<AccessType>,
+    /// Guest path the directory is mounted at, defaults to the path in the URI
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mount: Option<String>,
+    /// Limit on what the component may store below the granted directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quota: Option<StorageQuota>,
+    /// Initial contents of a `mem://` directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<MemorySeed>,
+}
+
+/// Initial contents of an in-memory directory
+///
+/// tarball: Host path of a `.tar` or `.tar.gz` archive unpacked into the directory
+/// files: Inline files by path inside the directory, written after the archive
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+pub struct MemorySeed {
+    /// Archive to unpack, resolved like an `fs://` path
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tarball: Option<String>,
+    /// File contents by relative path
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub files: Option<BTreeMap<String, String>>,
+}
+
+/// Disk quota for writable storage
+///
+/// bytes: Total size of the stored files ("100Mi", "1Gi" or a number of bytes)
+/// files: Number of files and directories
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+pub struct StorageQuota {
+    /// Total size of the stored files
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<SizeLimit>,
+    /// Number of files and directories
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub files: Option<u64>,
 }
 
 /// Network host permission
 ///
 /// host: Hostname or pattern (supports wildcards like *.domain.com)
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+///
+/// `*.domain.com` matches exactly one leading label (`api.domain.com`), while
+/// `**.domain.com` matches one or more (`eu.api.domain.com`). Neither matches
+/// `domain.com` itself.
+///
+/// The host may also carry an HTTP method, a port and a path prefix or glob,
+/// e.g. `GET https://api.github.com:443/repos/our-org/**`. The same scopes can be
+/// given as lists in `methods`, `ports` and `paths`, but not in both places.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
 pub struct NetworkHostPermission {
     /// Hostname or pattern (supports wildcards like *.domain.com)
     pub host: String,
+    /// HTTP methods the host may be called with, any method when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub methods: Option<Vec<String>>,
+    /// Ports the host may be reached on, any port when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ports: Option<Vec<u16>>,
+    /// URL path prefixes or globs (e.g. /repos/our-org/**), any path when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub paths: Option<Vec<String>>,
 }
 
 /// Network CIDR permission
@@ -49,12 +112,53 @@ pub struct NetworkCidrPermission {
     pub cidr: String,
 }
 
-/// Network permission entry - can be either host or CIDR
+/// Raw socket permission
+///
+/// socket: Protocol, destination and port for `wasi:sockets` (e.g. tcp://db.internal:5432)
+///
+/// The destination may be a host name, an IP address or a CIDR range, and the
+/// port may be `*` to allow any port. Host and CIDR entries only cover HTTP
+/// requests made through `wasi:http`, raw sockets need an entry like this.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NetworkSocketPermission {
+    /// `tcp://host:port` or `udp://host:port`
+    pub socket: String,
+}
+
+/// Credential injection entry
+///
+/// inject: Header to add host-side to outbound requests, filled from a stored secret
+///
+/// The secret is read when the request is sent and never handed to the component,
+/// so it does not show up in the component's environment or configuration.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NetworkInjectPermission {
+    pub inject: CredentialInjection,
+}
+
+/// A header carrying a secret, e.g. `Authorization: Bearer {}` for `api.example.com`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CredentialInjection {
+    /// Host rule the header is added for, same syntax as a host entry
+    pub host: String,
+    /// Header name (e.g. Authorization)
+    pub header: String,
+    /// Name of the component secret holding the value
+    pub secret: String,
+    /// Header value with `{}` standing for the secret, the bare secret when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+}
+
+/// Network permission entry - can be a host, a CIDR range, a raw socket destination
+/// or a credential injection
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum NetworkPermission {
     Host(NetworkHostPermission),
     Cidr(NetworkCidrPermission),
+    Socket(NetworkSocketPermission),
+    Inject(NetworkInjectPermission),
 }
 
 /// Environment variable permission
@@ -140,6 +244,82 @@ pub enum MemoryLimit {
     Number(u64),
 }
 
+/// Wall-clock timeout for a single component call
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum TimeoutLimit {
+    /// String format supporting ms, s, m suffixes ("500ms", "30s", "2m")
+    String(String),
+    /// Numeric format in seconds
+    Number(u64),
+}
+
+/// Size of a message body
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SizeLimit {
+    /// String format supporting Ki, Mi, Gi suffixes ("512Ki", "10Mi")
+    String(String),
+    /// Numeric format in bytes
+    Number(u64),
+}
+
+/// Limits on a component's outbound HTTP traffic
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+pub struct NetworkLimits {
+    /// Time allowed to connect, including DNS and the TLS handshake
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connect_timeout: Option<TimeoutLimit>,
+    /// Time allowed between sending a request and receiving the response headers
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub first_byte_timeout: Option<TimeoutLimit>,
+    /// Time allowed between two chunks of a response body
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub between_bytes_timeout: Option<TimeoutLimit>,
+    /// Largest request body the component may send
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_request_body: Option<SizeLimit>,
+    /// Largest response body the component may receive
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_response_body: Option<SizeLimit>,
+    /// Number of outbound requests allowed in a single tool call
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_requests: Option<u32>,
+    /// Token-bucket limits shared by all of the component's calls
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate_limits: Option<Vec<RateLimit>>,
+}
+
+/// What happens to a request that goes over a rate limit
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RateLimitAction {
+    /// Hold the request until the bucket has a token again
+    #[default]
+    Delay,
+    /// Fail the request right away
+    Reject,
+}
+
+/// A token-bucket limit on outbound requests
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RateLimit {
+    /// Host the limit applies to, using the host entry syntax. Without one the
+    /// limit covers every request of the component.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    /// Requests allowed in each `per` interval
+    pub requests: u32,
+    /// Length of the interval, e.g. "1s" or "1m"
+    pub per: TimeoutLimit,
+    /// Requests that can be sent back to back before the rate applies, defaults to `requests`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub burst: Option<u32>,
+    /// Whether to delay or reject requests over the limit, defaults to delay
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub on_limit: Option<RateLimitAction>,
+}
+
 /// Resource limit values under the limits section
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
 pub struct ResourceLimitValues {
@@ -167,6 +347,15 @@ pub struct ResourceLimits {
     pub memory: Option<u64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub io: Option<u64>,
+    /// Wall-clock timeout for each tool call, overriding the server default
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timeout: Option<TimeoutLimit>,
+    /// Limits on outbound HTTP requests
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<NetworkLimits>,
+    /// Disk quota across all of the component's writable storage
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub storage: Option<StorageQuota>,
 }
 
 /// IPC permission configuration (future/TODO)
@@ -204,6 +393,36 @@ impl<T> Default for PermissionList<T> {
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
 pub struct EnvironmentPermissions {
     pub allow: Option<Vec<EnvironmentPermission>>,
+    /// Keep secrets and configuration out of the environment: they are only
+    /// readable through `wasi:config/store`, and only the keys in `allow` are set
+    /// as environment variables
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub separate_config: Option<bool>,
+}
+
+/// Process-level settings for the guest (stdio, arguments)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+pub struct ProcessPermissions {
+    /// Return captured stdout/stderr to the MCP client along with the tool result
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expose_output: Option<bool>,
+    /// Static arguments passed to the guest as its argv
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub args: Option<Vec<String>>,
+    /// Forward the host process's command line to the guest (off by default)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub inherit_args: Option<bool>,
+}
+
+/// Directories Wassette provisions for the guest
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+pub struct DirectoryPermissions {
+    /// Mount a private directory at `/data` that persists across calls
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub data: Option<bool>,
+    /// Mount an empty directory at `/tmp` that is deleted after each call
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub scratch: Option<bool>,
 }
 
 /// Complete permissions structure
@@ -215,6 +434,10 @@ pub struct Permissions {
     pub runtime: Option<Runtime>,
     pub resources: Option<ResourceLimits>,
     pub ipc: Option<PermissionList<IpcPermission>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub process: Option<ProcessPermissions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub directories: Option<DirectoryPermissions>,
 }
 
 impl CpuLimit {
@@ -309,6 +532,132 @@ impl MemoryLimit {
     }
 }
 
+impl TimeoutLimit {
+    /// Validate and convert timeout to a duration
+    pub fn to_duration(&self) -> PolicyResult<Duration> {
+        let duration = match self {
+            TimeoutLimit::String(s) => {
+                if s.is_empty() {
+                    bail!("Timeout string cannot be empty");
+                }
+
+                let (value_str, to_duration): (&str, fn(u64) -> Duration) =
+                    if let Some(ms) = s.strip_suffix("ms") {
+                        (ms, Duration::from_millis)
+                    } else if let Some(secs) = s.strip_suffix('s') {
+                        (secs, Duration::from_secs)
+                    } else if let Some(mins) = s.strip_suffix('m') {
+                        (mins, |m| Duration::from_secs(m.saturating_mul(60)))
+                    } else {
+                        // No suffix, assume seconds
+                        (s.as_str(), Duration::from_secs)
+                    };
+
+                let value: u64 = value_str
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("Invalid timeout value: {}", s))?;
+
+                to_duration(value)
+            }
+            TimeoutLimit::Number(n) => Duration::from_secs(*n),
+        };
+
+        if duration.is_zero() {
+            bail!("Timeout cannot be zero");
+        }
+
+        Ok(duration)
+    }
+}
+
+impl SizeLimit {
+    /// Validate and convert size limit to bytes
+    pub fn to_bytes(&self) -> PolicyResult<u64> {
+        match self {
+            SizeLimit::String(s) => {
+                let (value_str, multiplier) = if let Some(v) = s.strip_suffix("Ki") {
+                    (v, 1024u64)
+                } else if let Some(v) = s.strip_suffix("Mi") {
+                    (v, 1024u64 * 1024)
+                } else if let Some(v) = s.strip_suffix("Gi") {
+                    (v, 1024u64 * 1024 * 1024)
+                } else {
+                    // No suffix, assume bytes
+                    (s.as_str(), 1u64)
+                };
+
+                let value: u64 = value_str
+                    .parse()
+                    .map_err(|_| anyhow::anyhow!("Invalid size value: {}", s))?;
+
+                value
+                    .checked_mul(multiplier)
+                    .ok_or_else(|| anyhow::anyhow!("Size value too large: {}", s))
+            }
+            SizeLimit::Number(n) => Ok(*n),
+        }
+    }
+}
+
+impl NetworkLimits {
+    /// Validate network limits
+    pub fn validate(&self) -> PolicyResult<()> {
+        for timeout in [
+            &self.connect_timeout,
+            &self.first_byte_timeout,
+            &self.between_bytes_timeout,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            timeout.to_duration()?;
+        }
+
+        for size in [&self.max_request_body, &self.max_response_body]
+            .into_iter()
+            .flatten()
+        {
+            size.to_bytes()?;
+        }
+
+        for rate_limit in self.rate_limits.iter().flatten() {
+            rate_limit.validate()?;
+        }
+
+        Ok(())
+    }
+}
+
+impl StorageQuota {
+    /// Validate a storage quota
+    pub fn validate(&self) -> PolicyResult<()> {
+        if self.bytes.is_none() && self.files.is_none() {
+            bail!("Storage quota needs a bytes or files limit");
+        }
+        if let Some(bytes) = &self.bytes {
+            bytes.to_bytes()?;
+        }
+        Ok(())
+    }
+}
+
+impl RateLimit {
+    /// Validate a rate limit
+    pub fn validate(&self) -> PolicyResult<()> {
+        if let Some(host) = &self.host {
+            Permissions::validate_network_host(host)?;
+        }
+        if self.requests == 0 {
+            bail!("Rate limit must allow at least one request");
+        }
+        if self.burst == Some(0) {
+            bail!("Rate limit burst must be at least one request");
+        }
+        self.per.to_duration()?;
+        Ok(())
+    }
+}
+
 impl ResourceLimitValues {
     /// Create a new ResourceLimitValues instance
     pub fn new(cpu: Option<CpuLimit>, memory: Option<MemoryLimit>) -> Self {
@@ -385,6 +734,18 @@ impl ResourceLimits {
             // IO values are fine as u64 is naturally non-negative
         }
 
+        if let Some(timeout) = &self.timeout {
+            timeout.to_duration()?;
+        }
+
+        if let Some(network) = &self.network {
+            network.validate()?;
+        }
+
+        if let Some(storage) = &self.storage {
+            storage.validate()?;
+        }
+
         Ok(())
     }
 }
@@ -418,23 +779,213 @@ impl Permissions {
         Ok(())
     }
 
+    /// Validate a `mem://` URI and the seed that goes with it
+    pub fn validate_memory_storage(perm: &StoragePermission) -> PolicyResult<()> {
+        let Some(name) = perm.uri.strip_prefix("mem://") else {
+            if perm.seed.is_some() {
+                bail!("Only mem:// storage can have a seed: {}", perm.uri);
+            }
+            return Ok(());
+        };
+        if name.is_empty() || name.contains('*') {
+            bail!("mem:// storage needs a plain name: {}", perm.uri);
+        }
+        if name.split('/').any(|part| part == "..") {
+            bail!("mem:// storage name can't contain '..': {}", perm.uri);
+        }
+        if perm.quota.is_some() {
+            bail!("mem:// storage can't have a quota: {}", perm.uri);
+        }
+        let files = perm.seed.iter().filter_map(|seed| seed.files.as_ref());
+        for path in files.flat_map(|files| files.keys()) {
+            if path.is_empty() || path.starts_with('/') || path.split('/').any(|part| part == "..")
+            {
+                bail!(
+                    "Seed file paths must be relative and stay inside the directory: {}",
+                    path
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Validate the guest path a storage grant is mounted at
+    pub fn validate_storage_mount(mount: &str) -> PolicyResult<()> {
+        if mount.is_empty() {
+            bail!("Storage mount point can't be empty");
+        }
+        if mount.contains('*') {
+            bail!("Storage mount point can't contain wildcards: {}", mount);
+        }
+        if mount.split('/').any(|part| part == "..") {
+            bail!("Storage mount point can't contain '..': {}", mount);
+        }
+        Ok(())
+    }
+
+    fn validate_network_host_permission(perm: &NetworkHostPermission) -> PolicyResult<()> {
+        Self::validate_network_host(&perm.host)?;
+
+        let (method, rest) = Self::split_http_method(&perm.host);
+        let rest = rest.split_once("://").map_or(rest, |(_, rest)| rest);
+        let (authority, path) = rest
+            .find('/')
+            .map_or((rest, None), |idx| (&rest[..idx], Some(&rest[idx..])));
+
+        if let Some(methods) = &perm.methods {
+            if method.is_some() {
+                bail!(
+                    "Method is set both in the host and in methods: {}",
+                    perm.host
+                );
+            }
+            for method in methods {
+                Self::validate_http_method(method)?;
+            }
+        }
+        if perm.ports.is_some() && Self::split_port(authority).1.is_some() {
+            bail!("Port is set both in the host and in ports: {}", perm.host);
+        }
+        if let Some(paths) = &perm.paths {
+            if path.is_some() {
+                bail!("Path is set both in the host and in paths: {}", perm.host);
+            }
+            for path in paths {
+                if !path.starts_with('/') {
+                    bail!("Path needs to start with a slash: {}", path);
+                }
+                Self::validate_http_path(path)?;
+            }
+        }
+
+        Ok(())
+    }
+
     fn validate_network_host(host: &str) -> PolicyResult<()> {
         if host.is_empty() {
             bail!("Host can't be empty");
         }
 
-        if host.matches('*').count() > 1 {
-            bail!("Too many wildcards in host: {}", host);
+        if host.starts_with("tcp://") || host.starts_with("udp://") {
+            bail!(
+                "Raw socket destinations go in a socket entry, not a host: {}",
+                host
+            );
         }
 
-        if host.contains('*') && !host.starts_with("*.") && host != "*" {
-            bail!("Wildcard should be at start like *.domain.com in: {}", host);
+        // An HTTP method may come first, e.g. `GET https://api.github.com/repos/**`
+        let (method, rest) = Self::split_http_method(host);
+        if let Some(method) = method {
+            Self::validate_http_method(method)?;
+        }
+
+        // Scheme-qualified entries (https://*.domain.com) follow the same rules
+        let rest = rest.split_once("://").map_or(rest, |(_, rest)| rest);
+
+        // Anything from the first slash on is a path prefix or glob
+        let (authority, path) = rest
+            .find('/')
+            .map_or((rest, None), |idx| (&rest[..idx], Some(&rest[idx..])));
+        if let Some(path) = path {
+            Self::validate_http_path(path)?;
+        }
+
+        let (pattern, port) = Self::split_port(authority);
+        if port.is_some_and(|port| port.parse::<u16>().is_err()) {
+            bail!("Port looks wrong in: {}", host);
         }
 
-        if let Some(domain_part) = host.strip_prefix("*.") {
+        // *.domain.com matches one label, **.domain.com matches any number of them
+        let domain_part = pattern
+            .strip_prefix("**.")
+            .or_else(|| pattern.strip_prefix("*."));
+        if let Some(domain_part) = domain_part {
+            if domain_part.contains('*') {
+                bail!("Too many wildcards in host: {}", host);
+            }
             if domain_part.is_empty() || domain_part.ends_with('.') {
                 bail!("Domain part looks wrong in: {}", host);
             }
+        } else if pattern.contains('*') && pattern != "*" {
+            bail!("Wildcard should be at start like *.domain.com in: {}", host);
+        }
+
+        Ok(())
+    }
+
+    fn split_http_method(host: &str) -> (Option<&str>, &str) {
+        match host.split_once(char::is_whitespace) {
+            Some((method, rest)) => (Some(method), rest.trim_start()),
+            None => (None, host),
+        }
+    }
+
+    /// Splits `host:port`, leaving bracketed IPv6 addresses like `[::1]` alone
+    fn split_port(authority: &str) -> (&str, Option<&str>) {
+        match authority.rsplit_once(':') {
+            Some((name, port)) if !authority.ends_with(']') => (name, Some(port)),
+            _ => (authority, None),
+        }
+    }
+
+    fn validate_http_method(method: &str) -> PolicyResult<()> {
+        if method.is_empty() || !method.chars().all(|c| c.is_ascii_alphabetic()) {
+            bail!("HTTP method looks wrong: {}", method);
+        }
+        Ok(())
+    }
+
+    fn validate_http_path(path: &str) -> PolicyResult<()> {
+        // Same rule as storage globs, ** has to be a whole segment
+        for part in path.split('/') {
+            if part.contains("**") && part != "**" {
+                bail!("Wildcard ** needs to be its own path segment in: {}", path);
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_credential_injection(inject: &CredentialInjection) -> PolicyResult<()> {
+        Self::validate_network_host(&inject.host)?;
+        if inject.host == "*" {
+            bail!("Credential injection needs a concrete host");
+        }
+        if inject.header.is_empty()
+            || !inject
+                .header
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            bail!("Header name looks wrong: {}", inject.header);
+        }
+        if inject.secret.is_empty() {
+            bail!("Credential injection for {} needs a secret", inject.host);
+        }
+        if let Some(format) = &inject.format {
+            if format.matches("{}").count() != 1 {
+                bail!("Header format needs exactly one {{}}: {}", format);
+            }
+        }
+        Ok(())
+    }
+
+    fn validate_network_socket(socket: &str) -> PolicyResult<()> {
+        let Some(target) = socket
+            .strip_prefix("tcp://")
+            .or_else(|| socket.strip_prefix("udp://"))
+        else {
+            bail!("Socket needs a tcp:// or udp:// prefix: {}", socket);
+        };
+
+        // The port comes after the last colon so IPv6 addresses in brackets work too
+        let Some((host, port)) = target.rsplit_once(':') else {
+            bail!("Socket needs a port (or *): {}", socket);
+        };
+        if host.is_empty() || host.contains('*') {
+            bail!("Socket needs a concrete host or CIDR: {}", socket);
+        }
+        if port != "*" && port.parse::<u16>().is_err() {
+            bail!("Socket port looks wrong in: {}", socket);
         }
 
         Ok(())
@@ -462,11 +1013,30 @@ impl Permissions {
                     if perm.access.is_empty() {
                         bail!("Storage needs some access permissions");
                     }
+                    if let Some(mount) = &perm.mount {
+                        Self::validate_storage_mount(mount)?;
+                    }
+                    if let Some(quota) = &perm.quota {
+                        if !perm.access.contains(&AccessType::Write) {
+                            bail!("Storage quota needs write access: {}", perm.uri);
+                        }
+                        quota.validate()?;
+                    }
+                    Self::validate_memory_storage(perm)?;
                 }
             }
             if let Some(deny_list) = &storage.deny {
                 for perm in deny_list {
                     Self::validate_storage_uri(&perm.uri)?;
+                    if perm.mount.is_some() {
+                        bail!("Storage deny rules can't have a mount point");
+                    }
+                    if perm.quota.is_some() {
+                        bail!("Storage deny rules can't have a quota");
+                    }
+                    if perm.uri.starts_with("mem://") || perm.seed.is_some() {
+                        bail!("Storage deny rules only apply to fs:// paths");
+                    }
                     if perm.access.is_empty() {
                         bail!("Storage needs some access permissions");
                     }
@@ -479,7 +1049,7 @@ impl Permissions {
                 for perm in allow_list {
                     match perm {
                         NetworkPermission::Host(host_perm) => {
-                            Self::validate_network_host(&host_perm.host)?;
+                            Self::validate_network_host_permission(host_perm)?;
                         }
                         NetworkPermission::Cidr(cidr_perm) => {
                             if cidr_perm.cidr.is_empty() {
@@ -489,6 +1059,12 @@ impl Permissions {
                                 bail!("CIDR needs a slash: {}", cidr_perm.cidr);
                             }
                         }
+                        NetworkPermission::Socket(socket_perm) => {
+                            Self::validate_network_socket(&socket_perm.socket)?;
+                        }
+                        NetworkPermission::Inject(inject_perm) => {
+                            Self::validate_credential_injection(&inject_perm.inject)?;
+                        }
                     }
                 }
             }
@@ -496,7 +1072,7 @@ impl Permissions {
                 for perm in deny_list {
                     match perm {
                         NetworkPermission::Host(host_perm) => {
-                            Self::validate_network_host(&host_perm.host)?;
+                            Self::validate_network_host_permission(host_perm)?;
                         }
                         NetworkPermission::Cidr(cidr_perm) => {
                             if cidr_perm.cidr.is_empty() {
@@ -506,6 +1082,15 @@ impl Permissions {
                                 bail!("CIDR needs a slash: {}", cidr_perm.cidr);
                             }
                         }
+                        NetworkPermission::Socket(socket_perm) => {
+                            Self::validate_network_socket(&socket_perm.socket)?;
+                        }
+                        NetworkPermission::Inject(inject_perm) => {
+                            bail!(
+                                "Credential injection belongs in the allow list: {}",
+                                inject_perm.inject.host
+                            );
+                        }
                     }
                 }
             }
@@ -538,6 +1123,9 @@ mod tests {
                 allow: Some(vec![StoragePermission {
                     uri: "".to_string(),
                     access: vec![AccessType::Read],
+                    mount: None,
+                    quota: None,
+                    seed: None,
                 }]),
                 deny: None,
             }),
@@ -547,6 +1135,92 @@ mod tests {
         assert!(permissions.validate().is_err());
     }
 
+    #[test]
+    fn test_storage_quota_validation() {
+        let grant = |access: Vec<AccessType>, quota: StorageQuota| Permissions {
+            storage: Some(PermissionList {
+                allow: Some(vec![StoragePermission {
+                    uri: "fs://work".to_string(),
+                    access,
+                    mount: None,
+                    quota: Some(quota),
+                    seed: None,
+                }]),
+                deny: None,
+            }),
+            ..Default::default()
+        };
+        let quota = StorageQuota {
+            bytes: Some(SizeLimit::String("100Mi".to_string())),
+            files: Some(1000),
+        };
+
+        assert!(grant(vec![AccessType::Write], quota.clone())
+            .validate()
+            .is_ok());
+        assert!(grant(vec![AccessType::Read], quota.clone())
+            .validate()
+            .is_err());
+        assert!(grant(vec![AccessType::Write], StorageQuota::default())
+            .validate()
+            .is_err());
+        let bad_size = StorageQuota {
+            bytes: Some(SizeLimit::String("100Xi".to_string())),
+            files: None,
+        };
+        assert!(grant(vec![AccessType::Write], bad_size.clone())
+            .validate()
+            .is_err());
+
+        let resources = ResourceLimits {
+            storage: Some(bad_size),
+            ..Default::default()
+        };
+        assert!(resources.validate().is_err());
+        let resources = ResourceLimits {
+            storage: Some(quota),
+            ..Default::default()
+        };
+        assert!(resources.validate().is_ok());
+    }
+
+    #[test]
+    fn test_memory_storage_validation() {
+        let grant = |uri: &str, seed: Option<MemorySeed>| Permissions {
+            storage: Some(PermissionList {
+                allow: Some(vec![StoragePermission {
+                    uri: uri.to_string(),
+                    access: vec![AccessType::Read],
+                    mount: None,
+                    quota: None,
+                    seed,
+                }]),
+                deny: None,
+            }),
+            ..Default::default()
+        };
+        let seed = |path: &str| MemorySeed {
+            tarball: Some("fixtures.tar.gz".to_string()),
+            files: Some(BTreeMap::from([(path.to_string(), "{}".to_string())])),
+        };
+
+        assert!(grant("mem://fixtures", Some(seed("config.json")))
+            .validate()
+            .is_ok());
+        assert!(grant("mem://fixtures", None).validate().is_ok());
+        assert!(grant("fs://fixtures", Some(seed("config.json")))
+            .validate()
+            .is_err());
+        assert!(grant("mem://", None).validate().is_err());
+        assert!(grant("mem://fix*", None).validate().is_err());
+        assert!(grant("mem://fixtures", Some(seed("../escape")))
+            .validate()
+            .is_err());
+        assert!(grant("mem://fixtures", Some(seed("/etc/passwd")))
+            .validate()
+            .is_err());
+    }
+
     #[test]
     fn test_network_cidr_validation() {
         let permissions = Permissions {
@@ -562,6 +1236,153 @@ mod tests {
         assert!(permissions.validate().is_err());
     }
 
+    #[test]
+    fn test_network_host_scope_validation() {
+        for host in [
+            "GET https://api.github.com/repos/our-org/**",
+            "api.example.com:8443",
+            "https://*.example.com/v1/*/items",
+            "post hooks.example.com/notify",
+        ] {
+            assert!(Permissions::validate_network_host(host).is_ok(), "{host}");
+        }
+        for host in [
+            "G3T api.example.com",
+            "api.example.com:https",
+            "api.example.com/repos/**x",
+        ] {
+            assert!(Permissions::validate_network_host(host).is_err(), "{host}");
+        }
+
+        let scoped = NetworkHostPermission {
+            host: "https://api.github.com".to_string(),
+            methods: Some(vec!["GET".to_string()]),
+            ports: Some(vec![443]),
+            paths: Some(vec!["/repos/our-org/**".to_string()]),
+        };
+        assert!(Permissions::validate_network_host_permission(&scoped).is_ok());
+
+        let both = NetworkHostPermission {
+            host: "GET https://api.github.com".to_string(),
+            methods: Some(vec!["POST".to_string()]),
+            ..Default::default()
+        };
+        assert!(Permissions::validate_network_host_permission(&both).is_err());
+        let relative = NetworkHostPermission {
+            host: "api.github.com".to_string(),
+            paths: Some(vec!["repos".to_string()]),
+            ..Default::default()
+        };
+        assert!(Permissions::validate_network_host_permission(&relative).is_err());
+
+        let yaml = r#"
+version: "1.0"
+permissions:
+  network:
+    allow:
+      - host: "api.github.com"
+        methods: ["GET"]
+        paths: ["/repos/our-org/**"]
+"#;
+        let policy = crate::PolicyParser::parse_str(yaml).unwrap();
+        let allow = policy.permissions.network.unwrap().allow.unwrap();
+        assert_eq!(
+            allow[0],
+            NetworkPermission::Host(NetworkHostPermission {
+                host: "api.github.com".to_string(),
+                methods: Some(vec!["GET".to_string()]),
+                ports: None,
+                paths: Some(vec!["/repos/our-org/**".to_string()]),
+            })
+        );
+    }
+
+    #[test]
+    fn test_credential_injection_validation() {
+        let yaml = r#"
+version: "1.0"
+permissions:
+  network:
+    allow:
+      - host: "api.example.com"
+      - inject:
+          host: "https://api.example.com"
+          header: Authorization
+          secret: X_TOKEN
+          format: "Bearer {}"
+"#;
+        let policy = crate::PolicyParser::parse_str(yaml).unwrap();
+        let allow = policy.permissions.network.unwrap().allow.unwrap();
+        assert_eq!(
+            allow[1],
+            NetworkPermission::Inject(NetworkInjectPermission {
+                inject: CredentialInjection {
+                    host: "https://api.example.com".to_string(),
+                    header: "Authorization".to_string(),
+                    secret: "X_TOKEN".to_string(),
+                    format: Some("Bearer {}".to_string()),
+                },
+            })
+        );
+
+        let inject = |host: &str, header: &str, secret: &str, format: Option<&str>| {
+            Permissions::validate_credential_injection(&CredentialInjection {
+                host: host.to_string(),
+                header: header.to_string(),
+                secret: secret.to_string(),
+                format: format.map(str::to_string),
+            })
+        };
+        assert!(inject("api.example.com", "X-Api-Key", "KEY", None).is_ok());
+        assert!(inject("*", "Authorization", "KEY", None).is_err());
+        assert!(inject("api.example.com", "Bad Header", "KEY", None).is_err());
+        assert!(inject("api.example.com", "Authorization", "", None).is_err());
+        assert!(inject("api.example.com", "Authorization", "KEY", Some("Bearer")).is_err());
+
+        let yaml = r#"
+version: "1.0"
+permissions:
+  network:
+    deny:
+      - inject:
+          host: "api.example.com"
+          header: Authorization
+          secret: X_TOKEN
+"#;
+        assert!(crate::PolicyParser::parse_str(yaml).is_err());
+    }
+
+    #[test]
+    fn test_network_socket_validation() {
+        for socket in [
+            "tcp://db.internal:5432",
+            "udp://10.0.0.53:53",
+            "tcp://[fd00::1]:8080",
+            "tcp://10.0.0.0/8:*",
+        ] {
+            assert!(
+                Permissions::validate_network_socket(socket).is_ok(),
+                "{socket}"
+            );
+        }
+        for socket in [
+            "db.internal:5432",
+            "https://db.internal:5432",
+            "tcp://db.internal",
+            "tcp://:5432",
+            "tcp://*.internal:5432",
+            "udp://10.0.0.53:99999",
+        ] {
+            assert!(
+                Permissions::validate_network_socket(socket).is_err(),
+                "{socket}"
+            );
+        }
+
+        // Raw socket destinations are not host entries
+        assert!(Permissions::validate_network_host("tcp://db.internal:5432").is_err());
+    }
+
     #[test]
     fn test_valid_permissions() {
         let permissions = Permissions {
@@ -569,6 +1390,9 @@ mod tests {
                 allow: Some(vec![StoragePermission {
                     uri: "fs://work/agent/**".to_string(),
                     access: vec![AccessType::Read, AccessType::Write],
+                    mount: None,
+                    quota: None,
+                    seed: None,
                 }]),
                 deny: None,
             }),
@@ -598,6 +1422,9 @@ mod tests {
         assert!(Permissions::validate_network_host("*.example.com").is_ok());
         assert!(Permissions::validate_network_host("sub.example.com").is_ok());
         assert!(Permissions::validate_network_host("*").is_ok()); // only deny is allowed for *
+        assert!(Permissions::validate_network_host("**.example.com").is_ok());
+        assert!(Permissions::validate_network_host("https://*.example.com").is_ok());
+        assert!(Permissions::validate_network_host("https://**.example.com").is_ok());
 
         assert!(Permissions::validate_network_host("").is_err());
         assert!(Permissions::validate_network_host("*.*.example.com").is_err());
@@ -632,28 +1459,40 @@ mod tests {
                     StoragePermission {
                         uri: "fs://work/agent/**".to_string(),
                         access: vec![AccessType::Read, AccessType::Write],
+                        mount: None,
+                        quota: None,
+                        seed: None,
                     },
                     StoragePermission {
                         uri: "fs://work/*/temp".to_string(),
                         access: vec![AccessType::Read],
+                        mount: None,
+                        quota: None,
+                        seed: None,
                     },
                 ]),
                 deny: Some(vec![StoragePermission {
                     uri: "fs://work/agent/secret/*".to_string(),
                     access: vec![AccessType::Write],
+                    mount: None,
+                    quota: None,
+                    seed: None,
                 }]),
             }),
             network: Some(PermissionList {
                 allow: Some(vec![
                     NetworkPermission::Host(NetworkHostPermission {
                         host: "*.example.com".to_string(),
+                        ..Default::default()
                     }),
                     NetworkPermission::Host(NetworkHostPermission {
                         host: "api.service.com".to_string(),
+                        ..Default::default()
                     }),
                 ]),
                 deny: Some(vec![NetworkPermission::Host(NetworkHostPermission {
                     host: "*.malicious.com".to_string(),
+                    ..Default::default()
                 })]),
             }),
             // Test environment with valid keys (no wildcards allowed)
@@ -669,6 +1508,7 @@ mod tests {
                         key: "MY_DEBUG_VAR".to_string(),
                     },
                 ]),
+                ..Default::default()
             }),
             ..Default::default()
         };
@@ -753,6 +1593,43 @@ mod tests {
         assert!(invalid_number.to_bytes().is_err());
     }
 
+    #[test]
+    fn test_timeout_limit_parsing() {
+        let timeout_ms = TimeoutLimit::String("500ms".to_string());
+        assert_eq!(
+            timeout_ms.to_duration().unwrap(),
+            Duration::from_millis(500)
+        );
+
+        let timeout_s = TimeoutLimit::String("30s".to_string());
+        assert_eq!(timeout_s.to_duration().unwrap(), Duration::from_secs(30));
+
+        let timeout_m = TimeoutLimit::String("2m".to_string());
+        assert_eq!(timeout_m.to_duration().unwrap(), Duration::from_secs(120));
+
+        // No suffix and numeric formats are seconds
+        let timeout_plain = TimeoutLimit::String("45".to_string());
+        assert_eq!(
+            timeout_plain.to_duration().unwrap(),
+            Duration::from_secs(45)
+        );
+        let timeout_numeric = TimeoutLimit::Number(10);
+        assert_eq!(
+            timeout_numeric.to_duration().unwrap(),
+            Duration::from_secs(10)
+        );
+
+        // Test invalid formats
+        assert!(TimeoutLimit::String("".to_string()).to_duration().is_err());
+        assert!(TimeoutLimit::String("10h".to_string())
+            .to_duration()
+            .is_err());
+        assert!(TimeoutLimit::String("0s".to_string())
+            .to_duration()
+            .is_err());
+        assert!(TimeoutLimit::Number(0).to_duration().is_err());
+    }
+
     #[test]
     fn test_resource_limit_values_validation() {
         // Valid resource limits
@@ -819,6 +1696,9 @@ mod tests {
             cpu: None,
             memory: None,
             io: None,
+            timeout: None,
+            network: None,
+            storage: None,
         };
         assert!(valid_new.validate().is_ok());
 
@@ -828,6 +1708,9 @@ mod tests {
             cpu: Some(1.5),
             memory: Some(512),
             io: Some(1000),
+            timeout: None,
+            network: None,
+            storage: None,
         };
         assert!(valid_legacy.validate().is_ok());
 
@@ -840,6 +1723,9 @@ mod tests {
             cpu: None,
             memory: None,
             io: None,
+            timeout: None,
+            network: None,
+            storage: None,
         };
         assert!(invalid_new.validate().is_err());
 
@@ -849,10 +1735,71 @@ mod tests {
             cpu: Some(-1.0),
             memory: None,
             io: None,
+            timeout: None,
+            network: None,
+            storage: None,
         };
         assert!(invalid_legacy.validate().is_err());
     }
 
+    #[test]
+    fn test_network_limits_validation() {
+        let limits = NetworkLimits {
+            connect_timeout: Some(TimeoutLimit::String("5s".to_string())),
+            first_byte_timeout: Some(TimeoutLimit::Number(30)),
+            between_bytes_timeout: None,
+            max_request_body: Some(SizeLimit::String("512Ki".to_string())),
+            max_response_body: Some(SizeLimit::Number(0)),
+            max_requests: Some(10),
+            rate_limits: Some(vec![RateLimit {
+                host: Some("api.github.com".to_string()),
+                requests: 10,
+                per: TimeoutLimit::String("1m".to_string()),
+                burst: Some(2),
+                on_limit: Some(RateLimitAction::Reject),
+            }]),
+        };
+        assert!(limits.validate().is_ok());
+        assert_eq!(
+            SizeLimit::String("10Mi".to_string()).to_bytes().unwrap(),
+            10 << 20
+        );
+        assert_eq!(SizeLimit::Number(1500).to_bytes().unwrap(), 1500);
+
+        let bad_size = NetworkLimits {
+            max_response_body: Some(SizeLimit::String("10MB".to_string())),
+            ..Default::default()
+        };
+        assert!(bad_size.validate().is_err());
+
+        let bad_timeout = NetworkLimits {
+            connect_timeout: Some(TimeoutLimit::String("0s".to_string())),
+            ..Default::default()
+        };
+        assert!(bad_timeout.validate().is_err());
+
+        let rate_limit = |requests, burst, host: &str| NetworkLimits {
+            rate_limits: Some(vec![RateLimit {
+                host: Some(host.to_string()),
+                requests,
+                per: TimeoutLimit::Number(1),
+                burst,
+                on_limit: None,
+            }]),
+            ..Default::default()
+        };
+        assert!(rate_limit(0, None, "api.github.com").validate().is_err());
+        assert!(rate_limit(1, Some(0), "api.github.com").validate().is_err());
+        assert!(rate_limit(1, None, "api.*.com").validate().is_err());
+        assert!(rate_limit(1, None, "*.github.com").validate().is_ok());
+
+        let resources = ResourceLimits {
+            network: Some(bad_size),
+            ..Default::default()
+        };
+        assert!(resources.validate().is_err());
+    }
+
     #[test]
     fn test_k8s_style_permissions_validation() {
         let permissions = Permissions {
@@ -860,6 +1807,9 @@ mod tests {
                 allow: Some(vec![StoragePermission {
                     uri: "fs://workspace/**".to_string(),
                     access: vec![AccessType::Read, AccessType::Write],
+                    mount: None,
+                    quota: None,
+                    seed: None,
                 }]),
                 deny: None,
             }),
@@ -874,8 +1824,13 @@ mod tests {
                 cpu: None,
                 memory: None,
                 io: None,
+                timeout: None,
+                network: None,
+                storage: None,
             }),
             ipc: None,
+            process: None,
+            directories: None,
         };
 
         assert!(permissions.validate().is_ok());
@@ -888,6 +1843,9 @@ mod tests {
                 allow: Some(vec![StoragePermission {
                     uri: "fs://work/agent/**file".to_string(),
                     access: vec![AccessType::Read],
+                    mount: None,
+                    quota: None,
+                    seed: None,
                 }]),
                 deny: None,
             }),
@@ -900,6 +1858,7 @@ mod tests {
         permissions.network = Some(PermissionList {
             allow: Some(vec![NetworkPermission::Host(NetworkHostPermission {
                 host: "example*.com".to_string(), // Invalid: * in middle
+                ..Default::default()
             })]),
             deny: None,
         });
@@ -910,6 +1869,7 @@ mod tests {
             allow: Some(vec![EnvironmentPermission {
                 key: "PATH_WITH_WILDCARD_*".to_string(),
             }]),
+            ..Default::default()
         });
         assert!(permissions.validate().is_err());
     }
@@ -43,6 +43,15 @@ impl ComponentStorage {
                 )
             })?;
 
+        // Created up front so a symlink into them can't pass a grant check while
+        // it still dangles
+        for dir in [crate::DATA_DIR, crate::SCRATCH_DIR, crate::AUDIT_DIR] {
+            let dir = root.join(dir);
+            tokio::fs::create_dir_all(&dir)
+                .await
+                .with_context(|| format!("Failed to create directory at {}", dir.display()))?;
+        }
+
         Ok(Self {
             root,
             downloads_dir,
@@ -96,6 +105,16 @@ impl ComponentStorage {
         self.root.join(format!("{component_id}.policy.meta.json"))
     }
 
+    /// Private data directory of a component that persists across calls.
+    pub fn data_dir(&self, component_id: &str) -> PathBuf {
+        self.root.join(crate::DATA_DIR).join(component_id)
+    }
+
+    /// Directory holding the per-call temporary directories of a component.
+    pub fn scratch_dir(&self, component_id: &str) -> PathBuf {
+        self.root.join(crate::SCRATCH_DIR).join(component_id)
+    }
+
     /// Stage a downloaded component artifact into storage, replacing any existing files.
     pub async fn install_component_artifact(
         &self,
@@ -104,7 +123,8 @@ impl ComponentStorage {
     ) -> Result<PathBuf> {
         let _permit = self.acquire_download_permit().await;
 
-        self.remove_component_artifacts(component_id).await?;
+        // Reinstalling keeps the component's data directory
+        self.remove_component_files(component_id).await?;
 
         resource.copy_to(self.root()).await.with_context(|| {
             format!(
@@ -116,8 +136,23 @@ impl ComponentStorage {
         Ok(self.component_path(component_id))
     }
 
-    /// Remove persisted component artifacts (wasm, metadata, cache) if they exist.
+    /// Remove persisted component artifacts (wasm, metadata, cache, data and
+    /// scratch directories) if they exist.
     pub async fn remove_component_artifacts(&self, component_id: &str) -> Result<()> {
+        self.remove_component_files(component_id).await?;
+        self.remove_dir_if_exists(&self.data_dir(component_id), "data directory", component_id)
+            .await?;
+        self.remove_dir_if_exists(
+            &self.scratch_dir(component_id),
+            "scratch directory",
+            component_id,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Remove the component's wasm, metadata and precompiled cache files if they exist.
+    async fn remove_component_files(&self, component_id: &str) -> Result<()> {
         self.remove_if_exists(
             &self.component_path(component_id),
             "component file",
@@ -207,6 +242,28 @@ impl ComponentStorage {
         Ok(())
     }
 
+    /// Remove a directory tree if it exists.
+    async fn remove_dir_if_exists(
+        &self,
+        path: &Path,
+        description: &str,
+        component_id: &str,
+    ) -> Result<()> {
+        match tokio::fs::remove_dir_all(path).await {
+            Ok(()) => {
+                tracing::debug!(component_id = %component_id, path = %path.display(), "Removed {}", description);
+                Ok(())
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(anyhow!(
+                "Failed to remove {} at {}: {}",
+                description,
+                path.display(),
+                e
+            )),
+        }
+    }
+
     /// Create a validation stamp for a component artifact to track stale data on disk.
     ///
     /// When `include_hash` is `true` the SHA-256 hash of the file is
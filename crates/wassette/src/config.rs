@@ -6,12 +6,15 @@
 
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::{Context, Result};
 
+use crate::egress::{EgressSettings, GuestEgress};
 use crate::{
-    get_default_secrets_dir, LifecycleManager, DEFAULT_HTTP_TIMEOUT_SECS, DEFAULT_OCI_TIMEOUT_SECS,
+    get_default_secrets_dir, LifecycleManager, SecretProvider, SecretRedactor, SecretsKey,
+    AUDIT_DIR, DEFAULT_CALL_TIMEOUT_SECS, DEFAULT_HTTP_TIMEOUT_SECS, DEFAULT_OCI_TIMEOUT_SECS,
 };
 
 /// Fully-specified configuration for constructing a [`LifecycleManager`].
@@ -19,10 +22,18 @@ use crate::{
 pub struct LifecycleConfig {
     plugin_dir: PathBuf,
     secrets_dir: PathBuf,
+    secrets_key: SecretsKey,
+    secret_providers: HashMap<String, Arc<dyn SecretProvider>>,
+    secret_redactor: Arc<SecretRedactor>,
     environment_vars: HashMap<String, String>,
     http_client: reqwest::Client,
     oci_client: oci_client::Client,
     eager_load: bool,
+    call_timeout: Duration,
+    ssrf_protection: bool,
+    egress: EgressSettings,
+    guest_egress: Arc<GuestEgress>,
+    network_audit_dir: Option<PathBuf>,
 }
 
 impl LifecycleConfig {
@@ -36,6 +47,21 @@ impl LifecycleConfig {
         &self.secrets_dir
     }
 
+    /// Where the key that encrypts component secrets comes from.
+    pub fn secrets_key(&self) -> &SecretsKey {
+        &self.secrets_key
+    }
+
+    /// Secret backends chosen for individual components, by component ID.
+    pub fn secret_providers(&self) -> &HashMap<String, Arc<dyn SecretProvider>> {
+        &self.secret_providers
+    }
+
+    /// Redactor that hides secret values in tool results and logs.
+    pub fn secret_redactor(&self) -> &Arc<SecretRedactor> {
+        &self.secret_redactor
+    }
+
     /// Environment variables exposed to components.
     pub fn environment_vars(&self) -> &HashMap<String, String> {
         &self.environment_vars
@@ -56,36 +82,80 @@ impl LifecycleConfig {
         self.eager_load
     }
 
-    pub(crate) fn into_parts(
-        self,
-    ) -> (
-        PathBuf,
-        PathBuf,
-        HashMap<String, String>,
-        reqwest::Client,
-        oci_client::Client,
-        bool,
-    ) {
+    /// Default wall-clock timeout applied to each component call.
+    pub fn call_timeout(&self) -> Duration {
+        self.call_timeout
+    }
+
+    /// Whether components are kept away from loopback, private and link-local
+    /// addresses unless their policy grants them by CIDR.
+    pub fn ssrf_protection(&self) -> bool {
+        self.ssrf_protection
+    }
+
+    /// Proxy, CA and client certificate settings for guest HTTP requests.
+    pub fn egress(&self) -> &EgressSettings {
+        &self.egress
+    }
+
+    /// Directory of the network audit log, or `None` when the log is disabled.
+    pub fn network_audit_dir(&self) -> Option<&Path> {
+        self.network_audit_dir.as_deref()
+    }
+
+    pub(crate) fn into_parts(self) -> LifecycleParts {
         (
             self.plugin_dir,
             self.secrets_dir,
+            self.secrets_key,
+            self.secret_providers,
+            self.secret_redactor,
             self.environment_vars,
             self.http_client,
             self.oci_client,
             self.eager_load,
+            self.call_timeout,
+            self.ssrf_protection,
+            self.guest_egress,
+            self.network_audit_dir,
         )
     }
 }
 
+/// The fields of a [`LifecycleConfig`], in the order `into_parts` returns them
+pub(crate) type LifecycleParts = (
+    PathBuf,
+    PathBuf,
+    SecretsKey,
+    HashMap<String, Arc<dyn SecretProvider>>,
+    Arc<SecretRedactor>,
+    HashMap<String, String>,
+    reqwest::Client,
+    oci_client::Client,
+    bool,
+    Duration,
+    bool,
+    Arc<GuestEgress>,
+    Option<PathBuf>,
+);
+
 /// Builder that validates inputs and produces a [`LifecycleConfig`] or [`LifecycleManager`].
 #[derive(Clone)]
 pub struct LifecycleBuilder {
     plugin_dir: PathBuf,
     secrets_dir: Option<PathBuf>,
+    secrets_key: Option<SecretsKey>,
+    secret_providers: HashMap<String, Arc<dyn SecretProvider>>,
+    secret_redactor: Arc<SecretRedactor>,
     environment_vars: HashMap<String, String>,
     http_client: Option<reqwest::Client>,
     oci_client: Option<oci_client::Client>,
     eager_load: bool,
+    call_timeout: Option<Duration>,
+    ssrf_protection: bool,
+    egress: EgressSettings,
+    network_audit: bool,
+    network_audit_dir: Option<PathBuf>,
 }
 
 impl LifecycleBuilder {
@@ -95,10 +165,18 @@ impl LifecycleBuilder {
         Self {
             plugin_dir,
             secrets_dir: None,
+            secrets_key: None,
+            secret_providers: HashMap::new(),
+            secret_redactor: Arc::default(),
             environment_vars: HashMap::new(),
             http_client: None,
             oci_client: None,
             eager_load: true,
+            call_timeout: None,
+            ssrf_protection: true,
+            egress: EgressSettings::default(),
+            network_audit: true,
+            network_audit_dir: None,
         }
     }
 
@@ -124,6 +202,31 @@ impl LifecycleBuilder {
         self
     }
 
+    /// Encrypt component secrets with the key from `key` instead of the key file
+    /// from [`SecretsKey::default_key`]. The OS keyring is only used when `key` asks
+    /// for it.
+    pub fn with_secrets_key(mut self, key: SecretsKey) -> Self {
+        self.secrets_key = Some(key);
+        self
+    }
+
+    /// Read a component's secrets from `provider` instead of the secrets directory.
+    pub fn with_secret_provider(
+        mut self,
+        component_id: impl Into<String>,
+        provider: Arc<dyn SecretProvider>,
+    ) -> Self {
+        self.secret_providers.insert(component_id.into(), provider);
+        self
+    }
+
+    /// Redact secret values with `redactor`, which can be shared with a logging
+    /// layer. By default nothing is redacted.
+    pub fn with_secret_redactor(mut self, redactor: Arc<SecretRedactor>) -> Self {
+        self.secret_redactor = redactor;
+        self
+    }
+
     /// Override the HTTP client.
     pub fn with_http_client(mut self, client: reqwest::Client) -> Self {
         self.http_client = Some(client);
@@ -142,6 +245,43 @@ impl LifecycleBuilder {
         self
     }
 
+    /// Override the default wall-clock timeout for component calls.
+    ///
+    /// Individual components can still set their own timeout in the policy
+    /// `resources` section.
+    pub fn with_call_timeout(mut self, timeout: Duration) -> Self {
+        self.call_timeout = Some(timeout);
+        self
+    }
+
+    /// Control whether components may reach loopback, private and link-local
+    /// addresses that their policy only grants by host name. Enabled by default.
+    pub fn with_ssrf_protection(mut self, enabled: bool) -> Self {
+        self.ssrf_protection = enabled;
+        self
+    }
+
+    /// Send guest HTTP requests through a proxy, trust extra CAs or present client
+    /// certificates. Certificate files are read when the config is built.
+    pub fn with_egress(mut self, egress: EgressSettings) -> Self {
+        self.egress = egress;
+        self
+    }
+
+    /// Control whether the outbound network access of components is recorded in
+    /// the network audit log. Enabled by default.
+    pub fn with_network_audit(mut self, enabled: bool) -> Self {
+        self.network_audit = enabled;
+        self
+    }
+
+    /// Write the network audit log to `dir` instead of the `audit` directory
+    /// under the plugin directory.
+    pub fn with_network_audit_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.network_audit_dir = Some(dir.into());
+        self
+    }
+
     /// Produce a validated [`LifecycleConfig`] without constructing a manager.
     pub fn build_config(self) -> Result<LifecycleConfig> {
         let plugin_dir = match self.plugin_dir.canonicalize() {
@@ -150,6 +290,7 @@ impl LifecycleBuilder {
         };
 
         let secrets_dir = self.secrets_dir.unwrap_or_else(get_default_secrets_dir);
+        let secrets_key = self.secrets_key.unwrap_or_else(SecretsKey::default_key);
 
         let http_client = match self.http_client {
             Some(client) => client,
@@ -161,13 +302,37 @@ impl LifecycleBuilder {
             None => default_oci_client()?,
         };
 
+        let guest_egress = if self.egress == EgressSettings::default() {
+            GuestEgress::shared_default()
+        } else {
+            Arc::new(
+                GuestEgress::from_settings(&self.egress)
+                    .context("Invalid proxy or TLS settings for guest HTTP requests")?,
+            )
+        };
+
+        let network_audit_dir = self.network_audit.then(|| {
+            self.network_audit_dir
+                .unwrap_or_else(|| plugin_dir.join(AUDIT_DIR))
+        });
+
         Ok(LifecycleConfig {
             plugin_dir,
             secrets_dir,
+            secrets_key,
+            secret_providers: self.secret_providers,
+            secret_redactor: self.secret_redactor,
             environment_vars: self.environment_vars,
             http_client,
             oci_client,
             eager_load: self.eager_load,
+            call_timeout: self
+                .call_timeout
+                .unwrap_or(Duration::from_secs(DEFAULT_CALL_TIMEOUT_SECS)),
+            ssrf_protection: self.ssrf_protection,
+            egress: self.egress,
+            guest_egress,
+            network_audit_dir,
         })
     }
 
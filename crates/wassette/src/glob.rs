@@ -0,0 +1,57 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Segment globs shared by network path rules and storage grants.
+
+/// Matches path segments where a `**` pattern segment stands for any number of them
+pub(crate) fn glob_segments(pattern: &[String], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((first, rest)) if first == "**" => {
+            (0..=path.len()).any(|skip| glob_segments(rest, &path[skip..]))
+        }
+        Some((first, rest)) => path.split_first().is_some_and(|(segment, path)| {
+            glob_segment(first, segment) && glob_segments(rest, path)
+        }),
+    }
+}
+
+/// Matches a single path segment where `*` stands for any run of characters
+pub(crate) fn glob_segment(pattern: &str, segment: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let Some(mut rest) = segment.strip_prefix(parts.next().unwrap_or_default()) else {
+        return false;
+    };
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        return rest.is_empty();
+    };
+    for part in middle {
+        match rest.find(part) {
+            Some(idx) => rest = &rest[idx + part.len()..],
+            None => return false,
+        }
+    }
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_segments() {
+        let pattern =
+            |pattern: &str| -> Vec<String> { pattern.split('/').map(str::to_string).collect() };
+        assert!(glob_segments(&pattern("a/**/c"), &["a", "c"]));
+        assert!(glob_segments(&pattern("a/**/c"), &["a", "b", "b", "c"]));
+        assert!(!glob_segments(&pattern("a/**/c"), &["a", "b"]));
+        assert!(glob_segments(&pattern("a/*.rs"), &["a", "lib.rs"]));
+        assert!(!glob_segments(&pattern("a/*.rs"), &["a", "b", "lib.rs"]));
+
+        assert!(glob_segment("*", ""));
+        assert!(glob_segment("a*b*c", "aXbYc"));
+        assert!(!glob_segment("a*b*c", "aXc"));
+        assert!(!glob_segment("ab*ba", "aba"));
+    }
+}
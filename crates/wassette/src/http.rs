@@ -1,42 +1,136 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT license.
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
 
-use anyhow::Result;
+use anyhow::{bail, Result};
+use http_body_util::BodyExt;
+use hyper::body::{Body, Bytes, Frame, SizeHint};
+use hyper::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_LENGTH};
+use ipnet::IpNet;
+use policy::{CredentialInjection, NetworkHostPermission, NetworkLimits};
 use tracing::{debug, warn};
 use url::Url;
 use wasmtime::component::{Resource, ResourceTable};
 use wasmtime_wasi::{WasiCtxView, WasiView};
 use wasmtime_wasi_http::bindings::http::types;
-use wasmtime_wasi_http::types::{HostFutureIncomingResponse, OutgoingRequestConfig};
+use wasmtime_wasi_http::body::HyperOutgoingBody;
+use wasmtime_wasi_http::types::{
+    HostFutureIncomingResponse, IncomingResponse, OutgoingRequestConfig,
+};
 use wasmtime_wasi_http::{HttpResult, WasiHttpView};
 
+use crate::audit::NetworkAudit;
+use crate::egress::{ip_literal, GuestEgress};
+use crate::glob::glob_segments;
+use crate::network::{ip_in_ranges, is_protected_ip, send_request_handler};
+use crate::rate_limit::{ComponentRateLimiter, RateLimitDecision, RateLimitRule};
+use crate::secrets::SecretsManager;
+
+/// Whether `domain` is itself a suffix from the Public Suffix List, such as `com`
+/// or `co.uk`. Names the list doesn't know, such as `internal`, are taken to be
+/// private.
+fn is_public_suffix(domain: &str) -> bool {
+    let domain = domain.strip_suffix('.').unwrap_or(domain);
+    psl::suffix(domain.as_bytes())
+        .is_some_and(|suffix| suffix.is_known() && suffix.as_bytes() == domain.as_bytes())
+}
+
+/// How the leading labels of a wildcard host pattern are matched
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum HostWildcard {
+    /// `*.domain.com` matches exactly one label in front of `domain.com`
+    SingleLabel,
+    /// `**.domain.com` matches one or more labels in front of `domain.com`
+    MultiLabel,
+    /// `*` matches every host, only accepted in deny lists
+    Any,
+}
+
+/// A host entry from a component's network policy, e.g. `api.example.com`,
+/// `https://api.example.com` or `*.example.com`
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
-struct AllowedHost {
+pub(crate) struct HostPattern {
     scheme: Option<String>,
     host: String,
+    wildcard: Option<HostWildcard>,
 }
 
-impl AllowedHost {
-    fn from_str(host_str: &str) -> Result<Self> {
-        if let Ok(url) = Url::parse(host_str) {
-            Ok(AllowedHost {
-                scheme: Some(url.scheme().to_string()),
-                host: url.host_str().unwrap_or("").to_string(),
-            })
-        } else if let Ok(url) = Url::parse(&format!("http://{host_str}")) {
-            Ok(AllowedHost {
+impl HostPattern {
+    pub(crate) fn from_str(host_str: &str) -> Result<Self> {
+        let pattern = Self::parse(host_str)?;
+
+        // `*.com` or `*.co.uk` would open up every domain under a registry
+        if pattern.wildcard.is_some() && is_public_suffix(&pattern.host) {
+            bail!(
+                "Invalid host pattern: {}, a wildcard can't cover a whole public suffix",
+                host_str
+            );
+        }
+
+        Ok(pattern)
+    }
+
+    /// Parse a deny list entry, which may additionally be `*` to block every host.
+    /// Wildcards over a public suffix such as `*.com` are fine here, as they only
+    /// narrow what the allow list grants.
+    pub(crate) fn from_deny_str(host_str: &str) -> Result<Self> {
+        if host_str == "*" {
+            return Ok(HostPattern {
                 scheme: None,
-                host: url.host_str().unwrap_or("").to_string(),
-            })
+                host: String::new(),
+                wildcard: Some(HostWildcard::Any),
+            });
+        }
+        Self::parse(host_str)
+    }
+
+    fn parse(host_str: &str) -> Result<Self> {
+        let (scheme_prefix, rest) = match host_str.find("://") {
+            Some(idx) => host_str.split_at(idx + 3),
+            None => ("", host_str),
+        };
+        let (wildcard, rest) = if let Some(rest) = rest.strip_prefix("**.") {
+            (Some(HostWildcard::MultiLabel), rest)
+        } else if let Some(rest) = rest.strip_prefix("*.") {
+            (Some(HostWildcard::SingleLabel), rest)
         } else {
-            Err(anyhow::anyhow!("Invalid host format: {}", host_str))
+            (None, rest)
+        };
+        let authority = rest.split('/').next().unwrap_or_default();
+        if authority.contains('*') {
+            bail!(
+                "Invalid host pattern: {}, wildcards are only supported as the leading label (e.g. *.example.com)",
+                host_str
+            );
         }
+
+        let host_str_without_wildcard = format!("{scheme_prefix}{rest}");
+        let (scheme, host) = if let Ok(url) = Url::parse(&host_str_without_wildcard) {
+            (
+                Some(url.scheme().to_string()),
+                normalize_host(url.host_str().unwrap_or("")),
+            )
+        } else if let Ok(url) = Url::parse(&format!("http://{host_str_without_wildcard}")) {
+            (None, normalize_host(url.host_str().unwrap_or("")))
+        } else {
+            bail!("Invalid host format: {}", host_str);
+        };
+
+        Ok(HostPattern {
+            scheme,
+            host,
+            wildcard,
+        })
     }
 
     fn matches(&self, request_host: &str, request_scheme: Option<&str>) -> bool {
-        if self.host != request_host {
+        if !self.host_matches(request_host) {
             return false;
         }
 
@@ -45,6 +139,475 @@ impl AllowedHost {
             _ => true,
         }
     }
+
+    /// Whether `request_host`, as returned by [`normalize_host`], is covered by the pattern
+    pub(crate) fn host_matches(&self, request_host: &str) -> bool {
+        let Some(wildcard) = self.wildcard else {
+            return self.host == request_host;
+        };
+
+        if wildcard == HostWildcard::Any {
+            return true;
+        }
+
+        // Only match on a label boundary so `*.domain.com` never matches `evil-domain.com`
+        let Some(prefix) = request_host
+            .strip_suffix(self.host.as_str())
+            .and_then(|prefix| prefix.strip_suffix('.'))
+        else {
+            return false;
+        };
+
+        match wildcard {
+            HostWildcard::Any => true,
+            HostWildcard::SingleLabel => !prefix.is_empty() && !prefix.contains('.'),
+            HostWildcard::MultiLabel => prefix.split('.').all(|label| !label.is_empty()),
+        }
+    }
+}
+
+/// Lowercase a host name and drop the trailing dot of a fully qualified name, so
+/// `Metadata.Internal.` is matched like `metadata.internal`
+pub(crate) fn normalize_host(host: &str) -> String {
+    let host = host.to_ascii_lowercase();
+    match host.strip_suffix('.') {
+        Some(host) => host.to_string(),
+        None => host,
+    }
+}
+
+/// The pieces of a network rule such as `GET https://api.github.com:443/repos/**`
+struct RuleParts<'a> {
+    method: Option<&'a str>,
+    /// Scheme including `://`, or empty
+    scheme: &'a str,
+    host: &'a str,
+    port: Option<&'a str>,
+    path: Option<&'a str>,
+}
+
+impl<'a> RuleParts<'a> {
+    fn split(rule: &'a str) -> Self {
+        let (method, rest) = match rule.trim().split_once(char::is_whitespace) {
+            Some((method, rest)) => (Some(method), rest.trim_start()),
+            None => (None, rule.trim()),
+        };
+        let (scheme, rest) = match rest.find("://") {
+            Some(idx) => rest.split_at(idx + 3),
+            None => ("", rest),
+        };
+        let (authority, path) = match rest.find('/') {
+            Some(idx) => (&rest[..idx], Some(&rest[idx..])),
+            None => (rest, None),
+        };
+        // Bracketed IPv6 addresses like `[::1]` contain colons but no port
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) if !authority.ends_with(']') => (host, Some(port)),
+            _ => (authority, None),
+        };
+        RuleParts {
+            method,
+            scheme,
+            host,
+            port,
+            path,
+        }
+    }
+}
+
+/// Spells out a policy host entry as one rule string per listed method, port and path,
+/// e.g. `host: api.github.com` with `methods: [GET, HEAD]` becomes `GET api.github.com`
+/// and `HEAD api.github.com`.
+pub(crate) fn host_rule_strings(perm: &NetworkHostPermission) -> Result<Vec<String>> {
+    let listed = |values: &Option<Vec<String>>| values.as_ref().filter(|v| !v.is_empty()).cloned();
+    let methods = listed(&perm.methods);
+    let ports = perm
+        .ports
+        .as_ref()
+        .filter(|ports| !ports.is_empty())
+        .map(|ports| ports.iter().map(u16::to_string).collect::<Vec<_>>());
+    let paths = listed(&perm.paths);
+    if methods.is_none() && ports.is_none() && paths.is_none() {
+        return Ok(vec![perm.host.clone()]);
+    }
+
+    let parts = RuleParts::split(&perm.host);
+    let pick =
+        |inline: Option<&str>, listed: Option<Vec<String>>, what: &str| match (inline, listed) {
+            (Some(_), Some(_)) => bail!(
+                "Invalid network rule: {}, the {} is given both inline and as a list",
+                perm.host,
+                what
+            ),
+            (inline, None) => Ok(vec![inline.map(str::to_string)]),
+            (None, Some(listed)) => Ok(listed.into_iter().map(Some).collect()),
+        };
+    let methods = pick(parts.method, methods, "method")?;
+    let ports = pick(parts.port, ports, "port")?;
+    let paths = pick(parts.path, paths, "path")?;
+
+    let mut rules = Vec::new();
+    for method in &methods {
+        for port in &ports {
+            for path in &paths {
+                let mut rule = String::new();
+                if let Some(method) = method {
+                    rule.push_str(method);
+                    rule.push(' ');
+                }
+                rule.push_str(parts.scheme);
+                rule.push_str(parts.host);
+                if let Some(port) = port {
+                    rule.push(':');
+                    rule.push_str(port);
+                }
+                rule.push_str(path.as_deref().unwrap_or_default());
+                rules.push(rule);
+            }
+        }
+    }
+    Ok(rules)
+}
+
+/// A URL path scope from a network rule, e.g. `/repos/our-org` or `/repos/*/issues/**`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct PathPattern {
+    segments: Vec<String>,
+    /// Patterns without wildcards are prefixes that only match on segment boundaries
+    glob: bool,
+}
+
+impl PathPattern {
+    fn parse(path: &str) -> Result<Self> {
+        if !path.starts_with('/') {
+            bail!("Invalid path pattern: {path}, expected it to start with /");
+        }
+        // Written the way requests are compared, so `/repos/` is the same prefix as
+        // `/repos` and `/` matches every path
+        let Some(segments) = normalize_path(path) else {
+            bail!(
+                "Invalid path pattern: {path}, dot segments and encoded slashes can't be matched"
+            );
+        };
+        if segments
+            .iter()
+            .any(|segment| segment.contains("**") && segment != "**")
+        {
+            bail!("Invalid path pattern: {path}, ** needs to be its own path segment");
+        }
+
+        Ok(PathPattern {
+            segments,
+            glob: path.contains('*'),
+        })
+    }
+
+    /// Match the segments of a request path from [`normalize_path`]
+    fn matches(&self, segments: &[String]) -> bool {
+        let segments: Vec<&str> = segments.iter().map(String::as_str).collect();
+        if self.glob {
+            glob_segments(&self.segments, &segments)
+        } else {
+            segments.len() >= self.segments.len()
+                && self.segments.iter().zip(&segments).all(|(p, s)| p == s)
+        }
+    }
+}
+
+/// Split a URL path into segments the way a server would read them: unreserved
+/// characters are percent-decoded and empty segments dropped, so `//admin` and
+/// `/%61dmin` both become `admin`. Returns `None` for paths that servers may read
+/// differently, i.e. ones with dot segments, encoded slashes or bad escapes.
+fn normalize_path(path: &str) -> Option<Vec<String>> {
+    let path = if path.is_empty() { "/" } else { path };
+    let mut segments = Vec::new();
+    for segment in path.strip_prefix('/')?.split('/') {
+        let segment = decode_unreserved(segment)?;
+        match segment.as_str() {
+            "" => {}
+            "." | ".." => return None,
+            _ => segments.push(segment),
+        }
+    }
+    Some(segments)
+}
+
+/// Percent-decode the unreserved characters of a path segment (RFC 3986, section 2.3),
+/// which mean the same either way, and upper-case the hex digits of other escapes
+fn decode_unreserved(segment: &str) -> Option<String> {
+    if segment.contains('\\') {
+        return None;
+    }
+    let mut decoded = String::with_capacity(segment.len());
+    let mut rest = segment;
+    while let Some((before, after)) = rest.split_once('%') {
+        decoded.push_str(before);
+        let hex = after
+            .get(..2)
+            .filter(|hex| hex.bytes().all(|byte| byte.is_ascii_hexdigit()))?;
+        let byte = u8::from_str_radix(hex, 16).ok()?;
+        match byte {
+            b'/' | b'\\' => return None,
+            byte if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) => {
+                decoded.push(char::from(byte))
+            }
+            _ => {
+                decoded.push('%');
+                decoded.push_str(&hex.to_ascii_uppercase());
+            }
+        }
+        rest = &after[2..];
+    }
+    decoded.push_str(rest);
+    Some(decoded)
+}
+
+/// A network rule from a component's policy: a host pattern, optionally scoped to an
+/// HTTP method, a port and a URL path, e.g. `GET https://api.github.com/repos/our-org/**`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) struct HttpRule {
+    host: HostPattern,
+    method: Option<hyper::Method>,
+    port: Option<u16>,
+    path: Option<PathPattern>,
+}
+
+impl HttpRule {
+    pub(crate) fn from_str(rule: &str) -> Result<Self> {
+        Self::parse(rule, HostPattern::from_str)
+    }
+
+    /// Parse a deny list entry, which may additionally be `*` to block every host
+    pub(crate) fn from_deny_str(rule: &str) -> Result<Self> {
+        Self::parse(rule, HostPattern::from_deny_str)
+    }
+
+    fn parse(rule: &str, host_pattern: fn(&str) -> Result<HostPattern>) -> Result<Self> {
+        let parts = RuleParts::split(rule);
+        let host = host_pattern(&format!("{}{}", parts.scheme, parts.host))?;
+        let method = parts
+            .method
+            .map(|method| {
+                if !method.chars().all(|c| c.is_ascii_alphabetic()) {
+                    bail!("Invalid HTTP method in network rule: {rule}");
+                }
+                Ok(hyper::Method::from_bytes(
+                    method.to_ascii_uppercase().as_bytes(),
+                )?)
+            })
+            .transpose()?;
+        let port = parts
+            .port
+            .map(|port| {
+                port.parse::<u16>()
+                    .map_err(|_| anyhow::anyhow!("Invalid port in network rule: {rule}"))
+            })
+            .transpose()?;
+        let path = parts.path.map(PathPattern::parse).transpose()?;
+
+        Ok(HttpRule {
+            host,
+            method,
+            port,
+            path,
+        })
+    }
+
+    /// Whether a request falls under the rule. A path that servers may read
+    /// differently, e.g. one with `..` segments, never matches.
+    pub(crate) fn matches(&self, method: &hyper::Method, uri: &hyper::Uri) -> bool {
+        self.matches_request(method, uri, false)
+    }
+
+    /// Like [`HttpRule::matches`], but a path that servers may read differently
+    /// does match, so deny rules and limits fail closed
+    pub(crate) fn may_match(&self, method: &hyper::Method, uri: &hyper::Uri) -> bool {
+        self.matches_request(method, uri, true)
+    }
+
+    fn matches_request(
+        &self,
+        method: &hyper::Method,
+        uri: &hyper::Uri,
+        ambiguous_matches: bool,
+    ) -> bool {
+        let Some(host) = uri.host() else {
+            return false;
+        };
+        let scheme = uri.scheme_str();
+        let port = uri.port_u16().or(match scheme {
+            Some("http") => Some(80),
+            Some("https") => Some(443),
+            _ => None,
+        });
+
+        self.host.matches(&normalize_host(host), scheme)
+            && self.method.as_ref().is_none_or(|allowed| allowed == method)
+            && self.port.is_none_or(|allowed| port == Some(allowed))
+            && self.path.as_ref().is_none_or(|pattern| {
+                normalize_path(uri.path())
+                    .map_or(ambiguous_matches, |segments| pattern.matches(&segments))
+            })
+    }
+}
+
+/// A header filled from a component secret and added host-side to matching requests,
+/// so the secret itself never reaches the guest
+#[derive(Debug, Clone)]
+pub struct HeaderInjection {
+    rule: HttpRule,
+    header: HeaderName,
+    secret: String,
+    format: String,
+}
+
+impl HeaderInjection {
+    pub(crate) fn from_policy(inject: &CredentialInjection) -> Result<Self> {
+        let rule = HttpRule::from_str(&inject.host)?;
+        if rule.host.wildcard == Some(HostWildcard::Any) {
+            bail!(
+                "Invalid credential injection: {}, needs a concrete host",
+                inject.host
+            );
+        }
+        let header = HeaderName::from_bytes(inject.header.as_bytes()).map_err(|_| {
+            anyhow::anyhow!("Invalid credential injection header: {}", inject.header)
+        })?;
+        if inject.secret.is_empty() {
+            bail!(
+                "Invalid credential injection: {}, missing secret",
+                inject.host
+            );
+        }
+        let format = inject.format.clone().unwrap_or_else(|| "{}".to_string());
+        if format.matches("{}").count() != 1 {
+            bail!("Invalid credential injection format: {format}, expected exactly one {{}}");
+        }
+
+        Ok(HeaderInjection {
+            rule,
+            header,
+            secret: inject.secret.clone(),
+            format,
+        })
+    }
+
+    /// The secret name this injection reads
+    pub(crate) fn secret(&self) -> &str {
+        &self.secret
+    }
+
+    fn applies_to(&self, method: &hyper::Method, uri: &hyper::Uri) -> bool {
+        // Credentials only travel in plain text when the rule asks for http:// explicitly
+        let scheme_ok = match uri.scheme_str() {
+            Some("https") => true,
+            Some("http") => self.rule.host.scheme.as_deref() == Some("http"),
+            _ => false,
+        };
+        scheme_ok && self.rule.matches(method, uri)
+    }
+}
+
+/// Where injected credentials are looked up when a request is sent
+#[derive(Clone)]
+struct SecretSource {
+    component_id: String,
+    secrets: Arc<SecretsManager>,
+}
+
+/// Limits on a component's outbound HTTP traffic, from the policy's `resources.network`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct OutboundLimits {
+    /// Upper bound for the connect timeout the guest asks for
+    pub connect_timeout: Option<Duration>,
+    /// Upper bound for the first-byte timeout the guest asks for
+    pub first_byte_timeout: Option<Duration>,
+    /// Upper bound for the between-bytes timeout the guest asks for
+    pub between_bytes_timeout: Option<Duration>,
+    /// Largest request body in bytes
+    pub max_request_body: Option<u64>,
+    /// Largest response body in bytes
+    pub max_response_body: Option<u64>,
+    /// Number of requests allowed in one tool call
+    pub max_requests: Option<u32>,
+    /// Token-bucket limits shared across calls
+    pub rate_limits: Vec<RateLimitRule>,
+}
+
+impl OutboundLimits {
+    pub(crate) fn from_policy(limits: &NetworkLimits) -> Result<Self> {
+        let duration = |limit: &Option<policy::TimeoutLimit>| {
+            limit.as_ref().map(|limit| limit.to_duration()).transpose()
+        };
+        let bytes = |limit: &Option<policy::SizeLimit>| {
+            limit.as_ref().map(|limit| limit.to_bytes()).transpose()
+        };
+
+        Ok(OutboundLimits {
+            connect_timeout: duration(&limits.connect_timeout)?,
+            first_byte_timeout: duration(&limits.first_byte_timeout)?,
+            between_bytes_timeout: duration(&limits.between_bytes_timeout)?,
+            max_request_body: bytes(&limits.max_request_body)?,
+            max_response_body: bytes(&limits.max_response_body)?,
+            max_requests: limits.max_requests,
+            rate_limits: limits
+                .rate_limits
+                .iter()
+                .flatten()
+                .map(RateLimitRule::from_policy)
+                .collect::<Result<_>>()?,
+        })
+    }
+
+    /// Lowers the guest's timeouts to the policy's where the policy is stricter
+    fn apply_timeouts(&self, config: &mut OutgoingRequestConfig) {
+        let cap = |timeout: &mut Duration, limit: Option<Duration>| {
+            if let Some(limit) = limit {
+                *timeout = (*timeout).min(limit);
+            }
+        };
+        cap(&mut config.connect_timeout, self.connect_timeout);
+        cap(&mut config.first_byte_timeout, self.first_byte_timeout);
+        cap(
+            &mut config.between_bytes_timeout,
+            self.between_bytes_timeout,
+        );
+    }
+
+    /// Rejects a request that declares an oversized body and caps the body as it streams
+    fn limit_request(
+        &self,
+        request: hyper::Request<HyperOutgoingBody>,
+    ) -> Result<hyper::Request<HyperOutgoingBody>, types::ErrorCode> {
+        let Some(limit) = self.max_request_body else {
+            return Ok(request);
+        };
+        let error = types::ErrorCode::HttpRequestBodySize(Some(limit));
+        if content_length(request.headers()).is_some_and(|len| len > limit) {
+            warn!(limit, "HTTP request body exceeds policy limit");
+            return Err(error);
+        }
+        Ok(request.map(|body| LimitedBody::new(body, limit, error).boxed()))
+    }
+
+    /// Rejects a response that declares an oversized body and caps the body as it streams
+    fn limit_response(
+        &self,
+        mut response: IncomingResponse,
+    ) -> Result<IncomingResponse, types::ErrorCode> {
+        let Some(limit) = self.max_response_body else {
+            return Ok(response);
+        };
+        let error = types::ErrorCode::HttpResponseBodySize(Some(limit));
+        if content_length(response.resp.headers()).is_some_and(|len| len > limit) {
+            warn!(limit, "HTTP response body exceeds policy limit");
+            return Err(error);
+        }
+        response.resp = response
+            .resp
+            .map(|body| LimitedBody::new(body, limit, error).boxed());
+        Ok(response)
+    }
 }
 
 /// WassetteWasiState is a wrapper around a WASI state that enforces network policies by filtering
@@ -53,8 +616,47 @@ pub struct WassetteWasiState<T> {
     /// The underlying WASI state
     pub inner: T,
 
-    /// Set of allowed hosts for network requests (extracted from policy document)
-    allowed_hosts: HashSet<AllowedHost>,
+    /// Set of allowed host rules for network requests (extracted from policy document)
+    allowed_hosts: HashSet<HttpRule>,
+
+    /// Network ranges a request may reach regardless of its host name
+    allowed_cidrs: Vec<IpNet>,
+
+    /// Host rules that block a request even when an allow entry matches it
+    denied_hosts: HashSet<HttpRule>,
+
+    /// Network ranges a request may never reach, checked against the resolved peer address
+    denied_cidrs: Vec<IpNet>,
+
+    /// Headers added to matching requests from the component's secrets
+    header_injections: Vec<HeaderInjection>,
+
+    /// Secrets for `header_injections`, read at send time so they stay current
+    secret_source: Option<SecretSource>,
+
+    /// Whether loopback, private and link-local peers need an explicit CIDR grant
+    ssrf_protection: bool,
+
+    /// Timeouts, body sizes and request count allowed for outbound requests
+    outbound_limits: OutboundLimits,
+
+    /// Requests sent so far, counted against `outbound_limits.max_requests`
+    requests_sent: u32,
+
+    /// Buckets for `outbound_limits.rate_limits`, shared with the component's other calls
+    rate_limiter: Option<Arc<ComponentRateLimiter>>,
+
+    /// When the call times out, so rate limits don't delay requests past it
+    call_deadline: Option<Instant>,
+
+    /// Messages for the tool result about limits that delayed or rejected requests
+    notices: CallNotices,
+
+    /// Proxy and TLS settings for outbound connections
+    egress: Arc<GuestEgress>,
+
+    /// Where every outbound request and its outcome are recorded
+    audit: Option<NetworkAudit>,
 }
 
 impl<T> WassetteWasiState<T> {
@@ -63,7 +665,7 @@ impl<T> WassetteWasiState<T> {
         let mut parsed_hosts = HashSet::new();
 
         for host_str in allowed_hosts {
-            match AllowedHost::from_str(&host_str) {
+            match HttpRule::from_str(&host_str) {
                 Ok(parsed_host) => {
                     parsed_hosts.insert(parsed_host);
                 }
@@ -77,27 +679,122 @@ impl<T> WassetteWasiState<T> {
         Ok(Self {
             inner,
             allowed_hosts: parsed_hosts,
+            allowed_cidrs: Vec::new(),
+            denied_hosts: HashSet::new(),
+            denied_cidrs: Vec::new(),
+            header_injections: Vec::new(),
+            secret_source: None,
+            ssrf_protection: true,
+            outbound_limits: OutboundLimits::default(),
+            requests_sent: 0,
+            rate_limiter: None,
+            call_deadline: None,
+            notices: CallNotices::default(),
+            egress: GuestEgress::shared_default(),
+            audit: None,
         })
     }
 
-    /// Check if a host is allowed by the policy
-    fn is_host_allowed(&self, uri: &hyper::Uri) -> bool {
-        let request_host = if let Some(host) = uri.host() {
-            host.to_string()
-        } else {
-            return false;
-        };
+    /// Allow requests to any resolved address within the given ranges
+    pub fn with_allowed_cidrs(mut self, allowed_cidrs: Vec<IpNet>) -> Self {
+        self.allowed_cidrs = allowed_cidrs;
+        self
+    }
 
-        let request_scheme = uri.scheme().map(|s| s.as_str());
+    /// Block the given hosts and network ranges, taking precedence over any allow entry
+    pub fn with_denied(
+        mut self,
+        denied_hosts: HashSet<String>,
+        denied_cidrs: Vec<IpNet>,
+    ) -> Result<Self> {
+        self.denied_hosts = denied_hosts
+            .iter()
+            .map(|host_str| HttpRule::from_deny_str(host_str))
+            .collect::<Result<_>>()?;
+        self.denied_cidrs = denied_cidrs;
+        Ok(self)
+    }
 
-        let req = request_host.to_ascii_lowercase();
-        for allowed_host in &self.allowed_hosts {
-            if allowed_host.matches(&req, request_scheme) {
-                return true;
-            }
-        }
+    /// Toggle blocking of loopback, private and link-local peers that no CIDR rule grants
+    pub fn with_ssrf_protection(mut self, ssrf_protection: bool) -> Self {
+        self.ssrf_protection = ssrf_protection;
+        self
+    }
+
+    /// Apply the policy's limits to every outbound request
+    pub fn with_outbound_limits(mut self, outbound_limits: OutboundLimits) -> Self {
+        self.outbound_limits = outbound_limits;
+        self
+    }
+
+    /// Take a token from the component's rate limit buckets for every request
+    pub(crate) fn with_rate_limiter(
+        mut self,
+        rate_limiter: Option<Arc<ComponentRateLimiter>>,
+    ) -> Self {
+        self.rate_limiter = rate_limiter;
+        self
+    }
+
+    /// Reject rate-limited requests that would have to wait past the call's deadline
+    pub(crate) fn with_call_deadline(mut self, call_deadline: Instant) -> Self {
+        self.call_deadline = Some(call_deadline);
+        self
+    }
 
-        false
+    /// Send requests through the server's proxy and TLS settings
+    pub(crate) fn with_egress(mut self, egress: Arc<GuestEgress>) -> Self {
+        self.egress = egress;
+        self
+    }
+
+    /// Record every outbound request to the network audit log
+    pub(crate) fn with_audit(mut self, audit: Option<NetworkAudit>) -> Self {
+        self.audit = audit;
+        self
+    }
+
+    /// Messages recorded during the call for the tool result
+    pub(crate) fn notices(&self) -> CallNotices {
+        self.notices.clone()
+    }
+
+    /// Add credential headers to matching requests, reading the secret values for
+    /// `component_id` from `secrets` each time a request is sent
+    pub fn with_header_injections(
+        mut self,
+        header_injections: Vec<HeaderInjection>,
+        component_id: &str,
+        secrets: Arc<SecretsManager>,
+    ) -> Self {
+        self.header_injections = header_injections;
+        self.secret_source = Some(SecretSource {
+            component_id: component_id.to_string(),
+            secrets,
+        });
+        self
+    }
+
+    /// Check if a request is allowed by the policy's host rules
+    fn is_request_allowed(&self, method: &hyper::Method, uri: &hyper::Uri) -> bool {
+        !self.is_request_denied(method, uri)
+            && self
+                .allowed_hosts
+                .iter()
+                .any(|rule| rule.matches(method, uri))
+    }
+
+    /// Check if a request is explicitly denied by the policy
+    fn is_request_denied(&self, method: &hyper::Method, uri: &hyper::Uri) -> bool {
+        self.denied_hosts
+            .iter()
+            .any(|rule| rule.may_match(method, uri))
+    }
+
+    fn audit_denied(&self, method: &hyper::Method, uri: &hyper::Uri, reason: &str) {
+        if let Some(audit) = &self.audit {
+            audit.http_denied(method, uri, reason);
+        }
     }
 }
 
@@ -127,28 +824,153 @@ impl<T: WasiHttpView> WasiHttpView for WassetteWasiState<T> {
 
     fn send_request(
         &mut self,
-        request: hyper::Request<wasmtime_wasi_http::body::HyperOutgoingBody>,
-        config: OutgoingRequestConfig,
+        request: hyper::Request<HyperOutgoingBody>,
+        mut config: OutgoingRequestConfig,
     ) -> HttpResult<HostFutureIncomingResponse> {
         let uri = request.uri();
+        let method = request.method();
 
         if uri.host().is_none() {
             warn!("HTTP request missing host, blocking request");
+            self.audit_denied(method, uri, "request has no host");
             return Err(types::ErrorCode::HttpRequestUriInvalid.into());
         }
 
-        if !self.is_host_allowed(uri) {
+        if self.is_request_denied(method, uri) {
+            warn!(%method, uri = %uri, "HTTP request blocked by network deny rule");
+            self.audit_denied(method, uri, "matches a network deny rule");
+            return Err(types::ErrorCode::HttpRequestDenied.into());
+        }
+
+        let host_allowed = self.is_request_allowed(method, uri);
+        if !host_allowed && self.allowed_cidrs.is_empty() {
             warn!(
+                %method,
                 uri = %uri,
                 allowed_hosts = ?self.allowed_hosts,
                 "HTTP request blocked by network policy"
             );
+            self.audit_denied(method, uri, "no network rule allows the request");
             return Err(types::ErrorCode::HttpRequestDenied.into());
         }
 
-        debug!(uri = %uri, "HTTP request allowed by network policy");
+        // The proxy resolves names itself, so a request that only a CIDR rule allows
+        // can't be checked when it goes through the proxy
+        if let Some(host) = uri.host() {
+            let host = host.trim_start_matches('[').trim_end_matches(']');
+            if !host_allowed && ip_literal(host).is_none() && self.egress.proxy_for(host).is_some()
+            {
+                warn!(uri = %uri, "HTTP request blocked: CIDR rules can't be checked through the proxy");
+                self.audit_denied(method, uri, "CIDR rules can't be checked through the proxy");
+                return Err(types::ErrorCode::HttpRequestDenied.into());
+            }
+        }
+
+        debug!(uri = %uri, host_allowed, "HTTP request passed host check, resolving peer address");
 
-        self.inner.send_request(request, config)
+        // CIDR rules apply to the address the request actually connects to, which is
+        // only known once the host has been resolved. Denied ranges win over everything,
+        // and a host rule alone never reaches a protected address, since DNS may point
+        // any name at one.
+        let address_check = {
+            let allowed_cidrs = self.allowed_cidrs.clone();
+            let denied_cidrs = self.denied_cidrs.clone();
+            let ssrf_protection = self.ssrf_protection;
+            move |ip: IpAddr| {
+                if ip_in_ranges(&denied_cidrs, ip) {
+                    return false;
+                }
+                if ip_in_ranges(&allowed_cidrs, ip) {
+                    return true;
+                }
+                host_allowed && !(ssrf_protection && is_protected_ip(ip))
+            }
+        };
+        if let Some(max_requests) = self.outbound_limits.max_requests {
+            if self.requests_sent >= max_requests {
+                warn!(max_requests, uri = %uri, "HTTP request blocked by per-call request limit");
+                self.audit_denied(method, uri, "per-call request limit reached");
+                return Err(types::ErrorCode::ConnectionLimitReached.into());
+            }
+        }
+
+        let max_wait = self
+            .call_deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        let delay = match self
+            .rate_limiter
+            .as_ref()
+            .map(|limiter| limiter.acquire(method, uri, max_wait))
+        {
+            None | Some(RateLimitDecision::Allow) => None,
+            Some(RateLimitDecision::Delay(wait, limit)) => {
+                debug!(uri = %uri, ?wait, %limit, "HTTP request delayed by rate limit");
+                self.notices.push(format!(
+                    "Outbound requests were delayed by the rate limit of {limit}"
+                ));
+                Some(wait)
+            }
+            Some(RateLimitDecision::Reject(limit)) => {
+                warn!(uri = %uri, %limit, "HTTP request rejected by rate limit");
+                self.notices.push(format!(
+                    "Outbound requests were rejected by the rate limit of {limit}"
+                ));
+                self.audit_denied(method, uri, &format!("rate limit exceeded: {limit}"));
+                return Err(types::ErrorCode::InternalError(Some(format!(
+                    "rate limit exceeded: {limit}"
+                )))
+                .into());
+            }
+        };
+        self.requests_sent += 1;
+
+        let injections: Vec<HeaderInjection> = self
+            .header_injections
+            .iter()
+            .filter(|injection| injection.applies_to(method, uri))
+            .cloned()
+            .collect();
+        let secret_source = self.secret_source.clone();
+        let limits = self.outbound_limits.clone();
+        let egress = Arc::clone(&self.egress);
+        let mut audit = self
+            .audit
+            .as_ref()
+            .map(|audit| audit.http_allowed(method, uri));
+        limits.apply_timeouts(&mut config);
+        let mut request = limits.limit_request(request)?;
+        if let Some(audit) = &audit {
+            request = audit.count_request(request);
+        }
+        let handle = wasmtime_wasi::runtime::spawn(async move {
+            if let Some(delay) = delay {
+                tokio::time::sleep(delay).await;
+                if let Some(audit) = &mut audit {
+                    audit.start();
+                }
+            }
+            let result = async {
+                if !injections.is_empty() {
+                    inject_credentials(&mut request, &injections, secret_source.as_ref()).await?;
+                }
+                send_request_handler(request, config, address_check, &egress)
+                    .await
+                    .and_then(|response| limits.limit_response(response))
+            }
+            .await;
+            Ok(match (result, audit) {
+                (Ok(mut response), Some(audit)) => {
+                    response.resp = audit.count_response(response.resp);
+                    Ok(response)
+                }
+                (Err(code), Some(audit)) => {
+                    audit.failed(&code);
+                    Err(code)
+                }
+                (result, None) => result,
+            })
+        });
+        Ok(HostFutureIncomingResponse::pending(handle))
     }
 
     fn is_forbidden_header(&mut self, name: &hyper::header::HeaderName) -> bool {
@@ -164,10 +986,125 @@ impl<T: WasiHttpView> WasiHttpView for WassetteWasiState<T> {
     }
 }
 
+/// Sets the injected headers on an outgoing request, replacing any the guest set itself
+async fn inject_credentials(
+    request: &mut hyper::Request<HyperOutgoingBody>,
+    injections: &[HeaderInjection],
+    secret_source: Option<&SecretSource>,
+) -> Result<(), types::ErrorCode> {
+    let secrets = match secret_source {
+        Some(source) => source
+            .secrets
+            .load_component_secrets(&source.component_id)
+            .await
+            .unwrap_or_else(|e| {
+                warn!(component_id = %source.component_id, error = %e, "Failed to load secrets for credential injection");
+                HashMap::new()
+            }),
+        None => HashMap::new(),
+    };
+
+    for injection in injections {
+        let Some(secret) = secrets.get(&injection.secret) else {
+            warn!(secret = %injection.secret, "Secret for credential injection is not set, blocking request");
+            return Err(types::ErrorCode::InternalError(Some(format!(
+                "secret {} for credential injection is not set",
+                injection.secret
+            ))));
+        };
+        let mut value = HeaderValue::from_str(&injection.format.replacen("{}", secret, 1))
+            .map_err(|_| {
+                warn!(secret = %injection.secret, "Secret is not a valid header value");
+                types::ErrorCode::InternalError(Some(format!(
+                    "secret {} is not a valid header value",
+                    injection.secret
+                )))
+            })?;
+        value.set_sensitive(true);
+        debug!(header = %injection.header, secret = %injection.secret, "Injecting credential header");
+        request
+            .headers_mut()
+            .insert(injection.header.clone(), value);
+    }
+    Ok(())
+}
+
+/// Messages about a call that are added to its tool result, each recorded once
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CallNotices(Arc<std::sync::Mutex<Vec<String>>>);
+
+impl CallNotices {
+    fn push(&self, notice: String) {
+        let mut notices = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        if !notices.contains(&notice) {
+            notices.push(notice);
+        }
+    }
+
+    pub(crate) fn take(&self) -> Vec<String> {
+        std::mem::take(&mut *self.0.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+fn content_length(headers: &HeaderMap) -> Option<u64> {
+    headers.get(CONTENT_LENGTH)?.to_str().ok()?.parse().ok()
+}
+
+/// A body that fails with `error` once more than `remaining` bytes have passed through it
+struct LimitedBody {
+    inner: HyperOutgoingBody,
+    remaining: u64,
+    error: types::ErrorCode,
+}
+
+impl LimitedBody {
+    fn new(inner: HyperOutgoingBody, limit: u64, error: types::ErrorCode) -> Self {
+        Self {
+            inner,
+            remaining: limit,
+            error,
+        }
+    }
+}
+
+impl Body for LimitedBody {
+    type Data = Bytes;
+    type Error = types::ErrorCode;
+
+    fn poll_frame(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Bytes>, types::ErrorCode>>> {
+        let frame = match Pin::new(&mut self.inner).poll_frame(cx) {
+            Poll::Ready(Some(Ok(frame))) => frame,
+            other => return other,
+        };
+        if let Some(data) = frame.data_ref() {
+            let len = data.len() as u64;
+            if len > self.remaining {
+                warn!("HTTP body exceeds policy limit");
+                return Poll::Ready(Some(Err(self.error.clone())));
+            }
+            self.remaining -= len;
+        }
+        Poll::Ready(Some(Ok(frame)))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::collections::HashSet;
 
+    use hyper::Method;
+
     use super::*;
 
     fn create_mock_wasi_state() -> MockWasiState {
@@ -217,9 +1154,9 @@ mod tests {
         let uri2: hyper::Uri = "http://other.example.com".parse().unwrap();
         let uri3: hyper::Uri = "http://malicious.com".parse().unwrap();
 
-        assert!(state.is_host_allowed(&uri1));
-        assert!(!state.is_host_allowed(&uri2));
-        assert!(!state.is_host_allowed(&uri3));
+        assert!(state.is_request_allowed(&Method::GET, &uri1));
+        assert!(!state.is_request_allowed(&Method::GET, &uri2));
+        assert!(!state.is_request_allowed(&Method::GET, &uri3));
     }
 
     #[test]
@@ -233,9 +1170,9 @@ mod tests {
         let uri2: hyper::Uri = "https://api.example.com".parse().unwrap();
         let uri3: hyper::Uri = "http://api.example.com".parse().unwrap();
 
-        assert!(!state.is_host_allowed(&uri1));
-        assert!(state.is_host_allowed(&uri2));
-        assert!(!state.is_host_allowed(&uri3));
+        assert!(!state.is_request_allowed(&Method::GET, &uri1));
+        assert!(state.is_request_allowed(&Method::GET, &uri2));
+        assert!(!state.is_request_allowed(&Method::GET, &uri3));
     }
 
     #[test]
@@ -248,8 +1185,8 @@ mod tests {
         let uri1: hyper::Uri = "http://api.example.com:8080".parse().unwrap();
         let uri2: hyper::Uri = "http://api.example.com:443".parse().unwrap();
 
-        assert!(state.is_host_allowed(&uri1));
-        assert!(state.is_host_allowed(&uri2));
+        assert!(state.is_request_allowed(&Method::GET, &uri1));
+        assert!(state.is_request_allowed(&Method::GET, &uri2));
     }
 
     #[test]
@@ -264,15 +1201,15 @@ mod tests {
         let https_secure: hyper::Uri = "https://secure.api.com".parse().unwrap();
         let http_secure: hyper::Uri = "http://secure.api.com".parse().unwrap();
 
-        assert!(state.is_host_allowed(&https_secure));
-        assert!(!state.is_host_allowed(&http_secure));
+        assert!(state.is_request_allowed(&Method::GET, &https_secure));
+        assert!(!state.is_request_allowed(&Method::GET, &http_secure));
 
         // Scheme-agnostic host should match both
         let https_example: hyper::Uri = "https://api.example.com".parse().unwrap();
         let http_example: hyper::Uri = "http://api.example.com".parse().unwrap();
 
-        assert!(state.is_host_allowed(&https_example));
-        assert!(state.is_host_allowed(&http_example));
+        assert!(state.is_request_allowed(&Method::GET, &https_example));
+        assert!(state.is_request_allowed(&Method::GET, &http_example));
     }
 
     #[test]
@@ -297,7 +1234,782 @@ mod tests {
         let uri1: hyper::Uri = "http://api.example.com".parse().unwrap();
         let uri2: hyper::Uri = "http://API.EXAMPLE.COM".parse().unwrap();
 
-        assert!(state.is_host_allowed(&uri1));
-        assert!(state.is_host_allowed(&uri2));
+        assert!(state.is_request_allowed(&Method::GET, &uri1));
+        assert!(state.is_request_allowed(&Method::GET, &uri2));
+    }
+
+    #[test]
+    fn test_host_allowed_single_label_wildcard() {
+        let mut allowed_hosts = HashSet::new();
+        allowed_hosts.insert("*.domain.com".to_string());
+
+        let state = WassetteWasiState::new(create_mock_wasi_state(), allowed_hosts).unwrap();
+
+        let allowed = [
+            "https://api.domain.com",
+            "http://eu-west-1.domain.com",
+            "https://API.DOMAIN.COM",
+        ];
+        for uri in allowed {
+            assert!(
+                state.is_request_allowed(&Method::GET, &uri.parse().unwrap()),
+                "{uri}"
+            );
+        }
+
+        let denied = [
+            "https://domain.com",
+            "https://evil-domain.com",
+            "https://domain.com.evil.net",
+            "https://api.domain.com.evil.net",
+            "https://a.b.domain.com",
+            "https://domain.com.",
+        ];
+        for uri in denied {
+            assert!(
+                !state.is_request_allowed(&Method::GET, &uri.parse().unwrap()),
+                "{uri}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_host_allowed_multi_label_wildcard() {
+        let mut allowed_hosts = HashSet::new();
+        allowed_hosts.insert("https://**.domain.com".to_string());
+
+        let state = WassetteWasiState::new(create_mock_wasi_state(), allowed_hosts).unwrap();
+
+        let allowed = ["https://api.domain.com", "https://a.b.c.domain.com"];
+        for uri in allowed {
+            assert!(
+                state.is_request_allowed(&Method::GET, &uri.parse().unwrap()),
+                "{uri}"
+            );
+        }
+
+        let denied = [
+            "http://api.domain.com",
+            "https://domain.com",
+            "https://evil-domain.com",
+            "https://a.evil-domain.com",
+            "https://domain.com.evil.net",
+        ];
+        for uri in denied {
+            assert!(
+                !state.is_request_allowed(&Method::GET, &uri.parse().unwrap()),
+                "{uri}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_invalid_wildcard_patterns() {
+        for pattern in [
+            "*",
+            "*.com",
+            "**.co.uk",
+            "*.github.io",
+            "api.*.com",
+            "*api.com",
+            "**",
+            "https://*.com",
+            "https://api.*",
+        ] {
+            let err = HostPattern::from_str(pattern).unwrap_err();
+            assert!(
+                err.to_string().contains("Invalid host pattern"),
+                "{pattern}: {err}"
+            );
+        }
+        assert!(HostPattern::from_str("*.domain.com").is_ok());
+        assert!(HostPattern::from_str("https://**.domain.com").is_ok());
+        assert!(HostPattern::from_str("*.internal").is_ok());
+        assert!(HostPattern::from_str("**.corp").is_ok());
+        assert!(HostPattern::from_str("*.example.co.uk").is_ok());
+        assert!(HostPattern::from_deny_str("*.com").is_ok());
+    }
+
+    #[test]
+    fn test_is_public_suffix() {
+        for domain in ["com", "co.uk", "github.io", "anything.ck", "xn--p1ai"] {
+            assert!(is_public_suffix(domain), "{domain}");
+        }
+        for domain in [
+            "example.com",
+            "internal",
+            "corp.internal",
+            "www.ck",
+            "example.co.uk",
+        ] {
+            assert!(!is_public_suffix(domain), "{domain}");
+        }
+    }
+
+    #[test]
+    fn test_denied_host_takes_precedence() {
+        let allowed_hosts =
+            HashSet::from(["*.internal".to_string(), "api.example.com".to_string()]);
+        let denied_hosts = HashSet::from(["metadata.internal".to_string()]);
+
+        let state = WassetteWasiState::new(create_mock_wasi_state(), allowed_hosts)
+            .unwrap()
+            .with_denied(denied_hosts, Vec::new())
+            .unwrap();
+
+        let metadata: hyper::Uri = "http://metadata.internal".parse().unwrap();
+        let other: hyper::Uri = "http://service.internal".parse().unwrap();
+
+        assert!(state.is_request_denied(&Method::GET, &metadata));
+        assert!(!state.is_request_allowed(&Method::GET, &metadata));
+        assert!(state.is_request_allowed(&Method::GET, &other));
+        assert!(state.is_request_allowed(&Method::GET, &"https://api.example.com".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_trailing_dot_is_ignored() {
+        let allowed_hosts =
+            HashSet::from(["*.internal".to_string(), "api.example.com.".to_string()]);
+        let denied_hosts = HashSet::from(["metadata.internal".to_string()]);
+
+        let state = WassetteWasiState::new(create_mock_wasi_state(), allowed_hosts)
+            .unwrap()
+            .with_denied(denied_hosts, Vec::new())
+            .unwrap();
+
+        let metadata: hyper::Uri = "http://Metadata.Internal.".parse().unwrap();
+        assert!(state.is_request_denied(&Method::GET, &metadata));
+        assert!(!state.is_request_allowed(&Method::GET, &metadata));
+
+        assert!(state.is_request_allowed(&Method::GET, &"https://api.example.com".parse().unwrap()));
+        assert!(
+            state.is_request_allowed(&Method::GET, &"https://api.example.com.".parse().unwrap())
+        );
+
+        assert_eq!(normalize_host("Metadata.Internal."), "metadata.internal");
+        assert_eq!(normalize_host("metadata.internal"), "metadata.internal");
+    }
+
+    #[test]
+    fn test_deny_all_hosts() {
+        let allowed_hosts = HashSet::from(["api.example.com".to_string()]);
+        let denied_hosts = HashSet::from(["*".to_string()]);
+
+        let state = WassetteWasiState::new(create_mock_wasi_state(), allowed_hosts)
+            .unwrap()
+            .with_denied(denied_hosts, Vec::new())
+            .unwrap();
+
+        assert!(
+            !state.is_request_allowed(&Method::GET, &"https://api.example.com".parse().unwrap())
+        );
+        assert!(state.is_request_denied(&Method::GET, &"https://anything.net".parse().unwrap()));
+
+        // `*` is only meaningful as a deny entry
+        assert!(HostPattern::from_str("*").is_err());
+    }
+
+    #[test]
+    fn test_method_port_and_path_scoped_rules() {
+        let allowed_hosts = HashSet::from([
+            "GET https://api.github.com/repos/our-org/**".to_string(),
+            "internal.example.com:8443".to_string(),
+            "post https://hooks.example.com/notify".to_string(),
+        ]);
+        let state = WassetteWasiState::new(create_mock_wasi_state(), allowed_hosts).unwrap();
+        let allowed =
+            |method: Method, uri: &str| state.is_request_allowed(&method, &uri.parse().unwrap());
+
+        assert!(allowed(Method::GET, "https://api.github.com/repos/our-org"));
+        assert!(allowed(
+            Method::GET,
+            "https://api.github.com/repos/our-org/wassette/issues?state=open"
+        ));
+        assert!(allowed(
+            Method::GET,
+            "https://api.github.com:443/repos/our-org/x"
+        ));
+        assert!(!allowed(
+            Method::POST,
+            "https://api.github.com/repos/our-org/x"
+        ));
+        assert!(!allowed(
+            Method::GET,
+            "https://api.github.com/repos/other-org/x"
+        ));
+        assert!(!allowed(
+            Method::GET,
+            "https://api.github.com/repos/our-org-evil/x"
+        ));
+        // Without a port in the rule any port is allowed
+        assert!(allowed(
+            Method::GET,
+            "https://api.github.com:8443/repos/our-org/x"
+        ));
+        assert!(!allowed(
+            Method::GET,
+            "http://api.github.com/repos/our-org/x"
+        ));
+        assert!(!allowed(
+            Method::GET,
+            "https://api.github.com/repos/our-org/../other-org/x"
+        ));
+        assert!(!allowed(
+            Method::GET,
+            "https://api.github.com/repos/our-org/%2E%2e/other-org/x"
+        ));
+
+        assert!(allowed(
+            Method::DELETE,
+            "https://internal.example.com:8443/any"
+        ));
+        assert!(!allowed(Method::GET, "https://internal.example.com/any"));
+
+        // A path without wildcards is a prefix on segment boundaries
+        assert!(allowed(Method::POST, "https://hooks.example.com/notify"));
+        assert!(allowed(
+            Method::POST,
+            "https://hooks.example.com/notify/team"
+        ));
+        assert!(!allowed(
+            Method::POST,
+            "https://hooks.example.com/notifyall"
+        ));
+        assert!(!allowed(Method::GET, "https://hooks.example.com/notify"));
+    }
+
+    fn path_matches(pattern: &PathPattern, path: &str) -> bool {
+        normalize_path(path).is_some_and(|segments| pattern.matches(&segments))
+    }
+
+    #[test]
+    fn test_path_globs() {
+        let pattern = PathPattern::parse("/repos/*/issues/**").unwrap();
+        assert!(path_matches(&pattern, "/repos/wassette/issues"));
+        assert!(path_matches(&pattern, "/repos/wassette/issues/1/comments"));
+        assert!(!path_matches(&pattern, "/repos/wassette/pulls/1"));
+        assert!(!path_matches(&pattern, "/repos/a/b/issues"));
+
+        let pattern = PathPattern::parse("/v1/*.json").unwrap();
+        assert!(path_matches(&pattern, "/v1/data.json"));
+        assert!(!path_matches(&pattern, "/v1/data.json/x"));
+        assert!(!path_matches(&pattern, "/v1/data.txt"));
+
+        assert!(path_matches(
+            &PathPattern::parse("/").unwrap(),
+            "/anything/at/all"
+        ));
+        assert!(PathPattern::parse("/repos/**x").is_err());
+        assert!(PathPattern::parse("/repos/../admin").is_err());
+        assert!(HttpRule::from_str("G3T api.example.com").is_err());
+        assert!(HttpRule::from_str("api.example.com:http").is_err());
+    }
+
+    #[test]
+    fn test_normalize_path() {
+        let segments = |path| normalize_path(path).unwrap();
+        assert_eq!(segments(""), Vec::<String>::new());
+        assert_eq!(segments("//admin//x/"), vec!["admin", "x"]);
+        assert_eq!(segments("/%61dmin/%7e%2Dx"), vec!["admin", "~-x"]);
+        // Reserved characters stay encoded, in one spelling
+        assert_eq!(segments("/a%3fb/c%3F"), vec!["a%3Fb", "c%3F"]);
+
+        for path in [
+            "/x/../admin",
+            "/./admin",
+            "/x/%2e%2E/admin",
+            "/x/.%2e",
+            "/a%2fb",
+            "/a%5Cb",
+            "/a\\b",
+            "/a%zz",
+            "/a%+1",
+            "/a%2",
+            "*",
+        ] {
+            assert_eq!(normalize_path(path), None, "{path}");
+        }
+    }
+
+    #[test]
+    fn test_denied_path_bypasses() {
+        let state = WassetteWasiState::new(
+            create_mock_wasi_state(),
+            HashSet::from(["https://api.example.com".to_string()]),
+        )
+        .unwrap()
+        .with_denied(
+            HashSet::from(["https://api.example.com/admin".to_string()]),
+            Vec::new(),
+        )
+        .unwrap();
+        let allowed = |path: &str| {
+            let uri = format!("https://api.example.com{path}").parse().unwrap();
+            state.is_request_allowed(&Method::GET, &uri)
+        };
+
+        assert!(allowed("/public"));
+        assert!(allowed("/administrator"));
+        for path in [
+            "/admin",
+            "/admin/users",
+            "//admin",
+            "/%61dmin",
+            "/x/../admin",
+            "/x/%2e%2e/admin",
+            "/admin%2fusers",
+            // Ambiguous paths are refused even when they may not reach /admin
+            "/x/./y",
+        ] {
+            assert!(!allowed(path), "{path}");
+        }
+
+        // Allow rules don't match ambiguous paths either
+        let state = WassetteWasiState::new(
+            create_mock_wasi_state(),
+            HashSet::from(["https://api.example.com/public/**".to_string()]),
+        )
+        .unwrap();
+        let uri = "https://api.example.com/public/../admin".parse().unwrap();
+        assert!(!state.is_request_allowed(&Method::GET, &uri));
+        let uri = "https://api.example.com//public/%64ocs".parse().unwrap();
+        assert!(state.is_request_allowed(&Method::GET, &uri));
+    }
+
+    #[test]
+    fn test_denied_scoped_rule() {
+        let state = WassetteWasiState::new(
+            create_mock_wasi_state(),
+            HashSet::from(["https://api.github.com".to_string()]),
+        )
+        .unwrap()
+        .with_denied(
+            HashSet::from(["DELETE https://api.github.com/**".to_string()]),
+            Vec::new(),
+        )
+        .unwrap();
+
+        let uri: hyper::Uri = "https://api.github.com/repos/our-org/x".parse().unwrap();
+        assert!(state.is_request_allowed(&Method::GET, &uri));
+        assert!(state.is_request_denied(&Method::DELETE, &uri));
+        assert!(!state.is_request_allowed(&Method::DELETE, &uri));
+    }
+
+    #[test]
+    fn test_host_rule_strings() {
+        let perm = NetworkHostPermission {
+            host: "https://api.github.com".to_string(),
+            methods: Some(vec!["GET".to_string(), "HEAD".to_string()]),
+            ports: Some(vec![443]),
+            paths: Some(vec!["/repos/our-org/**".to_string()]),
+        };
+        let mut rules = host_rule_strings(&perm).unwrap();
+        rules.sort();
+        assert_eq!(
+            rules,
+            vec![
+                "GET https://api.github.com:443/repos/our-org/**",
+                "HEAD https://api.github.com:443/repos/our-org/**",
+            ]
+        );
+
+        let perm = NetworkHostPermission {
+            host: "GET api.github.com".to_string(),
+            methods: Some(vec!["POST".to_string()]),
+            ..Default::default()
+        };
+        assert!(host_rule_strings(&perm).is_err());
+
+        let perm = NetworkHostPermission {
+            host: "api.github.com".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(host_rule_strings(&perm).unwrap(), vec!["api.github.com"]);
+    }
+
+    fn injection(host: &str, header: &str, secret: &str, format: Option<&str>) -> HeaderInjection {
+        HeaderInjection::from_policy(&CredentialInjection {
+            host: host.to_string(),
+            header: header.to_string(),
+            secret: secret.to_string(),
+            format: format.map(str::to_string),
+        })
+        .unwrap()
+    }
+
+    fn empty_request(uri: &str) -> hyper::Request<wasmtime_wasi_http::body::HyperOutgoingBody> {
+        use http_body_util::BodyExt;
+
+        hyper::Request::builder()
+            .uri(uri)
+            .header("authorization", "guest-supplied")
+            .body(
+                http_body_util::Empty::new()
+                    .map_err(|never| match never {})
+                    .boxed(),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn test_header_injection_matching() {
+        let bearer = injection(
+            "GET api.example.com/v1/**",
+            "Authorization",
+            "X_TOKEN",
+            Some("Bearer {}"),
+        );
+        let applies = |injection: &HeaderInjection, method: Method, uri: &str| {
+            injection.applies_to(&method, &uri.parse().unwrap())
+        };
+        assert!(applies(
+            &bearer,
+            Method::GET,
+            "https://api.example.com/v1/items"
+        ));
+        assert!(!applies(
+            &bearer,
+            Method::POST,
+            "https://api.example.com/v1/items"
+        ));
+        assert!(!applies(
+            &bearer,
+            Method::GET,
+            "https://api.example.com/v2/items"
+        ));
+        assert!(!applies(
+            &bearer,
+            Method::GET,
+            "https://other.example.com/v1/items"
+        ));
+        // Never sent in plain text unless the rule names http:// itself
+        assert!(!applies(
+            &bearer,
+            Method::GET,
+            "http://api.example.com/v1/items"
+        ));
+        let plain = injection("http://localhost", "X-Api-Key", "KEY", None);
+        assert!(applies(&plain, Method::GET, "http://localhost/"));
+
+        for (host, header, format) in [
+            ("*", "Authorization", None),
+            ("api.example.com", "Bad Header", None),
+            ("api.example.com", "Authorization", Some("Bearer")),
+        ] {
+            assert!(HeaderInjection::from_policy(&CredentialInjection {
+                host: host.to_string(),
+                header: header.to_string(),
+                secret: "X_TOKEN".to_string(),
+                format: format.map(str::to_string),
+            })
+            .is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_inject_credentials_from_secrets() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let secrets = Arc::new(
+            SecretsManager::new(temp_dir.path().join("secrets"))
+                .with_key(crate::SecretsKey::File(temp_dir.path().join("secrets.key"))),
+        );
+        secrets
+            .set_component_secrets(
+                "component",
+                &[("X_TOKEN".to_string(), "s3cret".to_string())],
+            )
+            .await
+            .unwrap();
+        let source = SecretSource {
+            component_id: "component".to_string(),
+            secrets,
+        };
+
+        let mut request = empty_request("https://api.example.com/v1/items");
+        let bearer = injection(
+            "api.example.com",
+            "Authorization",
+            "X_TOKEN",
+            Some("Bearer {}"),
+        );
+        inject_credentials(&mut request, &[bearer], Some(&source))
+            .await
+            .unwrap();
+        let value = request.headers().get("authorization").unwrap();
+        assert_eq!(value, "Bearer s3cret");
+        assert!(value.is_sensitive());
+        assert_eq!(request.headers().get_all("authorization").iter().count(), 1);
+
+        // A missing secret fails the request instead of sending it without credentials
+        let mut request = empty_request("https://api.example.com/v1/items");
+        let missing = injection("api.example.com", "X-Api-Key", "MISSING", None);
+        let err = inject_credentials(&mut request, &[missing], Some(&source))
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, types::ErrorCode::InternalError(Some(msg)) if msg.contains("MISSING"))
+        );
+    }
+
+    fn body_request(uri: &str, body: &'static str) -> hyper::Request<HyperOutgoingBody> {
+        hyper::Request::builder()
+            .uri(uri)
+            .body(
+                http_body_util::Full::new(Bytes::from_static(body.as_bytes()))
+                    .map_err(|never| match never {})
+                    .boxed(),
+            )
+            .unwrap()
+    }
+
+    #[test]
+    fn test_outbound_limits_cap_guest_timeouts() {
+        let limits = OutboundLimits::from_policy(&NetworkLimits {
+            connect_timeout: Some(policy::TimeoutLimit::String("2s".to_string())),
+            first_byte_timeout: Some(policy::TimeoutLimit::Number(600)),
+            max_response_body: Some(policy::SizeLimit::String("1Ki".to_string())),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(limits.max_response_body, Some(1024));
+
+        let mut config = OutgoingRequestConfig {
+            use_tls: true,
+            connect_timeout: Duration::from_secs(10),
+            first_byte_timeout: Duration::from_secs(60),
+            between_bytes_timeout: Duration::from_secs(60),
+        };
+        limits.apply_timeouts(&mut config);
+        assert_eq!(config.connect_timeout, Duration::from_secs(2));
+        // A looser policy never extends what the guest asked for
+        assert_eq!(config.first_byte_timeout, Duration::from_secs(60));
+        assert_eq!(config.between_bytes_timeout, Duration::from_secs(60));
+    }
+
+    #[tokio::test]
+    async fn test_request_body_limit() {
+        let limits = OutboundLimits {
+            max_request_body: Some(4),
+            ..Default::default()
+        };
+
+        let request = limits
+            .limit_request(body_request("https://api.example.com/", "abcd"))
+            .unwrap();
+        assert_eq!(
+            request.into_body().collect().await.unwrap().to_bytes(),
+            "abcd"
+        );
+
+        let request = limits
+            .limit_request(body_request("https://api.example.com/", "abcdef"))
+            .unwrap();
+        assert!(matches!(
+            request.into_body().collect().await,
+            Err(types::ErrorCode::HttpRequestBodySize(Some(4)))
+        ));
+
+        let mut request = body_request("https://api.example.com/", "");
+        request
+            .headers_mut()
+            .insert(CONTENT_LENGTH, HeaderValue::from_static("1000"));
+        assert!(matches!(
+            limits.limit_request(request),
+            Err(types::ErrorCode::HttpRequestBodySize(Some(4)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_response_body_limit() {
+        let limits = OutboundLimits {
+            max_response_body: Some(4),
+            ..Default::default()
+        };
+        let response = |body: &'static str, content_length: Option<&'static str>| {
+            let mut resp = hyper::Response::new(
+                http_body_util::Full::new(Bytes::from_static(body.as_bytes()))
+                    .map_err(|never| match never {})
+                    .boxed(),
+            );
+            if let Some(len) = content_length {
+                resp.headers_mut()
+                    .insert(CONTENT_LENGTH, HeaderValue::from_static(len));
+            }
+            IncomingResponse {
+                resp,
+                worker: None,
+                between_bytes_timeout: Duration::from_secs(1),
+            }
+        };
+
+        let ok = limits.limit_response(response("abc", None)).unwrap();
+        assert_eq!(
+            ok.resp.into_body().collect().await.unwrap().to_bytes(),
+            "abc"
+        );
+
+        let streamed = limits.limit_response(response("abcdefgh", None)).unwrap();
+        assert!(matches!(
+            streamed.resp.into_body().collect().await,
+            Err(types::ErrorCode::HttpResponseBodySize(Some(4)))
+        ));
+
+        assert!(matches!(
+            limits.limit_response(response("abcdefgh", Some("8"))),
+            Err(types::ErrorCode::HttpResponseBodySize(Some(4)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_max_requests_per_call() {
+        let mut state = WassetteWasiState::new(
+            create_mock_wasi_state(),
+            HashSet::from(["api.example.com".to_string()]),
+        )
+        .unwrap()
+        .with_outbound_limits(OutboundLimits {
+            max_requests: Some(1),
+            ..Default::default()
+        });
+        let config = || OutgoingRequestConfig {
+            use_tls: true,
+            connect_timeout: Duration::from_secs(1),
+            first_byte_timeout: Duration::from_secs(1),
+            between_bytes_timeout: Duration::from_secs(1),
+        };
+
+        assert!(state
+            .send_request(empty_request("https://api.example.com/"), config())
+            .is_ok());
+        let err = state
+            .send_request(empty_request("https://api.example.com/"), config())
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(types::ErrorCode::ConnectionLimitReached)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_rejection_is_reported() {
+        let rules = vec![RateLimitRule::from_policy(&policy::RateLimit {
+            host: Some("api.example.com".to_string()),
+            requests: 1,
+            per: policy::TimeoutLimit::String("1m".to_string()),
+            burst: None,
+            on_limit: Some(policy::RateLimitAction::Reject),
+        })
+        .unwrap()];
+        let limiters = crate::rate_limit::RateLimiters::default();
+        let mut state = WassetteWasiState::new(
+            create_mock_wasi_state(),
+            HashSet::from(["api.example.com".to_string()]),
+        )
+        .unwrap()
+        .with_rate_limiter(limiters.for_component("component", &rules));
+        let config = || OutgoingRequestConfig {
+            use_tls: true,
+            connect_timeout: Duration::from_secs(1),
+            first_byte_timeout: Duration::from_secs(1),
+            between_bytes_timeout: Duration::from_secs(1),
+        };
+
+        assert!(state
+            .send_request(empty_request("https://api.example.com/"), config())
+            .is_ok());
+        let err = state
+            .send_request(empty_request("https://api.example.com/"), config())
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(types::ErrorCode::InternalError(Some(msg))) if msg.contains("rate limit exceeded")
+        ));
+        assert_eq!(
+            state.notices().take(),
+            vec!["Outbound requests were rejected by the rate limit of 1 requests per 60s (api.example.com)"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_requests_dont_count_towards_max_requests() {
+        let rules = vec![RateLimitRule::from_policy(&policy::RateLimit {
+            host: Some("api.example.com".to_string()),
+            requests: 1,
+            per: policy::TimeoutLimit::String("1m".to_string()),
+            burst: None,
+            on_limit: Some(policy::RateLimitAction::Reject),
+        })
+        .unwrap()];
+        let limiters = crate::rate_limit::RateLimiters::default();
+        let mut state = WassetteWasiState::new(
+            create_mock_wasi_state(),
+            HashSet::from(["*.example.com".to_string()]),
+        )
+        .unwrap()
+        .with_outbound_limits(OutboundLimits {
+            max_requests: Some(2),
+            ..Default::default()
+        })
+        .with_rate_limiter(limiters.for_component("component", &rules));
+        let config = || OutgoingRequestConfig {
+            use_tls: true,
+            connect_timeout: Duration::from_secs(1),
+            first_byte_timeout: Duration::from_secs(1),
+            between_bytes_timeout: Duration::from_secs(1),
+        };
+        let mut send = |uri| state.send_request(empty_request(uri), config());
+
+        assert!(send("https://api.example.com/").is_ok());
+        let err = send("https://api.example.com/").unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(types::ErrorCode::InternalError(Some(msg))) if msg.contains("rate limit exceeded")
+        ));
+        // The rejected request was never sent, so the call still has one left
+        assert!(send("https://www.example.com/").is_ok());
+        let err = send("https://www.example.com/").unwrap_err();
+        assert!(matches!(
+            err.downcast_ref(),
+            Some(types::ErrorCode::ConnectionLimitReached)
+        ));
+    }
+
+    #[test]
+    fn test_blocked_requests_are_audited() {
+        use crate::audit::{AuditDecision, NetworkAuditLog, NetworkAuditQuery};
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let log = Arc::new(NetworkAuditLog::new(dir.path()).unwrap());
+        let mut state = WassetteWasiState::new(
+            create_mock_wasi_state(),
+            HashSet::from(["api.example.com".to_string()]),
+        )
+        .unwrap()
+        .with_audit(Some(NetworkAudit::new(
+            Arc::clone(&log),
+            "component",
+            Some("fetch"),
+        )));
+        let config = OutgoingRequestConfig {
+            use_tls: true,
+            connect_timeout: Duration::from_secs(1),
+            first_byte_timeout: Duration::from_secs(1),
+            between_bytes_timeout: Duration::from_secs(1),
+        };
+
+        assert!(state
+            .send_request(empty_request("https://evil.example.com/steal"), config)
+            .is_err());
+
+        let events = log.query(&NetworkAuditQuery::default()).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].component_id, "component");
+        assert_eq!(events[0].tool.as_deref(), Some("fetch"));
+        assert_eq!(events[0].target, "https://evil.example.com/steal");
+        assert_eq!(events[0].decision, AuditDecision::Deny);
+        assert_eq!(
+            events[0].reason.as_deref(),
+            Some("no network rule allows the request")
+        );
     }
 }
@@ -8,15 +8,16 @@
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use anyhow::{anyhow, bail, Context, Result};
 use component2json::{
-    component_exports_to_json_schema, component_exports_to_json_schema_with_docs,
     component_exports_to_tools, component_exports_to_tools_with_docs, create_placeholder_results,
     extract_package_docs, json_to_vals, vals_to_json, FunctionIdentifier, ToolMetadata,
 };
 use etcetera::BaseStrategy;
+use http_body_util::{BodyExt, Full};
+use hyper::body::Bytes;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use tokio::fs::DirEntry;
@@ -24,38 +25,67 @@ use tokio::sync::{RwLock, Semaphore};
 use tracing::{debug, info, instrument, warn};
 use wasmtime::component::{Component, InstancePre};
 use wasmtime::Store;
+use wasmtime_wasi_http::bindings::http::types::Scheme;
+use wasmtime_wasi_http::bindings::ProxyPre;
+use wasmtime_wasi_http::body::HyperOutgoingBody;
+use wasmtime_wasi_http::WasiHttpView;
 
+mod audit;
 mod component_storage;
 mod config;
+mod egress;
+mod glob;
 mod http;
 mod loader;
+mod memory_mount;
+mod network;
 pub mod oci_multi_layer;
 mod policy_internal;
+mod rate_limit;
+mod redaction;
 mod runtime_context;
 pub mod schema;
+mod secret_providers;
 mod secrets;
+mod secrets_cipher;
+mod stdio;
+mod storage_guard;
+mod storage_quota;
 mod wasistate;
 
+pub use audit::{AuditDecision, NetworkAccessKind, NetworkAuditEvent, NetworkAuditQuery};
+use audit::{NetworkAudit, NetworkAuditLog};
 use component_storage::ComponentStorage;
 pub use config::{LifecycleBuilder, LifecycleConfig};
+use egress::GuestEgress;
+pub use egress::{ClientCertificateSettings, EgressSettings};
 pub use http::WassetteWasiState;
 use loader::{ComponentResource, DownloadedResource};
 use policy_internal::PolicyManager;
 pub use policy_internal::{PermissionGrantRequest, PermissionRule, PolicyInfo};
-use runtime_context::RuntimeContext;
-pub use secrets::SecretsManager;
-use wasistate::WasiState;
+use rate_limit::RateLimiters;
+pub use redaction::SecretRedactor;
+use runtime_context::{epoch_ticks_for, RuntimeContext};
+pub use secret_providers::{EnvSecretProvider, ExecSecretProvider};
+pub use secrets::{FileSecretProvider, SecretProvider, SecretsManager};
+pub use secrets_cipher::SecretsKey;
+pub use storage_quota::QuotaUsage;
 pub use wasistate::{
     create_wasi_state_template_from_policy, CustomResourceLimiter, WasiStateTemplate,
 };
+use wasistate::{WasiState, FUEL_ASYNC_YIELD_INTERVAL};
 
 const DOWNLOADS_DIR: &str = "downloads";
+const AUDIT_DIR: &str = "audit";
+const DATA_DIR: &str = "data";
+const SCRATCH_DIR: &str = "scratch";
 const PRECOMPILED_EXT: &str = "cwasm";
 const METADATA_EXT: &str = "metadata.json";
 
 // Default timeout configurations
 pub(crate) const DEFAULT_OCI_TIMEOUT_SECS: u64 = 30;
 pub(crate) const DEFAULT_HTTP_TIMEOUT_SECS: u64 = 30;
+pub(crate) const DEFAULT_CALL_TIMEOUT_SECS: u64 = 300;
 pub(crate) const DEFAULT_DOWNLOAD_CONCURRENCY: usize = 8;
 
 /// Get the default secrets directory path based on the OS
@@ -292,6 +322,19 @@ impl ComponentRegistryState {
     }
 }
 
+/// The outcome of a component call along with any guest output the policy exposes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComponentCallOutput {
+    /// The JSON-encoded (or plain string) return value of the call
+    pub result: String,
+    /// Captured stdout, present only when the policy sets `process.expose_output`
+    pub stdout: Option<String>,
+    /// Captured stderr, present only when the policy sets `process.expose_output`
+    pub stderr: Option<String>,
+    /// Host-side notes about the call, such as rate limits that held back its requests
+    pub notices: Vec<String>,
+}
+
 /// A manager that handles the dynamic lifecycle of WebAssembly components.
 #[derive(Clone)]
 pub struct LifecycleManager {
@@ -302,6 +345,33 @@ pub struct LifecycleManager {
     oci_client: Arc<oci_wasm::WasmClient>,
     http_client: reqwest::Client,
     secrets_manager: Arc<SecretsManager>,
+    call_timeout: Duration,
+    ssrf_protection: bool,
+    rate_limiters: RateLimiters,
+    egress: Arc<GuestEgress>,
+    network_audit: Option<Arc<NetworkAuditLog>>,
+}
+
+/// The interface a component exports to be served as an HTTP endpoint
+const HTTP_HANDLER_INTERFACE: &str = "wasi:http/incoming-handler";
+
+/// The MCP tools for a component's exports, using its package docs when it has them.
+/// `wasi:http/incoming-handler` is left out, since it is served over HTTP instead.
+fn component_tools(instance: &ComponentInstance, engine: &wasmtime::Engine) -> Vec<ToolMetadata> {
+    let tools = match &instance.package_docs {
+        Some(docs) => component_exports_to_tools_with_docs(&instance.component, engine, true, docs),
+        None => component_exports_to_tools(&instance.component, engine, true),
+    };
+    tools
+        .into_iter()
+        .filter(|tool| {
+            tool.identifier
+                .interface_name
+                .as_deref()
+                .and_then(|name| name.split('@').next())
+                != Some(HTTP_HANDLER_INTERFACE)
+        })
+        .collect()
 }
 
 /// A representation of a loaded component instance. It contains both the base component info and a
@@ -338,15 +408,37 @@ impl LifecycleManager {
     /// Construct a lifecycle manager from an explicit configuration without loading components.
     #[instrument(skip_all, fields(plugin_dir = %config.plugin_dir().display()))]
     pub async fn from_config(config: LifecycleConfig) -> Result<Self> {
-        let (plugin_dir, secrets_dir, environment_vars, http_client, oci_client, _) =
-            config.into_parts();
-
+        let (
+            plugin_dir,
+            secrets_dir,
+            secrets_key,
+            secret_providers,
+            secret_redactor,
+            environment_vars,
+            http_client,
+            oci_client,
+            _,
+            call_timeout,
+            ssrf_protection,
+            egress,
+            network_audit_dir,
+        ) = config.into_parts();
+
+        let network_audit = network_audit_dir
+            .map(|dir| NetworkAuditLog::new(dir).map(Arc::new))
+            .transpose()?;
         let storage =
             ComponentStorage::new(plugin_dir.clone(), DEFAULT_DOWNLOAD_CONCURRENCY).await?;
 
         let runtime = Arc::new(RuntimeContext::initialize()?);
 
-        let secrets_manager = Arc::new(SecretsManager::new(secrets_dir.clone()));
+        let secrets_manager = secret_providers.into_iter().fold(
+            SecretsManager::new(secrets_dir.clone())
+                .with_key(secrets_key)
+                .with_redactor(secret_redactor),
+            |manager, (component_id, provider)| manager.with_provider(component_id, provider),
+        );
+        let secrets_manager = Arc::new(secrets_manager);
         secrets_manager.ensure_secrets_dir().await?;
 
         let environment_vars = Arc::new(environment_vars);
@@ -368,6 +460,11 @@ impl LifecycleManager {
             oci_client,
             http_client,
             secrets_manager,
+            call_timeout,
+            ssrf_protection,
+            rate_limiters: RateLimiters::default(),
+            egress,
+            network_audit,
         })
     }
 
@@ -380,20 +477,7 @@ impl LifecycleManager {
         let mut registered_ids = Vec::new();
 
         for (component_instance, name) in loaded_components {
-            let tool_metadata = if let Some(ref package_docs) = component_instance.package_docs {
-                component_exports_to_tools_with_docs(
-                    &component_instance.component,
-                    self.runtime.as_ref(),
-                    true,
-                    package_docs,
-                )
-            } else {
-                component_exports_to_tools(
-                    &component_instance.component,
-                    self.runtime.as_ref(),
-                    true,
-                )
-            };
+            let tool_metadata = component_tools(&component_instance, self.runtime.as_ref());
 
             if let Err(error) = self
                 .registry
@@ -468,17 +552,7 @@ impl LifecycleManager {
             package_docs: package_docs.clone(),
         };
 
-        // Use package docs if available
-        let tool_metadata = if let Some(ref docs) = package_docs {
-            component_exports_to_tools_with_docs(
-                &component_instance.component,
-                self.runtime.as_ref(),
-                true,
-                docs,
-            )
-        } else {
-            component_exports_to_tools(&component_instance.component, self.runtime.as_ref(), true)
-        };
+        let tool_metadata = component_tools(&component_instance, self.runtime.as_ref());
 
         let tool_names: Vec<String> = tool_metadata
             .iter()
@@ -564,6 +638,7 @@ impl LifecycleManager {
         // Only cleanup memory after all files are successfully removed
         self.registry.remove_component(id).await;
         self.policy_manager.cleanup(id).await;
+        self.rate_limiters.remove(id);
 
         info!(component_id = %id, "Component unloaded successfully");
         Ok(())
@@ -674,22 +749,10 @@ impl LifecycleManager {
     pub async fn get_component_schema(&self, component_id: &str) -> Option<Value> {
         // Prefer live component schema if loaded
         if let Some(component_instance) = self.get_component(component_id).await {
-            return Some(
-                if let Some(ref package_docs) = component_instance.package_docs {
-                    component_exports_to_json_schema_with_docs(
-                        &component_instance.component,
-                        self.runtime.as_ref(),
-                        true,
-                        package_docs,
-                    )
-                } else {
-                    component_exports_to_json_schema(
-                        &component_instance.component,
-                        self.runtime.as_ref(),
-                        true,
-                    )
-                },
-            );
+            let tools = component_tools(&component_instance, self.runtime.as_ref());
+            return Some(serde_json::json!({
+                "tools": tools.into_iter().map(|tool| tool.schema).collect::<Vec<_>>()
+            }));
         }
 
         // Fallback to metadata-based schema without compiling the component
@@ -777,6 +840,38 @@ impl LifecycleManager {
             .await
     }
 
+    /// Add a deny rule to a component, overriding any matching allow rule.
+    #[instrument(skip(self))]
+    pub async fn deny_permission(
+        &self,
+        component_id: &str,
+        permission_type: &str,
+        details: &serde_json::Value,
+    ) -> Result<()> {
+        if !self.registry.contains_component(component_id).await {
+            return Err(anyhow!("Component not found: {}", component_id));
+        }
+        self.policy_manager
+            .deny_permission(component_id, permission_type, details)
+            .await
+    }
+
+    /// Remove a deny rule from a component.
+    #[instrument(skip(self))]
+    pub async fn revoke_deny_permission(
+        &self,
+        component_id: &str,
+        permission_type: &str,
+        details: &serde_json::Value,
+    ) -> Result<()> {
+        if !self.registry.contains_component(component_id).await {
+            return Err(anyhow!("Component not found: {}", component_id));
+        }
+        self.policy_manager
+            .revoke_deny_permission(component_id, permission_type, details)
+            .await
+    }
+
     /// Reset all permissions for a component to defaults.
     #[instrument(skip(self))]
     pub async fn reset_permission(&self, component_id: &str) -> Result<()> {
@@ -935,36 +1030,92 @@ impl LifecycleManager {
     async fn get_wasi_state_for_component(
         &self,
         component_id: &str,
+        tool_name: Option<&str>,
     ) -> Result<(WassetteWasiState<WasiState>, Option<CustomResourceLimiter>)> {
         let policy_template = self
             .policy_manager
             .template_for_component(component_id)
             .await;
 
-        let wasi_state = policy_template.build()?;
+        let audit = self
+            .network_audit
+            .as_ref()
+            .map(|log| NetworkAudit::new(Arc::clone(log), component_id, tool_name));
+        let wasi_state = policy_template.build_audited(self.ssrf_protection, audit.clone())?;
         let allowed_hosts = policy_template.allowed_hosts.clone();
+        let allowed_cidrs = policy_template.allowed_cidrs.clone();
+        let denied_hosts = policy_template.denied_hosts.clone();
+        let denied_cidrs = policy_template.denied_cidrs.clone();
+        let header_injections = policy_template.header_injections.clone();
+        let outbound_limits = policy_template.outbound_limits.clone();
+        let rate_limiter = self
+            .rate_limiters
+            .for_component(component_id, &outbound_limits.rate_limits);
         let resource_limiter = wasi_state.resource_limiter.clone();
 
-        let wassette_wasi_state = WassetteWasiState::new(wasi_state, allowed_hosts)?;
+        let wassette_wasi_state = WassetteWasiState::new(wasi_state, allowed_hosts)?
+            .with_allowed_cidrs(allowed_cidrs)
+            .with_denied(denied_hosts, denied_cidrs)?
+            .with_ssrf_protection(self.ssrf_protection)
+            .with_outbound_limits(outbound_limits)
+            .with_rate_limiter(rate_limiter)
+            .with_egress(Arc::clone(&self.egress))
+            .with_audit(audit)
+            .with_header_injections(
+                header_injections,
+                component_id,
+                Arc::clone(&self.secrets_manager),
+            );
         Ok((wassette_wasi_state, resource_limiter))
     }
 
-    /// Executes a function call on a WebAssembly component
-    #[instrument(skip(self))]
-    pub async fn execute_component_call(
+    /// Reads back the outbound network accesses recorded for component calls,
+    /// oldest first. Fails when the network audit log is disabled.
+    pub fn network_audit_events(
         &self,
-        component_id: &str,
-        function_name: &str,
-        parameters: &str,
-    ) -> Result<String> {
-        let component = self
-            .get_component(component_id)
+        query: &NetworkAuditQuery,
+    ) -> Result<Vec<NetworkAuditEvent>> {
+        match &self.network_audit {
+            Some(log) => log.query(query),
+            None => bail!("The network audit log is disabled"),
+        }
+    }
+
+    /// Measures what the component stores in each of its writable directories,
+    /// alongside the quotas its policy sets
+    pub async fn storage_usage(&self, component_id: &str) -> Result<Vec<QuotaUsage>> {
+        let template = self
+            .policy_manager
+            .template_for_component(component_id)
+            .await;
+        let quotas = template.disk_quotas.clone();
+        tokio::task::spawn_blocking(move || quotas.iter().map(|quota| quota.usage()).collect())
             .await
-            .ok_or_else(|| anyhow!("Component not found: {}", component_id))?;
+            .context("Failed to measure storage usage")
+    }
+
+    /// Creates a store for one call into the component, with its policy's sandbox,
+    /// CPU budget, memory limit and deadline applied. Returns the call timeout too.
+    async fn new_call_store(
+        &self,
+        component_id: &str,
+        tool_name: Option<&str>,
+    ) -> Result<(Store<WassetteWasiState<WasiState>>, Duration)> {
+        let (state, resource_limiter) = self
+            .get_wasi_state_for_component(component_id, tool_name)
+            .await?;
 
-        let (state, resource_limiter) = self.get_wasi_state_for_component(component_id).await?;
+        let fuel_budget = state.inner.fuel_budget();
+        let call_timeout = state.inner.call_timeout.unwrap_or(self.call_timeout);
+        let state = state.with_call_deadline(Instant::now() + call_timeout);
 
         let mut store = Store::new(self.runtime.as_ref(), state);
+        store.set_fuel(fuel_budget)?;
+        store.fuel_async_yield_interval(Some(FUEL_ASYNC_YIELD_INTERVAL))?;
+        // Trap guests that keep running past the deadline; the tokio timeout around
+        // the call covers guests that are parked on host I/O instead.
+        store.set_epoch_deadline(epoch_ticks_for(call_timeout) + 1);
+        store.epoch_deadline_trap();
 
         // Apply memory limits if configured in the policy by setting up a limiter closure
         // that extracts the resource limiter from the WasiState
@@ -979,68 +1130,260 @@ impl LifecycleManager {
             });
         }
 
-        let instance = component.instance_pre.instantiate_async(&mut store).await?;
+        Ok((store, call_timeout))
+    }
 
-        // Use the new function identifier lookup instead of dot-splitting
-        let function_id = self
-            .registry
-            .tool_identifier(function_name)
+    /// Whether the component is loaded and exports `wasi:http/incoming-handler`, so
+    /// [`LifecycleManager::handle_http_request`] can serve requests with it
+    pub async fn serves_http(&self, component_id: &str) -> bool {
+        self.get_component(component_id)
             .await
-            .ok_or_else(|| anyhow!("Unknown tool name: {}", function_name))?;
+            .is_some_and(|component| ProxyPre::new((*component.instance_pre).clone()).is_ok())
+    }
 
-        let (interface_name, func_name) = (
-            function_id.interface_name.as_deref().unwrap_or(""),
-            &function_id.function_name,
-        );
+    /// Serves an HTTP request with a component that exports `wasi:http/incoming-handler`,
+    /// in the same sandbox and under the same limits as its tool calls.
+    ///
+    /// The request body is passed in full. The response body streams from the guest,
+    /// which keeps running until it has written all of it or the call timeout expires.
+    #[instrument(skip(self, request), fields(method = %request.method(), uri = %request.uri()))]
+    pub async fn handle_http_request(
+        &self,
+        component_id: &str,
+        request: hyper::Request<Bytes>,
+    ) -> Result<hyper::Response<HyperOutgoingBody>> {
+        let component = self
+            .get_component(component_id)
+            .await
+            .ok_or_else(|| anyhow!("Component not found: {}", component_id))?;
+        let proxy_pre = ProxyPre::new((*component.instance_pre).clone()).with_context(|| {
+            format!("Component '{component_id}' does not export wasi:http/incoming-handler")
+        })?;
+
+        let (mut store, call_timeout) = self.new_call_store(component_id, None).await?;
+        let (sender, receiver) = tokio::sync::oneshot::channel();
+        let request =
+            request.map(|body| Full::new(body).map_err(|never| -> hyper::Error { match never {} }));
+        let request = store
+            .data_mut()
+            .new_incoming_request(Scheme::Http, request)?;
+        let response_out = store.data_mut().new_response_outparam(sender)?;
+
+        let id = component_id.to_string();
+        let task = tokio::spawn(async move {
+            let handled = tokio::time::timeout(call_timeout, async {
+                let proxy = proxy_pre.instantiate_async(&mut store).await?;
+                proxy
+                    .wasi_http_incoming_handler()
+                    .call_handle(&mut store, request, response_out)
+                    .await
+            })
+            .await;
 
-        let func = if !interface_name.is_empty() {
-            let interface_index = instance
-                .get_export_index(&mut store, None, interface_name)
-                .ok_or_else(|| anyhow!("Interface not found: {}", interface_name))?;
-
-            let function_index = instance
-                .get_export_index(&mut store, Some(&interface_index), func_name)
-                .ok_or_else(|| {
-                    anyhow!(
-                        "Function not found in interface: {}.{}",
-                        interface_name,
-                        func_name
-                    )
-                })?;
-
-            instance
-                .get_func(&mut store, function_index)
-                .ok_or_else(|| {
-                    anyhow!(
-                        "Function not found in interface: {}.{}",
-                        interface_name,
-                        func_name
+            let state = &store.data().inner;
+            let (stdout, stderr) = (state.stdout.contents(), state.stderr.contents());
+            if !stdout.is_empty() {
+                info!(component_id = %id, stream = "stdout", output = %stdout, "Captured component output");
+            }
+            if !stderr.is_empty() {
+                info!(component_id = %id, stream = "stderr", output = %stderr, "Captured component output");
+            }
+
+            match handled {
+                Ok(result) => result,
+                Err(_) => {
+                    warn!(component_id = %id, timeout = ?call_timeout, "HTTP handler timed out");
+                    bail!(
+                        "Call timed out: component '{}' did not finish within {:?}",
+                        id,
+                        call_timeout
                     )
-                })?
-        } else {
-            let func_index = instance
-                .get_export_index(&mut store, None, func_name)
-                .ok_or_else(|| anyhow!("Function not found: {}", func_name))?;
-            instance
-                .get_func(&mut store, func_index)
-                .ok_or_else(|| anyhow!("Function not found: {}", func_name))?
-        };
+                }
+            }
+        });
+
+        match receiver.await {
+            Ok(Ok(response)) => Ok(response),
+            Ok(Err(code)) => bail!(
+                "Component '{}' failed to handle the request: {:?}",
+                component_id,
+                code
+            ),
+            // The guest dropped the response outparam without setting it
+            Err(_) => match task.await {
+                Ok(Ok(())) => bail!(
+                    "Component '{}' returned without setting a response",
+                    component_id
+                ),
+                Ok(Err(e)) => Err(e),
+                Err(e) => Err(e.into()),
+            },
+        }
+    }
 
-        let params: serde_json::Value = serde_json::from_str(parameters)?;
-        let argument_vals = json_to_vals(&params, &func.params(&store))?;
+    /// Executes a function call on a WebAssembly component
+    pub async fn execute_component_call(
+        &self,
+        component_id: &str,
+        function_name: &str,
+        parameters: &str,
+    ) -> Result<String> {
+        self.execute_component_call_with_output(component_id, function_name, parameters)
+            .await
+            .map(|output| output.result)
+    }
 
-        let mut results = create_placeholder_results(&func.results(&store));
+    /// Executes a function call on a WebAssembly component, also returning the
+    /// stdout/stderr captured from the guest when its policy exposes it.
+    ///
+    /// Captured output is always mirrored to `tracing`, whether or not the call succeeds.
+    #[instrument(skip(self))]
+    pub async fn execute_component_call_with_output(
+        &self,
+        component_id: &str,
+        function_name: &str,
+        parameters: &str,
+    ) -> Result<ComponentCallOutput> {
+        let component = self
+            .get_component(component_id)
+            .await
+            .ok_or_else(|| anyhow!("Component not found: {}", component_id))?;
 
-        func.call_async(&mut store, &argument_vals, &mut results)
+        let (mut store, call_timeout) = self
+            .new_call_store(component_id, Some(function_name))
             .await?;
 
-        let result_json = vals_to_json(&results);
+        let state = &store.data().inner;
+        let cpu_limit = state.cpu_limit;
+        let (stdout, stderr) = (state.stdout.clone(), state.stderr.clone());
+        let expose_output = state.expose_output;
+        let notices = store.data().notices();
 
-        if let Some(result_str) = result_json.as_str() {
-            Ok(result_str.to_string())
-        } else {
-            Ok(serde_json::to_string(&result_json)?)
+        let outcome: Result<String> = async {
+            let instance = component.instance_pre.instantiate_async(&mut store).await?;
+
+            // Use the new function identifier lookup instead of dot-splitting
+            let function_id = self
+                .registry
+                .tool_identifier(function_name)
+                .await
+                .ok_or_else(|| anyhow!("Unknown tool name: {}", function_name))?;
+
+            let (interface_name, func_name) = (
+                function_id.interface_name.as_deref().unwrap_or(""),
+                &function_id.function_name,
+            );
+
+            let func = if !interface_name.is_empty() {
+                let interface_index = instance
+                    .get_export_index(&mut store, None, interface_name)
+                    .ok_or_else(|| anyhow!("Interface not found: {}", interface_name))?;
+
+                let function_index = instance
+                    .get_export_index(&mut store, Some(&interface_index), func_name)
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "Function not found in interface: {}.{}",
+                            interface_name,
+                            func_name
+                        )
+                    })?;
+
+                instance
+                    .get_func(&mut store, function_index)
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "Function not found in interface: {}.{}",
+                            interface_name,
+                            func_name
+                        )
+                    })?
+            } else {
+                let func_index = instance
+                    .get_export_index(&mut store, None, func_name)
+                    .ok_or_else(|| anyhow!("Function not found: {}", func_name))?;
+                instance
+                    .get_func(&mut store, func_index)
+                    .ok_or_else(|| anyhow!("Function not found: {}", func_name))?
+            };
+
+            let params: serde_json::Value = serde_json::from_str(parameters)?;
+            let argument_vals = json_to_vals(&params, &func.params(&store))?;
+
+            let mut results = create_placeholder_results(&func.results(&store));
+
+            let call_result = tokio::time::timeout(
+                call_timeout,
+                func.call_async(&mut store, &argument_vals, &mut results),
+            )
+            .await;
+
+            let timed_out = || {
+                warn!(%component_id, timeout = ?call_timeout, "Component call timed out");
+                anyhow!(
+                    "Call timed out: component '{}' did not finish within {:?}",
+                    component_id,
+                    call_timeout
+                )
+            };
+
+            match call_result {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => match (e.downcast_ref::<wasmtime::Trap>(), cpu_limit) {
+                    (Some(wasmtime::Trap::OutOfFuel), Some(cores)) => {
+                        warn!(%component_id, cpu_limit = cores, "CPU budget exhausted");
+                        bail!(
+                            "CPU budget exhausted: component '{}' used up the per-call \
+                             instruction budget of its CPU limit of {} core(s)",
+                            component_id,
+                            cores
+                        );
+                    }
+                    (Some(wasmtime::Trap::Interrupt), _) => return Err(timed_out()),
+                    _ => return Err(e),
+                },
+                Err(_) => return Err(timed_out()),
+            }
+
+            let result_json = vals_to_json(&results);
+
+            if let Some(result_str) = result_json.as_str() {
+                Ok(result_str.to_string())
+            } else {
+                Ok(serde_json::to_string(&result_json)?)
+            }
+        }
+        .await;
+
+        let (stdout, stderr) = (stdout.contents(), stderr.contents());
+        if !stdout.is_empty() {
+            info!(%component_id, stream = "stdout", output = %stdout, "Captured component output");
+        }
+        if !stderr.is_empty() {
+            info!(%component_id, stream = "stderr", output = %stderr, "Captured component output");
         }
+
+        let notices = notices.take();
+        let outcome = match outcome {
+            Err(e) if !notices.is_empty() => Err(anyhow!("{}\n\n{}", e, notices.join("\n"))),
+            outcome => outcome,
+        };
+
+        let result = match outcome {
+            Ok(result) => result,
+            Err(e) if expose_output && !stderr.is_empty() => {
+                bail!("{}\n\nCaptured stderr:\n{}", e, stderr)
+            }
+            Err(e) => return Err(e),
+        };
+
+        let exposed = |output: String| (expose_output && !output.is_empty()).then_some(output);
+        Ok(ComponentCallOutput {
+            result,
+            stdout: exposed(stdout),
+            stderr: exposed(stderr),
+            notices,
+        })
     }
 
     /// Load existing components from plugin directory in the background with bounded parallelism
@@ -1252,6 +1595,11 @@ impl LifecycleManager {
         &self.secrets_manager
     }
 
+    /// Get the redactor that hides secret values in tool results and logs
+    pub fn secret_redactor(&self) -> &SecretRedactor {
+        self.secrets_manager.redactor()
+    }
+
     /// List secrets for a component
     pub async fn list_component_secrets(
         &self,
@@ -1430,6 +1778,75 @@ mod tests {
         Ok(component_path)
     }
 
+    #[test]
+    fn test_builder_call_timeout() -> Result<()> {
+        let tempdir = tempfile::tempdir()?;
+
+        let config = LifecycleManager::builder(&tempdir).build_config()?;
+        assert_eq!(
+            config.call_timeout(),
+            Duration::from_secs(DEFAULT_CALL_TIMEOUT_SECS)
+        );
+
+        let config = LifecycleManager::builder(&tempdir)
+            .with_call_timeout(Duration::from_secs(5))
+            .build_config()?;
+        assert_eq!(config.call_timeout(), Duration::from_secs(5));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_builder_network_audit_dir() -> Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let audit_dir = tempfile::tempdir()?;
+
+        let config = LifecycleManager::builder(&tempdir).build_config()?;
+        assert_eq!(
+            config.network_audit_dir(),
+            Some(config.plugin_dir().join(AUDIT_DIR).as_path())
+        );
+
+        let config = LifecycleManager::builder(&tempdir)
+            .with_network_audit_dir(audit_dir.path())
+            .build_config()?;
+        assert_eq!(config.network_audit_dir(), Some(audit_dir.path()));
+
+        let config = LifecycleManager::builder(&tempdir)
+            .with_network_audit_dir(audit_dir.path())
+            .with_network_audit(false)
+            .build_config()?;
+        assert_eq!(config.network_audit_dir(), None);
+
+        Ok(())
+    }
+
+    #[test(tokio::test)]
+    async fn test_disabled_network_audit_cannot_be_queried() -> Result<()> {
+        let tempdir = tempfile::tempdir()?;
+        let manager = LifecycleManager::builder(&tempdir)
+            .with_eager_loading(false)
+            .with_network_audit(false)
+            .build()
+            .await?;
+
+        let err = manager
+            .network_audit_events(&NetworkAuditQuery::default())
+            .unwrap_err();
+        assert!(err.to_string().contains("disabled"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_epoch_ticks_for_timeout() {
+        assert_eq!(epoch_ticks_for(Duration::ZERO), 0);
+        assert_eq!(epoch_ticks_for(Duration::from_millis(1)), 1);
+        assert_eq!(epoch_ticks_for(Duration::from_secs(1)), 10);
+        assert_eq!(epoch_ticks_for(Duration::from_millis(1050)), 11);
+        assert_eq!(epoch_ticks_for(Duration::MAX), u64::from(u32::MAX));
+    }
+
     #[test(tokio::test)]
     async fn test_lifecycle_manager_tool_registry() -> Result<()> {
         let manager = create_test_manager().await?;
@@ -1567,7 +1984,7 @@ permissions:
 
         // Test getting WASI state for component with attached policy
         let _wasi_state = manager
-            .get_wasi_state_for_component(TEST_COMPONENT_ID)
+            .get_wasi_state_for_component(TEST_COMPONENT_ID, None)
             .await?;
 
         Ok(())
@@ -0,0 +1,1204 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! In-memory directories for `mem://` storage grants.
+//!
+//! A `mem://` grant does not name a host path. Its seed, an archive and/or inline
+//! files from the policy, is read into memory once when the policy is loaded.
+//! Each call then gets its own [`MemoryFs`] built from those contents and kept in
+//! the call's state. [`GuardedFilesystem`](crate::storage_guard::GuardedFilesystem)
+//! serves the directory's descriptors and streams from it instead of from
+//! wasmtime-wasi, so anything the component writes is gone by the next call and
+//! no host file is ever touched.
+//!
+//! Each copy holds at most [`MAX_MEMORY_DIR_BYTES`] in [`MAX_MEMORY_DIR_FILES`]
+//! files and directories, seed included. There are no symlinks or hard links, and
+//! a file that is removed or replaced can no longer be used through descriptors
+//! that are still open on it.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{BufRead, Read};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context, Result};
+use bytes::Bytes;
+use policy::MemorySeed;
+use tracing::warn;
+use wasmtime_wasi::p2::bindings::clocks::wall_clock::Datetime;
+use wasmtime_wasi::p2::bindings::filesystem::types::{
+    DescriptorFlags, DescriptorStat, DescriptorType, DirectoryEntry, ErrorCode, MetadataHashValue,
+    NewTimestamp, OpenFlags,
+};
+use wasmtime_wasi::p2::{FsResult, InputStream, OutputStream, Pollable, StreamError, StreamResult};
+
+use crate::storage_guard::{check_storage_grant, storage_host_path};
+use crate::storage_quota::QuotaExceeded;
+
+/// First two bytes of a gzip stream
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Most bytes a `mem://` directory can hold
+pub(crate) const MAX_MEMORY_DIR_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Most files and directories a `mem://` directory can hold
+pub(crate) const MAX_MEMORY_DIR_FILES: u64 = 10_000;
+
+/// Most bytes a stream reads or accepts at once
+const MAX_STREAM_CHUNK: usize = 1024 * 1024;
+
+/// The contents a `mem://` directory starts out with
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct MemoryDir {
+    /// Relative paths with their contents, `None` for a directory
+    entries: BTreeMap<PathBuf, Option<Vec<u8>>>,
+    /// Total size of the files in `entries`
+    bytes: u64,
+}
+
+impl MemoryDir {
+    /// Read the seed of a `mem://` grant, resolving and checking the archive path
+    /// like an `fs://` grant relative to `plugin_dir`
+    pub(crate) fn load(seed: Option<&MemorySeed>, plugin_dir: &Path) -> Result<Self> {
+        let mut dir = Self::default();
+        let Some(seed) = seed else {
+            return Ok(dir);
+        };
+        if let Some(tarball) = &seed.tarball {
+            check_storage_grant(tarball, plugin_dir)?;
+            let path = storage_host_path(tarball, plugin_dir)?;
+            let archive = std::fs::File::open(&path)
+                .with_context(|| format!("Failed to read seed archive {}", path.display()))?;
+            dir.unpack(std::io::BufReader::new(archive))
+                .with_context(|| format!("Failed to unpack seed archive {}", path.display()))?;
+        }
+        for (path, contents) in seed.files.iter().flatten() {
+            dir.insert(
+                relative_seed_path(Path::new(path))?,
+                Some(contents.clone().into_bytes()),
+            )?;
+        }
+        Ok(dir)
+    }
+
+    /// Add the directories and regular files of a `.tar` or `.tar.gz` archive
+    fn unpack(&mut self, mut archive: impl BufRead) -> Result<()> {
+        if archive.fill_buf()?.starts_with(&GZIP_MAGIC) {
+            self.unpack_tar(flate2::bufread::GzDecoder::new(archive))
+        } else {
+            self.unpack_tar(archive)
+        }
+    }
+
+    fn unpack_tar(&mut self, archive: impl Read) -> Result<()> {
+        let mut archive = tar::Archive::new(archive);
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            // Checked before the entry is read so a small archive can't inflate
+            // into more memory than the directory may hold
+            self.check_limits(entry.size(), 1)?;
+            let path = relative_seed_path(&entry.path()?)?;
+            if path.as_os_str().is_empty() {
+                continue;
+            }
+            let entry_type = entry.header().entry_type();
+            if entry_type.is_dir() {
+                self.insert(path, None)?;
+            } else if entry_type.is_file() {
+                let mut contents = Vec::new();
+                entry.read_to_end(&mut contents)?;
+                self.insert(path, Some(contents))?;
+            } else {
+                // Links could point anywhere on the host once written out
+                warn!(path = %path.display(), ?entry_type, "Skipping unsupported entry in seed archive");
+            }
+        }
+        Ok(())
+    }
+
+    /// Add an entry, replacing an earlier one at the same path
+    fn insert(&mut self, path: PathBuf, contents: Option<Vec<u8>>) -> Result<()> {
+        let size = |contents: &Option<Vec<u8>>| contents.as_ref().map_or(0, |c| c.len() as u64);
+        let added = size(&contents);
+        let replaced = self
+            .entries
+            .insert(path, contents)
+            .map_or(0, |old| size(&old));
+        self.bytes = self.bytes - replaced + added;
+        self.check_limits(0, 0)
+    }
+
+    /// Fail if the seed with `bytes` and `files` more wouldn't fit in the directory
+    fn check_limits(&self, bytes: u64, files: u64) -> Result<()> {
+        if self.bytes.saturating_add(bytes) > MAX_MEMORY_DIR_BYTES {
+            bail!("Seed holds more than the {MAX_MEMORY_DIR_BYTES} bytes a mem:// directory can");
+        }
+        if (self.entries.len() as u64).saturating_add(files) > MAX_MEMORY_DIR_FILES {
+            bail!("Seed holds more than the {MAX_MEMORY_DIR_FILES} files a mem:// directory can");
+        }
+        Ok(())
+    }
+
+    /// Build a fresh in-memory copy of the contents for one call. `scope` names
+    /// the directory in errors, and a copy that isn't `writable` refuses changes.
+    pub(crate) fn materialize(&self, scope: &str, writable: bool) -> Result<MemoryFs> {
+        let fs = MemoryFs::new(scope, writable);
+        let mut nodes = fs.lock();
+        for (path, contents) in &self.entries {
+            let parts = path
+                .iter()
+                .map(|part| part.to_str())
+                .collect::<Option<Vec<&str>>>()
+                .with_context(|| format!("Seed path isn't valid UTF-8: {}", path.display()))?;
+            let Some((name, parents)) = parts.split_last() else {
+                continue;
+            };
+            let mut dir = ROOT;
+            for part in parents {
+                dir = nodes.seed_dir(dir, part, path)?;
+            }
+            match contents {
+                None => {
+                    nodes.seed_dir(dir, name, path)?;
+                }
+                Some(contents) => {
+                    nodes
+                        .add(dir, name, Contents::File(contents.clone()))
+                        .map_err(|_| anyhow::anyhow!("Seed path clashes: {}", path.display()))?;
+                }
+            }
+        }
+        drop(nodes);
+        Ok(fs)
+    }
+}
+
+/// Check that a path from a seed stays inside the directory, dropping `.` components
+fn relative_seed_path(path: &Path) -> Result<PathBuf> {
+    let mut relative = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => relative.push(part),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => {
+                bail!("Seed path leaves the directory: {}", path.display())
+            }
+        }
+    }
+    Ok(relative)
+}
+
+/// Inode of the directory a [`MemoryFs`] is mounted at
+const ROOT: u64 = 0;
+
+/// One call's copy of a `mem://` directory. Clones share the same contents.
+#[derive(Clone)]
+pub(crate) struct MemoryFs {
+    nodes: Arc<Mutex<Nodes>>,
+    /// Whether the grant lets the component change anything
+    writable: bool,
+}
+
+impl MemoryFs {
+    fn new(scope: &str, writable: bool) -> Self {
+        let root = Node::new(Contents::Dir(BTreeMap::new()), ROOT);
+        Self {
+            nodes: Arc::new(Mutex::new(Nodes {
+                scope: scope.to_string(),
+                nodes: HashMap::from([(ROOT, root)]),
+                next_inode: ROOT + 1,
+                bytes: 0,
+            })),
+            writable,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Nodes> {
+        self.nodes.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// A descriptor for the top of the directory, as handed out for the preopen
+    pub(crate) fn root(&self) -> MemoryDescriptor {
+        let mut flags = DescriptorFlags::READ;
+        if self.writable {
+            flags |= DescriptorFlags::MUTATE_DIRECTORY;
+        }
+        MemoryDescriptor {
+            fs: self.clone(),
+            inode: ROOT,
+            flags,
+        }
+    }
+
+    fn check_writable(&self) -> Result<(), ErrorCode> {
+        if self.writable {
+            Ok(())
+        } else {
+            Err(ErrorCode::NotPermitted)
+        }
+    }
+}
+
+enum Contents {
+    /// Entry names to inodes
+    Dir(BTreeMap<String, u64>),
+    File(Vec<u8>),
+}
+
+struct Node {
+    contents: Contents,
+    /// Directory the node is linked into, the root is its own parent
+    parent: u64,
+    accessed: SystemTime,
+    modified: SystemTime,
+}
+
+impl Node {
+    fn new(contents: Contents, parent: u64) -> Self {
+        let now = SystemTime::now();
+        Self {
+            contents,
+            parent,
+            accessed: now,
+            modified: now,
+        }
+    }
+
+    fn is_dir(&self) -> bool {
+        matches!(self.contents, Contents::Dir(_))
+    }
+
+    fn len(&self) -> u64 {
+        match &self.contents {
+            Contents::Dir(_) => 0,
+            Contents::File(data) => data.len() as u64,
+        }
+    }
+}
+
+/// The files and directories of a [`MemoryFs`], by inode
+struct Nodes {
+    scope: String,
+    nodes: HashMap<u64, Node>,
+    next_inode: u64,
+    /// Total size of the files
+    bytes: u64,
+}
+
+impl Nodes {
+    fn node(&self, inode: u64) -> Result<&Node, ErrorCode> {
+        self.nodes.get(&inode).ok_or(ErrorCode::BadDescriptor)
+    }
+
+    fn node_mut(&mut self, inode: u64) -> Result<&mut Node, ErrorCode> {
+        self.nodes.get_mut(&inode).ok_or(ErrorCode::BadDescriptor)
+    }
+
+    fn entries(&self, inode: u64) -> Result<&BTreeMap<String, u64>, ErrorCode> {
+        match &self.node(inode)?.contents {
+            Contents::Dir(entries) => Ok(entries),
+            Contents::File(_) => Err(ErrorCode::NotDirectory),
+        }
+    }
+
+    fn entries_mut(&mut self, inode: u64) -> Result<&mut BTreeMap<String, u64>, ErrorCode> {
+        match &mut self.node_mut(inode)?.contents {
+            Contents::Dir(entries) => Ok(entries),
+            Contents::File(_) => Err(ErrorCode::NotDirectory),
+        }
+    }
+
+    fn data_mut(&mut self, inode: u64) -> Result<&mut Vec<u8>, ErrorCode> {
+        match &mut self.node_mut(inode)?.contents {
+            Contents::File(data) => Ok(data),
+            Contents::Dir(_) => Err(ErrorCode::IsDirectory),
+        }
+    }
+
+    /// Walk `path` from the directory `dir`, returning the directories passed
+    /// through, starting with `dir`. `..` can't climb above `dir`, the same as
+    /// for host directories.
+    fn walk(&self, dir: u64, path: &str) -> Result<Vec<u64>, ErrorCode> {
+        if path.starts_with('/') {
+            return Err(ErrorCode::NotPermitted);
+        }
+        if path.is_empty() {
+            return Err(ErrorCode::NoEntry);
+        }
+        let mut stack = vec![dir];
+        for part in path.split('/') {
+            let current = *stack.last().expect("the stack starts with `dir`");
+            match part {
+                "" | "." => {
+                    self.entries(current)?;
+                }
+                ".." => {
+                    self.entries(current)?;
+                    stack.pop();
+                    if stack.is_empty() {
+                        return Err(ErrorCode::NotPermitted);
+                    }
+                }
+                name => {
+                    let child = *self.entries(current)?.get(name).ok_or(ErrorCode::NoEntry)?;
+                    stack.push(child);
+                }
+            }
+        }
+        Ok(stack)
+    }
+
+    /// Resolve `path` relative to `dir` to an inode
+    fn resolve(&self, dir: u64, path: &str) -> Result<u64, ErrorCode> {
+        Ok(*self
+            .walk(dir, path)?
+            .last()
+            .expect("walk returns `dir` at least"))
+    }
+
+    /// Resolve all but the last component of `path`, returning the directory
+    /// holding the entry and the entry's name
+    fn resolve_parent<'p>(&self, dir: u64, path: &'p str) -> Result<(u64, &'p str), ErrorCode> {
+        let path = path.trim_end_matches('/');
+        let (parent, name) = match path.rsplit_once('/') {
+            Some((parent, name)) => (self.resolve(dir, parent)?, name),
+            None => (dir, path),
+        };
+        if path.starts_with('/') {
+            return Err(ErrorCode::NotPermitted);
+        }
+        if matches!(name, "" | "." | "..") {
+            return Err(ErrorCode::Invalid);
+        }
+        self.entries(parent)?;
+        Ok((parent, name))
+    }
+
+    fn child(&self, dir: u64, name: &str) -> Result<u64, ErrorCode> {
+        self.entries(dir)?
+            .get(name)
+            .copied()
+            .ok_or(ErrorCode::NoEntry)
+    }
+
+    /// Fail with `quota` unless `bytes` and `files` more fit in the directory
+    fn charge(&self, bytes: u64, files: u64) -> Result<(), ErrorCode> {
+        let stored = self.nodes.len() as u64 - 1;
+        if self.bytes.saturating_add(bytes) > MAX_MEMORY_DIR_BYTES
+            || stored.saturating_add(files) > MAX_MEMORY_DIR_FILES
+        {
+            warn!(scope = %self.scope, "Filesystem write blocked by the mem:// size limit");
+            return Err(ErrorCode::Quota);
+        }
+        Ok(())
+    }
+
+    /// Create `name` in the directory `dir`
+    fn add(&mut self, dir: u64, name: &str, contents: Contents) -> Result<u64, ErrorCode> {
+        if self.entries(dir)?.contains_key(name) {
+            return Err(ErrorCode::Exist);
+        }
+        let node = Node::new(contents, dir);
+        let len = node.len();
+        self.charge(len, 1)?;
+        let inode = self.next_inode;
+        self.next_inode += 1;
+        self.nodes.insert(inode, node);
+        self.bytes += len;
+        self.entries_mut(dir)?.insert(name.to_string(), inode);
+        self.node_mut(dir)?.modified = SystemTime::now();
+        Ok(inode)
+    }
+
+    /// Find or create the directory `name` in `dir` while building from a seed
+    fn seed_dir(&mut self, dir: u64, name: &str, path: &Path) -> Result<u64> {
+        let clash = || anyhow::anyhow!("Seed path clashes: {}", path.display());
+        match self.child(dir, name) {
+            Ok(inode) if self.node(inode).is_ok_and(Node::is_dir) => Ok(inode),
+            Ok(_) => Err(clash()),
+            Err(_) => self
+                .add(dir, name, Contents::Dir(BTreeMap::new()))
+                .map_err(|_| clash()),
+        }
+    }
+
+    /// Unlink `name` from `dir` and drop what it referred to
+    fn remove(&mut self, dir: u64, name: &str) -> Result<(), ErrorCode> {
+        let inode = self
+            .entries_mut(dir)?
+            .remove(name)
+            .ok_or(ErrorCode::NoEntry)?;
+        if let Some(node) = self.nodes.remove(&inode) {
+            self.bytes -= node.len();
+        }
+        self.node_mut(dir)?.modified = SystemTime::now();
+        Ok(())
+    }
+
+    /// Whether `ancestor` is `inode` or one of the directories above it
+    fn is_within(&self, mut inode: u64, ancestor: u64) -> bool {
+        loop {
+            if inode == ancestor {
+                return true;
+            }
+            match self.nodes.get(&inode) {
+                Some(node) if node.parent != inode => inode = node.parent,
+                _ => return false,
+            }
+        }
+    }
+
+    fn read_at(&mut self, inode: u64, offset: u64, len: usize) -> Result<Vec<u8>, ErrorCode> {
+        let node = self.node_mut(inode)?;
+        node.accessed = SystemTime::now();
+        let Contents::File(data) = &node.contents else {
+            return Err(ErrorCode::IsDirectory);
+        };
+        let start = usize::try_from(offset)
+            .unwrap_or(usize::MAX)
+            .min(data.len());
+        let end = start.saturating_add(len).min(data.len());
+        Ok(data[start..end].to_vec())
+    }
+
+    fn write_at(&mut self, inode: u64, offset: u64, buf: &[u8]) -> Result<(), ErrorCode> {
+        let end = offset.saturating_add(buf.len() as u64);
+        self.set_len_at_least(inode, end)?;
+        let start = usize::try_from(offset).map_err(|_| ErrorCode::Overflow)?;
+        let data = self.data_mut(inode)?;
+        data[start..start + buf.len()].copy_from_slice(buf);
+        self.node_mut(inode)?.modified = SystemTime::now();
+        Ok(())
+    }
+
+    /// Grow the file to `len` bytes if it is shorter
+    fn set_len_at_least(&mut self, inode: u64, len: u64) -> Result<(), ErrorCode> {
+        let current = self.data_mut(inode)?.len() as u64;
+        if len > current {
+            self.set_len(inode, len)?;
+        }
+        Ok(())
+    }
+
+    fn set_len(&mut self, inode: u64, len: u64) -> Result<(), ErrorCode> {
+        let current = self.data_mut(inode)?.len() as u64;
+        self.charge(len.saturating_sub(current), 0)?;
+        let new_len = usize::try_from(len).map_err(|_| ErrorCode::Overflow)?;
+        self.data_mut(inode)?.resize(new_len, 0);
+        self.bytes = self.bytes - current + len;
+        self.node_mut(inode)?.modified = SystemTime::now();
+        Ok(())
+    }
+
+    fn set_times(
+        &mut self,
+        inode: u64,
+        atim: NewTimestamp,
+        mtim: NewTimestamp,
+    ) -> Result<(), ErrorCode> {
+        let node = self.node_mut(inode)?;
+        if let Some(accessed) = system_time(atim) {
+            node.accessed = accessed;
+        }
+        if let Some(modified) = system_time(mtim) {
+            node.modified = modified;
+        }
+        Ok(())
+    }
+
+    fn stat(&self, inode: u64) -> Result<DescriptorStat, ErrorCode> {
+        let node = self.node(inode)?;
+        Ok(DescriptorStat {
+            type_: descriptor_type(node),
+            link_count: 1,
+            size: node.len(),
+            data_access_timestamp: Some(datetime(node.accessed)),
+            data_modification_timestamp: Some(datetime(node.modified)),
+            status_change_timestamp: Some(datetime(node.modified)),
+        })
+    }
+
+    fn rename(
+        &mut self,
+        old_dir: u64,
+        old_path: &str,
+        new_dir: u64,
+        new_path: &str,
+    ) -> Result<(), ErrorCode> {
+        let (old_parent, old_name) = self.resolve_parent(old_dir, old_path)?;
+        let source = self.child(old_parent, old_name)?;
+        let (new_parent, new_name) = self.resolve_parent(new_dir, new_path)?;
+        if self.is_within(new_parent, source) {
+            return Err(ErrorCode::Invalid);
+        }
+        if let Ok(existing) = self.child(new_parent, new_name) {
+            if existing == source {
+                return Ok(());
+            }
+            match (self.node(source)?.is_dir(), self.node(existing)?.is_dir()) {
+                (false, true) => return Err(ErrorCode::IsDirectory),
+                (true, false) => return Err(ErrorCode::NotDirectory),
+                (true, true) if !self.entries(existing)?.is_empty() => {
+                    return Err(ErrorCode::NotEmpty)
+                }
+                _ => {}
+            }
+            self.remove(new_parent, new_name)?;
+        }
+        self.entries_mut(old_parent)?.remove(old_name);
+        self.entries_mut(new_parent)?
+            .insert(new_name.to_string(), source);
+        self.node_mut(source)?.parent = new_parent;
+        let now = SystemTime::now();
+        self.node_mut(old_parent)?.modified = now;
+        self.node_mut(new_parent)?.modified = now;
+        Ok(())
+    }
+}
+
+fn descriptor_type(node: &Node) -> DescriptorType {
+    if node.is_dir() {
+        DescriptorType::Directory
+    } else {
+        DescriptorType::RegularFile
+    }
+}
+
+fn datetime(time: SystemTime) -> Datetime {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    Datetime {
+        seconds: since_epoch.as_secs(),
+        nanoseconds: since_epoch.subsec_nanos(),
+    }
+}
+
+fn system_time(timestamp: NewTimestamp) -> Option<SystemTime> {
+    match timestamp {
+        NewTimestamp::NoChange => None,
+        NewTimestamp::Now => Some(SystemTime::now()),
+        NewTimestamp::Timestamp(datetime) => {
+            Some(UNIX_EPOCH + Duration::new(datetime.seconds, datetime.nanoseconds))
+        }
+    }
+}
+
+/// A descriptor for a file or directory in a [`MemoryFs`], kept in the resource
+/// table in place of a wasmtime-wasi descriptor. Its methods follow the
+/// `wasi:filesystem` descriptor methods of the same name.
+#[derive(Clone)]
+pub(crate) struct MemoryDescriptor {
+    fs: MemoryFs,
+    inode: u64,
+    flags: DescriptorFlags,
+}
+
+impl MemoryDescriptor {
+    fn readable(&self) -> bool {
+        self.flags.contains(DescriptorFlags::READ) || !self.flags.contains(DescriptorFlags::WRITE)
+    }
+
+    fn check_file_writable(&self) -> Result<(), ErrorCode> {
+        self.fs.check_writable()?;
+        if !self.flags.contains(DescriptorFlags::WRITE) {
+            return Err(ErrorCode::BadDescriptor);
+        }
+        Ok(())
+    }
+
+    pub(crate) fn get_flags(&self) -> DescriptorFlags {
+        self.flags
+    }
+
+    pub(crate) fn get_type(&self) -> FsResult<DescriptorType> {
+        Ok(descriptor_type(self.fs.lock().node(self.inode)?))
+    }
+
+    pub(crate) fn set_size(&self, size: u64) -> FsResult<()> {
+        self.check_file_writable()?;
+        Ok(self.fs.lock().set_len(self.inode, size)?)
+    }
+
+    pub(crate) fn set_times(&self, atim: NewTimestamp, mtim: NewTimestamp) -> FsResult<()> {
+        self.fs.check_writable()?;
+        Ok(self.fs.lock().set_times(self.inode, atim, mtim)?)
+    }
+
+    pub(crate) fn read(&self, len: u64, offset: u64) -> FsResult<(Vec<u8>, bool)> {
+        if !self.readable() {
+            return Err(ErrorCode::BadDescriptor.into());
+        }
+        let len = usize::try_from(len).unwrap_or(usize::MAX);
+        let data = self.fs.lock().read_at(self.inode, offset, len)?;
+        let end = data.is_empty();
+        Ok((data, end))
+    }
+
+    pub(crate) fn write(&self, buf: &[u8], offset: u64) -> FsResult<u64> {
+        self.check_file_writable()?;
+        self.fs.lock().write_at(self.inode, offset, buf)?;
+        Ok(buf.len() as u64)
+    }
+
+    pub(crate) fn read_directory(&self) -> FsResult<MemoryDirectoryEntries> {
+        let nodes = self.fs.lock();
+        let entries = nodes
+            .entries(self.inode)?
+            .iter()
+            .filter_map(|(name, inode)| {
+                Some(DirectoryEntry {
+                    type_: descriptor_type(nodes.node(*inode).ok()?),
+                    name: name.clone(),
+                })
+            })
+            .collect::<Vec<_>>();
+        Ok(MemoryDirectoryEntries::new(entries))
+    }
+
+    pub(crate) fn create_directory_at(&self, path: &str) -> FsResult<()> {
+        self.fs.check_writable()?;
+        let mut nodes = self.fs.lock();
+        let (parent, name) = nodes.resolve_parent(self.inode, path)?;
+        nodes.add(parent, name, Contents::Dir(BTreeMap::new()))?;
+        Ok(())
+    }
+
+    pub(crate) fn stat(&self) -> FsResult<DescriptorStat> {
+        Ok(self.fs.lock().stat(self.inode)?)
+    }
+
+    pub(crate) fn stat_at(&self, path: &str) -> FsResult<DescriptorStat> {
+        let nodes = self.fs.lock();
+        Ok(nodes.stat(nodes.resolve(self.inode, path)?)?)
+    }
+
+    pub(crate) fn set_times_at(
+        &self,
+        path: &str,
+        atim: NewTimestamp,
+        mtim: NewTimestamp,
+    ) -> FsResult<()> {
+        self.fs.check_writable()?;
+        let mut nodes = self.fs.lock();
+        let inode = nodes.resolve(self.inode, path)?;
+        Ok(nodes.set_times(inode, atim, mtim)?)
+    }
+
+    pub(crate) fn open_at(
+        &self,
+        path: &str,
+        oflags: OpenFlags,
+        flags: DescriptorFlags,
+    ) -> FsResult<MemoryDescriptor> {
+        if oflags.intersects(OpenFlags::CREATE | OpenFlags::TRUNCATE)
+            || flags.intersects(DescriptorFlags::WRITE | DescriptorFlags::MUTATE_DIRECTORY)
+        {
+            self.fs.check_writable()?;
+        }
+        if oflags.contains(OpenFlags::DIRECTORY)
+            && oflags.intersects(OpenFlags::CREATE | OpenFlags::EXCLUSIVE | OpenFlags::TRUNCATE)
+        {
+            return Err(ErrorCode::Invalid.into());
+        }
+        let mut nodes = self.fs.lock();
+        nodes.entries(self.inode)?;
+        let inode = match nodes.resolve(self.inode, path) {
+            Ok(_) if oflags.contains(OpenFlags::CREATE | OpenFlags::EXCLUSIVE) => {
+                return Err(ErrorCode::Exist.into());
+            }
+            Ok(inode) => inode,
+            Err(ErrorCode::NoEntry) if oflags.contains(OpenFlags::CREATE) => {
+                let (parent, name) = nodes.resolve_parent(self.inode, path)?;
+                nodes.add(parent, name, Contents::File(Vec::new()))?
+            }
+            Err(code) => return Err(code.into()),
+        };
+        if nodes.node(inode)?.is_dir() {
+            if flags.contains(DescriptorFlags::WRITE) || oflags.contains(OpenFlags::TRUNCATE) {
+                return Err(ErrorCode::IsDirectory.into());
+            }
+        } else if oflags.contains(OpenFlags::DIRECTORY) {
+            return Err(ErrorCode::NotDirectory.into());
+        } else if oflags.contains(OpenFlags::TRUNCATE) {
+            nodes.set_len(inode, 0)?;
+        }
+        Ok(MemoryDescriptor {
+            fs: self.fs.clone(),
+            inode,
+            flags,
+        })
+    }
+
+    pub(crate) fn readlink_at(&self, path: &str) -> FsResult<String> {
+        // There are no symlinks, so anything that exists is the wrong kind of entry
+        self.fs.lock().resolve(self.inode, path)?;
+        Err(ErrorCode::Invalid.into())
+    }
+
+    pub(crate) fn remove_directory_at(&self, path: &str) -> FsResult<()> {
+        self.fs.check_writable()?;
+        let mut nodes = self.fs.lock();
+        let (parent, name) = nodes.resolve_parent(self.inode, path)?;
+        let inode = nodes.child(parent, name)?;
+        if !nodes.entries(inode)?.is_empty() {
+            return Err(ErrorCode::NotEmpty.into());
+        }
+        Ok(nodes.remove(parent, name)?)
+    }
+
+    pub(crate) fn rename_at(
+        &self,
+        old_path: &str,
+        new_descriptor: &MemoryDescriptor,
+        new_path: &str,
+    ) -> FsResult<()> {
+        if !Arc::ptr_eq(&self.fs.nodes, &new_descriptor.fs.nodes) {
+            return Err(ErrorCode::CrossDevice.into());
+        }
+        self.fs.check_writable()?;
+        let mut nodes = self.fs.lock();
+        Ok(nodes.rename(self.inode, old_path, new_descriptor.inode, new_path)?)
+    }
+
+    pub(crate) fn unlink_file_at(&self, path: &str) -> FsResult<()> {
+        self.fs.check_writable()?;
+        let mut nodes = self.fs.lock();
+        let (parent, name) = nodes.resolve_parent(self.inode, path)?;
+        let inode = nodes.child(parent, name)?;
+        if nodes.node(inode)?.is_dir() {
+            return Err(ErrorCode::IsDirectory.into());
+        }
+        Ok(nodes.remove(parent, name)?)
+    }
+
+    pub(crate) fn read_via_stream(&self, offset: u64) -> FsResult<MemoryInputStream> {
+        if !self.readable() {
+            return Err(ErrorCode::BadDescriptor.into());
+        }
+        if self.fs.lock().node(self.inode)?.is_dir() {
+            return Err(ErrorCode::IsDirectory.into());
+        }
+        Ok(MemoryInputStream {
+            fs: self.fs.clone(),
+            inode: self.inode,
+            position: offset,
+        })
+    }
+
+    /// A stream writing at `offset`, or at the end of the file when `None`
+    pub(crate) fn write_via_stream(&self, offset: Option<u64>) -> FsResult<MemoryOutputStream> {
+        self.check_file_writable()?;
+        if self.fs.lock().node(self.inode)?.is_dir() {
+            return Err(ErrorCode::IsDirectory.into());
+        }
+        Ok(MemoryOutputStream {
+            fs: self.fs.clone(),
+            inode: self.inode,
+            position: offset,
+        })
+    }
+
+    pub(crate) fn is_same_object(&self, other: &MemoryDescriptor) -> bool {
+        Arc::ptr_eq(&self.fs.nodes, &other.fs.nodes) && self.inode == other.inode
+    }
+
+    pub(crate) fn metadata_hash(&self) -> FsResult<MetadataHashValue> {
+        self.fs.lock().node(self.inode)?;
+        Ok(self.hash_of(self.inode))
+    }
+
+    pub(crate) fn metadata_hash_at(&self, path: &str) -> FsResult<MetadataHashValue> {
+        let inode = self.fs.lock().resolve(self.inode, path)?;
+        Ok(self.hash_of(inode))
+    }
+
+    fn hash_of(&self, inode: u64) -> MetadataHashValue {
+        MetadataHashValue {
+            lower: inode,
+            upper: Arc::as_ptr(&self.fs.nodes) as u64,
+        }
+    }
+}
+
+/// The entries of a directory in a [`MemoryFs`], kept in the resource table in
+/// place of a wasmtime-wasi directory entry stream
+pub(crate) struct MemoryDirectoryEntries(std::vec::IntoIter<DirectoryEntry>);
+
+impl MemoryDirectoryEntries {
+    /// Serve an already collected listing
+    pub(crate) fn new(entries: Vec<DirectoryEntry>) -> Self {
+        Self(entries.into_iter())
+    }
+}
+
+impl Iterator for MemoryDirectoryEntries {
+    type Item = DirectoryEntry;
+
+    fn next(&mut self) -> Option<DirectoryEntry> {
+        self.0.next()
+    }
+}
+
+/// The error behind a stream operation on a file that was removed
+fn removed_file() -> StreamError {
+    StreamError::LastOperationFailed(anyhow::anyhow!("The file was removed"))
+}
+
+pub(crate) struct MemoryInputStream {
+    fs: MemoryFs,
+    inode: u64,
+    position: u64,
+}
+
+#[async_trait::async_trait]
+impl Pollable for MemoryInputStream {
+    async fn ready(&mut self) {}
+}
+
+#[async_trait::async_trait]
+impl InputStream for MemoryInputStream {
+    fn read(&mut self, size: usize) -> StreamResult<Bytes> {
+        let data = self
+            .fs
+            .lock()
+            .read_at(self.inode, self.position, size.min(MAX_STREAM_CHUNK))
+            .map_err(|_| removed_file())?;
+        if data.is_empty() && size > 0 {
+            return Err(StreamError::Closed);
+        }
+        self.position += data.len() as u64;
+        Ok(Bytes::from(data))
+    }
+}
+
+pub(crate) struct MemoryOutputStream {
+    fs: MemoryFs,
+    inode: u64,
+    /// Offset of the next write, `None` for an append stream
+    position: Option<u64>,
+}
+
+#[async_trait::async_trait]
+impl Pollable for MemoryOutputStream {
+    async fn ready(&mut self) {}
+}
+
+#[async_trait::async_trait]
+impl OutputStream for MemoryOutputStream {
+    fn write(&mut self, bytes: Bytes) -> StreamResult<()> {
+        let mut nodes = self.fs.lock();
+        let offset = match self.position {
+            Some(position) => position,
+            None => nodes.node(self.inode).map_err(|_| removed_file())?.len(),
+        };
+        nodes
+            .write_at(self.inode, offset, &bytes)
+            .map_err(|code| match code {
+                ErrorCode::Quota => {
+                    StreamError::LastOperationFailed(QuotaExceeded::new(nodes.scope.clone()).into())
+                }
+                _ => removed_file(),
+            })?;
+        if let Some(position) = &mut self.position {
+            *position = offset + bytes.len() as u64;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> StreamResult<()> {
+        Ok(())
+    }
+
+    fn check_write(&mut self) -> StreamResult<usize> {
+        Ok(MAX_STREAM_CHUNK)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn tarball(gzip: bool) -> Vec<u8> {
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Directory);
+        header.set_size(0);
+        header.set_mode(0o755);
+        builder
+            .append_data(&mut header, "fixtures/", std::io::empty())
+            .unwrap();
+        let mut header = tar::Header::new_gnu();
+        header.set_size(5);
+        header.set_mode(0o644);
+        builder
+            .append_data(&mut header, "fixtures/input.txt", &b"hello"[..])
+            .unwrap();
+        let archive = builder.into_inner().unwrap();
+        if !gzip {
+            return archive;
+        }
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::fast());
+        std::io::Write::write_all(&mut encoder, &archive).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn test_load_and_materialize() {
+        let plugin_dir = TempDir::new().unwrap();
+        std::fs::write(plugin_dir.path().join("seed.tar.gz"), tarball(true)).unwrap();
+        let seed = MemorySeed {
+            tarball: Some("seed.tar.gz".to_string()),
+            files: Some(BTreeMap::from([
+                ("fixtures/input.txt".to_string(), "replaced".to_string()),
+                ("./config.json".to_string(), "{}".to_string()),
+            ])),
+        };
+        let dir = MemoryDir::load(Some(&seed), plugin_dir.path()).unwrap();
+
+        let first = dir.materialize("/fixtures", true).unwrap().root();
+        assert_eq!(read(&first, "fixtures/input.txt"), "replaced");
+        assert_eq!(read(&first, "config.json"), "{}");
+
+        // Writes to one copy don't show up in the next one
+        let file = first
+            .open_at(
+                "fixtures/input.txt",
+                OpenFlags::TRUNCATE,
+                DescriptorFlags::WRITE,
+            )
+            .unwrap();
+        file.write(b"changed", 0).unwrap();
+        assert_eq!(read(&first, "fixtures/input.txt"), "changed");
+        let second = dir.materialize("/fixtures", true).unwrap().root();
+        assert_eq!(read(&second, "fixtures/input.txt"), "replaced");
+    }
+
+    fn read(dir: &MemoryDescriptor, path: &str) -> String {
+        let file = dir
+            .open_at(path, OpenFlags::empty(), DescriptorFlags::READ)
+            .unwrap();
+        let (data, _) = file.read(1024, 0).unwrap();
+        String::from_utf8(data).unwrap()
+    }
+
+    fn error_code(err: wasmtime_wasi::p2::FsError) -> ErrorCode {
+        err.downcast().unwrap()
+    }
+
+    #[test]
+    fn test_memory_fs() {
+        let root = MemoryDir::default()
+            .materialize("/data", true)
+            .unwrap()
+            .root();
+        root.create_directory_at("a").unwrap();
+        let file = root
+            .open_at("a/b.txt", OpenFlags::CREATE, DescriptorFlags::WRITE)
+            .unwrap();
+        file.write(b"hello", 0).unwrap();
+        assert_eq!(
+            error_code(
+                root.open_at(
+                    "a/b.txt",
+                    OpenFlags::CREATE | OpenFlags::EXCLUSIVE,
+                    DescriptorFlags::WRITE
+                )
+                .err()
+                .unwrap()
+            ),
+            ErrorCode::Exist
+        );
+
+        // Paths can't climb out of the directory
+        for path in ["..", "a/../..", "/etc/passwd"] {
+            assert_eq!(
+                error_code(root.stat_at(path).unwrap_err()),
+                ErrorCode::NotPermitted
+            );
+        }
+        assert_eq!(root.stat_at("a/../a/b.txt").unwrap().size, 5);
+
+        root.rename_at("a/b.txt", &root, "c.txt").unwrap();
+        assert_eq!(read(&root, "c.txt"), "hello");
+        assert_eq!(
+            error_code(root.stat_at("a/b.txt").unwrap_err()),
+            ErrorCode::NoEntry
+        );
+        let a = root
+            .open_at("a", OpenFlags::DIRECTORY, DescriptorFlags::READ)
+            .unwrap();
+        assert_eq!(
+            error_code(root.rename_at("a", &a, "inside").unwrap_err()),
+            ErrorCode::Invalid
+        );
+
+        let names = root
+            .read_directory()
+            .unwrap()
+            .map(|entry| entry.name)
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["a", "c.txt"]);
+
+        // Descriptors on a removed file stop working
+        root.unlink_file_at("c.txt").unwrap();
+        assert_eq!(
+            error_code(file.stat().unwrap_err()),
+            ErrorCode::BadDescriptor
+        );
+        root.remove_directory_at("a").unwrap();
+        assert_eq!(root.read_directory().unwrap().count(), 0);
+
+        // A read-only copy refuses changes
+        let root = MemoryDir::default()
+            .materialize("/data", false)
+            .unwrap()
+            .root();
+        assert_eq!(
+            error_code(
+                root.open_at("new.txt", OpenFlags::CREATE, DescriptorFlags::WRITE)
+                    .err()
+                    .unwrap()
+            ),
+            ErrorCode::NotPermitted
+        );
+        assert_eq!(
+            error_code(root.create_directory_at("a").unwrap_err()),
+            ErrorCode::NotPermitted
+        );
+    }
+
+    #[test]
+    fn test_memory_streams() {
+        let root = MemoryDir::default()
+            .materialize("/data", true)
+            .unwrap()
+            .root();
+        let file = root
+            .open_at(
+                "log.txt",
+                OpenFlags::CREATE,
+                DescriptorFlags::READ | DescriptorFlags::WRITE,
+            )
+            .unwrap();
+        let mut output = file.write_via_stream(Some(0)).unwrap();
+        output.write(Bytes::from_static(b"one ")).unwrap();
+        let mut append = file.write_via_stream(None).unwrap();
+        append.write(Bytes::from_static(b"two")).unwrap();
+
+        let mut input = file.read_via_stream(0).unwrap();
+        assert_eq!(&input.read(1024).unwrap()[..], b"one two");
+        assert!(matches!(input.read(1024), Err(StreamError::Closed)));
+    }
+
+    #[test]
+    fn test_unpack_plain_tar() {
+        let mut dir = MemoryDir::default();
+        dir.unpack(&tarball(false)[..]).unwrap();
+        assert_eq!(
+            dir.entries,
+            BTreeMap::from([
+                (PathBuf::from("fixtures"), None),
+                (PathBuf::from("fixtures/input.txt"), Some(b"hello".to_vec())),
+            ])
+        );
+        assert_eq!(dir.bytes, 5);
+
+        // Replacing a file only counts its new size
+        dir.insert(PathBuf::from("fixtures/input.txt"), Some(b"hi".to_vec()))
+            .unwrap();
+        assert_eq!(dir.bytes, 2);
+    }
+
+    #[test]
+    fn test_seed_paths_stay_inside() {
+        assert_eq!(
+            relative_seed_path(Path::new("./a/b.txt")).unwrap(),
+            PathBuf::from("a/b.txt")
+        );
+        assert!(relative_seed_path(Path::new("../b.txt")).is_err());
+        assert!(relative_seed_path(Path::new("/etc/passwd")).is_err());
+
+        let plugin_dir = TempDir::new().unwrap();
+        let missing = MemorySeed {
+            tarball: Some("missing.tar".to_string()),
+            files: None,
+        };
+        assert!(MemoryDir::load(Some(&missing), plugin_dir.path()).is_err());
+        assert_eq!(
+            MemoryDir::load(None, plugin_dir.path()).unwrap(),
+            MemoryDir::default()
+        );
+
+        // The archive is checked like an fs:// grant
+        std::fs::create_dir_all(plugin_dir.path().join("data/other-tool")).unwrap();
+        std::fs::write(
+            plugin_dir.path().join("data/other-tool/seed.tar"),
+            tarball(false),
+        )
+        .unwrap();
+        std::os::unix::fs::symlink(
+            plugin_dir.path().join("data/other-tool/seed.tar"),
+            plugin_dir.path().join("link.tar"),
+        )
+        .unwrap();
+        for tarball in ["data/other-tool/seed.tar", "link.tar"] {
+            let seed = MemorySeed {
+                tarball: Some(tarball.to_string()),
+                files: None,
+            };
+            assert!(
+                MemoryDir::load(Some(&seed), plugin_dir.path()).is_err(),
+                "{tarball}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_seed_limits() {
+        // An entry claiming more than the limit is refused before it is read
+        let mut header = tar::Header::new_gnu();
+        header.set_path("huge.bin").unwrap();
+        header.set_size(MAX_MEMORY_DIR_BYTES + 1);
+        header.set_mode(0o644);
+        header.set_cksum();
+        let mut archive = header.as_bytes().to_vec();
+        archive.extend_from_slice(&[0; 1024]);
+        let err = MemoryDir::default().unpack(&archive[..]).unwrap_err();
+        assert!(
+            err.to_string().contains("bytes a mem:// directory can"),
+            "{err}"
+        );
+
+        let mut dir = MemoryDir::default();
+        for i in 0..MAX_MEMORY_DIR_FILES {
+            dir.insert(PathBuf::from(i.to_string()), None).unwrap();
+        }
+        let err = dir.insert(PathBuf::from("one-more"), None).unwrap_err();
+        assert!(
+            err.to_string().contains("files a mem:// directory can"),
+            "{err}"
+        );
+
+        // Copies are capped the same way while the component writes to them
+        let root = MemoryDir::default()
+            .materialize("/data", true)
+            .unwrap()
+            .root();
+        let file = root
+            .open_at("a.txt", OpenFlags::CREATE, DescriptorFlags::WRITE)
+            .unwrap();
+        file.set_size(MAX_MEMORY_DIR_BYTES).unwrap();
+        assert_eq!(
+            error_code(file.write(b"x", MAX_MEMORY_DIR_BYTES).unwrap_err()),
+            ErrorCode::Quota
+        );
+        let mut output = file.write_via_stream(None).unwrap();
+        let Err(StreamError::LastOperationFailed(err)) = output.write(Bytes::from_static(b"x"))
+        else {
+            panic!("the write should be over the limit");
+        };
+        assert!(err.is::<QuotaExceeded>());
+    }
+}
@@ -0,0 +1,951 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Outbound connections made on behalf of guests.
+//!
+//! The default wasi:http sender resolves and connects in a single step, which
+//! leaves no room to look at the peer address. Here the authority is resolved
+//! up front, every candidate address is checked against the component's policy,
+//! and the connection is pinned to an address that passed the check, so the DNS
+//! answer cannot change between the check and the connect.
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use http_body_util::BodyExt;
+use ipnet::IpNet;
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+use tracing::{debug, warn};
+use wasmtime_wasi::SocketAddrUse;
+use wasmtime_wasi_http::bindings::http::types::{DnsErrorPayload, ErrorCode};
+use wasmtime_wasi_http::body::HyperOutgoingBody;
+use wasmtime_wasi_http::hyper_request_error;
+use wasmtime_wasi_http::io::TokioIo;
+use wasmtime_wasi_http::types::{IncomingResponse, OutgoingRequestConfig};
+
+use crate::egress::{ip_literal, open_tunnel, GuestEgress};
+use crate::http::normalize_host;
+
+/// Parse a policy CIDR entry such as `10.0.0.0/8`.
+///
+/// A bare address is accepted as a single-host range.
+pub(crate) fn parse_cidr(cidr: &str) -> Result<IpNet> {
+    cidr.parse::<IpNet>()
+        .or_else(|_| cidr.parse::<IpAddr>().map(IpNet::from))
+        .with_context(|| format!("Invalid CIDR: {cidr}"))
+}
+
+/// Ranges that SSRF protection keeps components away from unless their policy
+/// grants them with a CIDR rule: loopback, private (RFC 1918 and unique local),
+/// carrier-grade NAT, link-local (including the `169.254.169.254` cloud metadata
+/// service), benchmarking, multicast, reserved and broadcast, the unspecified
+/// network and the local-use NAT64 prefix. Addresses under the well-known NAT64
+/// and 6to4 prefixes are checked by the IPv4 address they carry.
+const PROTECTED_RANGES: &[&str] = &[
+    "0.0.0.0/8",
+    "10.0.0.0/8",
+    "100.64.0.0/10",
+    "127.0.0.0/8",
+    "169.254.0.0/16",
+    "172.16.0.0/12",
+    "192.168.0.0/16",
+    "198.18.0.0/15",
+    "224.0.0.0/4",
+    "240.0.0.0/4",
+    "::/128",
+    "::1/128",
+    "64:ff9b:1::/48",
+    "fc00::/7",
+    "fe80::/10",
+    "ff00::/8",
+];
+
+/// Returns true if `ip` is a loopback, private, link-local or metadata address.
+pub(crate) fn is_protected_ip(ip: IpAddr) -> bool {
+    static RANGES: std::sync::OnceLock<Vec<IpNet>> = std::sync::OnceLock::new();
+    let ranges = RANGES.get_or_init(|| {
+        PROTECTED_RANGES
+            .iter()
+            .map(|range| range.parse().expect("valid protected range"))
+            .collect()
+    });
+    ip_in_ranges(ranges, ip)
+}
+
+/// Returns true if `ip` falls into any of the given ranges.
+pub(crate) fn ip_in_ranges(ranges: &[IpNet], ip: IpAddr) -> bool {
+    let ip = canonical_ip(ip);
+    ranges.iter().any(|range| range.contains(&ip))
+}
+
+/// Treat IPv6 addresses that carry an IPv4 address as that IPv4 address, so they
+/// cannot be used to sidestep IPv4 rules: IPv4-mapped (`::ffff:10.0.0.1`), NAT64
+/// under the well-known prefix (`64:ff9b::10.0.0.1`) and 6to4 (`2002:a00:1::`).
+fn canonical_ip(ip: IpAddr) -> IpAddr {
+    let IpAddr::V6(v6) = ip else {
+        return ip;
+    };
+    if let Some(v4) = v6.to_ipv4_mapped() {
+        return IpAddr::V4(v4);
+    }
+    let octets = v6.octets();
+    let embedded = |start: usize| {
+        IpAddr::V4(Ipv4Addr::new(
+            octets[start],
+            octets[start + 1],
+            octets[start + 2],
+            octets[start + 3],
+        ))
+    };
+    match v6.segments() {
+        [0x64, 0xff9b, 0, 0, 0, 0, _, _] => embedded(12),
+        [0x2002, ..] => embedded(2),
+        _ => ip,
+    }
+}
+
+/// Transport protocol of a raw socket grant
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SocketProtocol {
+    Tcp,
+    Udp,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum SocketTarget {
+    /// An address or range, matched directly against the peer address
+    Range(IpNet),
+    /// A host name, matched against the addresses it currently resolves to
+    Name(String),
+}
+
+/// A raw socket grant from a component's network policy, e.g. `tcp://db.internal:5432`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SocketRule {
+    protocol: SocketProtocol,
+    target: SocketTarget,
+    /// `None` allows any port
+    port: Option<u16>,
+}
+
+impl SocketRule {
+    /// The protocol this rule grants
+    pub(crate) fn protocol(&self) -> SocketProtocol {
+        self.protocol
+    }
+
+    fn applies_to(&self, protocol: SocketProtocol, port: u16) -> bool {
+        self.protocol == protocol && self.port.is_none_or(|allowed| allowed == port)
+    }
+}
+
+/// Parse a policy socket entry such as `tcp://db.internal:5432` or `udp://10.0.0.0/8:*`.
+pub(crate) fn parse_socket_rule(rule: &str) -> Result<SocketRule> {
+    let (protocol, target) = if let Some(target) = rule.strip_prefix("tcp://") {
+        (SocketProtocol::Tcp, target)
+    } else if let Some(target) = rule.strip_prefix("udp://") {
+        (SocketProtocol::Udp, target)
+    } else {
+        bail!("Invalid socket rule: {rule}, expected tcp://host:port or udp://host:port");
+    };
+
+    let (host, port) = target
+        .rsplit_once(':')
+        .with_context(|| format!("Invalid socket rule: {rule}, missing port"))?;
+    let port = match port {
+        "*" => None,
+        port => Some(
+            port.parse::<u16>()
+                .with_context(|| format!("Invalid socket rule: {rule}, bad port"))?,
+        ),
+    };
+
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if host.is_empty() || host.contains('*') {
+        bail!("Invalid socket rule: {rule}, expected a host name, address or CIDR");
+    }
+    let target = match parse_cidr(host) {
+        Ok(range) => SocketTarget::Range(range),
+        Err(_) => SocketTarget::Name(host.to_ascii_lowercase()),
+    };
+
+    Ok(SocketRule {
+        protocol,
+        target,
+        port,
+    })
+}
+
+/// Returns true if a socket rule naming an address or range for `protocol` grants `addr`.
+///
+/// Unlike [`socket_rules_allow`] this ignores rules naming a host, which say nothing
+/// about which addresses the host may resolve to.
+fn socket_ranges_allow(rules: &[SocketRule], protocol: SocketProtocol, addr: SocketAddr) -> bool {
+    let ip = canonical_ip(addr.ip());
+    rules.iter().any(|rule| {
+        rule.applies_to(protocol, addr.port())
+            && matches!(&rule.target, SocketTarget::Range(range) if range.contains(&ip))
+    })
+}
+
+/// Returns true if any socket rule for `protocol` grants `addr`.
+///
+/// Rules naming a host are matched against the addresses the name resolves to
+/// right now, on a blocking thread since the lookup goes through the system resolver.
+async fn socket_rules_allow(
+    rules: Arc<[SocketRule]>,
+    protocol: SocketProtocol,
+    addr: SocketAddr,
+) -> bool {
+    let ip = canonical_ip(addr.ip());
+    let mut names = Vec::new();
+    for rule in rules
+        .iter()
+        .filter(|rule| rule.applies_to(protocol, addr.port()))
+    {
+        match &rule.target {
+            SocketTarget::Range(range) => {
+                if range.contains(&ip) {
+                    return true;
+                }
+            }
+            SocketTarget::Name(name) => names.push(name.clone()),
+        }
+    }
+    if names.is_empty() {
+        return false;
+    }
+
+    tokio::task::spawn_blocking(move || {
+        names.iter().any(|name| {
+            (name.as_str(), addr.port())
+                .to_socket_addrs()
+                .map(|mut resolved| resolved.any(|candidate| canonical_ip(candidate.ip()) == ip))
+                .unwrap_or(false)
+        })
+    })
+    .await
+    .unwrap_or(false)
+}
+
+/// Builds the `wasi:sockets` address check for a component.
+///
+/// Connections (and UDP datagrams) are only allowed to addresses inside the
+/// granted ranges or matching a socket rule for the protocol, and never to a
+/// denied range or socket rule. Binding needs an explicit socket rule, except
+/// for binding UDP to an ephemeral port on the unspecified address, which a UDP
+/// client needs before it can send.
+///
+/// With `ssrf_protection`, connections to protected addresses (see
+/// [`is_protected_ip`]) additionally need a CIDR grant or a socket rule naming
+/// the address, a host name rule is not enough.
+pub(crate) fn socket_addr_check(
+    allowed_cidrs: Arc<[IpNet]>,
+    denied_cidrs: Arc<[IpNet]>,
+    allowed_sockets: Arc<[SocketRule]>,
+    denied_sockets: Arc<[SocketRule]>,
+    ssrf_protection: bool,
+) -> impl Fn(
+    SocketAddr,
+    SocketAddrUse,
+) -> std::pin::Pin<Box<dyn std::future::Future<Output = bool> + Send + Sync>>
+       + Send
+       + Sync
+       + 'static {
+    move |addr, addr_use| {
+        let in_allowed_range = ip_in_ranges(&allowed_cidrs, addr.ip());
+        let in_denied_range = ip_in_ranges(&denied_cidrs, addr.ip());
+        let allowed_sockets = allowed_sockets.clone();
+        let denied_sockets = denied_sockets.clone();
+        Box::pin(async move {
+            let protocol = match addr_use {
+                SocketAddrUse::TcpConnect | SocketAddrUse::TcpBind => SocketProtocol::Tcp,
+                SocketAddrUse::UdpConnect
+                | SocketAddrUse::UdpOutgoingDatagram
+                | SocketAddrUse::UdpBind => SocketProtocol::Udp,
+            };
+            let (granted, outbound) = match addr_use {
+                SocketAddrUse::TcpConnect
+                | SocketAddrUse::UdpConnect
+                | SocketAddrUse::UdpOutgoingDatagram => (in_allowed_range, true),
+                SocketAddrUse::UdpBind => (addr.ip().is_unspecified() && addr.port() == 0, false),
+                SocketAddrUse::TcpBind => (false, false),
+            };
+            if ssrf_protection
+                && outbound
+                && is_protected_ip(addr.ip())
+                && !in_allowed_range
+                && !socket_ranges_allow(&allowed_sockets, protocol, addr)
+            {
+                warn!(%addr, use = ?addr_use, "Socket address blocked by SSRF protection");
+                return false;
+            }
+            let allowed = !in_denied_range
+                && (granted || socket_rules_allow(allowed_sockets, protocol, addr).await)
+                && !socket_rules_allow(denied_sockets, protocol, addr).await;
+            if !allowed {
+                warn!(%addr, use = ?addr_use, "Socket address blocked by network policy");
+            }
+            allowed
+        })
+    }
+}
+
+fn dns_error(rcode: &str) -> ErrorCode {
+    ErrorCode::DnsError(DnsErrorPayload {
+        rcode: Some(rcode.to_string()),
+        info_code: Some(0),
+    })
+}
+
+/// Checks every address `host` resolves to before a request for it is sent through
+/// the proxy. The proxy does its own lookup, so the address it connects to can't be
+/// pinned, but a name pointing at a denied or protected address is refused. So is a
+/// name that can't be resolved locally, unless the proxy is trusted to resolve
+/// names, as its addresses couldn't be checked at all.
+async fn check_proxied_host(
+    host: &str,
+    port: u16,
+    connect_timeout: Duration,
+    trust_proxy_dns: bool,
+    address_allowed: impl Fn(IpAddr) -> bool,
+) -> Result<(), ErrorCode> {
+    let host = normalize_host(host);
+    let host = host.as_str();
+    let resolved: Vec<IpAddr> = match ip_literal(host) {
+        Some(ip) => vec![ip],
+        None => match timeout(connect_timeout, tokio::net::lookup_host((host, port))).await {
+            Ok(Ok(addrs)) => addrs.map(|addr| addr.ip()).collect(),
+            Ok(Err(e)) if trust_proxy_dns => {
+                debug!(host, "Proxied host doesn't resolve locally: {e}");
+                Vec::new()
+            }
+            Err(_) if trust_proxy_dns => {
+                debug!(host, "Timed out resolving proxied host locally");
+                Vec::new()
+            }
+            Ok(Err(e)) => {
+                warn!(
+                    host,
+                    "HTTP request blocked: proxied host doesn't resolve locally: {e}"
+                );
+                return Err(dns_error("address not available"));
+            }
+            Err(_) => {
+                warn!(
+                    host,
+                    "HTTP request blocked: timed out resolving proxied host locally"
+                );
+                return Err(ErrorCode::ConnectionTimeout);
+            }
+        },
+    };
+    if resolved.iter().any(|ip| !address_allowed(*ip)) {
+        warn!(
+            host,
+            resolved = ?resolved,
+            "HTTP request blocked: a resolved address is not allowed by network policy"
+        );
+        return Err(ErrorCode::HttpRequestDenied);
+    }
+    Ok(())
+}
+
+/// Resolves `host` and connects to the first candidate address accepted by `address_allowed`
+async fn connect_checked(
+    host: &str,
+    port: u16,
+    connect_timeout: Duration,
+    address_allowed: impl Fn(IpAddr) -> bool,
+) -> Result<TcpStream, ErrorCode> {
+    let resolved: Vec<SocketAddr> = timeout(connect_timeout, tokio::net::lookup_host((host, port)))
+        .await
+        .map_err(|_| ErrorCode::ConnectionTimeout)?
+        .map_err(|_| dns_error("address not available"))?
+        .collect();
+    let candidates: Vec<SocketAddr> = resolved
+        .iter()
+        .copied()
+        .filter(|addr| address_allowed(addr.ip()))
+        .collect();
+    if candidates.is_empty() {
+        warn!(
+            host,
+            resolved = ?resolved,
+            "HTTP request blocked: no resolved address is allowed by network policy"
+        );
+        return Err(ErrorCode::HttpRequestDenied);
+    }
+
+    timeout(connect_timeout, TcpStream::connect(candidates.as_slice()))
+        .await
+        .map_err(|_| ErrorCode::ConnectionTimeout)?
+        .map_err(|_| ErrorCode::ConnectionRefused)
+}
+
+/// Sends a guest HTTP request, connecting only to resolved addresses accepted by `address_allowed`.
+///
+/// This mirrors `wasmtime_wasi_http::types::default_send_request_handler`, with
+/// the name resolution split out so the peer address can be checked, and with
+/// the server's proxy and TLS settings from `egress` applied.
+pub(crate) async fn send_request_handler(
+    mut request: hyper::Request<HyperOutgoingBody>,
+    OutgoingRequestConfig {
+        use_tls,
+        connect_timeout,
+        first_byte_timeout,
+        between_bytes_timeout,
+    }: OutgoingRequestConfig,
+    address_allowed: impl Fn(IpAddr) -> bool,
+    egress: &GuestEgress,
+) -> Result<IncomingResponse, ErrorCode> {
+    let authority = request
+        .uri()
+        .authority()
+        .ok_or(ErrorCode::HttpRequestUriInvalid)?
+        .clone();
+    let host = authority
+        .host()
+        .trim_start_matches('[')
+        .trim_end_matches(']');
+    let port = authority
+        .port_u16()
+        .unwrap_or(if use_tls { 443 } else { 80 });
+    let proxy = egress.proxy_for(host);
+
+    let mut tcp_stream = match proxy {
+        Some(proxy) => {
+            check_proxied_host(
+                host,
+                port,
+                connect_timeout,
+                proxy.trust_dns,
+                &address_allowed,
+            )
+            .await?;
+            timeout(
+                connect_timeout,
+                TcpStream::connect(proxy.authority.as_str()),
+            )
+            .await
+            .map_err(|_| ErrorCode::ConnectionTimeout)?
+            .map_err(|e| {
+                warn!(proxy = %proxy.authority, "Failed to connect to proxy: {e}");
+                ErrorCode::ConnectionRefused
+            })?
+        }
+        None => connect_checked(host, port, connect_timeout, &address_allowed).await?,
+    };
+
+    let (mut sender, worker) = if use_tls {
+        use rustls::pki_types::ServerName;
+
+        if let Some(proxy) = proxy {
+            let target = format!("{}:{port}", authority.host());
+            timeout(
+                connect_timeout,
+                open_tunnel(&mut tcp_stream, &target, proxy),
+            )
+            .await
+            .map_err(|_| ErrorCode::ConnectionTimeout)??;
+        }
+
+        let connector = tokio_rustls::TlsConnector::from(egress.tls_for(host));
+        let domain = ServerName::try_from(host)
+            .map_err(|e| {
+                warn!("dns lookup error: {e:?}");
+                dns_error("invalid dns name")
+            })?
+            .to_owned();
+        let stream = connector.connect(domain, tcp_stream).await.map_err(|e| {
+            warn!("tls protocol error: {e:?}");
+            ErrorCode::TlsProtocolError
+        })?;
+
+        let (sender, conn) = timeout(
+            connect_timeout,
+            hyper::client::conn::http1::handshake(TokioIo::new(stream)),
+        )
+        .await
+        .map_err(|_| ErrorCode::ConnectionTimeout)?
+        .map_err(hyper_request_error)?;
+
+        let worker = wasmtime_wasi::runtime::spawn(async move {
+            if let Err(e) = conn.await {
+                warn!("dropping error {e}");
+            }
+        });
+        (sender, worker)
+    } else {
+        let (sender, conn) = timeout(
+            connect_timeout,
+            hyper::client::conn::http1::handshake(TokioIo::new(tcp_stream)),
+        )
+        .await
+        .map_err(|_| ErrorCode::ConnectionTimeout)?
+        .map_err(hyper_request_error)?;
+
+        let worker = wasmtime_wasi::runtime::spawn(async move {
+            if let Err(e) = conn.await {
+                warn!("dropping error {e}");
+            }
+        });
+        (sender, worker)
+    };
+
+    match proxy {
+        // A plain HTTP request to a proxy keeps its absolute URI
+        Some(proxy) if !use_tls => {
+            if let Some(authorization) = &proxy.authorization {
+                let value = http::HeaderValue::from_str(authorization)
+                    .map_err(|_| ErrorCode::InternalError(None))?;
+                request
+                    .headers_mut()
+                    .insert(http::header::PROXY_AUTHORIZATION, value);
+            }
+        }
+        // The request carries the scheme and authority, but the HTTP/1 request line
+        // should only include them when talking to a proxy
+        _ => {
+            *request.uri_mut() = http::Uri::builder()
+                .path_and_query(
+                    request
+                        .uri()
+                        .path_and_query()
+                        .map(|p| p.as_str())
+                        .unwrap_or("/"),
+                )
+                .build()
+                .expect("comes from valid request");
+        }
+    }
+
+    let resp = timeout(first_byte_timeout, sender.send_request(request))
+        .await
+        .map_err(|_| ErrorCode::ConnectionReadTimeout)?
+        .map_err(hyper_request_error)?
+        .map(|body| body.map_err(hyper_request_error).boxed());
+
+    Ok(IncomingResponse {
+        resp,
+        worker: Some(worker),
+        between_bytes_timeout,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use super::*;
+
+    #[test]
+    fn test_parse_cidr() {
+        assert_eq!(
+            parse_cidr("10.0.0.0/8").unwrap(),
+            "10.0.0.0/8".parse::<IpNet>().unwrap()
+        );
+        assert_eq!(
+            parse_cidr("192.168.1.10").unwrap(),
+            "192.168.1.10/32".parse::<IpNet>().unwrap()
+        );
+        assert_eq!(
+            parse_cidr("fd00::/8").unwrap(),
+            "fd00::/8".parse::<IpNet>().unwrap()
+        );
+        assert!(parse_cidr("10.0.0.0/33").is_err());
+        assert!(parse_cidr("invalid-cidr").is_err());
+    }
+
+    #[test]
+    fn test_ip_in_ranges() {
+        let ranges = vec![parse_cidr("10.0.0.0/8").unwrap()];
+
+        assert!(ip_in_ranges(
+            &ranges,
+            IpAddr::V4(Ipv4Addr::new(10, 1, 2, 3))
+        ));
+        assert!(!ip_in_ranges(
+            &ranges,
+            IpAddr::V4(Ipv4Addr::new(11, 0, 0, 1))
+        ));
+        assert!(!ip_in_ranges(&ranges, IpAddr::V4(Ipv4Addr::LOCALHOST)));
+
+        // IPv4-mapped IPv6 addresses are matched against IPv4 ranges
+        let mapped = Ipv4Addr::new(10, 0, 0, 1).to_ipv6_mapped();
+        assert!(ip_in_ranges(&ranges, IpAddr::V6(mapped)));
+        assert!(!ip_in_ranges(&ranges, IpAddr::V6(Ipv6Addr::LOCALHOST)));
+    }
+
+    #[tokio::test]
+    async fn test_socket_addr_check() {
+        let check = socket_addr_check(
+            vec![parse_cidr("10.0.0.0/8").unwrap()].into(),
+            vec![parse_cidr("10.0.0.0/24").unwrap()].into(),
+            Vec::new().into(),
+            Vec::new().into(),
+            true,
+        );
+
+        let internal: SocketAddr = "10.1.2.3:5432".parse().unwrap();
+        let denied: SocketAddr = "10.0.0.1:5432".parse().unwrap();
+        let external: SocketAddr = "93.184.216.34:443".parse().unwrap();
+        let any: SocketAddr = "0.0.0.0:0".parse().unwrap();
+
+        assert!(check(internal, SocketAddrUse::TcpConnect).await);
+        assert!(check(internal, SocketAddrUse::UdpOutgoingDatagram).await);
+        assert!(!check(external, SocketAddrUse::TcpConnect).await);
+        assert!(!check(external, SocketAddrUse::UdpConnect).await);
+        assert!(!check(denied, SocketAddrUse::TcpConnect).await);
+        assert!(!check(denied, SocketAddrUse::UdpOutgoingDatagram).await);
+        assert!(check(any, SocketAddrUse::UdpBind).await);
+        assert!(!check(any, SocketAddrUse::TcpBind).await);
+        assert!(!check(internal, SocketAddrUse::UdpBind).await);
+    }
+
+    #[test]
+    fn test_parse_socket_rule() {
+        let rule = parse_socket_rule("tcp://DB.internal:5432").unwrap();
+        assert_eq!(rule.protocol(), SocketProtocol::Tcp);
+        assert_eq!(rule.target, SocketTarget::Name("db.internal".to_string()));
+        assert_eq!(rule.port, Some(5432));
+
+        let rule = parse_socket_rule("udp://10.0.0.0/8:*").unwrap();
+        assert_eq!(rule.protocol(), SocketProtocol::Udp);
+        assert_eq!(
+            rule.target,
+            SocketTarget::Range("10.0.0.0/8".parse().unwrap())
+        );
+        assert_eq!(rule.port, None);
+
+        let rule = parse_socket_rule("tcp://[::1]:8080").unwrap();
+        assert_eq!(rule.target, SocketTarget::Range("::1/128".parse().unwrap()));
+
+        for invalid in [
+            "db.internal:5432",
+            "http://db.internal:80",
+            "tcp://db.internal",
+            "tcp://:80",
+            "tcp://*.internal:80",
+            "udp://10.0.0.1:70000",
+        ] {
+            assert!(parse_socket_rule(invalid).is_err(), "{invalid}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_socket_addr_check_with_socket_rules() {
+        let check = socket_addr_check(
+            Vec::new().into(),
+            vec![parse_cidr("127.0.0.2").unwrap()].into(),
+            vec![
+                parse_socket_rule("tcp://localhost:5432").unwrap(),
+                parse_socket_rule("tcp://127.0.0.0/8:6379").unwrap(),
+                parse_socket_rule("udp://127.0.0.1:*").unwrap(),
+                parse_socket_rule("tcp://0.0.0.0:8080").unwrap(),
+            ]
+            .into(),
+            vec![parse_socket_rule("udp://127.0.0.1:5353").unwrap()].into(),
+            false,
+        );
+
+        let postgres: SocketAddr = "127.0.0.1:5432".parse().unwrap();
+        let redis: SocketAddr = "127.0.0.3:6379".parse().unwrap();
+        let denied_redis: SocketAddr = "127.0.0.2:6379".parse().unwrap();
+        let other_port: SocketAddr = "127.0.0.1:22".parse().unwrap();
+        let dns: SocketAddr = "127.0.0.1:53".parse().unwrap();
+
+        // Host names are matched against their resolved addresses
+        assert!(check(postgres, SocketAddrUse::TcpConnect).await);
+        assert!(check(redis, SocketAddrUse::TcpConnect).await);
+        assert!(!check(denied_redis, SocketAddrUse::TcpConnect).await);
+        assert!(!check(other_port, SocketAddrUse::TcpConnect).await);
+
+        // Rules are protocol specific
+        assert!(!check(redis, SocketAddrUse::UdpConnect).await);
+        assert!(check(dns, SocketAddrUse::UdpOutgoingDatagram).await);
+        assert!(!check(dns, SocketAddrUse::TcpConnect).await);
+        let mdns: SocketAddr = "127.0.0.1:5353".parse().unwrap();
+        assert!(!check(mdns, SocketAddrUse::UdpConnect).await);
+
+        // Binding needs an explicit rule
+        assert!(check("0.0.0.0:8080".parse().unwrap(), SocketAddrUse::TcpBind).await);
+        assert!(!check("0.0.0.0:8081".parse().unwrap(), SocketAddrUse::TcpBind).await);
+        assert!(check("0.0.0.0:0".parse().unwrap(), SocketAddrUse::UdpBind).await);
+        assert!(!check("0.0.0.0:53".parse().unwrap(), SocketAddrUse::UdpBind).await);
+    }
+
+    #[test]
+    fn test_protected_ips() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.100.100.200",
+            "0.0.0.0",
+            "::1",
+            "fd00:ec2::254",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "198.18.0.1",
+            "198.19.255.255",
+            "224.0.0.251",
+            "239.255.255.250",
+            "255.255.255.255",
+            "ff02::1",
+            // NAT64 and 6to4 addresses carrying the metadata address
+            "64:ff9b::169.254.169.254",
+            "64:ff9b::a9fe:a9fe",
+            "2002:a9fe:a9fe::1",
+            "2002:7f00:1::",
+            "64:ff9b:1::a00:1",
+        ] {
+            assert!(is_protected_ip(ip.parse().unwrap()), "{ip}");
+        }
+        for ip in [
+            "93.184.216.34",
+            "172.32.0.1",
+            "2606:4700::1111",
+            "64:ff9b::93.184.216.34",
+            "2002:5db8:d822::",
+        ] {
+            assert!(!is_protected_ip(ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_socket_addr_check_ssrf_protection() {
+        let check = socket_addr_check(
+            vec![parse_cidr("10.0.0.0/8").unwrap()].into(),
+            Vec::new().into(),
+            vec![
+                parse_socket_rule("tcp://localhost:5432").unwrap(),
+                parse_socket_rule("tcp://127.0.0.1:6379").unwrap(),
+                parse_socket_rule("tcp://0.0.0.0:8080").unwrap(),
+            ]
+            .into(),
+            Vec::new().into(),
+            true,
+        );
+
+        // A host name rule does not vouch for the addresses it resolves to
+        assert!(!check("127.0.0.1:5432".parse().unwrap(), SocketAddrUse::TcpConnect).await);
+        // Address rules and CIDR grants are explicit
+        assert!(check("127.0.0.1:6379".parse().unwrap(), SocketAddrUse::TcpConnect).await);
+        assert!(check("10.1.2.3:5432".parse().unwrap(), SocketAddrUse::UdpConnect).await);
+        // Binding is not an outbound connection
+        assert!(check("0.0.0.0:8080".parse().unwrap(), SocketAddrUse::TcpBind).await);
+        assert!(check("0.0.0.0:0".parse().unwrap(), SocketAddrUse::UdpBind).await);
+    }
+
+    #[tokio::test]
+    async fn test_send_request_denied_when_no_address_allowed() {
+        let request = hyper::Request::builder()
+            .uri("http://127.0.0.1:9/")
+            .body(
+                http_body_util::Empty::new()
+                    .map_err(|never| match never {})
+                    .boxed(),
+            )
+            .unwrap();
+
+        let result = send_request_handler(
+            request,
+            OutgoingRequestConfig {
+                use_tls: false,
+                connect_timeout: std::time::Duration::from_secs(5),
+                first_byte_timeout: std::time::Duration::from_secs(5),
+                between_bytes_timeout: std::time::Duration::from_secs(5),
+            },
+            |_| false,
+            &GuestEgress::default(),
+        )
+        .await;
+
+        assert!(matches!(result, Err(ErrorCode::HttpRequestDenied)));
+    }
+
+    #[tokio::test]
+    async fn test_send_request_connects_to_allowed_address() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let _ = socket.read(&mut buf).await.unwrap();
+            socket
+                .write_all(b"HTTP/1.1 204 No Content\r\ncontent-length: 0\r\n\r\n")
+                .await
+                .unwrap();
+        });
+
+        let request = hyper::Request::builder()
+            .uri(format!("http://{addr}/"))
+            .body(
+                http_body_util::Empty::new()
+                    .map_err(|never| match never {})
+                    .boxed(),
+            )
+            .unwrap();
+        let loopback = vec![parse_cidr("127.0.0.0/8").unwrap()];
+
+        let response = send_request_handler(
+            request,
+            OutgoingRequestConfig {
+                use_tls: false,
+                connect_timeout: std::time::Duration::from_secs(5),
+                first_byte_timeout: std::time::Duration::from_secs(5),
+                between_bytes_timeout: std::time::Duration::from_secs(5),
+            },
+            |ip| ip_in_ranges(&loopback, ip),
+            &GuestEgress::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(response.resp.status(), hyper::StatusCode::NO_CONTENT);
+    }
+
+    #[tokio::test]
+    async fn test_send_request_through_proxy() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let proxy = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let n = socket.read(&mut buf).await.unwrap();
+            socket
+                .write_all(b"HTTP/1.1 204 No Content\r\ncontent-length: 0\r\n\r\n")
+                .await
+                .unwrap();
+            String::from_utf8_lossy(&buf[..n]).into_owned()
+        });
+
+        let egress = GuestEgress::from_settings(&crate::egress::EgressSettings {
+            proxy: Some(format!("http://user:pass@{addr}")),
+            ..Default::default()
+        })
+        .unwrap();
+        let request = || {
+            hyper::Request::builder()
+                .uri("http://localhost/items?page=2")
+                .body(
+                    http_body_util::Empty::new()
+                        .map_err(|never| match never {})
+                        .boxed(),
+                )
+                .unwrap()
+        };
+        let config = || OutgoingRequestConfig {
+            use_tls: false,
+            connect_timeout: std::time::Duration::from_secs(5),
+            first_byte_timeout: std::time::Duration::from_secs(5),
+            between_bytes_timeout: std::time::Duration::from_secs(5),
+        };
+
+        // Names are resolved and checked before the request goes to the proxy
+        let result = send_request_handler(request(), config(), |_| false, &egress).await;
+        assert!(matches!(result, Err(ErrorCode::HttpRequestDenied)));
+
+        let response = send_request_handler(request(), config(), |ip| ip.is_loopback(), &egress)
+            .await
+            .unwrap();
+        assert_eq!(response.resp.status(), hyper::StatusCode::NO_CONTENT);
+
+        let head = proxy.await.unwrap();
+        assert!(head.starts_with("GET http://localhost/items?page=2 HTTP/1.1\r\n"));
+        assert!(head.contains("proxy-authorization: Basic dXNlcjpwYXNz\r\n"));
+
+        // Address literals are still checked before going through the proxy
+        let request = hyper::Request::builder()
+            .uri("http://169.254.169.254/latest/meta-data")
+            .body(
+                http_body_util::Empty::new()
+                    .map_err(|never| match never {})
+                    .boxed(),
+            )
+            .unwrap();
+        let result = send_request_handler(request, config(), |_| false, &egress).await;
+        assert!(matches!(result, Err(ErrorCode::HttpRequestDenied)));
+
+        // A trailing dot doesn't hide an address literal
+        let result = check_proxied_host(
+            "169.254.169.254.",
+            80,
+            std::time::Duration::from_secs(5),
+            false,
+            |_| false,
+        )
+        .await;
+        assert!(matches!(result, Err(ErrorCode::HttpRequestDenied)));
+    }
+
+    #[tokio::test]
+    async fn test_unresolvable_proxied_host_is_refused() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let proxy = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buf = [0u8; 1024];
+            let n = socket.read(&mut buf).await.unwrap();
+            socket
+                .write_all(b"HTTP/1.1 204 No Content\r\ncontent-length: 0\r\n\r\n")
+                .await
+                .unwrap();
+            String::from_utf8_lossy(&buf[..n]).into_owned()
+        });
+
+        let egress = |trust_proxy_dns| {
+            GuestEgress::from_settings(&crate::egress::EgressSettings {
+                proxy: Some(format!("http://{addr}")),
+                trust_proxy_dns,
+                ..Default::default()
+            })
+            .unwrap()
+        };
+        // `.invalid` never resolves, so the proxy could point it anywhere, including
+        // at the denied range
+        let request = || {
+            hyper::Request::builder()
+                .uri("http://metadata.invalid/latest")
+                .body(
+                    http_body_util::Empty::new()
+                        .map_err(|never| match never {})
+                        .boxed(),
+                )
+                .unwrap()
+        };
+        let config = || OutgoingRequestConfig {
+            use_tls: false,
+            connect_timeout: std::time::Duration::from_secs(5),
+            first_byte_timeout: std::time::Duration::from_secs(5),
+            between_bytes_timeout: std::time::Duration::from_secs(5),
+        };
+        let denied: Vec<IpNet> = vec!["169.254.0.0/16".parse().unwrap()];
+        let address_allowed = |ip| !ip_in_ranges(&denied, ip);
+
+        let result =
+            send_request_handler(request(), config(), address_allowed, &egress(false)).await;
+        assert!(result.is_err());
+        assert!(!proxy.is_finished());
+
+        // Only when the proxy is trusted to resolve names does the request reach it
+        let response = send_request_handler(request(), config(), address_allowed, &egress(true))
+            .await
+            .unwrap();
+        assert_eq!(response.resp.status(), hyper::StatusCode::NO_CONTENT);
+        let head = proxy.await.unwrap();
+        assert!(head.starts_with("GET http://metadata.invalid/latest HTTP/1.1\r\n"));
+    }
+}
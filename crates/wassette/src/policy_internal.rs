@@ -10,8 +10,9 @@ use std::sync::Arc;
 use anyhow::{anyhow, Result};
 use oci_wasm::WasmClient;
 use policy::{
-    AccessType, EnvironmentPermission, NetworkHostPermission, NetworkPermission, PolicyDocument,
-    PolicyParser, StoragePermission,
+    AccessType, CredentialInjection, EnvironmentPermission, MemorySeed, NetworkCidrPermission,
+    NetworkHostPermission, NetworkInjectPermission, NetworkPermission, NetworkSocketPermission,
+    Permissions, PolicyDocument, PolicyParser, StoragePermission, StorageQuota,
 };
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
@@ -19,7 +20,10 @@ use tokio::sync::RwLock;
 use tracing::{info, instrument, warn};
 
 use crate::component_storage::ComponentStorage;
+use crate::http::{host_rule_strings, HeaderInjection, HttpRule};
 use crate::loader::{self, PolicyResource};
+use crate::network::{parse_cidr, parse_socket_rule};
+use crate::storage_guard::{check_storage_grant, split_storage_pattern};
 use crate::{SecretsManager, WasiStateTemplate};
 
 /// Granular permission rule types
@@ -191,12 +195,7 @@ impl PolicyManager {
 
         let secrets = self.secrets.load_component_secrets(component_id).await.ok();
 
-        let wasi_template = crate::create_wasi_state_template_from_policy(
-            &policy,
-            self.storage.root(),
-            self.environment_vars.as_ref(),
-            secrets.as_ref(),
-        )?;
+        let wasi_template = self.template_from_policy(component_id, &policy, secrets.as_ref())?;
 
         self.store_template(component_id, Arc::new(wasi_template))
             .await;
@@ -260,6 +259,23 @@ impl PolicyManager {
         })
     }
 
+    /// Builds the WASI state template for a component's policy
+    fn template_from_policy(
+        &self,
+        component_id: &str,
+        policy: &PolicyDocument,
+        secrets: Option<&HashMap<String, String>>,
+    ) -> Result<WasiStateTemplate> {
+        crate::create_wasi_state_template_from_policy(
+            policy,
+            self.storage.root(),
+            self.environment_vars.as_ref(),
+            secrets,
+        )?
+        .with_component_dirs(policy, &self.storage, component_id)?
+        .with_disk_quotas(policy, self.storage.root())
+    }
+
     pub(crate) async fn update_policy_registry(
         &self,
         component_id: &str,
@@ -267,12 +283,7 @@ impl PolicyManager {
     ) -> Result<()> {
         let secrets = self.secrets.load_component_secrets(component_id).await.ok();
 
-        let wasi_template = crate::create_wasi_state_template_from_policy(
-            policy,
-            self.storage.root(),
-            self.environment_vars.as_ref(),
-            secrets.as_ref(),
-        )?;
+        let wasi_template = self.template_from_policy(component_id, policy, secrets.as_ref())?;
 
         self.store_template(component_id, Arc::new(wasi_template))
             .await;
@@ -291,21 +302,18 @@ impl PolicyManager {
 
         match tokio::fs::read_to_string(&policy_path).await {
             Ok(policy_content) => match PolicyParser::parse_str(&policy_content) {
-                Ok(policy) => match crate::create_wasi_state_template_from_policy(
-                    &policy,
-                    self.storage.root(),
-                    self.environment_vars.as_ref(),
-                    secrets.as_ref(),
-                ) {
-                    Ok(wasi_template) => {
-                        self.store_template(component_id, Arc::new(wasi_template))
-                            .await;
-                        info!(component_id = %component_id, "Restored policy association from co-located file");
+                Ok(policy) => {
+                    match self.template_from_policy(component_id, &policy, secrets.as_ref()) {
+                        Ok(wasi_template) => {
+                            self.store_template(component_id, Arc::new(wasi_template))
+                                .await;
+                            info!(component_id = %component_id, "Restored policy association from co-located file");
+                        }
+                        Err(e) => {
+                            warn!(component_id = %component_id, error = %e, "Failed to create WASI template from policy");
+                        }
                     }
-                    Err(e) => {
-                        warn!(component_id = %component_id, error = %e, "Failed to create WASI template from policy");
-                    }
-                },
+                }
                 Err(e) => {
                     warn!(component_id = %component_id, error = %e, "Failed to parse co-located policy file");
                 }
@@ -323,9 +331,7 @@ impl PolicyManager {
         component_id: &str,
         uri: &str,
     ) -> Result<()> {
-        if uri.is_empty() {
-            return Err(anyhow!("Storage URI cannot be empty"));
-        }
+        validate_storage_uri(uri)?;
         let mut policy = self.load_or_create_component_policy(component_id).await?;
         self.remove_storage_permission_by_uri_from_policy(&mut policy, uri)?;
         self.save_component_policy(component_id, &policy).await?;
@@ -347,6 +353,15 @@ impl PolicyManager {
         );
         let permission_rule = self.parse_permission_rule(permission_type, details)?;
         self.validate_permission_rule(&permission_rule)?;
+        if let PermissionRule::Storage(storage) = &permission_rule {
+            if let Some(path) = storage.uri.strip_prefix("fs://") {
+                let (literal, _) = split_storage_pattern(path)?;
+                check_storage_grant(&literal, self.storage.root())?;
+            }
+            if let Some(tarball) = storage.seed.as_ref().and_then(|seed| seed.tarball.as_ref()) {
+                check_storage_grant(tarball, self.storage.root())?;
+            }
+        }
         let mut policy = self.load_or_create_component_policy(component_id).await?;
         self.add_permission_rule_to_policy(&mut policy, permission_rule)?;
         self.save_component_policy(component_id, &policy).await?;
@@ -359,6 +374,68 @@ impl PolicyManager {
         Ok(())
     }
 
+    /// Add a deny rule to a component, blocking access even where an allow rule matches
+    ///
+    /// Only network and storage permissions support deny rules. A storage deny without
+    /// an `access` list blocks both reads and writes.
+    #[instrument(skip(self))]
+    pub async fn deny_permission(
+        &self,
+        component_id: &str,
+        permission_type: &str,
+        details: &serde_json::Value,
+    ) -> Result<()> {
+        info!(
+            component_id,
+            permission_type, "Adding deny rule to component"
+        );
+        let mut permission_rule = self.parse_permission_rule(permission_type, details)?;
+        if let PermissionRule::Storage(storage) = &mut permission_rule {
+            if storage.access.is_empty() {
+                storage.access = vec![AccessType::Read, AccessType::Write];
+            }
+        }
+        self.validate_deny_rule(&permission_rule)?;
+        let mut policy = self.load_or_create_component_policy(component_id).await?;
+        self.add_deny_rule_to_policy(&mut policy, permission_rule)?;
+        self.save_component_policy(component_id, &policy).await?;
+        self.update_policy_registry(component_id, &policy).await?;
+
+        info!(
+            component_id,
+            permission_type, "Deny rule added successfully"
+        );
+        Ok(())
+    }
+
+    /// Remove a deny rule from a component
+    ///
+    /// Storage deny rules are removed by URI, regardless of their access types.
+    #[instrument(skip(self))]
+    pub async fn revoke_deny_permission(
+        &self,
+        component_id: &str,
+        permission_type: &str,
+        details: &serde_json::Value,
+    ) -> Result<()> {
+        info!(
+            component_id,
+            permission_type, "Removing deny rule from component"
+        );
+        let permission_rule = self.parse_permission_rule(permission_type, details)?;
+        self.validate_deny_rule(&permission_rule)?;
+        let mut policy = self.load_or_create_component_policy(component_id).await?;
+        self.remove_deny_rule_from_policy(&mut policy, permission_rule)?;
+        self.save_component_policy(component_id, &policy).await?;
+        self.update_policy_registry(component_id, &policy).await?;
+
+        info!(
+            component_id,
+            permission_type, "Deny rule removed successfully"
+        );
+        Ok(())
+    }
+
     /// Parse a permission rule from the request details
     fn parse_permission_rule(
         &self,
@@ -367,13 +444,61 @@ impl PolicyManager {
     ) -> Result<PermissionRule> {
         let permission_rule = match permission_type {
             "network" => {
-                let host = details
-                    .get("host")
-                    .and_then(|v| v.as_str())
-                    .ok_or_else(|| anyhow!("Missing 'host' field for network permission"))?;
-                PermissionRule::Network(NetworkPermission::Host(NetworkHostPermission {
-                    host: host.to_string(),
-                }))
+                if let Some(cidr) = details.get("cidr").and_then(|v| v.as_str()) {
+                    PermissionRule::Network(NetworkPermission::Cidr(NetworkCidrPermission {
+                        cidr: cidr.to_string(),
+                    }))
+                } else if let Some(inject) = details.get("inject") {
+                    let inject: CredentialInjection = serde_json::from_value(inject.clone())
+                        .map_err(|e| {
+                            anyhow!("Invalid 'inject' field for network permission: {}", e)
+                        })?;
+                    PermissionRule::Network(NetworkPermission::Inject(NetworkInjectPermission {
+                        inject,
+                    }))
+                } else if let Some(socket) = details.get("socket").and_then(|v| v.as_str()) {
+                    PermissionRule::Network(NetworkPermission::Socket(NetworkSocketPermission {
+                        socket: socket.to_string(),
+                    }))
+                } else {
+                    let host = details
+                        .get("host")
+                        .and_then(|v| v.as_str())
+                        .ok_or_else(|| anyhow!("Missing 'host' field for network permission"))?;
+                    // Tools that only know about hosts can still pass a raw socket destination
+                    if host.starts_with("tcp://") || host.starts_with("udp://") {
+                        PermissionRule::Network(NetworkPermission::Socket(
+                            NetworkSocketPermission {
+                                socket: host.to_string(),
+                            },
+                        ))
+                    } else {
+                        let strings = |key: &str| -> Result<Option<Vec<String>>> {
+                            details
+                                .get(key)
+                                .map(|value| {
+                                    serde_json::from_value(value.clone()).map_err(|_| {
+                                        anyhow!("'{}' field must be an array of strings", key)
+                                    })
+                                })
+                                .transpose()
+                        };
+                        let ports = details
+                            .get("ports")
+                            .map(|value| {
+                                serde_json::from_value(value.clone()).map_err(|_| {
+                                    anyhow!("'ports' field must be an array of port numbers")
+                                })
+                            })
+                            .transpose()?;
+                        PermissionRule::Network(NetworkPermission::Host(NetworkHostPermission {
+                            host: host.to_string(),
+                            methods: strings("methods")?,
+                            ports,
+                            paths: strings("paths")?,
+                        }))
+                    }
+                }
             }
             "storage" => {
                 let uri = details
@@ -403,15 +528,45 @@ impl PolicyManager {
                         })
                         .collect();
 
+                    let mount = details
+                        .get("mount")
+                        .map(|mount| {
+                            mount
+                                .as_str()
+                                .map(str::to_string)
+                                .ok_or_else(|| anyhow!("'mount' field must be a string"))
+                        })
+                        .transpose()?;
+                    let quota = details
+                        .get("quota")
+                        .map(|quota| {
+                            serde_json::from_value::<StorageQuota>(quota.clone())
+                                .map_err(|e| anyhow!("Invalid 'quota' field: {}", e))
+                        })
+                        .transpose()?;
+                    let seed = details
+                        .get("seed")
+                        .map(|seed| {
+                            serde_json::from_value::<MemorySeed>(seed.clone())
+                                .map_err(|e| anyhow!("Invalid 'seed' field: {}", e))
+                        })
+                        .transpose()?;
+
                     PermissionRule::Storage(StoragePermission {
                         uri: uri.to_string(),
                         access: access_types?,
+                        mount,
+                        quota,
+                        seed,
                     })
                 } else {
                     // No access field provided - used for revocation, create empty access
                     PermissionRule::Storage(StoragePermission {
                         uri: uri.to_string(),
                         access: Vec::new(),
+                        mount: None,
+                        quota: None,
+                        seed: None,
                     })
                 }
             }
@@ -559,6 +714,15 @@ impl PolicyManager {
                     existing.access.push(access_type);
                 }
             }
+            if storage.mount.is_some() {
+                existing.mount = storage.mount;
+            }
+            if storage.quota.is_some() {
+                existing.quota = storage.quota;
+            }
+            if storage.seed.is_some() {
+                existing.seed = storage.seed;
+            }
         } else {
             // Add new storage permission (only if not already present)
             if !allow_set.contains(&storage) {
@@ -666,16 +830,39 @@ impl PolicyManager {
     /// Validate permission rule
     fn validate_permission_rule(&self, rule: &PermissionRule) -> Result<()> {
         match rule {
-            PermissionRule::Network(NetworkPermission::Host(NetworkHostPermission { host })) => {
-                if host.is_empty() {
+            PermissionRule::Network(NetworkPermission::Host(host)) => {
+                if host.host.is_empty() {
                     return Err(anyhow!("Network host cannot be empty"));
                 }
+                for rule in host_rule_strings(host)? {
+                    HttpRule::from_str(&rule)?;
+                }
+            }
+            PermissionRule::Network(NetworkPermission::Cidr(NetworkCidrPermission { cidr })) => {
+                parse_cidr(cidr)?;
+            }
+            PermissionRule::Network(NetworkPermission::Socket(NetworkSocketPermission {
+                socket,
+            })) => {
+                parse_socket_rule(socket)?;
+            }
+            PermissionRule::Network(NetworkPermission::Inject(NetworkInjectPermission {
+                inject,
+            })) => {
+                HeaderInjection::from_policy(inject)?;
             }
             PermissionRule::Storage(storage) => {
-                // TODO: the validation should verify if the uri is actually valid or not
-                if storage.uri.is_empty() {
-                    return Err(anyhow!("Storage URI cannot be empty"));
+                validate_storage_uri(&storage.uri)?;
+                if let Some(mount) = &storage.mount {
+                    Permissions::validate_storage_mount(mount)?;
                 }
+                if let Some(quota) = &storage.quota {
+                    if !storage.access.contains(&AccessType::Write) {
+                        return Err(anyhow!("Storage quota needs write access"));
+                    }
+                    quota.validate()?;
+                }
+                Permissions::validate_memory_storage(storage)?;
                 // Note: access can be empty for revocation operations, but not for grant operations
                 // The validation for non-empty access is now done during parsing
             }
@@ -689,6 +876,132 @@ impl PolicyManager {
         Ok(())
     }
 
+    /// Validate a deny rule, which unlike an allow rule may use `*` to match every host
+    fn validate_deny_rule(&self, rule: &PermissionRule) -> Result<()> {
+        match rule {
+            PermissionRule::Network(NetworkPermission::Host(host)) => {
+                if host.host.is_empty() {
+                    return Err(anyhow!("Network host cannot be empty"));
+                }
+                for rule in host_rule_strings(host)? {
+                    HttpRule::from_deny_str(&rule)?;
+                }
+                Ok(())
+            }
+            PermissionRule::Network(NetworkPermission::Inject(_)) => {
+                Err(anyhow!("Credential injection can't be used as a deny rule"))
+            }
+            PermissionRule::Storage(storage) => {
+                validate_storage_uri(&storage.uri)?;
+                if storage.mount.is_some() {
+                    return Err(anyhow!("Storage deny rules can't have a mount point"));
+                }
+                if storage.quota.is_some() {
+                    return Err(anyhow!("Storage deny rules can't have a quota"));
+                }
+                if storage.uri.starts_with("mem://") {
+                    return Err(anyhow!("Storage deny rules only apply to fs:// paths"));
+                }
+                let path = storage.uri.strip_prefix("fs://").unwrap_or(&storage.uri);
+                let path = path
+                    .strip_suffix("/**")
+                    .or_else(|| path.strip_suffix("/*"))
+                    .unwrap_or(path);
+                if path.contains('*') {
+                    return Err(anyhow!(
+                        "Storage deny rules only support a trailing /* or /**, got {}",
+                        storage.uri
+                    ));
+                }
+                Ok(())
+            }
+            PermissionRule::Network(_) => self.validate_permission_rule(rule),
+            _ => Err(anyhow!(
+                "Deny rules are only supported for network and storage permissions"
+            )),
+        }
+    }
+
+    /// Add deny rule to policy
+    fn add_deny_rule_to_policy(
+        &self,
+        policy: &mut PolicyDocument,
+        rule: PermissionRule,
+    ) -> Result<()> {
+        match rule {
+            PermissionRule::Network(network) => {
+                let deny_set = policy
+                    .permissions
+                    .network
+                    .get_or_insert_with(Default::default)
+                    .deny
+                    .get_or_insert_with(Vec::new);
+                if !deny_set.contains(&network) {
+                    deny_set.push(network);
+                }
+            }
+            PermissionRule::Storage(storage) => {
+                let deny_set = policy
+                    .permissions
+                    .storage
+                    .get_or_insert_with(Default::default)
+                    .deny
+                    .get_or_insert_with(Vec::new);
+                if let Some(existing) = deny_set.iter_mut().find(|p| p.uri == storage.uri) {
+                    for access_type in storage.access {
+                        if !existing.access.contains(&access_type) {
+                            existing.access.push(access_type);
+                        }
+                    }
+                } else {
+                    deny_set.push(storage);
+                }
+            }
+            _ => {
+                return Err(anyhow!(
+                    "Deny rules are only supported for network and storage permissions"
+                ))
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove deny rule from policy
+    fn remove_deny_rule_from_policy(
+        &self,
+        policy: &mut PolicyDocument,
+        rule: PermissionRule,
+    ) -> Result<()> {
+        match rule {
+            PermissionRule::Network(network) => {
+                if let Some(network_perms) = &mut policy.permissions.network {
+                    if let Some(deny_set) = &mut network_perms.deny {
+                        deny_set.retain(|perm| perm != &network);
+                        if deny_set.is_empty() {
+                            network_perms.deny = None;
+                        }
+                    }
+                }
+            }
+            PermissionRule::Storage(storage) => {
+                if let Some(storage_perms) = &mut policy.permissions.storage {
+                    if let Some(deny_set) = &mut storage_perms.deny {
+                        deny_set.retain(|perm| perm.uri != storage.uri);
+                        if deny_set.is_empty() {
+                            storage_perms.deny = None;
+                        }
+                    }
+                }
+            }
+            _ => {
+                return Err(anyhow!(
+                    "Deny rules are only supported for network and storage permissions"
+                ))
+            }
+        }
+        Ok(())
+    }
+
     /// Revoke a specific permission rule from a component
     #[instrument(skip(self))]
     pub async fn revoke_permission(
@@ -774,7 +1087,18 @@ impl PolicyManager {
     ) -> Result<()> {
         if let Some(network_perms) = &mut policy.permissions.network {
             if let Some(allow_set) = &mut network_perms.allow {
-                allow_set.retain(|perm| perm != &network);
+                match &network {
+                    // A bare host removes every grant for it, whatever it was scoped to
+                    NetworkPermission::Host(NetworkHostPermission {
+                        host,
+                        methods: None,
+                        ports: None,
+                        paths: None,
+                    }) => allow_set.retain(|perm| {
+                        !matches!(perm, NetworkPermission::Host(granted) if &granted.host == host)
+                    }),
+                    _ => allow_set.retain(|perm| perm != &network),
+                }
                 // Clean up empty structures
                 if allow_set.is_empty() {
                     network_perms.allow = None;
@@ -845,6 +1169,17 @@ impl PolicyManager {
     }
 }
 
+/// Checks that a storage URI is non-empty and that an `fs://` glob is well formed
+fn validate_storage_uri(uri: &str) -> Result<()> {
+    if uri.is_empty() {
+        return Err(anyhow!("Storage URI cannot be empty"));
+    }
+    if let Some(path) = uri.strip_prefix("fs://") {
+        split_storage_pattern(path)?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1195,6 +1530,372 @@ permissions: {}
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_grant_permission_wildcard_host() -> Result<()> {
+        let manager = create_test_manager().await?;
+        manager.load_test_component().await?;
+
+        let details = serde_json::json!({"host": "*.example.com"});
+        manager
+            .grant_permission(TEST_COMPONENT_ID, "network", &details)
+            .await?;
+
+        let details = serde_json::json!({"host": "api.*.com"});
+        let result = manager
+            .grant_permission(TEST_COMPONENT_ID, "network", &details)
+            .await;
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Invalid host pattern"));
+
+        let details = serde_json::json!({"host": "*.example.com"});
+        manager
+            .revoke_permission(TEST_COMPONENT_ID, "network", &details)
+            .await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_grant_permission_cidr() -> Result<()> {
+        let manager = create_test_manager().await?;
+        manager.load_test_component().await?;
+
+        let details = serde_json::json!({"cidr": "10.0.0.0/8"});
+        manager
+            .grant_permission(TEST_COMPONENT_ID, "network", &details)
+            .await?;
+
+        let policy_path = manager.get_component_policy_path(TEST_COMPONENT_ID);
+        let policy_content = tokio::fs::read_to_string(&policy_path).await?;
+        assert!(policy_content.contains("cidr: 10.0.0.0/8"));
+
+        let details = serde_json::json!({"cidr": "10.0.0.0/99"});
+        let result = manager
+            .grant_permission(TEST_COMPONENT_ID, "network", &details)
+            .await;
+        assert!(result.unwrap_err().to_string().contains("Invalid CIDR"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_grant_storage_mount() -> Result<()> {
+        let manager = create_test_manager().await?;
+        manager.load_test_component().await?;
+        let data_dir = tempfile::tempdir()?;
+
+        let details = serde_json::json!({
+            "uri": format!("fs://{}", data_dir.path().display()),
+            "access": ["read"],
+            "mount": "/data",
+        });
+        manager
+            .grant_permission(TEST_COMPONENT_ID, "storage", &details)
+            .await?;
+        let template = manager
+            .policy_manager
+            .template_for_component(TEST_COMPONENT_ID)
+            .await;
+        assert_eq!(template.preopened_dirs[0].host_path, data_dir.path());
+        assert_eq!(template.preopened_dirs[0].guest_path, "/data");
+
+        let details = serde_json::json!({"uri": "fs://work", "access": ["read"], "mount": "../up"});
+        let result = manager
+            .grant_permission(TEST_COMPONENT_ID, "storage", &details)
+            .await;
+        assert!(result.unwrap_err().to_string().contains("'..'"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_grant_storage_quota() -> Result<()> {
+        let manager = create_test_manager().await?;
+        manager.load_test_component().await?;
+        let data_dir = tempfile::tempdir()?;
+        std::fs::write(data_dir.path().join("state.json"), b"{}")?;
+
+        let details = serde_json::json!({
+            "uri": format!("fs://{}", data_dir.path().display()),
+            "access": ["read", "write"],
+            "quota": {"bytes": "10Mi", "files": 50},
+        });
+        manager
+            .grant_permission(TEST_COMPONENT_ID, "storage", &details)
+            .await?;
+        let usage = manager.storage_usage(TEST_COMPONENT_ID).await?;
+        let grant = &usage[0];
+        assert_eq!((grant.bytes, grant.files), (2, 1));
+        assert_eq!(grant.max_bytes, Some(10 * 1024 * 1024));
+        assert_eq!(grant.max_files, Some(50));
+
+        let details =
+            serde_json::json!({"uri": "fs://work", "access": ["read"], "quota": {"files": 5}});
+        let result = manager
+            .grant_permission(TEST_COMPONENT_ID, "storage", &details)
+            .await;
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("needs write access"));
+
+        let details = serde_json::json!({"uri": "fs://work", "access": ["write"], "quota": {}});
+        let result = manager
+            .grant_permission(TEST_COMPONENT_ID, "storage", &details)
+            .await;
+        assert!(result.is_err());
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_grant_storage_symlink_is_refused() -> Result<()> {
+        let manager = create_test_manager().await?;
+        manager.load_test_component().await?;
+        let outside = tempfile::tempdir()?;
+        let plugin_dir = manager.policy_manager.storage.root().to_path_buf();
+        std::os::unix::fs::symlink(outside.path(), plugin_dir.join("link"))?;
+
+        let details = serde_json::json!({"uri": "fs://link", "access": ["read"]});
+        let result = manager
+            .grant_permission(TEST_COMPONENT_ID, "storage", &details)
+            .await;
+        assert!(result.unwrap_err().to_string().contains("is a symlink"));
+
+        let details = serde_json::json!({"uri": "fs://link/data/**", "access": ["read"]});
+        let result = manager
+            .grant_permission(TEST_COMPONENT_ID, "storage", &details)
+            .await;
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("through a symlink"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_grant_storage_glob() -> Result<()> {
+        let manager = create_test_manager().await?;
+        manager.load_test_component().await?;
+
+        let details = serde_json::json!({"uri": "fs://projects/*/docs/**", "access": ["read"]});
+        manager
+            .grant_permission(TEST_COMPONENT_ID, "storage", &details)
+            .await?;
+        let template = manager
+            .policy_manager
+            .template_for_component(TEST_COMPONENT_ID)
+            .await;
+        assert_eq!(template.preopened_dirs[0].guest_path, "projects");
+        assert!(template.allowed_paths[0].pattern.is_some());
+
+        let details = serde_json::json!({"uri": "fs://projects/**.md", "access": ["read"]});
+        let result = manager
+            .grant_permission(TEST_COMPONENT_ID, "storage", &details)
+            .await;
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("** needs to be its own path segment"));
+
+        let details = serde_json::json!({"uri": "fs://projects/*/secrets"});
+        let result = manager
+            .deny_permission(TEST_COMPONENT_ID, "storage", &details)
+            .await;
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("only support a trailing"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_deny_permission() -> Result<()> {
+        let manager = create_test_manager().await?;
+        manager.load_test_component().await?;
+
+        manager
+            .grant_permission(
+                TEST_COMPONENT_ID,
+                "network",
+                &serde_json::json!({"host": "*.internal"}),
+            )
+            .await?;
+        manager
+            .deny_permission(
+                TEST_COMPONENT_ID,
+                "network",
+                &serde_json::json!({"host": "metadata.internal"}),
+            )
+            .await?;
+        manager
+            .deny_permission(
+                TEST_COMPONENT_ID,
+                "network",
+                &serde_json::json!({"host": "*"}),
+            )
+            .await?;
+        manager
+            .deny_permission(
+                TEST_COMPONENT_ID,
+                "storage",
+                &serde_json::json!({"uri": "fs://work/secrets"}),
+            )
+            .await?;
+
+        let policy_path = manager.get_component_policy_path(TEST_COMPONENT_ID);
+        let policy = PolicyParser::parse_file(&policy_path)?;
+        let network = policy.permissions.network.as_ref().unwrap();
+        assert_eq!(network.allow.as_ref().unwrap().len(), 1);
+        assert_eq!(network.deny.as_ref().unwrap().len(), 2);
+        let storage_deny = policy.permissions.storage.as_ref().unwrap().deny.as_ref();
+        assert_eq!(
+            storage_deny.unwrap()[0].access,
+            vec![AccessType::Read, AccessType::Write]
+        );
+
+        let template = manager
+            .policy_manager
+            .template_for_component(TEST_COMPONENT_ID)
+            .await;
+        assert!(template.denied_hosts.contains("metadata.internal"));
+        assert_eq!(template.denied_paths.len(), 1);
+
+        let result = manager
+            .deny_permission(
+                TEST_COMPONENT_ID,
+                "environment",
+                &serde_json::json!({"key": "API_KEY"}),
+            )
+            .await;
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Deny rules are only supported"));
+
+        manager
+            .revoke_deny_permission(
+                TEST_COMPONENT_ID,
+                "network",
+                &serde_json::json!({"host": "*"}),
+            )
+            .await?;
+        manager
+            .revoke_deny_permission(
+                TEST_COMPONENT_ID,
+                "storage",
+                &serde_json::json!({"uri": "fs://work/secrets"}),
+            )
+            .await?;
+
+        let policy = PolicyParser::parse_file(&policy_path)?;
+        let network = policy.permissions.network.as_ref().unwrap();
+        assert_eq!(network.deny.as_ref().unwrap().len(), 1);
+        assert!(policy.permissions.storage.as_ref().unwrap().deny.is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_grant_permission_scoped_host() -> Result<()> {
+        let manager = create_test_manager().await?;
+        manager.load_test_component().await?;
+
+        let details = serde_json::json!({
+            "host": "https://api.github.com",
+            "methods": ["GET", "HEAD"],
+            "paths": ["/repos/our-org/**"]
+        });
+        manager
+            .grant_permission(TEST_COMPONENT_ID, "network", &details)
+            .await?;
+        let details = serde_json::json!({"host": "POST https://hooks.example.com:8443/notify"});
+        manager
+            .grant_permission(TEST_COMPONENT_ID, "network", &details)
+            .await?;
+
+        let template = manager
+            .policy_manager
+            .template_for_component(TEST_COMPONENT_ID)
+            .await;
+        assert!(template
+            .allowed_hosts
+            .contains("GET https://api.github.com/repos/our-org/**"));
+        assert!(template
+            .allowed_hosts
+            .contains("HEAD https://api.github.com/repos/our-org/**"));
+        assert!(template
+            .allowed_hosts
+            .contains("POST https://hooks.example.com:8443/notify"));
+
+        // The same scope can't be given inline and as a list
+        let details = serde_json::json!({"host": "GET api.example.com", "methods": ["POST"]});
+        assert!(manager
+            .grant_permission(TEST_COMPONENT_ID, "network", &details)
+            .await
+            .is_err());
+        let details = serde_json::json!({"host": "api.example.com", "ports": ["https"]});
+        assert!(manager
+            .grant_permission(TEST_COMPONENT_ID, "network", &details)
+            .await
+            .is_err());
+
+        // Revoking the bare host drops the grant whatever it was scoped to
+        let details = serde_json::json!({"host": "https://api.github.com"});
+        manager
+            .revoke_permission(TEST_COMPONENT_ID, "network", &details)
+            .await?;
+        let policy_path = manager.get_component_policy_path(TEST_COMPONENT_ID);
+        let policy_content = tokio::fs::read_to_string(&policy_path).await?;
+        assert!(!policy_content.contains("api.github.com"));
+        assert!(policy_content.contains("hooks.example.com"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_grant_permission_socket() -> Result<()> {
+        let manager = create_test_manager().await?;
+        manager.load_test_component().await?;
+
+        let details = serde_json::json!({"socket": "tcp://db.internal:5432"});
+        manager
+            .grant_permission(TEST_COMPONENT_ID, "network", &details)
+            .await?;
+        let details = serde_json::json!({"host": "udp://10.0.0.53:53"});
+        manager
+            .grant_permission(TEST_COMPONENT_ID, "network", &details)
+            .await?;
+
+        let policy_path = manager.get_component_policy_path(TEST_COMPONENT_ID);
+        let policy_content = tokio::fs::read_to_string(&policy_path).await?;
+        assert!(policy_content.contains("socket: tcp://db.internal:5432"));
+        assert!(policy_content.contains("socket: udp://10.0.0.53:53"));
+
+        let template = manager
+            .policy_manager
+            .template_for_component(TEST_COMPONENT_ID)
+            .await;
+        assert!(template.network_perms.allow_tcp);
+        assert!(template.network_perms.allow_udp);
+
+        let details = serde_json::json!({"socket": "tcp://db.internal"});
+        let result = manager
+            .grant_permission(TEST_COMPONENT_ID, "network", &details)
+            .await;
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("Invalid socket rule"));
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_grant_permission_multiple_permissions() -> Result<()> {
         let manager = create_test_manager().await?;
@@ -1236,7 +1937,7 @@ permissions: {}
 
         // Verify policy registry was updated by attempting to get WASI state
         let _wasi_state = manager
-            .get_wasi_state_for_component(TEST_COMPONENT_ID)
+            .get_wasi_state_for_component(TEST_COMPONENT_ID, None)
             .await?;
 
         // If we get here without error, the policy registry was updated successfully
@@ -1287,6 +1988,7 @@ permissions:
         let network_rule =
             PermissionRule::Network(NetworkPermission::Host(NetworkHostPermission {
                 host: "example.com".to_string(),
+                ..Default::default()
             }));
         let serialized = serde_json::to_string(&network_rule)?;
         assert!(serialized.contains("example.com"));
@@ -1294,6 +1996,9 @@ permissions:
         let storage_rule = PermissionRule::Storage(StoragePermission {
             uri: "fs:///tmp/test".to_string(),
             access: vec![AccessType::Read, AccessType::Write],
+            mount: None,
+            quota: None,
+            seed: None,
         });
         let serialized = serde_json::to_string(&storage_rule)?;
         assert!(serialized.contains("fs:///tmp/test"));
@@ -1309,10 +2014,14 @@ permissions:
         let network_perm =
             PermissionRule::Network(NetworkPermission::Host(NetworkHostPermission {
                 host: "example.com".to_string(),
+                ..Default::default()
             }));
         let storage_perm = PermissionRule::Storage(StoragePermission {
             uri: "fs:///tmp".to_string(),
             access: vec![AccessType::Read, AccessType::Write],
+            mount: None,
+            quota: None,
+            seed: None,
         });
         let env_perm = PermissionRule::Environment(EnvironmentPermission {
             key: "API_KEY".to_string(),
@@ -1335,9 +2044,13 @@ permissions:
         // Test pattern matching works correctly
         let rule = PermissionRule::Network(NetworkPermission::Host(NetworkHostPermission {
             host: "test.com".to_string(),
+            ..Default::default()
         }));
         match rule {
-            PermissionRule::Network(NetworkPermission::Host(NetworkHostPermission { host })) => {
+            PermissionRule::Network(NetworkPermission::Host(NetworkHostPermission {
+                host,
+                ..
+            })) => {
                 assert_eq!(host, "test.com");
             }
             _ => panic!("Expected network permission"),
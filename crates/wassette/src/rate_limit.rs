@@ -0,0 +1,356 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Token-bucket rate limits on outbound requests, shared across all calls of a component
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Result};
+use policy::{RateLimit, RateLimitAction};
+
+use crate::http::HttpRule;
+
+/// A rate limit from a component's `resources.network.rate_limits` policy
+#[derive(Debug, Clone, PartialEq)]
+pub struct RateLimitRule {
+    /// Requests the limit applies to, or every request when `None`
+    host: Option<(String, HttpRule)>,
+    requests: u32,
+    per: Duration,
+    burst: u32,
+    action: RateLimitAction,
+}
+
+impl RateLimitRule {
+    pub(crate) fn from_policy(limit: &RateLimit) -> Result<Self> {
+        if limit.requests == 0 || limit.burst == Some(0) {
+            bail!("Rate limit must allow at least one request");
+        }
+        let host = limit
+            .host
+            .as_ref()
+            .map(|host| HttpRule::from_str(host).map(|rule| (host.clone(), rule)))
+            .transpose()?;
+
+        Ok(RateLimitRule {
+            host,
+            requests: limit.requests,
+            per: limit.per.to_duration()?,
+            burst: limit.burst.unwrap_or(limit.requests),
+            action: limit.on_limit.unwrap_or_default(),
+        })
+    }
+
+    fn applies_to(&self, method: &hyper::Method, uri: &hyper::Uri) -> bool {
+        self.host
+            .as_ref()
+            .is_none_or(|(_, rule)| rule.may_match(method, uri))
+    }
+
+    /// Tokens added to the bucket per second
+    fn refill_rate(&self) -> f64 {
+        f64::from(self.requests) / self.per.as_secs_f64()
+    }
+
+    fn describe(&self) -> String {
+        let scope = match &self.host {
+            Some((host, _)) => host.as_str(),
+            None => "all requests",
+        };
+        format!("{} requests per {:?} ({scope})", self.requests, self.per)
+    }
+}
+
+/// Outcome of taking a token for an outbound request
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum RateLimitDecision {
+    /// Send the request now
+    Allow,
+    /// Send the request after waiting, because of the described limit
+    Delay(Duration, String),
+    /// Don't send the request, because of the described limit
+    Reject(String),
+}
+
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// The buckets for one component's rate limits
+pub(crate) struct ComponentRateLimiter {
+    rules: Vec<RateLimitRule>,
+    buckets: Mutex<Vec<TokenBucket>>,
+}
+
+impl ComponentRateLimiter {
+    fn new(rules: Vec<RateLimitRule>) -> Self {
+        let now = Instant::now();
+        let buckets = rules
+            .iter()
+            .map(|rule| TokenBucket {
+                tokens: f64::from(rule.burst),
+                updated: now,
+            })
+            .collect();
+        Self {
+            rules,
+            buckets: Mutex::new(buckets),
+        }
+    }
+
+    /// Takes a token from every bucket the request falls under. Delayed requests that
+    /// would wait longer than `max_wait` or a limit's period are rejected instead.
+    pub(crate) fn acquire(
+        &self,
+        method: &hyper::Method,
+        uri: &hyper::Uri,
+        max_wait: Option<Duration>,
+    ) -> RateLimitDecision {
+        self.acquire_at(method, uri, Instant::now(), max_wait)
+    }
+
+    fn acquire_at(
+        &self,
+        method: &hyper::Method,
+        uri: &hyper::Uri,
+        now: Instant,
+        max_wait: Option<Duration>,
+    ) -> RateLimitDecision {
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+        let matching: Vec<usize> = (0..self.rules.len())
+            .filter(|&idx| self.rules[idx].applies_to(method, uri))
+            .collect();
+
+        for &idx in &matching {
+            let rule = &self.rules[idx];
+            let bucket = &mut buckets[idx];
+            let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+            bucket.tokens =
+                (bucket.tokens + elapsed * rule.refill_rate()).min(f64::from(rule.burst));
+            bucket.updated = now;
+        }
+
+        let wait_for = |idx: usize| {
+            let tokens = buckets[idx].tokens;
+            (tokens < 1.0)
+                .then(|| Duration::from_secs_f64((1.0 - tokens) / self.rules[idx].refill_rate()))
+        };
+
+        // A request over a rejecting limit, or one that would queue for longer than the
+        // limit's period or the rest of the call, fails without using up any tokens.
+        // That keeps the debt of a delaying bucket under one period's worth of requests.
+        if let Some(&idx) = matching.iter().find(|&&idx| {
+            let rule = &self.rules[idx];
+            wait_for(idx).is_some_and(|wait| {
+                rule.action == RateLimitAction::Reject
+                    || wait > rule.per
+                    || max_wait.is_some_and(|max_wait| wait > max_wait)
+            })
+        }) {
+            return RateLimitDecision::Reject(self.rules[idx].describe());
+        }
+
+        // Delayed requests take their token up front, so the next request waits behind them
+        let mut decision = RateLimitDecision::Allow;
+        let mut longest = Duration::ZERO;
+        for &idx in &matching {
+            if let Some(wait) = wait_for(idx) {
+                if wait > longest {
+                    longest = wait;
+                    decision = RateLimitDecision::Delay(wait, self.rules[idx].describe());
+                }
+            }
+        }
+        for &idx in &matching {
+            buckets[idx].tokens -= 1.0;
+        }
+        decision
+    }
+}
+
+/// Rate limiter state for all components, kept across calls by the `LifecycleManager`
+#[derive(Clone, Default)]
+pub(crate) struct RateLimiters {
+    limiters: Arc<Mutex<HashMap<String, Arc<ComponentRateLimiter>>>>,
+}
+
+impl RateLimiters {
+    /// Returns the component's limiter, starting fresh buckets when its limits changed
+    pub(crate) fn for_component(
+        &self,
+        component_id: &str,
+        rules: &[RateLimitRule],
+    ) -> Option<Arc<ComponentRateLimiter>> {
+        let mut limiters = self.limiters.lock().unwrap_or_else(|e| e.into_inner());
+        if rules.is_empty() {
+            limiters.remove(component_id);
+            return None;
+        }
+        if let Some(limiter) = limiters.get(component_id) {
+            if limiter.rules == rules {
+                return Some(Arc::clone(limiter));
+            }
+        }
+        let limiter = Arc::new(ComponentRateLimiter::new(rules.to_vec()));
+        limiters.insert(component_id.to_string(), Arc::clone(&limiter));
+        Some(limiter)
+    }
+
+    /// Drops the state of an unloaded component
+    pub(crate) fn remove(&self, component_id: &str) {
+        self.limiters
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .remove(component_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use hyper::Method;
+    use policy::TimeoutLimit;
+
+    use super::*;
+
+    fn rule(host: Option<&str>, requests: u32, per: u64, action: RateLimitAction) -> RateLimitRule {
+        RateLimitRule::from_policy(&RateLimit {
+            host: host.map(str::to_string),
+            requests,
+            per: TimeoutLimit::Number(per),
+            burst: None,
+            on_limit: Some(action),
+        })
+        .unwrap()
+    }
+
+    fn uri(uri: &str) -> hyper::Uri {
+        uri.parse().unwrap()
+    }
+
+    #[test]
+    fn test_rejecting_bucket_refills() {
+        let limiter = ComponentRateLimiter::new(vec![rule(None, 2, 1, RateLimitAction::Reject)]);
+        let start = Instant::now();
+        let get = |at| limiter.acquire_at(&Method::GET, &uri("https://a.example.com/"), at, None);
+
+        assert_eq!(get(start), RateLimitDecision::Allow);
+        assert_eq!(get(start), RateLimitDecision::Allow);
+        assert!(
+            matches!(get(start), RateLimitDecision::Reject(msg) if msg.contains("2 requests per 1s"))
+        );
+        // Half a second refills one token at two requests per second
+        assert_eq!(
+            get(start + Duration::from_millis(500)),
+            RateLimitDecision::Allow
+        );
+        assert!(matches!(
+            get(start + Duration::from_millis(500)),
+            RateLimitDecision::Reject(_)
+        ));
+    }
+
+    #[test]
+    fn test_delaying_bucket_queues_requests() {
+        let limiter = ComponentRateLimiter::new(vec![rule(None, 1, 2, RateLimitAction::Delay)]);
+        let start = Instant::now();
+        let get = || limiter.acquire_at(&Method::GET, &uri("https://a.example.com/"), start, None);
+
+        assert_eq!(get(), RateLimitDecision::Allow);
+        assert!(
+            matches!(get(), RateLimitDecision::Delay(wait, _) if wait == Duration::from_secs(2))
+        );
+        // Waiting another period behind the queued request is more than the limit allows
+        assert!(
+            matches!(get(), RateLimitDecision::Reject(msg) if msg.contains("1 requests per 2s"))
+        );
+    }
+
+    #[test]
+    fn test_delaying_bucket_debt_is_bounded() {
+        let limiter = ComponentRateLimiter::new(vec![rule(None, 2, 1, RateLimitAction::Delay)]);
+        let start = Instant::now();
+        let get = |at| limiter.acquire_at(&Method::GET, &uri("https://a.example.com/"), at, None);
+
+        let delayed = (0..100)
+            .filter(|_| matches!(get(start), RateLimitDecision::Delay(..)))
+            .count();
+        assert_eq!(delayed, 2);
+
+        // The rejected requests took no tokens, so a full period later the bucket is
+        // back to where it would be after the allowed and delayed requests alone
+        assert!(matches!(
+            get(start + Duration::from_secs(1)),
+            RateLimitDecision::Delay(wait, _) if wait == Duration::from_millis(500)
+        ));
+    }
+
+    #[test]
+    fn test_delay_longer_than_remaining_call_is_rejected() {
+        let limiter = ComponentRateLimiter::new(vec![rule(None, 1, 10, RateLimitAction::Delay)]);
+        let start = Instant::now();
+        let get = |max_wait| {
+            limiter.acquire_at(
+                &Method::GET,
+                &uri("https://a.example.com/"),
+                start,
+                Some(max_wait),
+            )
+        };
+
+        assert_eq!(get(Duration::from_secs(1)), RateLimitDecision::Allow);
+        assert!(matches!(
+            get(Duration::from_secs(5)),
+            RateLimitDecision::Reject(_)
+        ));
+        // The rejected request left the bucket as it was
+        assert!(matches!(
+            get(Duration::from_secs(30)),
+            RateLimitDecision::Delay(wait, _) if wait == Duration::from_secs(10)
+        ));
+    }
+
+    #[test]
+    fn test_host_limits_only_apply_to_matching_requests() {
+        let limiter = ComponentRateLimiter::new(vec![
+            rule(Some("api.github.com"), 1, 60, RateLimitAction::Reject),
+            rule(None, 3, 60, RateLimitAction::Reject),
+        ]);
+        let start = Instant::now();
+        let get = |url| limiter.acquire_at(&Method::GET, &uri(url), start, None);
+
+        assert_eq!(
+            get("https://api.github.com/repos"),
+            RateLimitDecision::Allow
+        );
+        assert!(matches!(
+            get("https://api.github.com/repos"),
+            RateLimitDecision::Reject(msg) if msg.contains("api.github.com")
+        ));
+        // The rejected request did not use up a token of the component-wide limit
+        assert_eq!(get("https://example.com/"), RateLimitDecision::Allow);
+        assert_eq!(get("https://example.com/"), RateLimitDecision::Allow);
+        assert!(matches!(
+            get("https://example.com/"),
+            RateLimitDecision::Reject(msg) if msg.contains("all requests")
+        ));
+    }
+
+    #[test]
+    fn test_limiter_is_shared_until_rules_change() {
+        let limiters = RateLimiters::default();
+        let rules = vec![rule(None, 1, 60, RateLimitAction::Reject)];
+
+        let first = limiters.for_component("component", &rules).unwrap();
+        let second = limiters.for_component("component", &rules).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+
+        let changed = vec![rule(None, 2, 60, RateLimitAction::Reject)];
+        let third = limiters.for_component("component", &changed).unwrap();
+        assert!(!Arc::ptr_eq(&first, &third));
+
+        assert!(limiters.for_component("component", &[]).is_none());
+    }
+}
@@ -0,0 +1,182 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Redaction of component secret values from tool results and logs.
+//!
+//! The [`SecretRedactor`] remembers the secret values loaded for the components it
+//! covers and replaces them with `[REDACTED:KEY]`, so a component that echoes a
+//! secret back doesn't hand it to the model or write it to the server log.
+
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::sync::RwLock;
+
+use serde_json::Value;
+
+/// Component ID that turns on redaction for every component
+const ALL_COMPONENTS: &str = "*";
+
+/// Values shorter than this are left alone, as replacing them would mangle
+/// unrelated text
+const MIN_REDACTED_LEN: usize = 4;
+
+/// Replaces the secret values of selected components with `[REDACTED:KEY]`
+#[derive(Debug, Default)]
+pub struct SecretRedactor {
+    /// Components whose secrets are redacted
+    components: HashSet<String>,
+    /// Whether every component's secrets are redacted
+    all: bool,
+    /// Secret values and their keys seen for each covered component, longest
+    /// value first so overlapping values are replaced whole
+    values: RwLock<HashMap<String, Vec<(String, String)>>>,
+}
+
+impl SecretRedactor {
+    /// Create a redactor for the given component IDs, where `*` covers every component
+    pub fn new(components: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        let components: HashSet<String> = components.into_iter().map(Into::into).collect();
+        Self {
+            all: components.contains(ALL_COMPONENTS),
+            components,
+            values: RwLock::default(),
+        }
+    }
+
+    /// Whether the secrets of `component_id` are redacted
+    pub fn is_enabled(&self, component_id: &str) -> bool {
+        self.all || self.components.contains(component_id)
+    }
+
+    /// Remember the values of secrets loaded for a component. Values seen earlier
+    /// are kept, since a running instance may still hold them.
+    pub(crate) fn remember(&self, component_id: &str, secrets: &HashMap<String, String>) {
+        if !self.is_enabled(component_id) {
+            return;
+        }
+        let mut values = self.values.write().unwrap_or_else(|e| e.into_inner());
+        let known = values.entry(component_id.to_string()).or_default();
+        for (key, value) in secrets {
+            if value.len() >= MIN_REDACTED_LEN && !known.iter().any(|(v, k)| v == value && k == key)
+            {
+                known.push((value.clone(), key.clone()));
+            }
+        }
+        known.sort_by(|(a, _), (b, _)| b.len().cmp(&a.len()));
+    }
+
+    /// Redact the secrets of `component_id` in `text`
+    pub fn redact<'a>(&self, component_id: &str, text: &'a str) -> Cow<'a, str> {
+        let values = self.values.read().unwrap_or_else(|e| e.into_inner());
+        match values.get(component_id) {
+            Some(known) => replace_values(text, known),
+            None => Cow::Borrowed(text),
+        }
+    }
+
+    /// Redact the secrets of `component_id` in every string inside `value`
+    pub fn redact_value(&self, component_id: &str, value: &mut Value) {
+        match value {
+            Value::String(text) => {
+                if let Cow::Owned(redacted) = self.redact(component_id, text) {
+                    *text = redacted;
+                }
+            }
+            Value::Array(items) => items
+                .iter_mut()
+                .for_each(|item| self.redact_value(component_id, item)),
+            Value::Object(map) => map
+                .values_mut()
+                .for_each(|item| self.redact_value(component_id, item)),
+            _ => {}
+        }
+    }
+
+    /// Redact the secrets of every covered component in `text`, for output such as
+    /// log lines that isn't tied to one component
+    pub fn redact_all<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let values = self.values.read().unwrap_or_else(|e| e.into_inner());
+        values.values().fold(Cow::Borrowed(text), |text, known| {
+            match replace_values(&text, known) {
+                Cow::Borrowed(_) => text,
+                Cow::Owned(redacted) => Cow::Owned(redacted),
+            }
+        })
+    }
+}
+
+fn replace_values<'a>(text: &'a str, known: &[(String, String)]) -> Cow<'a, str> {
+    let mut text = Cow::Borrowed(text);
+    for (value, key) in known {
+        if text.contains(value.as_str()) {
+            text = Cow::Owned(text.replace(value.as_str(), &format!("[REDACTED:{key}]")));
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn secrets(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_redacts_covered_components() {
+        let redactor = SecretRedactor::new(["weather"]);
+        redactor.remember(
+            "weather",
+            &secrets(&[
+                ("API_KEY", "sk-12345"),
+                ("TOKEN", "sk-12345-long"),
+                ("FLAG", "on"),
+            ]),
+        );
+        redactor.remember("fetch", &secrets(&[("TOKEN", "fetch-token")]));
+
+        assert!(redactor.is_enabled("weather"));
+        assert!(!redactor.is_enabled("fetch"));
+        assert_eq!(
+            redactor.redact("weather", "key sk-12345-long and sk-12345, flag on"),
+            "key [REDACTED:TOKEN] and [REDACTED:API_KEY], flag on"
+        );
+        assert!(matches!(
+            redactor.redact("weather", "nothing here"),
+            Cow::Borrowed(_)
+        ));
+        assert_eq!(redactor.redact("fetch", "fetch-token"), "fetch-token");
+
+        // Values from earlier loads stay redacted
+        redactor.remember("weather", &secrets(&[("API_KEY", "sk-67890")]));
+        assert_eq!(
+            redactor.redact_all("old sk-12345 new sk-67890"),
+            "old [REDACTED:API_KEY] new [REDACTED:API_KEY]"
+        );
+
+        let mut value = json!({"echo": ["sk-67890", {"nested": "x sk-12345"}], "n": 1});
+        redactor.redact_value("weather", &mut value);
+        assert_eq!(
+            value,
+            json!({"echo": ["[REDACTED:API_KEY]", {"nested": "x [REDACTED:API_KEY]"}], "n": 1})
+        );
+    }
+
+    #[test]
+    fn test_all_components() {
+        let redactor = SecretRedactor::new([ALL_COMPONENTS]);
+        redactor.remember("fetch", &secrets(&[("TOKEN", "fetch-token")]));
+        assert!(redactor.is_enabled("anything"));
+        assert_eq!(redactor.redact("fetch", "fetch-token"), "[REDACTED:TOKEN]");
+
+        let disabled = SecretRedactor::default();
+        disabled.remember("fetch", &secrets(&[("TOKEN", "fetch-token")]));
+        assert_eq!(disabled.redact_all("fetch-token"), "fetch-token");
+    }
+}
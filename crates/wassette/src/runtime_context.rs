@@ -5,13 +5,14 @@
 //! manager instances.
 
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::Result;
 use wasmtime::component::{Component, InstancePre, Linker};
 use wasmtime::Engine;
 use wasmtime_wasi_config::WasiConfig;
 
-use crate::{WasiState, WassetteWasiState};
+use crate::{storage_guard, WasiState, WassetteWasiState};
 
 /// Encapsulates Wasmtime engine and linker setup for reuse across the lifecycle manager.
 #[derive(Clone)]
@@ -26,11 +27,18 @@ impl RuntimeContext {
         let mut config = wasmtime::Config::new();
         config.wasm_component_model(true);
         config.async_support(true);
+        // Fuel metering backs the per-component CPU limit from the policy
+        config.consume_fuel(true);
+        // Epoch interruption backs the per-call wall-clock timeout
+        config.epoch_interruption(true);
 
         let engine = Arc::new(Engine::new(&config)?);
+        spawn_epoch_ticker(&engine);
 
         let mut linker = Linker::new(engine.as_ref());
         wasmtime_wasi::p2::add_to_linker_async(&mut linker)?;
+        // Storage deny rules are enforced by wrapping the stock filesystem bindings
+        storage_guard::add_to_linker(&mut linker)?;
         wasmtime_wasi_http::add_only_http_to_linker_async(&mut linker)?;
         wasmtime_wasi_config::add_to_linker(
             &mut linker,
@@ -53,6 +61,32 @@ impl RuntimeContext {
     }
 }
 
+/// Interval at which the engine epoch is advanced.
+pub(crate) const EPOCH_TICK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Number of epoch ticks that cover the given timeout, rounded up.
+///
+/// The result is capped well below `u64::MAX` because wasmtime adds it to the
+/// current epoch without saturating.
+pub(crate) fn epoch_ticks_for(timeout: Duration) -> u64 {
+    let ticks = timeout.as_nanos().div_ceil(EPOCH_TICK_INTERVAL.as_nanos());
+    u64::try_from(ticks)
+        .unwrap_or(u64::MAX)
+        .min(u64::from(u32::MAX))
+}
+
+/// Advance the engine epoch on a background thread until the engine is dropped.
+fn spawn_epoch_ticker(engine: &Engine) {
+    let weak_engine = engine.weak();
+    std::thread::spawn(move || loop {
+        std::thread::sleep(EPOCH_TICK_INTERVAL);
+        match weak_engine.upgrade() {
+            Some(engine) => engine.increment_epoch(),
+            None => break,
+        }
+    });
+}
+
 impl AsRef<Engine> for RuntimeContext {
     fn as_ref(&self) -> &Engine {
         self.engine.as_ref()
@@ -0,0 +1,238 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Read-only secret backends that keep values outside Wassette's secrets directory.
+
+use std::collections::{BTreeMap, HashMap};
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use async_trait::async_trait;
+use tokio::sync::Mutex;
+use tracing::debug;
+
+use crate::secrets::SecretProvider;
+
+/// How long a secret command may run before it is killed
+const EXEC_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long the output of secret commands is reused unless configured otherwise
+pub const DEFAULT_EXEC_TTL: Duration = Duration::from_secs(300);
+
+/// Secrets read from the output of commands, e.g. a password manager's CLI
+///
+/// Each secret has its own command, given as the program followed by its
+/// arguments and run without a shell. The secret is the first line the command
+/// prints, which matches `pass show` and similar tools. Commands run when the
+/// secrets are needed and the values are reused for [`DEFAULT_EXEC_TTL`], or the
+/// TTL set with [`ExecSecretProvider::with_ttl`], so rotated secrets are picked up.
+#[derive(Debug)]
+pub struct ExecSecretProvider {
+    commands: BTreeMap<String, Vec<String>>,
+    ttl: Duration,
+    /// The last values read and when they were read
+    values: Mutex<Option<(Instant, HashMap<String, String>)>>,
+}
+
+impl ExecSecretProvider {
+    /// Create a backend that runs `commands[key]` to read the secret `key`
+    pub fn new(commands: BTreeMap<String, Vec<String>>) -> Result<Self> {
+        if let Some((key, _)) = commands.iter().find(|(_, command)| command.is_empty()) {
+            bail!("Empty command for secret {key}");
+        }
+        Ok(Self {
+            commands,
+            ttl: DEFAULT_EXEC_TTL,
+            values: Mutex::new(None),
+        })
+    }
+
+    /// Reuse the values for `ttl`, or run the commands on every load with a zero TTL
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    async fn run(key: &str, command: &[String]) -> Result<String> {
+        debug!(key, program = %command[0], "Reading secret from command");
+        let child = tokio::process::Command::new(&command[0])
+            .args(&command[1..])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("Failed to run `{}` for secret {key}", command[0]))?;
+        let output = tokio::time::timeout(EXEC_TIMEOUT, child.wait_with_output())
+            .await
+            .with_context(|| {
+                format!(
+                    "`{}` for secret {key} did not finish within {}s",
+                    command[0],
+                    EXEC_TIMEOUT.as_secs()
+                )
+            })??;
+        if !output.status.success() {
+            bail!(
+                "`{}` for secret {key} failed with {}: {}",
+                command[0],
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        let stdout = String::from_utf8(output.stdout)
+            .with_context(|| format!("`{}` for secret {key} printed invalid UTF-8", command[0]))?;
+        Ok(stdout.lines().next().unwrap_or_default().to_string())
+    }
+}
+
+#[async_trait]
+impl SecretProvider for ExecSecretProvider {
+    fn kind(&self) -> &'static str {
+        "exec"
+    }
+
+    async fn load(&self, _component_id: &str) -> Result<HashMap<String, String>> {
+        // Held while the commands run, so concurrent loads don't run them again
+        let mut cached = self.values.lock().await;
+        if let Some((read_at, values)) = cached.as_ref() {
+            if read_at.elapsed() < self.ttl {
+                return Ok(values.clone());
+            }
+        }
+        let mut values = HashMap::new();
+        for (key, command) in &self.commands {
+            values.insert(key.clone(), Self::run(key, command).await?);
+        }
+        *cached = Some((Instant::now(), values.clone()));
+        Ok(values)
+    }
+
+    async fn keys(&self, _component_id: &str) -> Result<Vec<String>> {
+        Ok(self.commands.keys().cloned().collect())
+    }
+}
+
+/// Secrets read from the server's environment variables that start with a prefix
+///
+/// The prefix is removed from the names, so with the prefix `WEATHER_` the
+/// variable `WEATHER_API_KEY` becomes the secret `API_KEY`.
+#[derive(Debug)]
+pub struct EnvSecretProvider {
+    prefix: String,
+}
+
+impl EnvSecretProvider {
+    /// Create a backend for the variables starting with `prefix`
+    pub fn new(prefix: impl Into<String>) -> Result<Self> {
+        let prefix = prefix.into();
+        if prefix.is_empty() {
+            bail!("The environment secret backend needs a non-empty prefix");
+        }
+        Ok(Self { prefix })
+    }
+}
+
+#[async_trait]
+impl SecretProvider for EnvSecretProvider {
+    fn kind(&self) -> &'static str {
+        "env"
+    }
+
+    async fn load(&self, _component_id: &str) -> Result<HashMap<String, String>> {
+        Ok(std::env::vars()
+            .filter_map(|(name, value)| {
+                let key = name.strip_prefix(&self.prefix)?;
+                (!key.is_empty()).then(|| (key.to_string(), value))
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_exec_provider() -> Result<()> {
+        let provider = ExecSecretProvider::new(BTreeMap::from([
+            (
+                "API_KEY".to_string(),
+                command(&["printf", "secret123\\nurl: https://example.com\\n"]),
+            ),
+            ("REGION".to_string(), command(&["echo", "us-west-2"])),
+        ]))?;
+
+        assert_eq!(provider.keys("weather").await?, vec!["API_KEY", "REGION"]);
+        let secrets = provider.load("weather").await?;
+        assert_eq!(secrets.get("API_KEY"), Some(&"secret123".to_string()));
+        assert_eq!(secrets.get("REGION"), Some(&"us-west-2".to_string()));
+
+        assert!(provider
+            .set("weather", &[("API_KEY".to_string(), "new".to_string())])
+            .await
+            .is_err());
+
+        let failing = ExecSecretProvider::new(BTreeMap::from([(
+            "API_KEY".to_string(),
+            command(&["sh", "-c", "echo 'not in store' >&2; exit 1"]),
+        )]))?;
+        let error = failing.load("weather").await.unwrap_err().to_string();
+        assert!(error.contains("not in store"), "{error}");
+
+        assert!(
+            ExecSecretProvider::new(BTreeMap::from([("API_KEY".to_string(), vec![])])).is_err()
+        );
+
+        // Values are reused until the TTL runs out
+        let dir = tempfile::TempDir::new()?;
+        let counter = dir.path().join("runs");
+        let counting = || {
+            BTreeMap::from([(
+                "RUNS".to_string(),
+                command(&[
+                    "sh",
+                    "-c",
+                    &format!("echo run >> {0}; wc -l < {0}", counter.display()),
+                ]),
+            )])
+        };
+        let cached = ExecSecretProvider::new(counting())?;
+        let first = cached.load("weather").await?;
+        assert_eq!(cached.load("weather").await?, first);
+        let uncached = ExecSecretProvider::new(counting())?.with_ttl(Duration::ZERO);
+        let second = uncached.load("weather").await?;
+        let third = uncached.load("weather").await?;
+        assert_ne!(second, third);
+        assert_eq!(third.get("RUNS").map(|runs| runs.trim()), Some("3"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_env_provider() -> Result<()> {
+        let provider = EnvSecretProvider::new("WASSETTE_TEST_ENV_PROVIDER_")?;
+        let secrets = temp_env::with_vars(
+            [
+                ("WASSETTE_TEST_ENV_PROVIDER_API_KEY", Some("secret123")),
+                ("WASSETTE_TEST_ENV_PROVIDER_", Some("ignored")),
+            ],
+            || futures::executor::block_on(provider.load("weather")),
+        )?;
+        assert_eq!(
+            secrets,
+            HashMap::from([("API_KEY".to_string(), "secret123".to_string())])
+        );
+        assert!(
+            futures::executor::block_on(provider.delete("weather", &["API_KEY".to_string()]))
+                .is_err()
+        );
+        assert!(EnvSecretProvider::new("").is_err());
+        Ok(())
+    }
+}
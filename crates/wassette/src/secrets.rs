@@ -5,19 +5,28 @@
 //!
 //! This module provides functionality to manage per-component secrets that are:
 //! - Stored in OS-appropriate directories with proper permissions
+//! - Encrypted at rest with a key from a key file, the OS keyring or a passphrase
 //! - Persisted across runs without requiring server restart
 //! - Easy to edit and audit via CLI
+//! - Optionally read from another backend, such as a password manager command or
+//!   prefixed environment variables, chosen per component
 //! - Integrated with component environment variable system
 
 use std::collections::HashMap;
 #[cfg(unix)]
 use std::os::unix::fs::PermissionsExt;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::SystemTime;
 
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
 use tokio::sync::RwLock;
 use tracing::{debug, info, warn};
+use zeroize::Zeroizing;
+
+use crate::redaction::SecretRedactor;
+use crate::secrets_cipher::{SecretsCipher, SecretsKey};
 
 /// Cache entry for component secrets
 #[derive(Debug, Clone)]
@@ -28,29 +37,75 @@ pub struct SecretCache {
     pub last_mtime: SystemTime,
 }
 
-/// Secrets manager for components
+/// A backend that component secrets are read from and, if it supports it, written to
+#[async_trait]
+pub trait SecretProvider: std::fmt::Debug + Send + Sync {
+    /// Short name of the backend used in messages, e.g. `file`
+    fn kind(&self) -> &'static str;
+
+    /// Load all secrets of a component
+    async fn load(&self, component_id: &str) -> Result<HashMap<String, String>>;
+
+    /// List the names of a component's secrets without necessarily reading their values
+    async fn keys(&self, component_id: &str) -> Result<Vec<String>> {
+        Ok(self.load(component_id).await?.into_keys().collect())
+    }
+
+    /// Add or replace secrets of a component
+    async fn set(&self, component_id: &str, _secrets: &[(String, String)]) -> Result<()> {
+        bail!(
+            "Secrets of component {component_id} come from the read-only {} backend",
+            self.kind()
+        )
+    }
+
+    /// Remove secrets of a component
+    async fn delete(&self, component_id: &str, _keys: &[String]) -> Result<()> {
+        bail!(
+            "Secrets of component {component_id} come from the read-only {} backend",
+            self.kind()
+        )
+    }
+}
+
+/// Secrets stored as files in the secrets directory, the default backend
 #[derive(Debug)]
-pub struct SecretsManager {
+pub struct FileSecretProvider {
     /// Directory where secrets are stored
     secrets_dir: PathBuf,
     /// Cache of component secrets
     cache: RwLock<HashMap<String, SecretCache>>,
+    /// Encrypts secrets files when they are written
+    cipher: SecretsCipher,
 }
 
-impl SecretsManager {
-    /// Create a new secrets manager
+impl FileSecretProvider {
+    /// Create a file backend that encrypts with the default key
     pub fn new(secrets_dir: PathBuf) -> Self {
+        let cipher = SecretsCipher::new(SecretsKey::default_key());
         Self {
             secrets_dir,
             cache: RwLock::new(HashMap::new()),
+            cipher,
         }
     }
 
+    /// Encrypt secrets with the key from `key` instead of the default key
+    pub fn with_key(mut self, key: SecretsKey) -> Self {
+        self.cipher = SecretsCipher::new(key);
+        self
+    }
+
     /// Get the secrets directory path
     pub fn secrets_dir(&self) -> &Path {
         &self.secrets_dir
     }
 
+    /// Where the key that encrypts secrets files comes from
+    pub fn secrets_key(&self) -> &SecretsKey {
+        self.cipher.source()
+    }
+
     /// Get the path to a component's secrets file
     pub fn get_component_secrets_path(&self, component_id: &str) -> PathBuf {
         let sanitized_id = sanitize_component_id(component_id);
@@ -97,11 +152,106 @@ impl SecretsManager {
         Ok(())
     }
 
-    /// Load secrets for a component, using cache if file hasn't changed
-    pub async fn load_component_secrets(
+    /// Read a component's secrets file, decrypting it unless it predates encryption.
+    /// Also returns whether the file was encrypted.
+    async fn read_secrets_file(
         &self,
         component_id: &str,
-    ) -> Result<HashMap<String, String>> {
+    ) -> Result<(HashMap<String, String>, bool)> {
+        let secrets_path = self.get_component_secrets_path(component_id);
+        let content = Zeroizing::new(
+            tokio::fs::read_to_string(&secrets_path)
+                .await
+                .with_context(|| {
+                    format!("Failed to read secrets file: {}", secrets_path.display())
+                })?,
+        );
+        let parse_error = || format!("Failed to parse secrets file: {}", secrets_path.display());
+
+        match self
+            .cipher
+            .open(&sanitize_component_id(component_id), &content)
+            .with_context(parse_error)?
+        {
+            Some(plaintext) => Ok((
+                serde_yaml::from_slice(&plaintext).with_context(parse_error)?,
+                true,
+            )),
+            None => Ok((
+                serde_yaml::from_str(&content).with_context(parse_error)?,
+                false,
+            )),
+        }
+    }
+
+    /// Encrypt secrets and write them to file atomically with proper permissions
+    async fn write_secrets_file(
+        &self,
+        component_id: &str,
+        secrets: &HashMap<String, String>,
+    ) -> Result<()> {
+        let secrets_path = self.get_component_secrets_path(component_id);
+        let plaintext = Zeroizing::new(
+            serde_yaml::to_string(secrets).context("Failed to serialize secrets to YAML")?,
+        );
+        let content = self
+            .cipher
+            .seal(&sanitize_component_id(component_id), plaintext.as_bytes())?;
+
+        // Write to temporary file first
+        let temp_path = secrets_path.with_extension("tmp");
+        tokio::fs::write(&temp_path, &content)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to write temporary secrets file: {}",
+                    temp_path.display()
+                )
+            })?;
+
+        // Set file permissions to 0600 (user read/write only)
+        #[cfg(unix)]
+        {
+            let metadata = tokio::fs::metadata(&temp_path).await.with_context(|| {
+                format!(
+                    "Failed to get metadata for temporary secrets file: {}",
+                    temp_path.display()
+                )
+            })?;
+            let mut perms = metadata.permissions();
+            perms.set_mode(0o600);
+            tokio::fs::set_permissions(&temp_path, perms)
+                .await
+                .with_context(|| {
+                    format!(
+                        "Failed to set permissions for temporary secrets file: {}",
+                        temp_path.display()
+                    )
+                })?;
+        }
+
+        // Atomic rename
+        tokio::fs::rename(&temp_path, &secrets_path)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to rename temporary secrets file to: {}",
+                    secrets_path.display()
+                )
+            })?;
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl SecretProvider for FileSecretProvider {
+    fn kind(&self) -> &'static str {
+        "file"
+    }
+
+    /// Load secrets for a component, using cache if file hasn't changed
+    async fn load(&self, component_id: &str) -> Result<HashMap<String, String>> {
         let secrets_path = self.get_component_secrets_path(component_id);
 
         // Check if file exists
@@ -137,12 +287,7 @@ impl SecretsManager {
 
         // Load from file
         debug!("Loading secrets from file for component: {}", component_id);
-        let content = tokio::fs::read_to_string(&secrets_path)
-            .await
-            .with_context(|| format!("Failed to read secrets file: {}", secrets_path.display()))?;
-
-        let secrets: HashMap<String, String> = serde_yaml::from_str(&content)
-            .with_context(|| format!("Failed to parse secrets file: {}", secrets_path.display()))?;
+        let (secrets, _) = self.read_secrets_file(component_id).await?;
 
         // Update cache
         let cache_entry = SecretCache {
@@ -158,49 +303,22 @@ impl SecretsManager {
         Ok(secrets)
     }
 
-    /// List secrets for a component (keys only by default)
-    pub async fn list_component_secrets(
-        &self,
-        component_id: &str,
-        show_values: bool,
-    ) -> Result<HashMap<String, Option<String>>> {
-        let secrets = self.load_component_secrets(component_id).await?;
-
-        let result = if show_values {
-            secrets.into_iter().map(|(k, v)| (k, Some(v))).collect()
-        } else {
-            secrets.into_keys().map(|k| (k, None)).collect()
-        };
-
-        Ok(result)
-    }
-
     /// Set secrets for a component
-    pub async fn set_component_secrets(
-        &self,
-        component_id: &str,
-        secrets: &[(String, String)],
-    ) -> Result<()> {
+    async fn set(&self, component_id: &str, secrets: &[(String, String)]) -> Result<()> {
         self.ensure_secrets_dir().await?;
 
         let secrets_path = self.get_component_secrets_path(component_id);
 
         // Load existing secrets
         let mut existing_secrets = if secrets_path.exists() {
-            let content = tokio::fs::read_to_string(&secrets_path)
-                .await
-                .with_context(|| {
-                    format!(
-                        "Failed to read existing secrets file: {}",
-                        secrets_path.display()
-                    )
-                })?;
-            serde_yaml::from_str::<HashMap<String, String>>(&content).with_context(|| {
-                format!(
-                    "Failed to parse existing secrets file: {}",
+            let (existing, encrypted) = self.read_secrets_file(component_id).await?;
+            if !encrypted {
+                info!(
+                    "Encrypting plaintext secrets file: {}",
                     secrets_path.display()
-                )
-            })?
+                );
+            }
+            existing
         } else {
             HashMap::new()
         };
@@ -211,7 +329,7 @@ impl SecretsManager {
         }
 
         // Write atomically
-        self.write_secrets_file(&secrets_path, &existing_secrets)
+        self.write_secrets_file(component_id, &existing_secrets)
             .await?;
 
         // Invalidate cache
@@ -225,11 +343,7 @@ impl SecretsManager {
     }
 
     /// Delete secrets for a component
-    pub async fn delete_component_secrets(
-        &self,
-        component_id: &str,
-        keys: &[String],
-    ) -> Result<()> {
+    async fn delete(&self, component_id: &str, keys: &[String]) -> Result<()> {
         let secrets_path = self.get_component_secrets_path(component_id);
 
         if !secrets_path.exists() {
@@ -240,11 +354,7 @@ impl SecretsManager {
         }
 
         // Load existing secrets
-        let content = tokio::fs::read_to_string(&secrets_path)
-            .await
-            .with_context(|| format!("Failed to read secrets file: {}", secrets_path.display()))?;
-        let mut secrets: HashMap<String, String> = serde_yaml::from_str(&content)
-            .with_context(|| format!("Failed to parse secrets file: {}", secrets_path.display()))?;
+        let (mut secrets, _) = self.read_secrets_file(component_id).await?;
 
         // Remove specified keys
         for key in keys {
@@ -269,7 +379,7 @@ impl SecretsManager {
             info!("Removed empty secrets file for component: {}", component_id);
         } else {
             // Write updated secrets
-            self.write_secrets_file(&secrets_path, &secrets).await?;
+            self.write_secrets_file(component_id, &secrets).await?;
             info!(
                 "Deleted {} secret(s) for component: {}",
                 keys.len(),
@@ -285,59 +395,139 @@ impl SecretsManager {
 
         Ok(())
     }
+}
 
-    /// Write secrets to file atomically with proper permissions
-    async fn write_secrets_file(
-        &self,
-        secrets_path: &Path,
-        secrets: &HashMap<String, String>,
-    ) -> Result<()> {
-        let content =
-            serde_yaml::to_string(secrets).context("Failed to serialize secrets to YAML")?;
+/// Secrets manager for components
+///
+/// Secrets come from the file backend unless a component has been given
+/// another [`SecretProvider`].
+#[derive(Debug)]
+pub struct SecretsManager {
+    /// Backend for components without a provider of their own
+    file: FileSecretProvider,
+    /// Providers chosen for individual components
+    providers: HashMap<String, Arc<dyn SecretProvider>>,
+    /// Learns the values to redact as secrets are loaded
+    redactor: Arc<SecretRedactor>,
+}
 
-        // Write to temporary file first
-        let temp_path = secrets_path.with_extension("tmp");
-        tokio::fs::write(&temp_path, &content)
-            .await
-            .with_context(|| {
-                format!(
-                    "Failed to write temporary secrets file: {}",
-                    temp_path.display()
-                )
-            })?;
+impl SecretsManager {
+    /// Create a new secrets manager that encrypts with the default key
+    pub fn new(secrets_dir: PathBuf) -> Self {
+        Self {
+            file: FileSecretProvider::new(secrets_dir),
+            providers: HashMap::new(),
+            redactor: Arc::default(),
+        }
+    }
 
-        // Set file permissions to 0600 (user read/write only)
-        #[cfg(unix)]
-        {
-            let metadata = tokio::fs::metadata(&temp_path).await.with_context(|| {
-                format!(
-                    "Failed to get metadata for temporary secrets file: {}",
-                    temp_path.display()
-                )
-            })?;
-            let mut perms = metadata.permissions();
-            perms.set_mode(0o600);
-            tokio::fs::set_permissions(&temp_path, perms)
-                .await
-                .with_context(|| {
-                    format!(
-                        "Failed to set permissions for temporary secrets file: {}",
-                        temp_path.display()
-                    )
-                })?;
+    /// Encrypt secrets files with the key from `key` instead of the default key
+    pub fn with_key(mut self, key: SecretsKey) -> Self {
+        self.file = self.file.with_key(key);
+        self
+    }
+
+    /// Read a component's secrets from `provider` instead of the secrets directory
+    pub fn with_provider(
+        mut self,
+        component_id: impl Into<String>,
+        provider: Arc<dyn SecretProvider>,
+    ) -> Self {
+        self.providers.insert(component_id.into(), provider);
+        self
+    }
+
+    /// Tell `redactor` about the secrets loaded for the components it covers
+    pub fn with_redactor(mut self, redactor: Arc<SecretRedactor>) -> Self {
+        self.redactor = redactor;
+        self
+    }
+
+    /// The redactor that learns secret values as they are loaded
+    pub fn redactor(&self) -> &SecretRedactor {
+        &self.redactor
+    }
+
+    /// Get the secrets directory path
+    pub fn secrets_dir(&self) -> &Path {
+        self.file.secrets_dir()
+    }
+
+    /// Where the key that encrypts secrets files comes from
+    pub fn secrets_key(&self) -> &SecretsKey {
+        self.file.secrets_key()
+    }
+
+    /// Get the path to a component's secrets file
+    pub fn get_component_secrets_path(&self, component_id: &str) -> PathBuf {
+        self.file.get_component_secrets_path(component_id)
+    }
+
+    /// Ensure the secrets directory exists with proper permissions
+    pub async fn ensure_secrets_dir(&self) -> Result<()> {
+        self.file.ensure_secrets_dir().await
+    }
+
+    /// The backend a component's secrets come from
+    pub fn provider(&self, component_id: &str) -> &dyn SecretProvider {
+        match self.providers.get(component_id) {
+            Some(provider) => provider.as_ref(),
+            None => &self.file,
         }
+    }
 
-        // Atomic rename
-        tokio::fs::rename(&temp_path, secrets_path)
-            .await
-            .with_context(|| {
-                format!(
-                    "Failed to rename temporary secrets file to: {}",
-                    secrets_path.display()
-                )
-            })?;
+    /// Load secrets for a component
+    pub async fn load_component_secrets(
+        &self,
+        component_id: &str,
+    ) -> Result<HashMap<String, String>> {
+        let secrets = self.provider(component_id).load(component_id).await?;
+        self.redactor.remember(component_id, &secrets);
+        Ok(secrets)
+    }
 
-        Ok(())
+    /// List secrets for a component (keys only by default)
+    pub async fn list_component_secrets(
+        &self,
+        component_id: &str,
+        show_values: bool,
+    ) -> Result<HashMap<String, Option<String>>> {
+        let provider = self.provider(component_id);
+        let result = if show_values {
+            provider
+                .load(component_id)
+                .await?
+                .into_iter()
+                .map(|(k, v)| (k, Some(v)))
+                .collect()
+        } else {
+            provider
+                .keys(component_id)
+                .await?
+                .into_iter()
+                .map(|k| (k, None))
+                .collect()
+        };
+
+        Ok(result)
+    }
+
+    /// Set secrets for a component
+    pub async fn set_component_secrets(
+        &self,
+        component_id: &str,
+        secrets: &[(String, String)],
+    ) -> Result<()> {
+        self.provider(component_id).set(component_id, secrets).await
+    }
+
+    /// Delete secrets for a component
+    pub async fn delete_component_secrets(
+        &self,
+        component_id: &str,
+        keys: &[String],
+    ) -> Result<()> {
+        self.provider(component_id).delete(component_id, keys).await
     }
 }
 
@@ -411,11 +601,16 @@ mod tests {
         assert!(sanitized.is_char_boundary(sanitized.len()));
     }
 
+    /// A manager for `secrets` in `temp_dir`, with its key file next to it
+    fn test_manager(temp_dir: &TempDir) -> SecretsManager {
+        SecretsManager::new(temp_dir.path().join("secrets"))
+            .with_key(SecretsKey::File(temp_dir.path().join("secrets.key")))
+    }
+
     #[tokio::test]
     async fn test_secrets_manager_basic() -> Result<()> {
         let temp_dir = TempDir::new()?;
-        let secrets_dir = temp_dir.path().join("secrets");
-        let manager = SecretsManager::new(secrets_dir);
+        let manager = test_manager(&temp_dir);
 
         // Test setting secrets
         let secrets = vec![
@@ -464,8 +659,7 @@ mod tests {
     #[tokio::test]
     async fn test_cache_invalidation() -> Result<()> {
         let temp_dir = TempDir::new()?;
-        let secrets_dir = temp_dir.path().join("secrets");
-        let manager = SecretsManager::new(secrets_dir);
+        let manager = test_manager(&temp_dir);
 
         // Set initial secrets
         let secrets = vec![("KEY1".to_string(), "value1".to_string())];
@@ -493,10 +687,69 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_secrets_with_environment_precedence() -> Result<()> {
+    async fn test_plaintext_secrets_are_migrated() -> Result<()> {
         let temp_dir = TempDir::new()?;
         let secrets_dir = temp_dir.path().join("secrets");
-        let manager = SecretsManager::new(secrets_dir);
+        let manager = test_manager(&temp_dir);
+        manager.ensure_secrets_dir().await?;
+
+        // A file written before secrets were encrypted
+        let secrets_path = manager.get_component_secrets_path("test");
+        tokio::fs::write(&secrets_path, "OLD_KEY: old_value\n").await?;
+        let loaded = manager.load_component_secrets("test").await?;
+        assert_eq!(loaded.get("OLD_KEY"), Some(&"old_value".to_string()));
+
+        manager
+            .set_component_secrets("test", &[("NEW_KEY".to_string(), "new_value".to_string())])
+            .await?;
+        let content = tokio::fs::read_to_string(&secrets_path).await?;
+        assert!(!content.contains("old_value"));
+        assert!(!content.contains("new_value"));
+        assert!(temp_dir.path().join("secrets.key").exists());
+
+        // Another manager with the same key reads the values back
+        let reopened = test_manager(&temp_dir);
+        let listed = reopened.list_component_secrets("test", true).await?;
+        assert_eq!(listed.get("OLD_KEY"), Some(&Some("old_value".to_string())));
+        assert_eq!(listed.get("NEW_KEY"), Some(&Some("new_value".to_string())));
+
+        // One with a different key can't
+        let other_key = temp_dir.path().join("other.key");
+        let wrong = SecretsManager::new(secrets_dir).with_key(SecretsKey::File(other_key));
+        assert!(wrong.load_component_secrets("test").await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_component_provider() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let provider = Arc::new(crate::EnvSecretProvider::new("WASSETTE_TEST_MANAGER_")?);
+        let manager = test_manager(&temp_dir).with_provider("weather", provider);
+
+        let loaded = temp_env::with_var("WASSETTE_TEST_MANAGER_API_KEY", Some("from_env"), || {
+            futures::executor::block_on(manager.load_component_secrets("weather"))
+        })?;
+        assert_eq!(loaded.get("API_KEY"), Some(&"from_env".to_string()));
+        assert!(manager
+            .set_component_secrets("weather", &[("API_KEY".to_string(), "x".to_string())])
+            .await
+            .is_err());
+
+        // Other components still use the secrets directory
+        manager
+            .set_component_secrets("fetch", &[("TOKEN".to_string(), "abc".to_string())])
+            .await?;
+        let listed = manager.list_component_secrets("fetch", true).await?;
+        assert_eq!(listed.get("TOKEN"), Some(&Some("abc".to_string())));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_secrets_with_environment_precedence() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let manager = test_manager(&temp_dir);
 
         // Set secrets
         let secrets = vec![
@@ -531,7 +784,7 @@ permissions:
         let loaded_secrets = manager.load_component_secrets("test").await?;
 
         // Test precedence
-        let result = extract_env_vars(&policy, &env_vars, Some(&loaded_secrets))?;
+        let result = extract_env_vars(&policy, &env_vars, Some(&loaded_secrets))?.config;
 
         // SECRET_KEY should come from env (highest precedence)
         assert_eq!(result.get("SECRET_KEY"), Some(&"from_env".to_string()));
@@ -0,0 +1,487 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Encryption of component secrets files at rest.
+//!
+//! Secrets are sealed with XChaCha20-Poly1305 under a 256-bit key that comes from a
+//! key file, the OS keyring or a passphrase in an environment variable. A passphrase
+//! is stretched with Argon2id, using a salt stored next to the ciphertext. Unless
+//! configured otherwise the key is kept in a key file outside the secrets directory;
+//! the OS keyring is only used when asked for. Files written before encryption was
+//! introduced are plain YAML maps; they are still read and get sealed the next time
+//! they are written.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use anyhow::{anyhow, bail, Context, Result};
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use etcetera::BaseStrategy;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use zeroize::Zeroizing;
+
+/// Key under which a sealed file records its format version
+const FORMAT_MARKER: &str = "wassette-secrets";
+const FORMAT_VERSION: u32 = 1;
+const CIPHER: &str = "xchacha20poly1305";
+const KEYRING_SERVICE: &str = "wassette";
+const KEYRING_USER: &str = "secrets-key";
+const KEY_LEN: usize = 32;
+const SALT_LEN: usize = 16;
+
+type KeyBytes = Zeroizing<[u8; KEY_LEN]>;
+
+/// Where the key that encrypts secrets files comes from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SecretsKey {
+    /// A file holding a hex-encoded key, created with a random key if missing
+    File(PathBuf),
+    /// A passphrase read from the named environment variable
+    PassphraseEnv(String),
+    /// A key kept in the OS keyring, created with a random key if missing
+    Keyring,
+    /// The OS keyring, or the key file at the path when there is no usable keyring
+    KeyringOrFile(PathBuf),
+}
+
+impl SecretsKey {
+    /// The key a [`crate::SecretsManager`] or [`crate::LifecycleManager`] uses unless
+    /// told otherwise, a key file in the data directory
+    pub fn default_key() -> Self {
+        Self::File(default_key_file())
+    }
+
+    /// The OS keyring, or the default key file when there is no usable keyring
+    pub fn keyring_or_default_file() -> Self {
+        Self::KeyringOrFile(default_key_file())
+    }
+}
+
+/// Where the secrets key is stored when the OS keyring can't be used. It is kept
+/// apart from the secrets directory so a copy of the secrets doesn't include it.
+fn default_key_file() -> PathBuf {
+    let dir = match etcetera::choose_base_strategy() {
+        Ok(strategy) => strategy.data_dir().join("wassette").join("keys"),
+        Err(_) => {
+            warn!("Unable to determine default data directory, using `keys` directory in the current working directory for the secrets key");
+            PathBuf::from("./keys")
+        }
+    };
+    dir.join("secrets.key")
+}
+
+impl FromStr for SecretsKey {
+    type Err = anyhow::Error;
+
+    /// Parses `file:<path>`, `env:<VAR>` or `keyring`
+    fn from_str(s: &str) -> Result<Self> {
+        if s == "keyring" {
+            return Ok(Self::Keyring);
+        }
+        match s.split_once(':') {
+            Some(("file", path)) if !path.is_empty() => Ok(Self::File(PathBuf::from(path))),
+            Some(("env", var)) if !var.is_empty() => Ok(Self::PassphraseEnv(var.to_string())),
+            _ => bail!("Invalid secrets key '{s}', expected file:<path>, env:<VAR> or keyring"),
+        }
+    }
+}
+
+impl fmt::Display for SecretsKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::File(path) => write!(f, "file:{}", path.display()),
+            Self::PassphraseEnv(var) => write!(f, "env:{var}"),
+            Self::Keyring => f.write_str("keyring"),
+            Self::KeyringOrFile(path) => write!(f, "keyring (or file:{})", path.display()),
+        }
+    }
+}
+
+/// On-disk form of an encrypted secrets file
+#[derive(Debug, Serialize, Deserialize)]
+struct SealedFile {
+    #[serde(rename = "wassette-secrets")]
+    version: u32,
+    cipher: String,
+    /// Argon2id salt, present when the key was derived from a passphrase
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    salt: Option<String>,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Seals and opens secrets files with the key from a [`SecretsKey`]
+///
+/// The key is only looked up the first time it's needed, so plaintext files can
+/// still be read when the configured source is unavailable.
+#[derive(Debug)]
+pub(crate) struct SecretsCipher {
+    source: SecretsKey,
+    /// Keys resolved so far, by the salt they were derived with (empty when the
+    /// source holds the key itself)
+    keys: Mutex<HashMap<Vec<u8>, KeyBytes>>,
+    /// Salt for the files this process writes when the key comes from a passphrase
+    salt: [u8; SALT_LEN],
+}
+
+impl SecretsCipher {
+    pub(crate) fn new(source: SecretsKey) -> Self {
+        let mut salt = [0u8; SALT_LEN];
+        salt.copy_from_slice(&XChaCha20Poly1305::generate_nonce(&mut OsRng)[..SALT_LEN]);
+        Self {
+            source,
+            keys: Mutex::new(HashMap::new()),
+            salt,
+        }
+    }
+
+    pub(crate) fn source(&self) -> &SecretsKey {
+        &self.source
+    }
+
+    /// Encrypt a serialized secrets map, binding it to `file_id` so a sealed file
+    /// can't be passed off as another component's
+    pub(crate) fn seal(&self, file_id: &str, plaintext: &[u8]) -> Result<String> {
+        let salt = matches!(self.source, SecretsKey::PassphraseEnv(_)).then_some(&self.salt[..]);
+        let cipher = XChaCha20Poly1305::new(Key::from_slice(&self.key(salt)?[..]));
+        let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plaintext,
+                    aad: file_id.as_bytes(),
+                },
+            )
+            .map_err(|_| anyhow!("Failed to encrypt secrets"))?;
+
+        let b64 = base64::engine::general_purpose::STANDARD;
+        let sealed = SealedFile {
+            version: FORMAT_VERSION,
+            cipher: CIPHER.to_string(),
+            salt: salt.map(|salt| b64.encode(salt)),
+            nonce: b64.encode(nonce),
+            ciphertext: b64.encode(ciphertext),
+        };
+        serde_yaml::to_string(&sealed).context("Failed to serialize encrypted secrets")
+    }
+
+    /// Decrypt the contents of a secrets file, or return `None` if it is a
+    /// plaintext file from before encryption
+    pub(crate) fn open(&self, file_id: &str, content: &str) -> Result<Option<Zeroizing<Vec<u8>>>> {
+        let Some(sealed) = parse_sealed(content)? else {
+            return Ok(None);
+        };
+        if sealed.version != FORMAT_VERSION || sealed.cipher != CIPHER {
+            bail!(
+                "Unsupported secrets file format {} with cipher {}",
+                sealed.version,
+                sealed.cipher
+            );
+        }
+
+        let b64 = base64::engine::general_purpose::STANDARD;
+        let salt = sealed
+            .salt
+            .as_deref()
+            .map(|salt| b64.decode(salt))
+            .transpose()
+            .context("Invalid salt in secrets file")?;
+        let nonce = b64
+            .decode(&sealed.nonce)
+            .context("Invalid nonce in secrets file")?;
+        if nonce.len() != 24 {
+            bail!("Invalid nonce in secrets file");
+        }
+        let ciphertext = b64
+            .decode(&sealed.ciphertext)
+            .context("Invalid ciphertext in secrets file")?;
+
+        let cipher = XChaCha20Poly1305::new(Key::from_slice(&self.key(salt.as_deref())?[..]));
+        let plaintext = cipher
+            .decrypt(
+                XNonce::from_slice(&nonce),
+                Payload {
+                    msg: &ciphertext,
+                    aad: file_id.as_bytes(),
+                },
+            )
+            .map_err(|_| {
+                anyhow!(
+                    "Failed to decrypt secrets: the key from {} does not match the one they were encrypted with",
+                    self.source
+                )
+            })?;
+        Ok(Some(Zeroizing::new(plaintext)))
+    }
+
+    /// Look up the key, deriving it with `salt` when it comes from a passphrase
+    fn key(&self, salt: Option<&[u8]>) -> Result<KeyBytes> {
+        let cache_key = salt.unwrap_or_default().to_vec();
+        let mut keys = self.keys.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(key) = keys.get(&cache_key) {
+            return Ok(key.clone());
+        }
+
+        let key = match (&self.source, salt) {
+            (SecretsKey::PassphraseEnv(var), Some(salt)) => {
+                let passphrase = Zeroizing::new(std::env::var(var).with_context(|| {
+                    format!("Secrets are encrypted with a passphrase, but {var} is not set")
+                })?);
+                let mut key = Zeroizing::new([0u8; KEY_LEN]);
+                argon2::Argon2::default()
+                    .hash_password_into(passphrase.as_bytes(), salt, &mut key[..])
+                    .map_err(|e| anyhow!("Failed to derive secrets key from passphrase: {e}"))?;
+                key
+            }
+            (SecretsKey::PassphraseEnv(var), None) => bail!(
+                "Secrets were encrypted with a key file or keyring key, but the secrets key is set to the passphrase in {var}"
+            ),
+            (source, Some(_)) => bail!(
+                "Secrets were encrypted with a passphrase, but the secrets key is set to {source}"
+            ),
+            (SecretsKey::File(path), None) => read_or_create_key_file(path)?,
+            (SecretsKey::Keyring, None) => read_or_create_keyring_key()?,
+            (SecretsKey::KeyringOrFile(path), None) => read_keyring_or_file_key(path)?,
+        };
+        keys.insert(cache_key, key.clone());
+        Ok(key)
+    }
+}
+
+/// Read the envelope of a sealed file; plaintext files don't have the format marker
+fn parse_sealed(content: &str) -> Result<Option<SealedFile>> {
+    let Ok(serde_yaml::Value::Mapping(mapping)) = serde_yaml::from_str(content) else {
+        return Ok(None);
+    };
+    if !mapping.contains_key(FORMAT_MARKER) {
+        return Ok(None);
+    }
+    serde_yaml::from_value(serde_yaml::Value::Mapping(mapping))
+        .map(Some)
+        .context("Invalid encrypted secrets file")
+}
+
+fn random_key() -> KeyBytes {
+    let mut key = Zeroizing::new([0u8; KEY_LEN]);
+    key.copy_from_slice(&XChaCha20Poly1305::generate_key(&mut OsRng));
+    key
+}
+
+fn decode_key(encoded: &str) -> Result<KeyBytes> {
+    let bytes = Zeroizing::new(hex::decode(encoded.trim()).context("Key is not hex-encoded")?);
+    if bytes.len() != KEY_LEN {
+        bail!("Key must be {KEY_LEN} bytes, found {}", bytes.len());
+    }
+    let mut key = Zeroizing::new([0u8; KEY_LEN]);
+    key.copy_from_slice(&bytes);
+    Ok(key)
+}
+
+fn read_or_create_key_file(path: &Path) -> Result<KeyBytes> {
+    match std::fs::read_to_string(path) {
+        Ok(encoded) => {
+            let encoded = Zeroizing::new(encoded);
+            return decode_key(&encoded)
+                .with_context(|| format!("Invalid secrets key file: {}", path.display()));
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => {
+            return Err(e)
+                .with_context(|| format!("Failed to read secrets key file: {}", path.display()))
+        }
+    }
+
+    if let Some(parent) = path.parent() {
+        let mut builder = std::fs::DirBuilder::new();
+        builder.recursive(true);
+        #[cfg(unix)]
+        std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+        builder.create(parent).with_context(|| {
+            format!(
+                "Failed to create directory for key file: {}",
+                parent.display()
+            )
+        })?;
+    }
+    let key = random_key();
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options
+        .open(path)
+        .with_context(|| format!("Failed to create secrets key file: {}", path.display()))?;
+    file.write_all(Zeroizing::new(hex::encode(&key[..])).as_bytes())
+        .with_context(|| format!("Failed to write secrets key file: {}", path.display()))?;
+    info!("Created secrets key file: {}", path.display());
+    Ok(key)
+}
+
+fn read_or_create_keyring_key() -> Result<KeyBytes> {
+    let entry = keyring::Entry::new(KEYRING_SERVICE, KEYRING_USER)
+        .context("Failed to open the secrets key in the OS keyring")?;
+    match entry.get_password() {
+        Ok(encoded) => {
+            decode_key(&Zeroizing::new(encoded)).context("Invalid secrets key in the OS keyring")
+        }
+        Err(keyring::Error::NoEntry) => {
+            let key = random_key();
+            entry
+                .set_password(&Zeroizing::new(hex::encode(&key[..])))
+                .context("Failed to store the secrets key in the OS keyring")?;
+            info!("Stored a new secrets key in the OS keyring");
+            Ok(key)
+        }
+        Err(e) => Err(e).context("Failed to read the secrets key from the OS keyring"),
+    }
+}
+
+/// Use the key in the OS keyring, falling back to the key file at `path`. A key
+/// file that already exists wins, as it holds the key earlier secrets were sealed
+/// with while the keyring couldn't be used.
+fn read_keyring_or_file_key(path: &Path) -> Result<KeyBytes> {
+    if path.exists() {
+        return read_or_create_key_file(path);
+    }
+    match read_or_create_keyring_key() {
+        Ok(key) => Ok(key),
+        Err(e) => {
+            warn!(
+                "The OS keyring is unavailable ({e:#}), so the key that encrypts secrets is stored in {}. Anyone who can read that file can decrypt the secrets; set secrets_key to keep it elsewhere.",
+                path.display()
+            );
+            read_or_create_key_file(path)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_secrets_key() {
+        assert_eq!(
+            "file:/etc/wassette/key".parse::<SecretsKey>().unwrap(),
+            SecretsKey::File(PathBuf::from("/etc/wassette/key"))
+        );
+        assert_eq!(
+            "env:WASSETTE_PASSPHRASE".parse::<SecretsKey>().unwrap(),
+            SecretsKey::PassphraseEnv("WASSETTE_PASSPHRASE".to_string())
+        );
+        assert_eq!(
+            "keyring".parse::<SecretsKey>().unwrap(),
+            SecretsKey::Keyring
+        );
+        assert!("file:".parse::<SecretsKey>().is_err());
+        assert!("vault:secret/wassette".parse::<SecretsKey>().is_err());
+        assert_eq!(SecretsKey::Keyring.to_string(), "keyring");
+    }
+
+    #[test]
+    fn test_default_key_is_outside_secrets_dir() {
+        let SecretsKey::File(key_file) = SecretsKey::default_key() else {
+            panic!("the default key should not use the keyring");
+        };
+        assert!(!key_file.starts_with(crate::get_default_secrets_dir()));
+        assert_eq!(
+            SecretsKey::keyring_or_default_file(),
+            SecretsKey::KeyringOrFile(key_file)
+        );
+    }
+
+    #[test]
+    fn test_existing_key_file_wins_over_keyring() {
+        let dir = TempDir::new().unwrap();
+        let key_file = dir.path().join("secrets.key");
+        let sealed = SecretsCipher::new(SecretsKey::File(key_file.clone()))
+            .seal("weather", b"API_KEY: secret123\n")
+            .unwrap();
+
+        let cipher = SecretsCipher::new(SecretsKey::KeyringOrFile(key_file));
+        let plaintext = cipher.open("weather", &sealed).unwrap().unwrap();
+        assert_eq!(plaintext.as_slice(), b"API_KEY: secret123\n");
+    }
+
+    #[test]
+    fn test_key_file_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let key_file = dir.path().join("keys/secrets.key");
+        let cipher = SecretsCipher::new(SecretsKey::File(key_file.clone()));
+        let sealed = cipher.seal("weather", b"API_KEY: secret123\n").unwrap();
+        assert!(!sealed.contains("secret123"));
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(&key_file).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+            let mode = std::fs::metadata(dir.path().join("keys"))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o700);
+        }
+
+        // A fresh cipher reads the key back from the file
+        let reopened = SecretsCipher::new(SecretsKey::File(key_file.clone()));
+        let plaintext = reopened.open("weather", &sealed).unwrap().unwrap();
+        assert_eq!(plaintext.as_slice(), b"API_KEY: secret123\n");
+
+        // The file is bound to the component it was written for
+        assert!(reopened.open("other", &sealed).is_err());
+
+        // A different key can't open it
+        let other = TempDir::new().unwrap();
+        let wrong = SecretsCipher::new(SecretsKey::File(other.path().join("secrets.key")));
+        assert!(wrong.open("weather", &sealed).is_err());
+
+        // Plaintext files are passed through
+        assert!(reopened
+            .open("weather", "API_KEY: secret123\n")
+            .unwrap()
+            .is_none());
+
+        std::fs::write(&key_file, "not hex").unwrap();
+        let broken = SecretsCipher::new(SecretsKey::File(key_file));
+        assert!(broken.open("weather", &sealed).is_err());
+    }
+
+    #[test]
+    fn test_passphrase_round_trip() {
+        let var = "WASSETTE_TEST_SECRETS_PASSPHRASE";
+        let sealed = temp_env::with_var(var, Some("correct horse battery staple"), || {
+            let cipher = SecretsCipher::new(SecretsKey::PassphraseEnv(var.to_string()));
+            let sealed = cipher.seal("weather", b"API_KEY: secret123\n").unwrap();
+            let reopened = SecretsCipher::new(SecretsKey::PassphraseEnv(var.to_string()));
+            let plaintext = reopened.open("weather", &sealed).unwrap().unwrap();
+            assert_eq!(plaintext.as_slice(), b"API_KEY: secret123\n");
+            sealed
+        });
+
+        temp_env::with_var(var, Some("wrong passphrase"), || {
+            let cipher = SecretsCipher::new(SecretsKey::PassphraseEnv(var.to_string()));
+            assert!(cipher.open("weather", &sealed).is_err());
+        });
+        temp_env::with_var_unset(var, || {
+            let cipher = SecretsCipher::new(SecretsKey::PassphraseEnv(var.to_string()));
+            assert!(cipher.open("weather", &sealed).is_err());
+        });
+
+        // A key file can't open a file sealed with a passphrase
+        let dir = TempDir::new().unwrap();
+        let cipher = SecretsCipher::new(SecretsKey::File(dir.path().join("secrets.key")));
+        assert!(cipher.open("weather", &sealed).is_err());
+        assert!(!dir.path().join("secrets.key").exists());
+    }
+}
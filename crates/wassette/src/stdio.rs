@@ -0,0 +1,128 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! In-memory capture of guest stdout/stderr.
+//!
+//! Components never write to the host's stdio: with the stdio MCP transport any
+//! stray byte would corrupt the JSON-RPC stream. Instead each call gets a pair of
+//! bounded pipes whose contents are collected once the call returns.
+
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use tokio::io::AsyncWrite;
+use wasmtime_wasi::cli::{IsTerminal, StdoutStream};
+
+/// Maximum number of bytes kept per stream for a single call.
+pub(crate) const MAX_CAPTURED_OUTPUT_BYTES: usize = 64 * 1024;
+
+const TRUNCATED_MARKER: &str = "\n[output truncated]";
+
+#[derive(Default)]
+struct CapturedBuffer {
+    bytes: Vec<u8>,
+    truncated: bool,
+}
+
+/// An output stream that keeps the first `capacity` bytes written by the guest.
+///
+/// Unlike `MemoryOutputPipe`, writes past the capacity are silently dropped
+/// rather than closing the stream, so a chatty guest does not fail (or panic on
+/// `println!`) just because its output is being captured.
+#[derive(Clone)]
+pub struct CapturedOutputPipe {
+    capacity: usize,
+    buffer: Arc<Mutex<CapturedBuffer>>,
+}
+
+impl CapturedOutputPipe {
+    /// Create a pipe that keeps at most `capacity` bytes.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            buffer: Arc::new(Mutex::new(CapturedBuffer::default())),
+        }
+    }
+
+    /// Returns the captured output as (lossy) UTF-8, noting when it was truncated.
+    pub fn contents(&self) -> String {
+        let buffer = self.buffer.lock().unwrap();
+        let mut text = String::from_utf8_lossy(&buffer.bytes).into_owned();
+        if buffer.truncated {
+            text.push_str(TRUNCATED_MARKER);
+        }
+        text
+    }
+
+    fn append(&self, data: &[u8]) {
+        let mut buffer = self.buffer.lock().unwrap();
+        let remaining = self.capacity.saturating_sub(buffer.bytes.len());
+        if data.len() > remaining {
+            buffer.truncated = true;
+        }
+        let kept = data.len().min(remaining);
+        buffer.bytes.extend_from_slice(&data[..kept]);
+    }
+}
+
+impl IsTerminal for CapturedOutputPipe {
+    fn is_terminal(&self) -> bool {
+        false
+    }
+}
+
+impl StdoutStream for CapturedOutputPipe {
+    fn async_stream(&self) -> Box<dyn AsyncWrite + Send + Sync> {
+        Box::new(self.clone())
+    }
+}
+
+impl AsyncWrite for CapturedOutputPipe {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<std::io::Result<usize>> {
+        self.append(buf);
+        // Report the full write as accepted even when it was truncated
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::AsyncWriteExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_captured_output_pipe_collects_writes() {
+        let pipe = CapturedOutputPipe::new(64);
+        let mut stream = Pin::from(pipe.async_stream());
+
+        stream.write_all(b"hello ").await.unwrap();
+        stream.write_all(b"world").await.unwrap();
+
+        assert_eq!(pipe.contents(), "hello world");
+    }
+
+    #[tokio::test]
+    async fn test_captured_output_pipe_truncates_without_failing() {
+        let pipe = CapturedOutputPipe::new(4);
+        let mut stream = Pin::from(pipe.async_stream());
+
+        stream.write_all(b"abcdef").await.unwrap();
+        stream.write_all(b"ghi").await.unwrap();
+
+        assert_eq!(pipe.contents(), format!("abcd{TRUNCATED_MARKER}"));
+    }
+}
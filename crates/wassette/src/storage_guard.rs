@@ -0,0 +1,1398 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Enforcement of storage `deny` rules, glob grants and disk quotas inside granted
+//! directories.
+//!
+//! WASI preopens grant whole directory trees, so neither a denied subdirectory
+//! inside a granted one nor a grant like `fs://work/*/docs/**` can be expressed
+//! with preopens alone. Instead the `wasi:filesystem` interfaces are re-linked
+//! through [`GuardedFilesystem`], which tracks the host path behind every
+//! descriptor and refuses path-based operations that resolve into a denied
+//! location or outside every granted pattern, and leaves such entries out of
+//! directory listings. Writes that grow what is stored below a [`DiskQuota`] are
+//! counted against it and fail with `quota` once it is used up. Descriptors
+//! inside `mem://` directories are served by [`memory_mount`](crate::memory_mount)
+//! instead. Everything else is delegated unchanged to wasmtime-wasi.
+
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use tracing::warn;
+use wasmtime::component::{HasData, Linker, Resource};
+use wasmtime_wasi::p2::bindings::filesystem::preopens;
+use wasmtime_wasi::p2::bindings::filesystem::types::{
+    self, Advice, DescriptorFlags, DescriptorStat, DescriptorType, DirectoryEntry, ErrorCode,
+    Filesize, HostDescriptor, HostDirectoryEntryStream, MetadataHashValue, NewTimestamp, OpenFlags,
+    PathFlags,
+};
+use wasmtime_wasi::p2::{DynInputStream, DynOutputStream, FsError, FsResult};
+use wasmtime_wasi::WasiCtxView;
+
+use crate::glob::{glob_segment, glob_segments};
+use crate::memory_mount::{MemoryDescriptor, MemoryDirectoryEntries, MemoryFs};
+use crate::storage_quota::{charge_all, measure_path, DiskQuota, QuotaExceeded, QuotaOutputStream};
+use crate::{WasiState, WassetteWasiState};
+
+/// A host path the component may not access, even when an enclosing directory is granted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeniedPath {
+    /// Host path of the denied file or directory tree
+    pub host_path: PathBuf,
+    /// Whether reads below this path are denied
+    pub read: bool,
+    /// Whether writes below this path are denied
+    pub write: bool,
+}
+
+impl DeniedPath {
+    fn denies(&self, path: &Path, access: Access) -> bool {
+        let denied = match access {
+            Access::Read => self.read,
+            Access::Write => self.write,
+        };
+        denied && path.starts_with(&self.host_path)
+    }
+}
+
+/// A host path or glob pattern the component may access, from a storage `allow` rule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AllowedPath {
+    /// Host path of the granted directory, or the literal prefix of a pattern
+    pub host_path: PathBuf,
+    /// Path segments below `host_path` that a glob grant is limited to, where `*`
+    /// matches within a segment and `**` matches any number of segments
+    pub pattern: Option<Vec<String>>,
+    /// Whether reads are granted
+    pub read: bool,
+    /// Whether writes are granted
+    pub write: bool,
+}
+
+impl AllowedPath {
+    /// Full access to a directory tree
+    pub(crate) fn read_write(host_path: &Path) -> Self {
+        Self {
+            host_path: host_path.to_path_buf(),
+            pattern: None,
+            read: true,
+            write: true,
+        }
+    }
+
+    fn allows(&self, path: &Path, access: Access) -> bool {
+        let granted = match access {
+            Access::Read => self.read,
+            Access::Write => self.write,
+        };
+        granted
+            && match &self.pattern {
+                Some(pattern) => relative_segments(&self.host_path, path)
+                    .is_some_and(|segments| glob_segments(pattern, &segments)),
+                None => path.starts_with(&self.host_path),
+            }
+    }
+
+    /// Whether `path` is a directory the component has to pass through to reach
+    /// files matching the pattern, e.g. `work/project` for `work/*/docs/**`
+    fn leads_to(&self, path: &Path) -> bool {
+        let Some(pattern) = &self.pattern else {
+            return false;
+        };
+        relative_segments(&self.host_path, path).is_some_and(|segments| {
+            for (index, segment) in segments.iter().enumerate() {
+                match pattern.get(index) {
+                    Some(glob) if glob == "**" => return true,
+                    Some(glob) if glob_segment(glob, segment) => {}
+                    _ => return false,
+                }
+            }
+            true
+        })
+    }
+}
+
+/// The segments of `path` below `base`, or `None` when it is not below it
+fn relative_segments<'a>(base: &Path, path: &'a Path) -> Option<Vec<&'a str>> {
+    path.strip_prefix(base)
+        .ok()?
+        .components()
+        .map(|component| component.as_os_str().to_str())
+        .collect()
+}
+
+/// Splits the path of an `fs://` storage URI into its literal directory and, for
+/// glob grants, the pattern segments below it.
+///
+/// `work/*/docs/**` gives `("work", Some(["*", "docs", "**"]))` and `work/output`
+/// gives `("work/output", None)`.
+pub(crate) fn split_storage_pattern(path: &str) -> Result<(String, Option<Vec<String>>)> {
+    if !path.contains('*') {
+        return Ok((path.to_string(), None));
+    }
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    for segment in &segments {
+        if segment.contains("**") && *segment != "**" {
+            bail!("Invalid storage pattern: {path}, ** needs to be its own path segment");
+        }
+        if *segment == "." || *segment == ".." {
+            bail!("Invalid storage pattern: {path}, . and .. segments are not allowed");
+        }
+    }
+    let literal_len = segments
+        .iter()
+        .take_while(|segment| !segment.contains('*'))
+        .count();
+    if literal_len == 0 && !path.starts_with('/') {
+        bail!("Invalid storage pattern: {path}, it needs a directory before the first wildcard");
+    }
+
+    let mut literal = segments[..literal_len].join("/");
+    if path.starts_with('/') {
+        literal.insert(0, '/');
+    }
+    let pattern = segments[literal_len..]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+    Ok((literal, Some(pattern)))
+}
+
+/// Resolves the literal path of an `fs://` URI to a host path.
+///
+/// `~` stands for the user's home directory, absolute paths are used as written
+/// and relative ones are below `plugin_dir`.
+pub(crate) fn storage_host_path(path: &str, plugin_dir: &Path) -> Result<PathBuf> {
+    resolve_host_path(path, plugin_dir, home_dir)
+}
+
+fn home_dir() -> Result<PathBuf> {
+    etcetera::home_dir().context("Unable to find the home directory for a ~ storage path")
+}
+
+fn resolve_host_path(
+    path: &str,
+    plugin_dir: &Path,
+    home: impl FnOnce() -> Result<PathBuf>,
+) -> Result<PathBuf> {
+    if path == "~" {
+        return home();
+    }
+    if let Some(rest) = path.strip_prefix("~/") {
+        return Ok(home()?.join(rest));
+    }
+    // Joining an absolute path replaces `plugin_dir`
+    Ok(plugin_dir.join(path))
+}
+
+/// Checks a storage path when it is granted, before symlinks in it can be used to
+/// hand out a different location than the one shown to the user.
+///
+/// The granted directory itself can't be a symlink, and paths below the plugin
+/// directory or the home directory have to stay inside it once symlinks are resolved.
+pub(crate) fn check_storage_grant(path: &str, plugin_dir: &Path) -> Result<()> {
+    check_storage_grant_with_home(path, plugin_dir, home_dir)
+}
+
+fn check_storage_grant_with_home(
+    path: &str,
+    plugin_dir: &Path,
+    home: impl Fn() -> Result<PathBuf>,
+) -> Result<()> {
+    if path.split('/').any(|segment| segment == "..") {
+        bail!("Storage path {path} can't contain '..'");
+    }
+    let host_path = resolve_host_path(path, plugin_dir, &home)?;
+    check_reserved_path(path, &host_path, plugin_dir)?;
+    if std::fs::symlink_metadata(&host_path).is_ok_and(|metadata| metadata.is_symlink()) {
+        bail!(
+            "Storage path {} is a symlink to {}, grant the target instead",
+            host_path.display(),
+            canonicalize_lenient(&host_path).display()
+        );
+    }
+
+    let base = if path == "~" || path.starts_with("~/") {
+        Some(home()?)
+    } else if Path::new(path).is_relative() {
+        Some(plugin_dir.to_path_buf())
+    } else {
+        None
+    };
+    if let Some(base) = base {
+        let real = canonicalize_lenient(&host_path);
+        let base = canonicalize_lenient(&base);
+        if !real.starts_with(&base) {
+            bail!(
+                "Storage path {} resolves to {} through a symlink, outside {}",
+                host_path.display(),
+                real.display(),
+                base.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Directories Wassette keeps in the plugin directory for itself. They hold every
+/// component's private data and the audit log, so no storage grant may reach them.
+const RESERVED_DIRS: &[&str] = &[
+    crate::DATA_DIR,
+    crate::SCRATCH_DIR,
+    crate::AUDIT_DIR,
+    crate::DOWNLOADS_DIR,
+];
+
+/// Refuses a storage path that leads into one of the [`RESERVED_DIRS`], or a
+/// relative path naming the plugin directory itself, which contains them.
+/// `host_path` is `path` resolved with [`storage_host_path`].
+pub(crate) fn check_reserved_path(path: &str, host_path: &Path, plugin_dir: &Path) -> Result<()> {
+    let real = canonicalize_lenient(host_path);
+    let plugin_dir = canonicalize_lenient(plugin_dir);
+    let relative = Path::new(path).is_relative() && !path.starts_with('~');
+    if relative && real == plugin_dir {
+        bail!("Storage path {path} would grant the whole plugin directory");
+    }
+    if let Some(dir) = RESERVED_DIRS
+        .iter()
+        .find(|dir| real.starts_with(plugin_dir.join(dir)))
+    {
+        bail!("Storage path {path} is inside the '{dir}' directory Wassette manages");
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Access {
+    Read,
+    Write,
+}
+
+/// Per-store state backing [`GuardedFilesystem`].
+///
+/// With no denied paths, no glob grants and no quota limits the guard is inactive
+/// and every call is passed straight through.
+#[derive(Default)]
+pub struct StorageGuard {
+    denied: Arc<[DeniedPath]>,
+    /// Allow rules, only checked when at least one of them is a glob
+    allowed: Arc<[AllowedPath]>,
+    /// Quotas with a limit, shared with the component's other calls
+    quotas: Vec<DiskQuota>,
+    /// Guest preopen name to its (canonical) host path
+    preopens: HashMap<String, PathBuf>,
+    /// Descriptor resource rep to the (canonical) host path it refers to
+    descriptors: HashMap<u32, PathBuf>,
+    /// Guest preopen name to the in-memory directory behind it
+    memory_mounts: Vec<(String, MemoryFs)>,
+}
+
+impl StorageGuard {
+    /// Create a guard for the given deny and allow rules, quotas and preopened directories.
+    pub(crate) fn new(
+        denied: Vec<DeniedPath>,
+        allowed: Vec<AllowedPath>,
+        quotas: Vec<DiskQuota>,
+        preopens: impl IntoIterator<Item = (String, PathBuf)>,
+    ) -> Self {
+        // Directory grants are already enforced by their preopens
+        let allowed = if allowed.iter().any(|rule| rule.pattern.is_some()) {
+            allowed
+        } else {
+            Vec::new()
+        };
+        let quotas: Vec<DiskQuota> = quotas.into_iter().filter(DiskQuota::is_limited).collect();
+        if denied.is_empty() && allowed.is_empty() && quotas.is_empty() {
+            return Self::default();
+        }
+        let denied = denied
+            .into_iter()
+            .map(|rule| DeniedPath {
+                host_path: canonicalize_lenient(&rule.host_path),
+                ..rule
+            })
+            .collect();
+        let allowed = allowed
+            .into_iter()
+            .map(|rule| AllowedPath {
+                host_path: canonicalize_lenient(&rule.host_path),
+                ..rule
+            })
+            .collect();
+        let preopens = preopens
+            .into_iter()
+            .map(|(guest_path, host_path)| (guest_path, canonicalize_lenient(&host_path)))
+            .collect();
+        Self {
+            denied,
+            allowed,
+            quotas,
+            preopens,
+            descriptors: HashMap::new(),
+            memory_mounts: Vec::new(),
+        }
+    }
+
+    /// Serve the given guest preopens from in-memory directories
+    pub(crate) fn with_memory_mounts(mut self, memory_mounts: Vec<(String, MemoryFs)>) -> Self {
+        self.memory_mounts = memory_mounts;
+        self
+    }
+
+    /// The in-memory directories mounted for this call, by guest path
+    pub(crate) fn memory_mounts(&self) -> &[(String, MemoryFs)] {
+        &self.memory_mounts
+    }
+
+    fn is_active(&self) -> bool {
+        !self.denied.is_empty() || !self.allowed.is_empty() || !self.quotas.is_empty()
+    }
+
+    /// Quotas that count what is stored at `path`
+    fn quotas_for(&self, path: &Path) -> Vec<DiskQuota> {
+        self.quotas
+            .iter()
+            .filter(|quota| quota.covers(path))
+            .cloned()
+            .collect()
+    }
+
+    /// Charge `bytes` and `files` to every quota covering `path`
+    fn charge(&self, path: &Path, bytes: u64, files: u64) -> FsResult<()> {
+        charge_all(&self.quotas_for(path), bytes, files).map_err(|err| {
+            warn!(path = %path.display(), %err, "Filesystem write blocked by storage quota");
+            ErrorCode::Quota.into()
+        })
+    }
+
+    /// Charge the quotas covering the file behind `fd` for growing it to `end` bytes
+    fn charge_growth(&self, fd: &Resource<types::Descriptor>, end: u64) -> FsResult<()> {
+        let Some(path) = self.descriptors.get(&fd.rep()) else {
+            return Ok(());
+        };
+        if self.quotas.is_empty() {
+            return Ok(());
+        }
+        let len = std::fs::metadata(path).map_or(0, |metadata| metadata.len());
+        self.charge(path, end.saturating_sub(len), 0)
+    }
+
+    /// The quotas covering the file behind `fd` and its current length, for wrapping
+    /// an output stream. `None` when no quota applies.
+    fn stream_quotas(&self, fd: &Resource<types::Descriptor>) -> Option<(Vec<DiskQuota>, u64)> {
+        let path = self.descriptors.get(&fd.rep())?;
+        let quotas = self.quotas_for(path);
+        if quotas.is_empty() {
+            return None;
+        }
+        let len = std::fs::metadata(path).map_or(0, |metadata| metadata.len());
+        Some((quotas, len))
+    }
+
+    /// Whether an allow rule covers `path`, always true when there are no glob grants
+    fn is_allowed(&self, path: &Path, access: Access) -> bool {
+        if self.allowed.is_empty() || self.allowed.iter().any(|rule| rule.allows(path, access)) {
+            return true;
+        }
+        // Directories on the way to a pattern can be opened and listed
+        access == Access::Read
+            && self.allowed.iter().any(|rule| rule.leads_to(path))
+            && std::fs::metadata(path).is_ok_and(|metadata| metadata.is_dir())
+    }
+
+    /// Whether directory listings have to be filtered, i.e. some entries may not be readable
+    fn filters_listings(&self) -> bool {
+        !self.allowed.is_empty() || self.denied.iter().any(|rule| rule.read)
+    }
+
+    /// Whether the entry `name` of the directory `fd` is shown in its listing: only
+    /// names that could be opened for reading are, without warning about the others.
+    fn lists(&self, fd: &Resource<types::Descriptor>, name: &str) -> bool {
+        let Some(target) = self.resolve(fd, name, false) else {
+            return false;
+        };
+        !self
+            .denied
+            .iter()
+            .any(|rule| rule.denies(&target, Access::Read))
+            && self.is_allowed(&target, Access::Read)
+    }
+
+    /// Resolve `path` relative to the descriptor `fd`, or `None` if the descriptor is unknown.
+    fn resolve(
+        &self,
+        fd: &Resource<types::Descriptor>,
+        path: &str,
+        follow: bool,
+    ) -> Option<PathBuf> {
+        self.descriptors
+            .get(&fd.rep())
+            .map(|base| resolve_path(base, path, follow))
+    }
+
+    /// Fail with `access` unless `path` (relative to `fd`) is outside every denied
+    /// location and, with glob grants, covered by an allow rule.
+    fn check(
+        &self,
+        fd: &Resource<types::Descriptor>,
+        path: &str,
+        follow: bool,
+        access: Access,
+    ) -> FsResult<Option<PathBuf>> {
+        if !self.is_active() {
+            return Ok(None);
+        }
+        // Every descriptor comes from a preopen or `open-at`, both of which are tracked,
+        // so an unknown one is refused rather than guessed at
+        let Some(target) = self.resolve(fd, path, follow) else {
+            return Err(ErrorCode::Access.into());
+        };
+        if let Some(rule) = self.denied.iter().find(|rule| rule.denies(&target, access)) {
+            warn!(
+                path = %target.display(),
+                denied = %rule.host_path.display(),
+                ?access,
+                "Filesystem access blocked by storage deny rule"
+            );
+            return Err(ErrorCode::Access.into());
+        }
+        if !self.is_allowed(&target, access) {
+            warn!(
+                path = %target.display(),
+                ?access,
+                "Filesystem access blocked, no storage grant matches the path"
+            );
+            return Err(ErrorCode::Access.into());
+        }
+        Ok(Some(target))
+    }
+}
+
+/// Resolve a guest path against a host directory the way the OS would, following
+/// symlinks in intermediate components (and the final one when `follow` is set),
+/// so a link inside a granted tree cannot be used to reach a denied one.
+fn resolve_path(base: &Path, path: &str, follow: bool) -> PathBuf {
+    let components: Vec<Component<'_>> = Path::new(path).components().collect();
+    let mut resolved = base.to_path_buf();
+    for (index, component) in components.iter().enumerate() {
+        match component {
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            Component::Normal(part) => {
+                resolved.push(part);
+                let is_final = index + 1 == components.len();
+                if !is_final || follow {
+                    if let Ok(real) = std::fs::canonicalize(&resolved) {
+                        resolved = real;
+                    }
+                }
+            }
+            // wasi-filesystem rejects absolute paths, and `.` is a no-op
+            Component::CurDir | Component::RootDir | Component::Prefix(_) => {}
+        }
+    }
+    resolved
+}
+
+/// Canonicalize the longest existing prefix of `path`, keeping the rest as-is.
+fn canonicalize_lenient(path: &Path) -> PathBuf {
+    if let Ok(real) = std::fs::canonicalize(path) {
+        return real;
+    }
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => canonicalize_lenient(parent).join(name),
+        _ => path.to_path_buf(),
+    }
+}
+
+fn open_access(oflags: OpenFlags, flags: DescriptorFlags) -> Access {
+    if oflags.intersects(OpenFlags::CREATE | OpenFlags::TRUNCATE)
+        || flags.intersects(DescriptorFlags::WRITE | DescriptorFlags::MUTATE_DIRECTORY)
+    {
+        Access::Write
+    } else {
+        Access::Read
+    }
+}
+
+fn follows_symlinks(path_flags: PathFlags) -> bool {
+    path_flags.contains(PathFlags::SYMLINK_FOLLOW)
+}
+
+/// `wasi:filesystem` host implementation that applies a [`StorageGuard`] on top of wasmtime-wasi.
+pub(crate) struct GuardedFilesystem<'a> {
+    view: WasiCtxView<'a>,
+    guard: &'a mut StorageGuard,
+}
+
+struct HasGuardedFilesystem;
+
+impl HasData for HasGuardedFilesystem {
+    type Data<'a> = GuardedFilesystem<'a>;
+}
+
+fn guarded_filesystem(state: &mut WassetteWasiState<WasiState>) -> GuardedFilesystem<'_> {
+    let WasiState {
+        ctx,
+        table,
+        storage_guard,
+        ..
+    } = &mut state.inner;
+    GuardedFilesystem {
+        view: WasiCtxView { ctx, table },
+        guard: storage_guard,
+    }
+}
+
+/// Replace the `wasi:filesystem` bindings in `linker` with the guarded implementation.
+pub(crate) fn add_to_linker(
+    linker: &mut Linker<WassetteWasiState<WasiState>>,
+) -> anyhow::Result<()> {
+    linker.allow_shadowing(true);
+    types::add_to_linker::<_, HasGuardedFilesystem>(linker, guarded_filesystem)?;
+    preopens::add_to_linker::<_, HasGuardedFilesystem>(linker, guarded_filesystem)?;
+    linker.allow_shadowing(false);
+    Ok(())
+}
+
+impl GuardedFilesystem<'_> {
+    /// Swap a file output stream in the table for one that charges `quotas`
+    fn wrap_stream(
+        &mut self,
+        stream: Resource<DynOutputStream>,
+        quotas: Vec<DiskQuota>,
+        position: Option<u64>,
+        len: u64,
+    ) -> FsResult<Resource<DynOutputStream>> {
+        let inner = self.view.table.delete(stream)?;
+        let wrapped: DynOutputStream =
+            Box::new(QuotaOutputStream::new(inner, quotas, position, len));
+        Ok(self.view.table.push(wrapped)?)
+    }
+
+    /// The in-memory descriptor behind `fd`, if it belongs to a `mem://` directory
+    fn memory(&self, fd: &Resource<types::Descriptor>) -> Option<MemoryDescriptor> {
+        self.view
+            .table
+            .get(&Resource::<MemoryDescriptor>::new_borrow(fd.rep()))
+            .ok()
+            .cloned()
+    }
+
+    /// Add an in-memory descriptor to the table in place of a wasmtime-wasi one
+    fn push_memory(&mut self, fd: MemoryDescriptor) -> FsResult<Resource<types::Descriptor>> {
+        let fd = self.view.table.push(fd)?;
+        Ok(Resource::new_own(fd.rep()))
+    }
+}
+
+impl preopens::Host for GuardedFilesystem<'_> {
+    fn get_directories(&mut self) -> anyhow::Result<Vec<(Resource<types::Descriptor>, String)>> {
+        let mut directories = preopens::Host::get_directories(&mut self.view)?;
+        for (name, fs) in self.guard.memory_mounts() {
+            let fd = self.view.table.push(fs.root())?;
+            directories.push((Resource::new_own(fd.rep()), name.clone()));
+        }
+        if self.guard.is_active() {
+            for (fd, name) in &directories {
+                if let Some(host_path) = self.guard.preopens.get(name).cloned() {
+                    self.guard.descriptors.insert(fd.rep(), host_path);
+                }
+            }
+        }
+        Ok(directories)
+    }
+}
+
+impl types::Host for GuardedFilesystem<'_> {
+    fn convert_error_code(&mut self, err: FsError) -> anyhow::Result<ErrorCode> {
+        types::Host::convert_error_code(&mut self.view, err)
+    }
+
+    fn filesystem_error_code(
+        &mut self,
+        err: Resource<anyhow::Error>,
+    ) -> anyhow::Result<Option<ErrorCode>> {
+        // Stream writes refused by a quota carry the error as a stream error
+        if self.view.table.get(&err)?.is::<QuotaExceeded>() {
+            return Ok(Some(ErrorCode::Quota));
+        }
+        types::Host::filesystem_error_code(&mut self.view, err)
+    }
+}
+
+impl HostDescriptor for GuardedFilesystem<'_> {
+    async fn advise(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+        offset: Filesize,
+        len: Filesize,
+        advice: Advice,
+    ) -> FsResult<()> {
+        if self.memory(&fd).is_some() {
+            return Ok(());
+        }
+        self.view.advise(fd, offset, len, advice).await
+    }
+
+    async fn sync_data(&mut self, fd: Resource<types::Descriptor>) -> FsResult<()> {
+        if self.memory(&fd).is_some() {
+            return Ok(());
+        }
+        self.view.sync_data(fd).await
+    }
+
+    async fn get_flags(&mut self, fd: Resource<types::Descriptor>) -> FsResult<DescriptorFlags> {
+        if let Some(dir) = self.memory(&fd) {
+            return Ok(dir.get_flags());
+        }
+        self.view.get_flags(fd).await
+    }
+
+    async fn get_type(&mut self, fd: Resource<types::Descriptor>) -> FsResult<DescriptorType> {
+        if let Some(dir) = self.memory(&fd) {
+            return dir.get_type();
+        }
+        self.view.get_type(fd).await
+    }
+
+    async fn set_size(&mut self, fd: Resource<types::Descriptor>, size: Filesize) -> FsResult<()> {
+        if let Some(file) = self.memory(&fd) {
+            return file.set_size(size);
+        }
+        self.guard.charge_growth(&fd, size)?;
+        self.view.set_size(fd, size).await
+    }
+
+    async fn set_times(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+        atim: NewTimestamp,
+        mtim: NewTimestamp,
+    ) -> FsResult<()> {
+        if let Some(dir) = self.memory(&fd) {
+            return dir.set_times(atim, mtim);
+        }
+        self.view.set_times(fd, atim, mtim).await
+    }
+
+    async fn read(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+        len: Filesize,
+        offset: Filesize,
+    ) -> FsResult<(Vec<u8>, bool)> {
+        if let Some(file) = self.memory(&fd) {
+            return file.read(len, offset);
+        }
+        self.view.read(fd, len, offset).await
+    }
+
+    async fn write(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+        buf: Vec<u8>,
+        offset: Filesize,
+    ) -> FsResult<Filesize> {
+        if let Some(file) = self.memory(&fd) {
+            return file.write(&buf, offset);
+        }
+        self.guard
+            .charge_growth(&fd, offset.saturating_add(buf.len() as u64))?;
+        self.view.write(fd, buf, offset).await
+    }
+
+    async fn read_directory(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+    ) -> FsResult<Resource<types::DirectoryEntryStream>> {
+        if let Some(dir) = self.memory(&fd) {
+            let entries = self.view.table.push(dir.read_directory()?)?;
+            return Ok(Resource::new_own(entries.rep()));
+        }
+        if !self.guard.filters_listings() || !self.guard.descriptors.contains_key(&fd.rep()) {
+            return self.view.read_directory(fd).await;
+        }
+        // Collect the listing up front and hide what the component couldn't open
+        let dir = Resource::<types::Descriptor>::new_borrow(fd.rep());
+        let stream = self.view.read_directory(fd).await?;
+        let mut entries = Vec::new();
+        let listed = loop {
+            let next = self
+                .view
+                .read_directory_entry(Resource::new_borrow(stream.rep()))
+                .await;
+            match next {
+                Ok(Some(entry)) if self.guard.lists(&dir, &entry.name) => entries.push(entry),
+                Ok(Some(_)) => {}
+                Ok(None) => break Ok(()),
+                Err(err) => break Err(err),
+            }
+        };
+        HostDirectoryEntryStream::drop(&mut self.view, stream).map_err(FsError::trap)?;
+        listed?;
+        let entries = self.view.table.push(MemoryDirectoryEntries::new(entries))?;
+        Ok(Resource::new_own(entries.rep()))
+    }
+
+    async fn sync(&mut self, fd: Resource<types::Descriptor>) -> FsResult<()> {
+        if self.memory(&fd).is_some() {
+            return Ok(());
+        }
+        self.view.sync(fd).await
+    }
+
+    async fn create_directory_at(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+        path: String,
+    ) -> FsResult<()> {
+        if let Some(dir) = self.memory(&fd) {
+            return dir.create_directory_at(&path);
+        }
+        if let Some(target) = self.guard.check(&fd, &path, false, Access::Write)? {
+            self.guard.charge(&target, 0, 1)?;
+        }
+        self.view.create_directory_at(fd, path).await
+    }
+
+    async fn stat(&mut self, fd: Resource<types::Descriptor>) -> FsResult<DescriptorStat> {
+        if let Some(dir) = self.memory(&fd) {
+            return dir.stat();
+        }
+        self.view.stat(fd).await
+    }
+
+    async fn stat_at(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+        path_flags: PathFlags,
+        path: String,
+    ) -> FsResult<DescriptorStat> {
+        if let Some(dir) = self.memory(&fd) {
+            return dir.stat_at(&path);
+        }
+        self.guard
+            .check(&fd, &path, follows_symlinks(path_flags), Access::Read)?;
+        self.view.stat_at(fd, path_flags, path).await
+    }
+
+    async fn set_times_at(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+        path_flags: PathFlags,
+        path: String,
+        atim: NewTimestamp,
+        mtim: NewTimestamp,
+    ) -> FsResult<()> {
+        if let Some(dir) = self.memory(&fd) {
+            return dir.set_times_at(&path, atim, mtim);
+        }
+        self.guard
+            .check(&fd, &path, follows_symlinks(path_flags), Access::Write)?;
+        self.view
+            .set_times_at(fd, path_flags, path, atim, mtim)
+            .await
+    }
+
+    async fn link_at(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+        old_path_flags: PathFlags,
+        old_path: String,
+        new_descriptor: Resource<types::Descriptor>,
+        new_path: String,
+    ) -> FsResult<()> {
+        match (self.memory(&fd), self.memory(&new_descriptor)) {
+            (None, None) => {}
+            // In-memory directories have no hard links
+            (Some(_), Some(_)) => return Err(ErrorCode::Unsupported.into()),
+            _ => return Err(ErrorCode::CrossDevice.into()),
+        }
+        // A hard link exposes the target under a new name, so the source must be
+        // fully accessible, not just readable
+        let follow = follows_symlinks(old_path_flags);
+        self.guard.check(&fd, &old_path, follow, Access::Read)?;
+        let source = self.guard.check(&fd, &old_path, follow, Access::Write)?;
+        let target = self
+            .guard
+            .check(&new_descriptor, &new_path, false, Access::Write)?;
+        if let (Some(source), Some(target)) = (source, target) {
+            // The new name is counted like a copy, the same as a rescan would
+            let usage = measure_path(&source);
+            self.guard.charge(&target, usage.bytes, 1)?;
+        }
+        self.view
+            .link_at(fd, old_path_flags, old_path, new_descriptor, new_path)
+            .await
+    }
+
+    async fn open_at(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+        path_flags: PathFlags,
+        path: String,
+        oflags: OpenFlags,
+        flags: DescriptorFlags,
+    ) -> FsResult<Resource<types::Descriptor>> {
+        if let Some(dir) = self.memory(&fd) {
+            let opened = dir.open_at(&path, oflags, flags)?;
+            return self.push_memory(opened);
+        }
+        let access = open_access(oflags, flags);
+        let target = self.guard.check(&fd, &path, true, access)?;
+        if let Some(target) = &target {
+            if oflags.contains(OpenFlags::CREATE) && target.symlink_metadata().is_err() {
+                self.guard.charge(target, 0, 1)?;
+            }
+        }
+        let opened = self
+            .view
+            .open_at(fd, path_flags, path, oflags, flags)
+            .await?;
+        if let Some(target) = target {
+            // Record the real path of what was actually opened, which may have been
+            // created by this call
+            self.guard
+                .descriptors
+                .insert(opened.rep(), canonicalize_lenient(&target));
+        }
+        Ok(opened)
+    }
+
+    fn drop(&mut self, fd: Resource<types::Descriptor>) -> anyhow::Result<()> {
+        if self.memory(&fd).is_some() {
+            self.view
+                .table
+                .delete(Resource::<MemoryDescriptor>::new_own(fd.rep()))?;
+            return Ok(());
+        }
+        self.guard.descriptors.remove(&fd.rep());
+        HostDescriptor::drop(&mut self.view, fd)
+    }
+
+    async fn readlink_at(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+        path: String,
+    ) -> FsResult<String> {
+        if let Some(dir) = self.memory(&fd) {
+            return dir.readlink_at(&path);
+        }
+        self.guard.check(&fd, &path, false, Access::Read)?;
+        self.view.readlink_at(fd, path).await
+    }
+
+    async fn remove_directory_at(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+        path: String,
+    ) -> FsResult<()> {
+        if let Some(dir) = self.memory(&fd) {
+            return dir.remove_directory_at(&path);
+        }
+        self.guard.check(&fd, &path, false, Access::Write)?;
+        self.view.remove_directory_at(fd, path).await
+    }
+
+    async fn rename_at(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+        old_path: String,
+        new_fd: Resource<types::Descriptor>,
+        new_path: String,
+    ) -> FsResult<()> {
+        match (self.memory(&fd), self.memory(&new_fd)) {
+            (None, None) => {}
+            (Some(dir), Some(new_dir)) => return dir.rename_at(&old_path, &new_dir, &new_path),
+            _ => return Err(ErrorCode::CrossDevice.into()),
+        }
+        let source = self.guard.check(&fd, &old_path, false, Access::Write)?;
+        let target = self.guard.check(&new_fd, &new_path, false, Access::Write)?;
+        if let (Some(source), Some(target)) = (source, target) {
+            // Only quotas the entry moves into grow, a move within a quota changes nothing
+            let quotas: Vec<DiskQuota> = self
+                .guard
+                .quotas_for(&target)
+                .into_iter()
+                .filter(|quota| !quota.covers(&source))
+                .collect();
+            if !quotas.is_empty() {
+                let usage = measure_path(&source);
+                charge_all(&quotas, usage.bytes, usage.files).map_err(|err| {
+                    warn!(path = %target.display(), %err, "Filesystem rename blocked by storage quota");
+                    FsError::from(ErrorCode::Quota)
+                })?;
+            }
+        }
+        self.view.rename_at(fd, old_path, new_fd, new_path).await
+    }
+
+    async fn symlink_at(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+        src_path: String,
+        dest_path: String,
+    ) -> FsResult<()> {
+        if self.memory(&fd).is_some() {
+            return Err(ErrorCode::Unsupported.into());
+        }
+        // The link target is checked whenever the link is later followed
+        if let Some(target) = self.guard.check(&fd, &dest_path, false, Access::Write)? {
+            self.guard.charge(&target, 0, 1)?;
+        }
+        self.view.symlink_at(fd, src_path, dest_path).await
+    }
+
+    async fn unlink_file_at(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+        path: String,
+    ) -> FsResult<()> {
+        if let Some(dir) = self.memory(&fd) {
+            return dir.unlink_file_at(&path);
+        }
+        self.guard.check(&fd, &path, false, Access::Write)?;
+        self.view.unlink_file_at(fd, path).await
+    }
+
+    fn read_via_stream(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+        offset: Filesize,
+    ) -> FsResult<Resource<DynInputStream>> {
+        if let Some(file) = self.memory(&fd) {
+            let stream: DynInputStream = Box::new(file.read_via_stream(offset)?);
+            return Ok(self.view.table.push(stream)?);
+        }
+        self.view.read_via_stream(fd, offset)
+    }
+
+    fn write_via_stream(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+        offset: Filesize,
+    ) -> FsResult<Resource<DynOutputStream>> {
+        if let Some(file) = self.memory(&fd) {
+            let stream: DynOutputStream = Box::new(file.write_via_stream(Some(offset))?);
+            return Ok(self.view.table.push(stream)?);
+        }
+        let quotas = self.guard.stream_quotas(&fd);
+        let stream = self.view.write_via_stream(fd, offset)?;
+        match quotas {
+            Some((quotas, len)) => self.wrap_stream(stream, quotas, Some(offset), len),
+            None => Ok(stream),
+        }
+    }
+
+    fn append_via_stream(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+    ) -> FsResult<Resource<DynOutputStream>> {
+        if let Some(file) = self.memory(&fd) {
+            let stream: DynOutputStream = Box::new(file.write_via_stream(None)?);
+            return Ok(self.view.table.push(stream)?);
+        }
+        let quotas = self.guard.stream_quotas(&fd);
+        let stream = self.view.append_via_stream(fd)?;
+        match quotas {
+            Some((quotas, len)) => self.wrap_stream(stream, quotas, None, len),
+            None => Ok(stream),
+        }
+    }
+
+    async fn is_same_object(
+        &mut self,
+        a: Resource<types::Descriptor>,
+        b: Resource<types::Descriptor>,
+    ) -> anyhow::Result<bool> {
+        match (self.memory(&a), self.memory(&b)) {
+            (None, None) => {}
+            (Some(a), Some(b)) => return Ok(a.is_same_object(&b)),
+            _ => return Ok(false),
+        }
+        self.view.is_same_object(a, b).await
+    }
+
+    async fn metadata_hash(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+    ) -> FsResult<MetadataHashValue> {
+        if let Some(dir) = self.memory(&fd) {
+            return dir.metadata_hash();
+        }
+        self.view.metadata_hash(fd).await
+    }
+
+    async fn metadata_hash_at(
+        &mut self,
+        fd: Resource<types::Descriptor>,
+        path_flags: PathFlags,
+        path: String,
+    ) -> FsResult<MetadataHashValue> {
+        if let Some(dir) = self.memory(&fd) {
+            return dir.metadata_hash_at(&path);
+        }
+        self.guard
+            .check(&fd, &path, follows_symlinks(path_flags), Access::Read)?;
+        self.view.metadata_hash_at(fd, path_flags, path).await
+    }
+}
+
+impl HostDirectoryEntryStream for GuardedFilesystem<'_> {
+    async fn read_directory_entry(
+        &mut self,
+        stream: Resource<types::DirectoryEntryStream>,
+    ) -> FsResult<Option<DirectoryEntry>> {
+        let memory = Resource::<MemoryDirectoryEntries>::new_borrow(stream.rep());
+        if let Ok(entries) = self.view.table.get_mut(&memory) {
+            return Ok(entries.next());
+        }
+        self.view.read_directory_entry(stream).await
+    }
+
+    fn drop(&mut self, stream: Resource<types::DirectoryEntryStream>) -> anyhow::Result<()> {
+        let memory = Resource::<MemoryDirectoryEntries>::new_borrow(stream.rep());
+        if self.view.table.get(&memory).is_ok() {
+            self.view
+                .table
+                .delete(Resource::<MemoryDirectoryEntries>::new_own(stream.rep()))?;
+            return Ok(());
+        }
+        HostDirectoryEntryStream::drop(&mut self.view, stream)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn guard_for(root: &Path, denied: &str, read: bool, write: bool) -> StorageGuard {
+        let mut guard = StorageGuard::new(
+            vec![DeniedPath {
+                host_path: root.join(denied),
+                read,
+                write,
+            }],
+            Vec::new(),
+            Vec::new(),
+            [("work".to_string(), root.to_path_buf())],
+        );
+        guard.descriptors.insert(0, canonicalize_lenient(root));
+        guard
+    }
+
+    #[test]
+    fn test_inactive_guard_allows_everything() {
+        let guard = StorageGuard::new(
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            [("work".to_string(), PathBuf::from("/w"))],
+        );
+        assert!(!guard.is_active());
+        let fd = Resource::new_own(7);
+        assert!(matches!(
+            guard.check(&fd, "anything", false, Access::Write),
+            Ok(None)
+        ));
+    }
+
+    #[test]
+    fn test_denied_subdirectory() {
+        let root = TempDir::new().unwrap();
+        std::fs::create_dir_all(root.path().join("secret/nested")).unwrap();
+        std::fs::create_dir_all(root.path().join("public")).unwrap();
+        let guard = guard_for(root.path(), "secret", true, true);
+        let fd = Resource::new_own(0);
+
+        assert!(guard
+            .check(&fd, "public/file.txt", true, Access::Read)
+            .is_ok());
+        assert!(guard.check(&fd, "secret", true, Access::Read).is_err());
+        assert!(guard
+            .check(&fd, "secret/nested/file.txt", true, Access::Write)
+            .is_err());
+        assert!(guard
+            .check(&fd, "public/../secret/file.txt", true, Access::Read)
+            .is_err());
+        assert!(guard.check(&fd, "./secret", true, Access::Read).is_err());
+        // A sibling sharing the prefix is not covered
+        assert!(guard.check(&fd, "secret-notes", true, Access::Read).is_ok());
+        // Denied entries are left out of listings
+        assert!(guard.filters_listings());
+        assert!(guard.lists(&fd, "public"));
+        assert!(!guard.lists(&fd, "secret"));
+        assert!(!guard_for(root.path(), "secret", false, true).filters_listings());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_into_denied_subdirectory() {
+        let root = TempDir::new().unwrap();
+        std::fs::create_dir_all(root.path().join("secret/inner")).unwrap();
+        std::os::unix::fs::symlink(root.path().join("secret/inner"), root.path().join("link"))
+            .unwrap();
+        let guard = guard_for(root.path(), "secret", true, true);
+        let fd = Resource::new_own(0);
+
+        assert!(guard
+            .check(&fd, "link/file.txt", true, Access::Read)
+            .is_err());
+        assert!(guard
+            .check(&fd, "link/../file.txt", true, Access::Read)
+            .is_err());
+        assert!(guard.check(&fd, "link", true, Access::Read).is_err());
+        // Removing the link itself does not touch the denied tree
+        assert!(guard.check(&fd, "link", false, Access::Write).is_ok());
+    }
+
+    #[test]
+    fn test_write_only_deny() {
+        let root = TempDir::new().unwrap();
+        std::fs::create_dir_all(root.path().join("readonly")).unwrap();
+        let guard = guard_for(root.path(), "readonly", false, true);
+        let fd = Resource::new_own(0);
+
+        assert!(guard
+            .check(&fd, "readonly/file.txt", true, Access::Read)
+            .is_ok());
+        assert!(guard
+            .check(&fd, "readonly/file.txt", true, Access::Write)
+            .is_err());
+    }
+
+    #[test]
+    fn test_unknown_descriptor_is_refused() {
+        let root = TempDir::new().unwrap();
+        let guard = guard_for(root.path(), "secret", true, true);
+        let fd = Resource::new_own(42);
+
+        assert!(guard.check(&fd, "public", true, Access::Read).is_err());
+    }
+
+    #[test]
+    fn test_split_storage_pattern() {
+        assert_eq!(
+            split_storage_pattern("work/output").unwrap(),
+            ("work/output".to_string(), None)
+        );
+        assert_eq!(
+            split_storage_pattern("work/*/docs/**").unwrap(),
+            (
+                "work".to_string(),
+                Some(vec!["*".to_string(), "docs".to_string(), "**".to_string()])
+            )
+        );
+        assert_eq!(
+            split_storage_pattern("/srv/data/*.csv").unwrap(),
+            ("/srv/data".to_string(), Some(vec!["*.csv".to_string()]))
+        );
+        assert_eq!(
+            split_storage_pattern("/**").unwrap(),
+            ("/".to_string(), Some(vec!["**".to_string()]))
+        );
+        assert!(split_storage_pattern("**/docs").is_err());
+        assert!(split_storage_pattern("work/**.md").is_err());
+        assert!(split_storage_pattern("work/*/../secrets").is_err());
+    }
+
+    #[test]
+    fn test_resolve_host_path() {
+        let plugin_dir = Path::new("/plugins");
+        let home = || Ok(PathBuf::from("/home/me"));
+
+        assert_eq!(
+            resolve_host_path("work", plugin_dir, home).unwrap(),
+            PathBuf::from("/plugins/work")
+        );
+        assert_eq!(
+            resolve_host_path("/srv/data", plugin_dir, home).unwrap(),
+            PathBuf::from("/srv/data")
+        );
+        assert_eq!(
+            resolve_host_path("~/notes", plugin_dir, home).unwrap(),
+            PathBuf::from("/home/me/notes")
+        );
+        assert_eq!(
+            resolve_host_path("~", plugin_dir, home).unwrap(),
+            PathBuf::from("/home/me")
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_check_storage_grant() {
+        let plugin_dir = TempDir::new().unwrap();
+        let home = TempDir::new().unwrap();
+        let outside = TempDir::new().unwrap();
+        let home_fn = || Ok(home.path().to_path_buf());
+        std::fs::create_dir_all(plugin_dir.path().join("work")).unwrap();
+        std::fs::create_dir_all(home.path().join("notes")).unwrap();
+        std::os::unix::fs::symlink(outside.path(), plugin_dir.path().join("escape")).unwrap();
+        std::os::unix::fs::symlink(outside.path(), home.path().join("escape")).unwrap();
+        let check = |path: &str| check_storage_grant_with_home(path, plugin_dir.path(), home_fn);
+
+        assert!(check("work").is_ok());
+        assert!(check("work/not-created-yet").is_ok());
+        assert!(check("~/notes").is_ok());
+        assert!(check(&outside.path().to_string_lossy()).is_ok());
+
+        let err = check("escape").unwrap_err().to_string();
+        assert!(err.contains("is a symlink"), "{err}");
+        let err = check("escape/data").unwrap_err().to_string();
+        assert!(err.contains("through a symlink"), "{err}");
+        assert!(check("~/escape/data").is_err());
+        assert!(check("work/../../etc").is_err());
+
+        // Other components' private directories live in the plugin directory
+        assert!(check("database").is_ok());
+        for path in ["data", "data/other-tool", "./scratch/x", "audit", ".", ""] {
+            let err = check(path).unwrap_err().to_string();
+            assert!(
+                err.contains("Wassette manages") || err.contains("whole plugin directory"),
+                "{path}: {err}"
+            );
+        }
+        let absolute = plugin_dir.path().join("data/other-tool");
+        assert!(check(&absolute.to_string_lossy()).is_err());
+        std::fs::create_dir_all(plugin_dir.path().join("data")).unwrap();
+        std::os::unix::fs::symlink(
+            plugin_dir.path().join("data"),
+            plugin_dir.path().join("work/link"),
+        )
+        .unwrap();
+        assert!(check("work/link/other-tool").is_err());
+    }
+
+    #[test]
+    fn test_glob_grant() {
+        let root = TempDir::new().unwrap();
+        for dir in ["api/docs/guides", "api/src", "web/docs"] {
+            std::fs::create_dir_all(root.path().join(dir)).unwrap();
+        }
+        std::fs::write(root.path().join("api/README.md"), "").unwrap();
+        let mut guard = StorageGuard::new(
+            Vec::new(),
+            vec![AllowedPath {
+                host_path: root.path().to_path_buf(),
+                pattern: Some(vec!["*".to_string(), "docs".to_string(), "**".to_string()]),
+                read: true,
+                write: true,
+            }],
+            Vec::new(),
+            [("work".to_string(), root.path().to_path_buf())],
+        );
+        guard
+            .descriptors
+            .insert(0, canonicalize_lenient(root.path()));
+        let fd = Resource::new_own(0);
+
+        assert!(guard
+            .check(&fd, "api/docs/guides/intro.md", true, Access::Write)
+            .is_ok());
+        assert!(guard.check(&fd, "web/docs", true, Access::Read).is_ok());
+        assert!(guard
+            .check(&fd, "api/src/main.rs", true, Access::Read)
+            .is_err());
+        assert!(guard
+            .check(&fd, "api/docs/../src/main.rs", true, Access::Read)
+            .is_err());
+        // Directories leading to the pattern can be listed, but not changed
+        assert!(guard.check(&fd, "", true, Access::Read).is_ok());
+        assert!(guard.check(&fd, "api", true, Access::Read).is_ok());
+        assert!(guard.check(&fd, "api", true, Access::Write).is_err());
+        assert!(guard
+            .check(&fd, "api/README.md", true, Access::Read)
+            .is_err());
+        assert!(guard.check(&fd, "api/src", true, Access::Read).is_err());
+
+        // Listings only show what could be opened
+        guard
+            .descriptors
+            .insert(1, canonicalize_lenient(&root.path().join("api")));
+        let api = Resource::new_own(1);
+        assert!(guard.lists(&fd, "api"));
+        assert!(guard.lists(&fd, "web"));
+        assert!(guard.lists(&api, "docs"));
+        assert!(!guard.lists(&api, "src"));
+        assert!(!guard.lists(&api, "README.md"));
+    }
+
+    #[test]
+    fn test_directory_grants_do_not_activate_guard() {
+        let guard = StorageGuard::new(
+            Vec::new(),
+            vec![AllowedPath {
+                host_path: PathBuf::from("/w"),
+                pattern: None,
+                read: true,
+                write: false,
+            }],
+            Vec::new(),
+            [("work".to_string(), PathBuf::from("/w"))],
+        );
+        assert!(!guard.is_active());
+    }
+
+    #[test]
+    fn test_quota_guard() {
+        let root = TempDir::new().unwrap();
+        std::fs::write(root.path().join("a.txt"), [0u8; 10]).unwrap();
+        let limits = policy::StorageQuota {
+            bytes: Some(policy::SizeLimit::Number(100)),
+            files: Some(2),
+        };
+        let quota =
+            DiskQuota::new("fs://work", [root.path().to_path_buf()], Some(&limits)).unwrap();
+        let mut guard = StorageGuard::new(
+            Vec::new(),
+            Vec::new(),
+            vec![quota],
+            [("work".to_string(), root.path().to_path_buf())],
+        );
+        assert!(guard.is_active());
+        let root_path = canonicalize_lenient(root.path());
+        guard.descriptors.insert(0, root_path.clone());
+        guard.descriptors.insert(1, root_path.join("a.txt"));
+        let file = Resource::new_own(1);
+
+        // Rewriting existing bytes is free, growing the file is not
+        assert!(guard.charge_growth(&file, 10).is_ok());
+        assert!(guard.charge_growth(&file, 90).is_ok());
+        let err = guard.charge_growth(&file, 101).unwrap_err();
+        assert!(matches!(err.downcast(), Ok(ErrorCode::Quota)));
+
+        assert!(guard.charge(&root_path.join("b.txt"), 0, 1).is_ok());
+        std::fs::write(root_path.join("b.txt"), b"").unwrap();
+        assert!(guard.charge(&root_path.join("c.txt"), 0, 1).is_err());
+        // Paths outside the quota are not counted
+        assert!(guard.charge(Path::new("/elsewhere/d.txt"), 1000, 1).is_ok());
+        assert!(guard.stream_quotas(&file).is_some());
+    }
+
+    #[test]
+    fn test_open_access() {
+        assert_eq!(
+            open_access(OpenFlags::empty(), DescriptorFlags::READ),
+            Access::Read
+        );
+        assert_eq!(
+            open_access(OpenFlags::CREATE, DescriptorFlags::READ),
+            Access::Write
+        );
+        assert_eq!(
+            open_access(OpenFlags::empty(), DescriptorFlags::WRITE),
+            Access::Write
+        );
+        assert_eq!(
+            open_access(OpenFlags::DIRECTORY, DescriptorFlags::MUTATE_DIRECTORY),
+            Access::Write
+        );
+    }
+}
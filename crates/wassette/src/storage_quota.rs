@@ -0,0 +1,396 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Disk quotas for writable storage grants.
+//!
+//! A [`DiskQuota`] caps the bytes and the number of files stored below a set of
+//! host directories. Usage is measured from disk the first time it is needed and
+//! then tracked as the guest writes through the storage guard. That running count
+//! drifts when files are removed, truncated or changed from outside the component,
+//! so a quota measures the disk again before it refuses anything. Clones share
+//! their usage, which makes every call of a component draw from the same quota.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use bytes::Bytes;
+use serde::Serialize;
+use wasmtime_wasi::p2::{DynOutputStream, OutputStream, Pollable, StreamError, StreamResult};
+
+/// Bytes and files stored below some directories
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct StorageUsage {
+    /// Total size of the regular files
+    pub bytes: u64,
+    /// Number of files, directories and symlinks
+    pub files: u64,
+}
+
+/// Current usage of a quota or writable directory, as shown by `get-policy`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct QuotaUsage {
+    /// What the usage is counted for, a storage URI or `component`
+    pub scope: String,
+    /// Host directories that are counted
+    pub paths: Vec<PathBuf>,
+    /// Total size of the stored files in bytes
+    pub bytes: u64,
+    /// Number of stored files and directories
+    pub files: u64,
+    /// Byte limit, if the policy sets one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_bytes: Option<u64>,
+    /// File limit, if the policy sets one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_files: Option<u64>,
+}
+
+/// The error behind a write that would take a quota over its limit
+#[derive(Debug)]
+pub(crate) struct QuotaExceeded {
+    scope: String,
+}
+
+impl QuotaExceeded {
+    pub(crate) fn new(scope: impl Into<String>) -> Self {
+        Self {
+            scope: scope.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "storage quota for {} exceeded", self.scope)
+    }
+}
+
+impl std::error::Error for QuotaExceeded {}
+
+/// Byte and file limits on a set of host directories
+#[derive(Debug, Clone)]
+pub struct DiskQuota {
+    scope: String,
+    /// Canonical host directories, none of them inside another
+    roots: Vec<PathBuf>,
+    max_bytes: Option<u64>,
+    max_files: Option<u64>,
+    /// Running usage, measured from disk on first use
+    usage: Arc<Mutex<Option<StorageUsage>>>,
+}
+
+impl DiskQuota {
+    /// Create a quota named `scope` over `roots`. Without `limits` it only reports usage.
+    pub(crate) fn new(
+        scope: impl Into<String>,
+        roots: impl IntoIterator<Item = PathBuf>,
+        limits: Option<&policy::StorageQuota>,
+    ) -> Result<Self> {
+        let mut roots: Vec<PathBuf> = roots
+            .into_iter()
+            .map(|root| std::fs::canonicalize(&root).unwrap_or(root))
+            .collect();
+        roots.sort();
+        roots.dedup();
+        // Directories inside another root would be counted twice
+        let nested: Vec<PathBuf> = roots
+            .iter()
+            .filter(|root| {
+                roots
+                    .iter()
+                    .any(|other| other != *root && root.starts_with(other))
+            })
+            .cloned()
+            .collect();
+        roots.retain(|root| !nested.contains(root));
+
+        let max_bytes = limits
+            .and_then(|limits| limits.bytes.as_ref())
+            .map(|bytes| bytes.to_bytes())
+            .transpose()?;
+        Ok(Self {
+            scope: scope.into(),
+            roots,
+            max_bytes,
+            max_files: limits.and_then(|limits| limits.files),
+            usage: Arc::new(Mutex::new(None)),
+        })
+    }
+
+    /// Whether the quota limits anything, as opposed to only reporting usage
+    pub(crate) fn is_limited(&self) -> bool {
+        self.max_bytes.is_some() || self.max_files.is_some()
+    }
+
+    /// Whether `path` is stored below one of the quota's directories
+    pub(crate) fn covers(&self, path: &Path) -> bool {
+        self.roots.iter().any(|root| path.starts_with(root))
+    }
+
+    fn fits(&self, usage: StorageUsage, bytes: u64, files: u64) -> bool {
+        self.max_bytes
+            .is_none_or(|max| usage.bytes.saturating_add(bytes) <= max)
+            && self
+                .max_files
+                .is_none_or(|max| usage.files.saturating_add(files) <= max)
+    }
+
+    /// Count `bytes` and `files` more against the quota, or fail if that goes over a limit
+    pub(crate) fn charge(&self, bytes: u64, files: u64) -> Result<(), QuotaExceeded> {
+        if bytes == 0 && files == 0 {
+            return Ok(());
+        }
+        let mut guard = self.usage.lock().unwrap_or_else(|e| e.into_inner());
+        let usage = guard.get_or_insert_with(|| measure(&self.roots));
+        if !self.fits(*usage, bytes, files) {
+            // Files may have been removed since the count was taken
+            *usage = measure(&self.roots);
+            if !self.fits(*usage, bytes, files) {
+                return Err(QuotaExceeded {
+                    scope: self.scope.clone(),
+                });
+            }
+        }
+        usage.bytes = usage.bytes.saturating_add(bytes);
+        usage.files = usage.files.saturating_add(files);
+        Ok(())
+    }
+
+    fn refund(&self, bytes: u64, files: u64) {
+        let mut guard = self.usage.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(usage) = guard.as_mut() {
+            usage.bytes = usage.bytes.saturating_sub(bytes);
+            usage.files = usage.files.saturating_sub(files);
+        }
+    }
+
+    /// Measure the quota's directories and report their usage
+    pub(crate) fn usage(&self) -> QuotaUsage {
+        let usage = measure(&self.roots);
+        *self.usage.lock().unwrap_or_else(|e| e.into_inner()) = Some(usage);
+        QuotaUsage {
+            scope: self.scope.clone(),
+            paths: self.roots.clone(),
+            bytes: usage.bytes,
+            files: usage.files,
+            max_bytes: self.max_bytes,
+            max_files: self.max_files,
+        }
+    }
+}
+
+/// Charge every quota in `quotas`, leaving all of them unchanged if one is exceeded
+pub(crate) fn charge_all(
+    quotas: &[DiskQuota],
+    bytes: u64,
+    files: u64,
+) -> Result<(), QuotaExceeded> {
+    for (index, quota) in quotas.iter().enumerate() {
+        if let Err(err) = quota.charge(bytes, files) {
+            for charged in &quotas[..index] {
+                charged.refund(bytes, files);
+            }
+            return Err(err);
+        }
+    }
+    Ok(())
+}
+
+/// Add up what is stored below `roots`, without following symlinks
+pub(crate) fn measure(roots: &[PathBuf]) -> StorageUsage {
+    let mut usage = StorageUsage::default();
+    let mut pending: Vec<PathBuf> = roots.to_vec();
+    while let Some(dir) = pending.pop() {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let Ok(metadata) = entry.path().symlink_metadata() else {
+                continue;
+            };
+            usage.files += 1;
+            if metadata.is_dir() {
+                pending.push(entry.path());
+            } else if metadata.is_file() {
+                usage.bytes += metadata.len();
+            }
+        }
+    }
+    usage
+}
+
+/// Size of `path` as [`measure`] counts it, for a file or directory moved into a quota
+pub(crate) fn measure_path(path: &Path) -> StorageUsage {
+    match path.symlink_metadata() {
+        Ok(metadata) if metadata.is_dir() => {
+            let inner = measure(&[path.to_path_buf()]);
+            StorageUsage {
+                bytes: inner.bytes,
+                files: inner.files + 1,
+            }
+        }
+        Ok(metadata) => StorageUsage {
+            bytes: if metadata.is_file() {
+                metadata.len()
+            } else {
+                0
+            },
+            files: 1,
+        },
+        Err(_) => StorageUsage::default(),
+    }
+}
+
+/// A file output stream that charges its quotas for the bytes that grow the file
+pub(crate) struct QuotaOutputStream {
+    inner: DynOutputStream,
+    quotas: Vec<DiskQuota>,
+    /// Offset of the next write, `None` for an append stream
+    position: Option<u64>,
+    /// Length of the file as far as this stream knows
+    len: u64,
+}
+
+impl QuotaOutputStream {
+    /// Wrap `inner`, which writes to a file of `len` bytes at `position` or at its end
+    pub(crate) fn new(
+        inner: DynOutputStream,
+        quotas: Vec<DiskQuota>,
+        position: Option<u64>,
+        len: u64,
+    ) -> Self {
+        Self {
+            inner,
+            quotas,
+            position,
+            len,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Pollable for QuotaOutputStream {
+    async fn ready(&mut self) {
+        self.inner.ready().await
+    }
+}
+
+#[async_trait::async_trait]
+impl OutputStream for QuotaOutputStream {
+    fn write(&mut self, bytes: Bytes) -> StreamResult<()> {
+        let start = self.position.unwrap_or(self.len);
+        let end = start.saturating_add(bytes.len() as u64);
+        charge_all(&self.quotas, end.saturating_sub(self.len), 0)
+            .map_err(|err| StreamError::LastOperationFailed(err.into()))?;
+        self.inner.write(bytes)?;
+        if let Some(position) = &mut self.position {
+            *position = end;
+        }
+        self.len = self.len.max(end);
+        Ok(())
+    }
+
+    fn flush(&mut self) -> StreamResult<()> {
+        self.inner.flush()
+    }
+
+    fn check_write(&mut self) -> StreamResult<usize> {
+        self.inner.check_write()
+    }
+
+    async fn cancel(&mut self) {
+        self.inner.cancel().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+
+    fn quota(root: &Path, bytes: Option<&str>, files: Option<u64>) -> DiskQuota {
+        let limits = policy::StorageQuota {
+            bytes: bytes.map(|bytes| policy::SizeLimit::String(bytes.to_string())),
+            files,
+        };
+        DiskQuota::new("fs://work", [root.to_path_buf()], Some(&limits)).unwrap()
+    }
+
+    #[test]
+    fn test_measure() {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        std::fs::write(dir.path().join("a.txt"), [0u8; 10]).unwrap();
+        std::fs::write(dir.path().join("sub/b.txt"), [0u8; 5]).unwrap();
+
+        let usage = measure(&[dir.path().to_path_buf()]);
+        assert_eq!(
+            usage,
+            StorageUsage {
+                bytes: 15,
+                files: 3
+            }
+        );
+        assert_eq!(
+            measure_path(&dir.path().join("sub")),
+            StorageUsage { bytes: 5, files: 2 }
+        );
+        assert_eq!(
+            measure(&[dir.path().join("missing")]),
+            StorageUsage::default()
+        );
+    }
+
+    #[test]
+    fn test_charge_rescans_before_failing() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("a.txt");
+        std::fs::write(&file, [0u8; 60]).unwrap();
+        let quota = quota(dir.path(), Some("100"), Some(2));
+
+        assert!(quota.charge(40, 0).is_ok());
+        std::fs::write(&file, [0u8; 100]).unwrap();
+        let err = quota.charge(1, 0).unwrap_err();
+        assert_eq!(err.to_string(), "storage quota for fs://work exceeded");
+
+        // Freeing space outside the guard is picked up on the next refusal
+        std::fs::remove_file(&file).unwrap();
+        assert!(quota.charge(100, 1).is_ok());
+        std::fs::write(&file, [0u8; 100]).unwrap();
+        assert!(quota.charge(1, 0).is_err());
+        assert!(quota.charge(0, 1).is_ok());
+    }
+
+    #[test]
+    fn test_charge_all_is_atomic() {
+        let dir = TempDir::new().unwrap();
+        let roomy = quota(dir.path(), Some("1Ki"), None);
+        let tight = quota(dir.path(), Some("10"), None);
+        let quotas = [roomy.clone(), tight];
+
+        assert!(charge_all(&quotas, 20, 0).is_err());
+        assert_eq!(roomy.usage.lock().unwrap().unwrap().bytes, 0);
+        assert!(charge_all(&quotas, 10, 0).is_ok());
+        assert_eq!(roomy.usage.lock().unwrap().unwrap().bytes, 10);
+    }
+
+    #[test]
+    fn test_nested_roots_are_counted_once() {
+        let dir = TempDir::new().unwrap();
+        std::fs::create_dir(dir.path().join("sub")).unwrap();
+        std::fs::write(dir.path().join("sub/a.txt"), [0u8; 7]).unwrap();
+        let quota = DiskQuota::new(
+            "component",
+            [dir.path().join("sub"), dir.path().to_path_buf()],
+            None,
+        )
+        .unwrap();
+
+        assert!(!quota.is_limited());
+        let usage = quota.usage();
+        assert_eq!(usage.paths.len(), 1);
+        assert_eq!((usage.bytes, usage.files), (7, 2));
+    }
+}
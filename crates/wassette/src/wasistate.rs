@@ -3,13 +3,51 @@
 
 use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
-
-use policy::{AccessType, PolicyDocument};
+use std::sync::Arc;
+use std::time::Duration;
+
+use ipnet::IpNet;
+use policy::{
+    AccessType, NetworkCidrPermission, NetworkInjectPermission, NetworkPermission,
+    NetworkSocketPermission, PolicyDocument,
+};
 use wasmtime::component::ResourceTable;
 use wasmtime_wasi::{WasiCtx, WasiCtxBuilder, WasiCtxView};
 use wasmtime_wasi_config::WasiConfigVariables;
 use wasmtime_wasi_http::{WasiHttpCtx, WasiHttpView};
 
+use crate::audit::NetworkAudit;
+use crate::component_storage::ComponentStorage;
+use crate::http::{host_rule_strings, HeaderInjection, OutboundLimits};
+use crate::memory_mount::MemoryDir;
+use crate::network::{
+    parse_cidr, parse_socket_rule, socket_addr_check, SocketProtocol, SocketRule,
+};
+use crate::stdio::{CapturedOutputPipe, MAX_CAPTURED_OUTPUT_BYTES};
+use crate::storage_guard::{
+    check_reserved_path, split_storage_pattern, storage_host_path, AllowedPath, DeniedPath,
+    StorageGuard,
+};
+use crate::storage_quota::DiskQuota;
+
+/// Amount of wasmtime fuel granted to a single call for each core of CPU limit.
+///
+/// One unit of fuel roughly corresponds to one executed wasm instruction, so a
+/// limit of `1` core allows on the order of a few seconds of pure guest compute.
+/// The budget is a total for the whole call, not a share of a core over time:
+/// time spent waiting on the host doesn't use it up, but a call that keeps
+/// computing runs out no matter how slowly it goes.
+pub(crate) const FUEL_PER_CPU_CORE: u64 = 10_000_000_000;
+
+/// How much fuel a guest may consume before yielding back to the async executor.
+pub(crate) const FUEL_ASYNC_YIELD_INTERVAL: u64 = 10_000_000;
+
+/// Guest path of the component's persistent data directory
+pub(crate) const DATA_GUEST_PATH: &str = "/data";
+
+/// Guest path of the per-call scratch directory
+pub(crate) const SCRATCH_GUEST_PATH: &str = "/tmp";
+
 /// Custom resource limiter that stores the limits
 #[derive(Clone)]
 pub struct CustomResourceLimiter {
@@ -49,6 +87,32 @@ pub struct WasiState {
     pub http: wasmtime_wasi_http::WasiHttpCtx,
     pub wasi_config_vars: WasiConfigVariables,
     pub resource_limiter: Option<CustomResourceLimiter>,
+    pub cpu_limit: Option<f64>,
+    pub call_timeout: Option<Duration>,
+    pub stdout: CapturedOutputPipe,
+    pub stderr: CapturedOutputPipe,
+    pub expose_output: bool,
+    pub storage_guard: StorageGuard,
+    /// The per-call scratch directory, deleted when the state is dropped after the call
+    pub scratch_dir: Option<tempfile::TempDir>,
+}
+
+impl WasiState {
+    /// Returns the fuel budget for a single call based on the configured CPU limit.
+    ///
+    /// Components without a CPU limit get an effectively unlimited budget.
+    pub fn fuel_budget(&self) -> u64 {
+        fuel_for_cpu_limit(self.cpu_limit)
+    }
+}
+
+/// Converts a CPU limit in cores into a per-call instruction budget
+pub(crate) fn fuel_for_cpu_limit(cpu_limit: Option<f64>) -> u64 {
+    match cpu_limit {
+        // Float to int casts saturate, so very large limits clamp to `u64::MAX`
+        Some(cores) => ((cores * FUEL_PER_CPU_CORE as f64) as u64).max(1),
+        None => u64::MAX,
+    }
 }
 
 impl wasmtime_wasi::WasiView for WasiState {
@@ -71,31 +135,106 @@ impl WasiHttpView for WasiState {
 }
 
 impl WasiStateTemplate {
-    /// Creates a new `WasiState` from the template.
+    /// Creates a new `WasiState` from the template, with SSRF protection enabled.
     pub fn build(&self) -> anyhow::Result<WasiState> {
+        self.build_with_ssrf_protection(true)
+    }
+
+    /// Creates a new `WasiState` from the template.
+    ///
+    /// With `ssrf_protection`, raw sockets can only reach loopback, private and
+    /// link-local addresses that the policy grants by address rather than by name.
+    pub fn build_with_ssrf_protection(&self, ssrf_protection: bool) -> anyhow::Result<WasiState> {
+        self.build_audited(ssrf_protection, None)
+    }
+
+    /// Creates a new `WasiState` that records the guest's socket address checks to `audit`.
+    pub(crate) fn build_audited(
+        &self,
+        ssrf_protection: bool,
+        audit: Option<NetworkAudit>,
+    ) -> anyhow::Result<WasiState> {
         let mut ctx_builder = WasiCtxBuilder::new();
+        // Guest output is captured per call rather than inherited, so it can never
+        // interleave with the host's own stdio (e.g. the stdio MCP transport).
+        let stdout = CapturedOutputPipe::new(MAX_CAPTURED_OUTPUT_BYTES);
+        let stderr = CapturedOutputPipe::new(MAX_CAPTURED_OUTPUT_BYTES);
         if self.allow_stdout {
-            ctx_builder.inherit_stdout();
+            ctx_builder.stdout(stdout.clone());
         }
         if self.allow_stderr {
-            ctx_builder.inherit_stderr();
+            ctx_builder.stderr(stderr.clone());
         }
-        ctx_builder.inherit_args();
+        // The host command line can carry secrets (e.g. `serve --env KEY=value`),
+        // so it is only forwarded when the policy explicitly opts in.
         if self.allow_args {
             ctx_builder.inherit_args();
         }
-        // Note(mossaka): removed ctx_builder.inherit_network() to implement deny-by-default network policy
-        // For HTTP requests to work, we need to allow TCP and DNS lookups when there are network permissions
-        // But HTTP-level filtering happens in WassetteWasiState::send_request
-        if self.network_perms.allow_tcp || !self.allowed_hosts.is_empty() {
-            ctx_builder.allow_tcp(true);
-            ctx_builder.allow_ip_name_lookup(true);
-        } else {
-            ctx_builder.allow_tcp(false);
-            ctx_builder.allow_ip_name_lookup(false);
-        }
+        ctx_builder.args(&self.args);
+        // Raw sockets are only enabled for socket and CIDR grants. HTTP host grants are
+        // served by wasi:http on the host side and never need guest sockets.
+        ctx_builder.allow_tcp(self.network_perms.allow_tcp);
         ctx_builder.allow_udp(self.network_perms.allow_udp);
+        ctx_builder.allow_ip_name_lookup(self.network_perms.allow_ip_name_lookup);
+        if self.network_perms.allow_tcp || self.network_perms.allow_udp {
+            let check = socket_addr_check(
+                self.allowed_cidrs.clone().into(),
+                self.denied_cidrs.clone().into(),
+                self.allowed_sockets.clone().into(),
+                self.denied_sockets.clone().into(),
+                ssrf_protection,
+            );
+            match audit {
+                Some(audit) => {
+                    ctx_builder.socket_addr_check(move |addr, addr_use| {
+                        let allowed = check(addr, addr_use);
+                        let audit = audit.clone();
+                        Box::pin(async move {
+                            let allowed = allowed.await;
+                            audit.socket(addr, addr_use, allowed);
+                            allowed
+                        })
+                    });
+                }
+                None => {
+                    ctx_builder.socket_addr_check(check);
+                }
+            }
+        }
+        let mut preopened_dirs = Vec::new();
+        let mut memory_mounts = Vec::new();
         for preopened_dir in &self.preopened_dirs {
+            match &preopened_dir.memory {
+                Some(memory) => {
+                    let memory_fs = memory
+                        .materialize(&preopened_dir.guest_path, preopened_dir.is_writable())?;
+                    memory_mounts.push((preopened_dir.guest_path.clone(), memory_fs));
+                }
+                None => preopened_dirs.push(preopened_dir.clone()),
+            }
+        }
+        let mut allowed_paths = self.allowed_paths.clone();
+        if let Some(data_dir) = &self.data_dir {
+            std::fs::create_dir_all(data_dir)?;
+            preopened_dirs.push(PreopenedDir::read_write(data_dir, DATA_GUEST_PATH));
+            allowed_paths.push(AllowedPath::read_write(data_dir));
+        }
+        let scratch_dir = match &self.scratch_root {
+            Some(scratch_root) => {
+                std::fs::create_dir_all(scratch_root)?;
+                let scratch_dir = tempfile::Builder::new()
+                    .prefix("call-")
+                    .tempdir_in(scratch_root)?;
+                preopened_dirs.push(PreopenedDir::read_write(
+                    scratch_dir.path(),
+                    SCRATCH_GUEST_PATH,
+                ));
+                allowed_paths.push(AllowedPath::read_write(scratch_dir.path()));
+                Some(scratch_dir)
+            }
+            None => None,
+        };
+        for preopened_dir in &preopened_dirs {
             ctx_builder.preopened_dir(
                 preopened_dir.host_path.as_path(),
                 preopened_dir.guest_path.as_str(),
@@ -104,9 +243,9 @@ impl WasiStateTemplate {
             )?;
         }
 
-        // Inject forwarded config variables as real WASI environment variables so that
+        // Inject forwarded variables as real WASI environment variables so that
         // component code using std::env::var can observe them.
-        for (k, v) in &self.config_vars {
+        for (k, v) in self.env_vars.as_ref().unwrap_or(&self.config_vars) {
             ctx_builder.env(k, v);
         }
 
@@ -119,17 +258,54 @@ impl WasiStateTemplate {
                 .store_limits
                 .as_ref()
                 .map(|limits| CustomResourceLimiter::new(limits.clone())),
+            cpu_limit: self.cpu_limit,
+            call_timeout: self.call_timeout,
+            stdout,
+            stderr,
+            expose_output: self.expose_output,
+            storage_guard: StorageGuard::new(
+                self.denied_paths.clone(),
+                allowed_paths,
+                self.disk_quotas.clone(),
+                preopened_dirs
+                    .into_iter()
+                    .map(|preopened_dir| (preopened_dir.guest_path, preopened_dir.host_path)),
+            )
+            .with_memory_mounts(memory_mounts),
+            scratch_dir,
         })
     }
 }
 
 /// A struct that presents the arguments passed to `wasmtime_wasi::WasiCtxBuilder::preopened_dir`
-#[derive(Clone)]
+#[derive(Debug, Clone)]
 pub struct PreopenedDir {
+    /// Host directory, empty for a `mem://` directory
     pub host_path: PathBuf,
     pub guest_path: String,
     pub dir_perms: wasmtime_wasi::DirPerms,
     pub file_perms: wasmtime_wasi::FilePerms,
+    /// Contents of a `mem://` directory, copied into memory for each call
+    pub memory: Option<Arc<MemoryDir>>,
+}
+
+impl PreopenedDir {
+    /// Whether the guest can create or change anything in the directory
+    fn is_writable(&self) -> bool {
+        self.file_perms.contains(wasmtime_wasi::FilePerms::WRITE)
+            || self.dir_perms.contains(wasmtime_wasi::DirPerms::MUTATE)
+    }
+
+    /// A directory the guest can read and change everything in
+    fn read_write(host_path: &Path, guest_path: &str) -> Self {
+        Self {
+            host_path: host_path.to_path_buf(),
+            guest_path: guest_path.to_string(),
+            dir_perms: wasmtime_wasi::DirPerms::all(),
+            file_perms: wasmtime_wasi::FilePerms::all(),
+            memory: None,
+        }
+    }
 }
 
 /// A struct that presents the network permissions passed to wasmtime_wasi::WasiContextBuilder
@@ -144,24 +320,60 @@ pub struct NetworkPermissions {
 /// this includes the wasmtime_wasi, wasmtime_wasi_config and wasmtime_wasi_http states
 #[derive(Clone)]
 pub struct WasiStateTemplate {
-    /// Whether to allow stdout access
+    /// Whether to capture the guest's stdout
     pub allow_stdout: bool,
-    /// Whether to allow stderr access
+    /// Whether to capture the guest's stderr
     pub allow_stderr: bool,
-    /// Whether to allow command line arguments access
+    /// Whether captured stdout/stderr is returned to the MCP client
+    pub expose_output: bool,
+    /// Whether to forward the host process's command line arguments
     pub allow_args: bool,
+    /// Static arguments declared by the policy, appended after any forwarded ones
+    pub args: Vec<String>,
     /// Network permissions configuration
     pub network_perms: NetworkPermissions,
     /// Configuration variables for wasmtime_wasi_config
     pub config_vars: HashMap<String, String>,
+    /// Environment variables for the guest, or `None` to set all of `config_vars`
+    pub env_vars: Option<HashMap<String, String>>,
     /// Preopened directories for filesystem access
     pub preopened_dirs: Vec<PreopenedDir>,
     /// Allowed network hosts for HTTP requests
     pub allowed_hosts: HashSet<String>,
+    /// Allowed network ranges, checked against the resolved peer address
+    pub allowed_cidrs: Vec<IpNet>,
+    /// Denied network hosts, taking precedence over `allowed_hosts`
+    pub denied_hosts: HashSet<String>,
+    /// Denied network ranges, taking precedence over both allow lists
+    pub denied_cidrs: Vec<IpNet>,
+    /// Raw socket destinations the component may connect or bind to
+    pub allowed_sockets: Vec<SocketRule>,
+    /// Raw socket destinations that are blocked even when otherwise granted
+    pub denied_sockets: Vec<SocketRule>,
+    /// Headers filled from secrets and added host-side to outbound HTTP requests
+    pub header_injections: Vec<HeaderInjection>,
+    /// Paths inside preopened directories that the component may not access
+    pub denied_paths: Vec<DeniedPath>,
+    /// Granted paths and patterns, enforced inside preopens when any of them is a glob
+    pub allowed_paths: Vec<AllowedPath>,
+    /// Host directory mounted at `/data` that persists across calls
+    pub data_dir: Option<PathBuf>,
+    /// Host directory in which a scratch directory is created for each call and
+    /// mounted at `/tmp`
+    pub scratch_root: Option<PathBuf>,
+    /// Usage of each writable grant and of the component as a whole, limited where
+    /// the policy sets a quota
+    pub disk_quotas: Vec<DiskQuota>,
     /// Memory limit in bytes for the component
     pub memory_limit: Option<u64>,
     /// Store limits for wasmtime (built from memory_limit)
     pub store_limits: Option<wasmtime::StoreLimits>,
+    /// CPU limit in cores for the component, enforced via wasmtime fuel
+    pub cpu_limit: Option<f64>,
+    /// Per-call timeout override for the component, falls back to the server default
+    pub call_timeout: Option<Duration>,
+    /// Timeouts, body sizes and request count allowed for outbound HTTP requests
+    pub outbound_limits: OutboundLimits,
 }
 
 impl Default for WasiStateTemplate {
@@ -169,17 +381,114 @@ impl Default for WasiStateTemplate {
         Self {
             allow_stdout: true,
             allow_stderr: true,
-            allow_args: true,
+            expose_output: false,
+            allow_args: false,
+            args: Vec::new(),
             network_perms: NetworkPermissions::default(),
             config_vars: HashMap::new(),
+            env_vars: None,
             preopened_dirs: Vec::new(),
             allowed_hosts: HashSet::new(),
+            allowed_cidrs: Vec::new(),
+            denied_hosts: HashSet::new(),
+            denied_cidrs: Vec::new(),
+            allowed_sockets: Vec::new(),
+            denied_sockets: Vec::new(),
+            header_injections: Vec::new(),
+            denied_paths: Vec::new(),
+            allowed_paths: Vec::new(),
+            data_dir: None,
+            scratch_root: None,
+            disk_quotas: Vec::new(),
             memory_limit: None,
             store_limits: None,
+            cpu_limit: None,
+            call_timeout: None,
+            outbound_limits: OutboundLimits::default(),
         }
     }
 }
 
+impl WasiStateTemplate {
+    /// Sets up the `/data` and `/tmp` directories the policy asks for, below the
+    /// component's directories in `storage`
+    pub(crate) fn with_component_dirs(
+        mut self,
+        policy: &PolicyDocument,
+        storage: &ComponentStorage,
+        component_id: &str,
+    ) -> anyhow::Result<Self> {
+        let Some(directories) = &policy.permissions.directories else {
+            return Ok(self);
+        };
+        let data = directories.data.unwrap_or(false);
+        let scratch = directories.scratch.unwrap_or(false);
+        for (enabled, guest_path) in [(data, DATA_GUEST_PATH), (scratch, SCRATCH_GUEST_PATH)] {
+            if enabled
+                && self
+                    .preopened_dirs
+                    .iter()
+                    .any(|preopened_dir| preopened_dir.guest_path == guest_path)
+            {
+                anyhow::bail!("A storage grant is mounted at {guest_path}, which is reserved");
+            }
+        }
+        self.data_dir = data.then(|| storage.data_dir(component_id));
+        self.scratch_root = scratch.then(|| storage.scratch_dir(component_id));
+        Ok(self)
+    }
+
+    /// Sets up usage tracking for every writable storage grant and for the
+    /// component's writable directories together, with the quotas from the policy.
+    /// Call after [`Self::with_component_dirs`] so `/data` and `/tmp` are counted.
+    pub(crate) fn with_disk_quotas(
+        mut self,
+        policy: &PolicyDocument,
+        plugin_dir: &Path,
+    ) -> anyhow::Result<Self> {
+        let mut disk_quotas = Vec::new();
+        let allow = policy
+            .permissions
+            .storage
+            .as_ref()
+            .and_then(|storage| storage.allow.as_deref())
+            .unwrap_or_default();
+        for storage_permission in allow {
+            let Some(uri) = storage_permission.uri.strip_prefix("fs://") else {
+                continue;
+            };
+            if !storage_permission.access.contains(&AccessType::Write) {
+                continue;
+            }
+            let (literal, _) = split_storage_pattern(uri)?;
+            disk_quotas.push(DiskQuota::new(
+                storage_permission.uri.clone(),
+                [storage_host_path(&literal, plugin_dir)?],
+                storage_permission.quota.as_ref(),
+            )?);
+        }
+
+        let roots: Vec<PathBuf> = self
+            .preopened_dirs
+            .iter()
+            .filter(|preopened_dir| preopened_dir.memory.is_none() && preopened_dir.is_writable())
+            .map(|preopened_dir| preopened_dir.host_path.clone())
+            .chain(self.data_dir.clone())
+            .chain(self.scratch_root.clone())
+            .collect();
+        let component_quota = policy
+            .permissions
+            .resources
+            .as_ref()
+            .and_then(|resources| resources.storage.as_ref());
+        if !roots.is_empty() || component_quota.is_some() {
+            disk_quotas.push(DiskQuota::new("component", roots, component_quota)?);
+        }
+        self.disk_quotas = disk_quotas;
+        Ok(self)
+    }
+}
+
 /// Maps the policy-mcp capabilities to the wasi state template
 pub fn create_wasi_state_template_from_policy(
     policy: &PolicyDocument,
@@ -187,10 +496,26 @@ pub fn create_wasi_state_template_from_policy(
     environment_vars: &HashMap<String, String>,
     secrets: Option<&HashMap<String, String>>,
 ) -> anyhow::Result<WasiStateTemplate> {
-    let env_vars = extract_env_vars(policy, environment_vars, secrets)?;
-    let network_perms = extract_network_perms(policy);
+    let header_injections = extract_header_injections(policy)?;
+    // Secrets used for credential injection stay on the host side
+    let guest_secrets = secrets.map(|secrets| {
+        let mut secrets = secrets.clone();
+        for injection in &header_injections {
+            secrets.remove(injection.secret());
+        }
+        secrets
+    });
+    let guest_vars = extract_env_vars(policy, environment_vars, guest_secrets.as_ref())?;
+    let allowed_sockets = extract_allowed_sockets(policy)?;
+    let denied_sockets = extract_denied_sockets(policy)?;
     let preopened_dirs = extract_storage_permissions(policy, plugin_dir)?;
-    let allowed_hosts = extract_allowed_hosts(policy);
+    let allowed_hosts = extract_allowed_hosts(policy)?;
+    let allowed_cidrs = extract_allowed_cidrs(policy)?;
+    let denied_hosts = extract_denied_hosts(policy)?;
+    let denied_cidrs = extract_denied_cidrs(policy)?;
+    let network_perms = extract_network_perms(policy)?;
+    let denied_paths = extract_denied_paths(policy, plugin_dir)?;
+    let allowed_paths = extract_allowed_paths(policy, plugin_dir)?;
     let memory_limit = extract_memory_limit(policy)?;
     let store_limits = memory_limit
         .map(|limit| -> anyhow::Result<wasmtime::StoreLimits> {
@@ -202,23 +527,60 @@ pub fn create_wasi_state_template_from_policy(
                 .build())
         })
         .transpose()?;
+    let cpu_limit = extract_cpu_limit(policy)?;
+    let call_timeout = extract_call_timeout(policy)?;
+    let outbound_limits = extract_outbound_limits(policy)?;
+    let process = policy.permissions.process.as_ref();
+    let expose_output = process
+        .and_then(|process| process.expose_output)
+        .unwrap_or(false);
+    let allow_args = process
+        .and_then(|process| process.inherit_args)
+        .unwrap_or(false);
+    let args = process
+        .and_then(|process| process.args.clone())
+        .unwrap_or_default();
 
     Ok(WasiStateTemplate {
         network_perms,
-        config_vars: env_vars,
+        config_vars: guest_vars.config,
+        env_vars: Some(guest_vars.env),
         preopened_dirs,
         allowed_hosts,
+        allowed_cidrs,
+        denied_hosts,
+        denied_cidrs,
+        allowed_sockets,
+        denied_sockets,
+        header_injections,
+        denied_paths,
+        allowed_paths,
         memory_limit,
         store_limits,
+        cpu_limit,
+        call_timeout,
+        outbound_limits,
+        expose_output,
+        allow_args,
+        args,
         ..Default::default()
     })
 }
 
+/// Variables passed to a component, split by how the component reads them
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct GuestVariables {
+    /// Set as WASI environment variables
+    pub env: HashMap<String, String>,
+    /// Readable through `wasi:config/store`
+    pub config: HashMap<String, String>,
+}
+
 pub(crate) fn extract_env_vars(
     policy: &PolicyDocument,
     environment_vars: &HashMap<String, String>,
     secrets: Option<&HashMap<String, String>>,
-) -> anyhow::Result<HashMap<String, String>> {
+) -> anyhow::Result<GuestVariables> {
     let mut env_vars = HashMap::new();
 
     // Add secrets first (lowest precedence)
@@ -241,68 +603,333 @@ pub(crate) fn extract_env_vars(
         }
     }
 
-    Ok(env_vars)
+    let env_perms = policy.permissions.environment.as_ref();
+    if !env_perms
+        .and_then(|env| env.separate_config)
+        .unwrap_or(false)
+    {
+        return Ok(GuestVariables {
+            env: env_vars.clone(),
+            config: env_vars,
+        });
+    }
+
+    // Only allow-listed host variables reach the environment, looked up on the host
+    // so a secret sharing a key with an allow entry stays in wasi:config
+    let env = env_perms
+        .and_then(|env| env.allow.as_deref())
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|env_allow| {
+            environment_vars
+                .get(&env_allow.key)
+                .map(|value| (env_allow.key.clone(), value.clone()))
+        })
+        .collect();
+    Ok(GuestVariables {
+        env,
+        config: env_vars,
+    })
 }
 
-pub(crate) fn extract_network_perms(policy: &PolicyDocument) -> NetworkPermissions {
-    if let Some(network_perms) = &policy.permissions.network {
-        let has_network_perms =
-            network_perms.allow.is_some() && !network_perms.allow.as_ref().unwrap().is_empty();
-        NetworkPermissions {
-            allow_tcp: has_network_perms,
-            allow_udp: has_network_perms,
-            allow_ip_name_lookup: has_network_perms,
-        }
-    } else {
-        NetworkPermissions::default()
-    }
+/// Enable only the socket kinds that the policy's CIDR ranges and socket rules can use
+pub(crate) fn extract_network_perms(policy: &PolicyDocument) -> anyhow::Result<NetworkPermissions> {
+    let allowed_cidrs = extract_allowed_cidrs(policy)?;
+    let allowed_sockets = extract_allowed_sockets(policy)?;
+    let grants = |protocol| {
+        !allowed_cidrs.is_empty()
+            || allowed_sockets
+                .iter()
+                .any(|rule| rule.protocol() == protocol)
+    };
+    let allow_tcp = grants(SocketProtocol::Tcp);
+    let allow_udp = grants(SocketProtocol::Udp);
+    Ok(NetworkPermissions {
+        allow_tcp,
+        allow_udp,
+        allow_ip_name_lookup: allow_tcp || allow_udp,
+    })
+}
+
+/// Extract allowed CIDR ranges from the policy document
+pub(crate) fn extract_allowed_cidrs(policy: &PolicyDocument) -> anyhow::Result<Vec<IpNet>> {
+    let network = policy.permissions.network.as_ref();
+    network_cidrs(network.and_then(|network| network.allow.as_deref()))
+}
+
+/// Extract denied CIDR ranges from the policy document
+pub(crate) fn extract_denied_cidrs(policy: &PolicyDocument) -> anyhow::Result<Vec<IpNet>> {
+    let network = policy.permissions.network.as_ref();
+    network_cidrs(network.and_then(|network| network.deny.as_deref()))
 }
 
 /// Extract allowed hosts from the policy document
-pub(crate) fn extract_allowed_hosts(policy: &PolicyDocument) -> HashSet<String> {
-    let mut allowed_hosts = HashSet::new();
-
-    if let Some(network_perms) = &policy.permissions.network {
-        if let Some(allow_list) = &network_perms.allow {
-            for allow_entry in allow_list {
-                // The policy uses serde_json::Value, so we need to extract the host field
-                if let Ok(json_value) = serde_json::to_value(allow_entry) {
-                    if let Some(host) = json_value.get("host").and_then(|h| h.as_str()) {
-                        allowed_hosts.insert(host.to_string());
-                    }
-                }
+pub(crate) fn extract_allowed_hosts(policy: &PolicyDocument) -> anyhow::Result<HashSet<String>> {
+    let network = policy.permissions.network.as_ref();
+    network_hosts(network.and_then(|network| network.allow.as_deref()))
+}
+
+/// Extract denied hosts from the policy document
+pub(crate) fn extract_denied_hosts(policy: &PolicyDocument) -> anyhow::Result<HashSet<String>> {
+    let network = policy.permissions.network.as_ref();
+    network_hosts(network.and_then(|network| network.deny.as_deref()))
+}
+
+/// Extract the credential headers to inject into outbound requests
+pub(crate) fn extract_header_injections(
+    policy: &PolicyDocument,
+) -> anyhow::Result<Vec<HeaderInjection>> {
+    let network = policy.permissions.network.as_ref();
+    network
+        .and_then(|network| network.allow.as_deref())
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|entry| match entry {
+            NetworkPermission::Inject(NetworkInjectPermission { inject }) => {
+                Some(HeaderInjection::from_policy(inject))
             }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Extract allowed raw socket destinations from the policy document
+pub(crate) fn extract_allowed_sockets(policy: &PolicyDocument) -> anyhow::Result<Vec<SocketRule>> {
+    let network = policy.permissions.network.as_ref();
+    network_sockets(network.and_then(|network| network.allow.as_deref()))
+}
+
+/// Extract denied raw socket destinations from the policy document
+pub(crate) fn extract_denied_sockets(policy: &PolicyDocument) -> anyhow::Result<Vec<SocketRule>> {
+    let network = policy.permissions.network.as_ref();
+    network_sockets(network.and_then(|network| network.deny.as_deref()))
+}
+
+fn network_sockets(entries: Option<&[NetworkPermission]>) -> anyhow::Result<Vec<SocketRule>> {
+    entries
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|entry| match entry {
+            NetworkPermission::Socket(NetworkSocketPermission { socket }) => {
+                Some(parse_socket_rule(socket))
+            }
+            NetworkPermission::Host(_)
+            | NetworkPermission::Cidr(_)
+            | NetworkPermission::Inject(_) => None,
+        })
+        .collect()
+}
+
+fn network_cidrs(entries: Option<&[NetworkPermission]>) -> anyhow::Result<Vec<IpNet>> {
+    entries
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|entry| match entry {
+            NetworkPermission::Cidr(NetworkCidrPermission { cidr }) => Some(parse_cidr(cidr)),
+            NetworkPermission::Host(_)
+            | NetworkPermission::Socket(_)
+            | NetworkPermission::Inject(_) => None,
+        })
+        .collect()
+}
+
+/// Host entries scoped to several methods, ports or paths become one rule string each
+fn network_hosts(entries: Option<&[NetworkPermission]>) -> anyhow::Result<HashSet<String>> {
+    let mut hosts = HashSet::new();
+    for entry in entries.unwrap_or_default() {
+        match entry {
+            NetworkPermission::Host(host) => hosts.extend(host_rule_strings(host)?),
+            NetworkPermission::Cidr(_)
+            | NetworkPermission::Socket(_)
+            | NetworkPermission::Inject(_) => {}
         }
     }
-
-    allowed_hosts
+    Ok(hosts)
 }
 
+/// Extract the preopened directories for the storage grants in the policy document
+///
+/// A glob grant such as `fs://work/*/docs/**` preopens its literal prefix, `work`,
+/// and the storage guard limits access below it to paths matching the pattern.
+/// The component sees a directory at the grant's `mount`, or else at the path in
+/// the URI with `~` expanded. Grants sharing a directory share one preopen with
+/// the combined permissions.
+///
+/// A `mem://name` grant becomes a virtual directory at its `mount`, or `/name`,
+/// holding the grant's seed.
 pub(crate) fn extract_storage_permissions(
     policy: &PolicyDocument,
     plugin_dir: &Path,
 ) -> anyhow::Result<Vec<PreopenedDir>> {
-    let mut preopened_dirs = Vec::new();
+    let denied_paths = extract_denied_paths(policy, plugin_dir)?;
+    let mut preopened_dirs: Vec<PreopenedDir> = Vec::new();
+    let mut memory_dirs: Vec<PreopenedDir> = Vec::new();
     if let Some(storage) = &policy.permissions.storage {
         if let Some(allow) = &storage.allow {
             for storage_permission in allow {
-                if storage_permission.uri.starts_with("fs://") {
-                    let uri = storage_permission.uri.strip_prefix("fs://").unwrap();
-                    let path = Path::new(uri);
+                if let Some(name) = storage_permission.uri.strip_prefix("mem://") {
+                    let guest_path = storage_permission
+                        .mount
+                        .clone()
+                        .unwrap_or_else(|| format!("/{name}"));
+                    if memory_dirs
+                        .iter()
+                        .any(|memory_dir| memory_dir.guest_path == guest_path)
+                    {
+                        anyhow::bail!("Two mem:// grants are mounted at {guest_path}");
+                    }
                     let (file_perms, dir_perms) = calculate_permissions(&storage_permission.access);
-                    let guest_path = path.to_string_lossy().to_string();
-                    let host_path = plugin_dir.join(path);
-                    preopened_dirs.push(PreopenedDir {
-                        host_path,
+                    memory_dirs.push(PreopenedDir {
+                        host_path: PathBuf::new(),
                         guest_path,
                         dir_perms,
                         file_perms,
+                        memory: Some(Arc::new(MemoryDir::load(
+                            storage_permission.seed.as_ref(),
+                            plugin_dir,
+                        )?)),
                     });
+                    continue;
+                }
+                let Some(uri) = storage_permission.uri.strip_prefix("fs://") else {
+                    continue;
+                };
+                let (literal, _) = split_storage_pattern(uri)?;
+                let host_path = storage_host_path(&literal, plugin_dir)?;
+                check_reserved_path(&literal, &host_path, plugin_dir)?;
+                let guest_path = match &storage_permission.mount {
+                    Some(mount) => mount.clone(),
+                    None if literal.starts_with('~') => host_path.to_string_lossy().to_string(),
+                    None => literal,
+                };
+                let (file_perms, dir_perms) = calculate_permissions(&storage_permission.access);
+                if let Some(existing) = preopened_dirs
+                    .iter_mut()
+                    .find(|preopened_dir| preopened_dir.guest_path == guest_path)
+                {
+                    if existing.host_path != host_path {
+                        anyhow::bail!(
+                            "Storage grants for {} and {} are both mounted at {guest_path}",
+                            existing.host_path.display(),
+                            host_path.display()
+                        );
+                    }
+                    existing.file_perms |= file_perms;
+                    existing.dir_perms |= dir_perms;
+                    continue;
                 }
+                preopened_dirs.push(PreopenedDir {
+                    host_path,
+                    guest_path,
+                    dir_perms,
+                    file_perms,
+                    memory: None,
+                });
             }
         }
     }
-    Ok(preopened_dirs)
+    if let Some(memory_dir) = memory_dirs.iter().find(|memory_dir| {
+        preopened_dirs
+            .iter()
+            .any(|preopened_dir| preopened_dir.guest_path == memory_dir.guest_path)
+    }) {
+        anyhow::bail!(
+            "A mem:// grant and an fs:// grant are both mounted at {}",
+            memory_dir.guest_path
+        );
+    }
+    Ok(preopened_dirs
+        .into_iter()
+        .filter_map(|preopened_dir| apply_denied_paths(preopened_dir, &denied_paths))
+        .chain(memory_dirs)
+        .collect())
+}
+
+/// Extract the granted storage locations and patterns from the policy document
+pub(crate) fn extract_allowed_paths(
+    policy: &PolicyDocument,
+    plugin_dir: &Path,
+) -> anyhow::Result<Vec<AllowedPath>> {
+    let Some(allow) = policy
+        .permissions
+        .storage
+        .as_ref()
+        .and_then(|storage| storage.allow.as_ref())
+    else {
+        return Ok(Vec::new());
+    };
+
+    let mut allowed = Vec::new();
+    for storage_permission in allow {
+        let Some(uri) = storage_permission.uri.strip_prefix("fs://") else {
+            continue;
+        };
+        let (literal, pattern) = split_storage_pattern(uri)?;
+        allowed.push(AllowedPath {
+            host_path: storage_host_path(&literal, plugin_dir)?,
+            pattern,
+            read: storage_permission.access.contains(&AccessType::Read),
+            write: storage_permission.access.contains(&AccessType::Write),
+        });
+    }
+    Ok(allowed)
+}
+
+/// Extract denied storage locations from the policy document
+///
+/// A trailing `/*` or `/**` is accepted and denies the whole tree, same as the bare path.
+pub(crate) fn extract_denied_paths(
+    policy: &PolicyDocument,
+    plugin_dir: &Path,
+) -> anyhow::Result<Vec<DeniedPath>> {
+    let Some(deny) = policy
+        .permissions
+        .storage
+        .as_ref()
+        .and_then(|storage| storage.deny.as_ref())
+    else {
+        return Ok(Vec::new());
+    };
+
+    let mut denied = Vec::new();
+    for storage_permission in deny {
+        let Some(uri) = storage_permission.uri.strip_prefix("fs://") else {
+            continue;
+        };
+        let uri = uri
+            .strip_suffix("/**")
+            .or_else(|| uri.strip_suffix("/*"))
+            .unwrap_or(uri);
+        denied.push(DeniedPath {
+            host_path: storage_host_path(uri, plugin_dir)?,
+            read: storage_permission.access.contains(&AccessType::Read),
+            write: storage_permission.access.contains(&AccessType::Write),
+        });
+    }
+    Ok(denied)
+}
+
+/// Strip the permissions of a preopened directory that a deny rule covers entirely.
+///
+/// Returns `None` when reads of the whole directory are denied. Denied paths
+/// below the directory are enforced per access by the storage guard instead.
+fn apply_denied_paths(
+    mut preopened_dir: PreopenedDir,
+    denied_paths: &[DeniedPath],
+) -> Option<PreopenedDir> {
+    for denied in denied_paths {
+        if !preopened_dir.host_path.starts_with(&denied.host_path) {
+            continue;
+        }
+        if denied.read {
+            return None;
+        }
+        if denied.write {
+            preopened_dir.file_perms -= wasmtime_wasi::FilePerms::WRITE;
+            preopened_dir.dir_perms -= wasmtime_wasi::DirPerms::MUTATE;
+        }
+    }
+    Some(preopened_dir)
 }
 
 pub(crate) fn calculate_permissions(
@@ -351,11 +978,54 @@ pub(crate) fn extract_memory_limit(policy: &PolicyDocument) -> anyhow::Result<Op
     Ok(None)
 }
 
+/// Extract CPU limit (in cores) from the policy document
+pub(crate) fn extract_cpu_limit(policy: &PolicyDocument) -> anyhow::Result<Option<f64>> {
+    if let Some(resources) = &policy.permissions.resources {
+        // Check the new k8s-style limits first
+        if let Some(limits) = &resources.limits {
+            if let Some(cpu_limit) = &limits.cpu {
+                return Ok(Some(cpu_limit.to_cores()?));
+            }
+        }
+
+        // Fall back to legacy cpu field for backward compatibility
+        if let Some(legacy_cpu) = resources.cpu {
+            return Ok(Some(legacy_cpu));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Extract the per-call timeout override from the policy document
+pub(crate) fn extract_call_timeout(policy: &PolicyDocument) -> anyhow::Result<Option<Duration>> {
+    policy
+        .permissions
+        .resources
+        .as_ref()
+        .and_then(|resources| resources.timeout.as_ref())
+        .map(|timeout| timeout.to_duration())
+        .transpose()
+}
+
+/// Extract the outbound HTTP limits from the policy document
+pub(crate) fn extract_outbound_limits(policy: &PolicyDocument) -> anyhow::Result<OutboundLimits> {
+    policy
+        .permissions
+        .resources
+        .as_ref()
+        .and_then(|resources| resources.network.as_ref())
+        .map(OutboundLimits::from_policy)
+        .transpose()
+        .map(Option::unwrap_or_default)
+}
+
 #[cfg(test)]
 mod tests {
     use policy::{AccessType, PolicyParser};
     use proptest::prelude::*;
     use tempfile::TempDir;
+    use wasmtime_wasi::p2::bindings::filesystem::types::{DescriptorFlags, OpenFlags};
 
     use super::*;
 
@@ -489,7 +1159,7 @@ permissions:
             let mut env_vars = HashMap::new();
             env_vars.insert("TEST_VAR".to_string(), "isolated_value".to_string());
 
-            let extracted_vars = extract_env_vars(&policy, &env_vars, None).unwrap();
+            let extracted_vars = extract_env_vars(&policy, &env_vars, None).unwrap().config;
             assert_eq!(
                 extracted_vars.get("TEST_VAR"),
                 Some(&"isolated_value".to_string())
@@ -504,7 +1174,7 @@ permissions:
 
         temp_env::with_vars(vec![("TEST_VAR", None::<&str>)], || {
             let env_vars = HashMap::new(); // Empty environment
-            let extracted_vars = extract_env_vars(&policy, &env_vars, None).unwrap();
+            let extracted_vars = extract_env_vars(&policy, &env_vars, None).unwrap().config;
             assert!(!extracted_vars.contains_key("TEST_VAR"));
         });
     }
@@ -513,7 +1183,7 @@ permissions:
     fn test_extract_environment_variables_no_permissions() {
         let policy = create_zero_permission_policy();
         let env_vars = HashMap::new();
-        let extracted_vars = extract_env_vars(&policy, &env_vars, None).unwrap();
+        let extracted_vars = extract_env_vars(&policy, &env_vars, None).unwrap().config;
         assert!(extracted_vars.is_empty());
     }
 
@@ -528,30 +1198,565 @@ permissions:
 "#;
         let policy = PolicyParser::parse_str(yaml_content).unwrap();
         let env_vars = HashMap::new();
-        let extracted_vars = extract_env_vars(&policy, &env_vars, None).unwrap();
+        let extracted_vars = extract_env_vars(&policy, &env_vars, None).unwrap().config;
         assert!(extracted_vars.is_empty());
     }
 
     #[test]
-    fn test_extract_network_permissions_with_allow() {
+    fn test_extract_network_permissions_http_only() {
+        // HTTP host grants go through wasi:http and leave raw sockets disabled
         let policy = create_test_policy();
-        let network_perms = extract_network_perms(&policy);
+        let network_perms = extract_network_perms(&policy).unwrap();
+
+        assert!(!network_perms.allow_tcp);
+        assert!(!network_perms.allow_udp);
+        assert!(!network_perms.allow_ip_name_lookup);
+    }
 
+    #[test]
+    fn test_extract_network_permissions_with_sockets() {
+        let yaml_content = r#"
+version: "1.0"
+description: "Policy with a raw TCP socket grant"
+permissions:
+  network:
+    allow:
+      - host: "api.example.com"
+      - socket: "tcp://db.internal:5432"
+    deny:
+      - socket: "tcp://db.internal:22"
+"#;
+        let policy = PolicyParser::parse_str(yaml_content).unwrap();
+        let network_perms = extract_network_perms(&policy).unwrap();
         assert!(network_perms.allow_tcp);
-        assert!(network_perms.allow_udp);
+        assert!(!network_perms.allow_udp);
         assert!(network_perms.allow_ip_name_lookup);
+
+        assert_eq!(
+            extract_allowed_sockets(&policy).unwrap(),
+            vec![parse_socket_rule("tcp://db.internal:5432").unwrap()]
+        );
+        assert_eq!(extract_denied_sockets(&policy).unwrap().len(), 1);
+        assert_eq!(
+            extract_allowed_hosts(&policy).unwrap(),
+            HashSet::from(["api.example.com".to_string()])
+        );
+
+        let temp_dir = TempDir::new().unwrap();
+        let template =
+            create_wasi_state_template_from_policy(&policy, temp_dir.path(), &HashMap::new(), None)
+                .unwrap();
+        assert_eq!(template.allowed_sockets.len(), 1);
+        assert!(template.build().is_ok());
+
+        // CIDR grants cover both protocols
+        let yaml_content = r#"
+version: "1.0"
+description: "Policy with a CIDR grant"
+permissions:
+  network:
+    allow:
+      - cidr: "10.0.0.0/8"
+"#;
+        let policy = PolicyParser::parse_str(yaml_content).unwrap();
+        let network_perms = extract_network_perms(&policy).unwrap();
+        assert!(network_perms.allow_tcp);
+        assert!(network_perms.allow_udp);
     }
 
     #[test]
     fn test_extract_network_permissions_no_permissions() {
         let policy = create_zero_permission_policy();
-        let network_perms = extract_network_perms(&policy);
+        let network_perms = extract_network_perms(&policy).unwrap();
 
         assert!(!network_perms.allow_tcp);
         assert!(!network_perms.allow_udp);
         assert!(!network_perms.allow_ip_name_lookup);
     }
 
+    #[test]
+    fn test_extract_allowed_cidrs() {
+        let yaml_content = r#"
+version: "1.0"
+description: "Policy with hosts and CIDR ranges"
+permissions:
+  network:
+    allow:
+      - host: "api.example.com"
+      - cidr: "10.0.0.0/8"
+      - cidr: "fd00::/8"
+"#;
+        let policy = PolicyParser::parse_str(yaml_content).unwrap();
+        let cidrs = extract_allowed_cidrs(&policy).unwrap();
+        assert_eq!(
+            cidrs,
+            vec![
+                "10.0.0.0/8".parse::<IpNet>().unwrap(),
+                "fd00::/8".parse::<IpNet>().unwrap()
+            ]
+        );
+
+        let hosts = extract_allowed_hosts(&policy).unwrap();
+        assert_eq!(hosts, HashSet::from(["api.example.com".to_string()]));
+
+        let policy = create_zero_permission_policy();
+        assert!(extract_allowed_cidrs(&policy).unwrap().is_empty());
+
+        let yaml_content = r#"
+version: "1.0"
+description: "Policy with a malformed CIDR"
+permissions:
+  network:
+    allow:
+      - cidr: "10.0.0.0/40"
+"#;
+        let policy = PolicyParser::parse_str(yaml_content).unwrap();
+        assert!(extract_allowed_cidrs(&policy).is_err());
+    }
+
+    #[test]
+    fn test_extract_denied_network_rules() {
+        let yaml_content = r#"
+version: "1.0"
+description: "Policy with network deny rules"
+permissions:
+  network:
+    allow:
+      - host: "*.internal"
+      - cidr: "10.0.0.0/8"
+    deny:
+      - host: "metadata.internal"
+      - cidr: "10.0.0.0/24"
+"#;
+        let policy = PolicyParser::parse_str(yaml_content).unwrap();
+        assert_eq!(
+            extract_denied_hosts(&policy).unwrap(),
+            HashSet::from(["metadata.internal".to_string()])
+        );
+        assert_eq!(
+            extract_denied_cidrs(&policy).unwrap(),
+            vec!["10.0.0.0/24".parse::<IpNet>().unwrap()]
+        );
+        assert_eq!(
+            extract_allowed_hosts(&policy).unwrap(),
+            HashSet::from(["*.internal".to_string()])
+        );
+
+        let policy = create_zero_permission_policy();
+        assert!(extract_denied_hosts(&policy).unwrap().is_empty());
+        assert!(extract_denied_cidrs(&policy).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_storage_deny_rules() {
+        let temp_dir = TempDir::new().unwrap();
+        let plugin_dir = temp_dir.path();
+
+        let yaml_content = r#"
+version: "1.0"
+description: "Policy with storage deny rules"
+permissions:
+  storage:
+    allow:
+      - uri: "fs://work"
+        access: ["read", "write"]
+      - uri: "fs://work/output"
+        access: ["read", "write"]
+      - uri: "fs://cache"
+        access: ["read", "write"]
+    deny:
+      - uri: "fs://work/secrets/**"
+        access: ["read", "write"]
+      - uri: "fs://work/output"
+        access: ["write"]
+      - uri: "fs://cache"
+        access: ["read"]
+"#;
+        let policy = PolicyParser::parse_str(yaml_content).unwrap();
+
+        let denied_paths = extract_denied_paths(&policy, plugin_dir).unwrap();
+        assert_eq!(denied_paths.len(), 3);
+        assert_eq!(denied_paths[0].host_path, plugin_dir.join("work/secrets"));
+        assert!(denied_paths[0].read && denied_paths[0].write);
+        assert!(!denied_paths[1].read && denied_paths[1].write);
+
+        // Denies covering a whole preopen are applied to the preopen itself, while
+        // `work/secrets` is left to the storage guard
+        let preopened_dirs = extract_storage_permissions(&policy, plugin_dir).unwrap();
+        assert_eq!(preopened_dirs.len(), 2);
+        assert_eq!(preopened_dirs[0].guest_path, "work");
+        assert_eq!(
+            preopened_dirs[0].file_perms,
+            wasmtime_wasi::FilePerms::READ | wasmtime_wasi::FilePerms::WRITE
+        );
+        assert_eq!(preopened_dirs[1].guest_path, "work/output");
+        assert_eq!(preopened_dirs[1].file_perms, wasmtime_wasi::FilePerms::READ);
+        assert_eq!(preopened_dirs[1].dir_perms, wasmtime_wasi::DirPerms::READ);
+
+        std::fs::create_dir_all(plugin_dir.join("work")).unwrap();
+        std::fs::create_dir_all(plugin_dir.join("work/output")).unwrap();
+        let template =
+            create_wasi_state_template_from_policy(&policy, plugin_dir, &HashMap::new(), None)
+                .unwrap();
+        assert_eq!(template.denied_paths, denied_paths);
+        assert!(template.build().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_component_directories() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = ComponentStorage::new(temp_dir.path(), 1).await.unwrap();
+
+        let policy = PolicyParser::parse_str(
+            r#"
+version: "1.0"
+permissions:
+  directories:
+    data: true
+    scratch: true
+"#,
+        )
+        .unwrap();
+        let template =
+            create_wasi_state_template_from_policy(&policy, temp_dir.path(), &HashMap::new(), None)
+                .unwrap()
+                .with_component_dirs(&policy, &storage, "cache-tool")
+                .unwrap();
+        assert_eq!(template.data_dir, Some(storage.data_dir("cache-tool")));
+
+        let first = template.build().unwrap();
+        let second = template.build().unwrap();
+        assert!(storage.data_dir("cache-tool").is_dir());
+        // Every call gets its own scratch directory, removed with the call's state
+        let first_scratch = first.scratch_dir.as_ref().unwrap().path().to_path_buf();
+        let second_scratch = second.scratch_dir.as_ref().unwrap().path().to_path_buf();
+        assert_ne!(first_scratch, second_scratch);
+        assert!(first_scratch.starts_with(storage.scratch_dir("cache-tool")));
+        drop(first);
+        assert!(!first_scratch.exists());
+        assert!(second_scratch.exists());
+        drop(second);
+
+        storage
+            .remove_component_artifacts("cache-tool")
+            .await
+            .unwrap();
+        assert!(!storage.data_dir("cache-tool").exists());
+        assert!(!storage.scratch_dir("cache-tool").exists());
+
+        let clashing = PolicyParser::parse_str(
+            r#"
+version: "1.0"
+permissions:
+  storage:
+    allow:
+      - uri: "fs://cache"
+        access: ["read"]
+        mount: "/data"
+  directories:
+    data: true
+"#,
+        )
+        .unwrap();
+        let result = create_wasi_state_template_from_policy(
+            &clashing,
+            temp_dir.path(),
+            &HashMap::new(),
+            None,
+        )
+        .unwrap()
+        .with_component_dirs(&clashing, &storage, "cache-tool");
+        let Err(err) = result else {
+            panic!("expected the /data mount to clash");
+        };
+        assert!(err.to_string().contains("reserved"));
+    }
+
+    #[test]
+    fn test_memory_mounts() {
+        let temp_dir = TempDir::new().unwrap();
+        let policy = PolicyParser::parse_str(
+            r#"
+version: "1.0"
+permissions:
+  storage:
+    allow:
+      - uri: "mem://fixtures"
+        access: ["read", "write"]
+        seed:
+          files:
+            input.txt: "hello"
+      - uri: "mem://empty"
+        access: ["read"]
+        mount: "/config"
+"#,
+        )
+        .unwrap();
+        let template =
+            create_wasi_state_template_from_policy(&policy, temp_dir.path(), &HashMap::new(), None)
+                .unwrap();
+        let guest_paths: Vec<&str> = template
+            .preopened_dirs
+            .iter()
+            .map(|preopened_dir| preopened_dir.guest_path.as_str())
+            .collect();
+        assert_eq!(guest_paths, ["/fixtures", "/config"]);
+        assert!(template.preopened_dirs[0].is_writable());
+        assert!(!template.preopened_dirs[1].is_writable());
+
+        // Each call works on its own copy in memory
+        let read = |state: &WasiState| {
+            let (_, fs) = &state.storage_guard.memory_mounts()[0];
+            let file = fs
+                .root()
+                .open_at("input.txt", OpenFlags::empty(), DescriptorFlags::READ)
+                .unwrap();
+            String::from_utf8(file.read(1024, 0).unwrap().0).unwrap()
+        };
+        let first = template.build().unwrap();
+        let (guest_path, fs) = &first.storage_guard.memory_mounts()[0];
+        assert_eq!(guest_path, "/fixtures");
+        assert_eq!(read(&first), "hello");
+        let file = fs
+            .root()
+            .open_at("input.txt", OpenFlags::TRUNCATE, DescriptorFlags::WRITE)
+            .unwrap();
+        file.write(b"changed", 0).unwrap();
+        assert_eq!(read(&first), "changed");
+        let second = template.build().unwrap();
+        assert_eq!(read(&second), "hello");
+
+        // A read-only grant's copy refuses changes
+        let (_, fs) = &second.storage_guard.memory_mounts()[1];
+        assert!(fs.root().create_directory_at("new").is_err());
+
+        let clashing = PolicyParser::parse_str(
+            r#"
+version: "1.0"
+permissions:
+  storage:
+    allow:
+      - uri: "mem://fixtures"
+        access: ["read"]
+        mount: "work"
+      - uri: "fs://work"
+        access: ["read"]
+"#,
+        )
+        .unwrap();
+        let result = create_wasi_state_template_from_policy(
+            &clashing,
+            temp_dir.path(),
+            &HashMap::new(),
+            None,
+        );
+        let Err(err) = result else {
+            panic!("expected the mounts to clash");
+        };
+        assert!(err.to_string().contains("both mounted at work"));
+    }
+
+    #[tokio::test]
+    async fn test_disk_quotas() {
+        let temp_dir = TempDir::new().unwrap();
+        let storage = ComponentStorage::new(temp_dir.path(), 1).await.unwrap();
+        std::fs::create_dir_all(temp_dir.path().join("work")).unwrap();
+        std::fs::write(temp_dir.path().join("work/notes.txt"), [0u8; 12]).unwrap();
+
+        let policy = PolicyParser::parse_str(
+            r#"
+version: "1.0"
+permissions:
+  storage:
+    allow:
+      - uri: "fs://work"
+        access: ["read", "write"]
+        quota:
+          bytes: "1Mi"
+          files: 100
+      - uri: "fs://docs"
+        access: ["read"]
+  directories:
+    data: true
+  resources:
+    storage:
+      files: 1000
+"#,
+        )
+        .unwrap();
+        let template =
+            create_wasi_state_template_from_policy(&policy, temp_dir.path(), &HashMap::new(), None)
+                .unwrap()
+                .with_component_dirs(&policy, &storage, "cache-tool")
+                .unwrap()
+                .with_disk_quotas(&policy, temp_dir.path())
+                .unwrap();
+
+        // Read-only grants are not counted
+        let usage: Vec<_> = template
+            .disk_quotas
+            .iter()
+            .map(|quota| quota.usage())
+            .collect();
+        assert_eq!(usage.len(), 2);
+        assert_eq!(usage[0].scope, "fs://work");
+        assert_eq!((usage[0].bytes, usage[0].files), (12, 1));
+        assert_eq!(usage[0].max_bytes, Some(1024 * 1024));
+        assert_eq!(usage[0].max_files, Some(100));
+        assert_eq!(usage[1].scope, "component");
+        assert_eq!(usage[1].paths.len(), 2);
+        assert_eq!(usage[1].max_bytes, None);
+        assert_eq!(usage[1].max_files, Some(1000));
+
+        // Without a quota the usage is still reported, but nothing is enforced
+        let unlimited = PolicyParser::parse_str(
+            r#"
+version: "1.0"
+permissions:
+  storage:
+    allow:
+      - uri: "fs://work"
+        access: ["write"]
+"#,
+        )
+        .unwrap();
+        let template = create_wasi_state_template_from_policy(
+            &unlimited,
+            temp_dir.path(),
+            &HashMap::new(),
+            None,
+        )
+        .unwrap()
+        .with_disk_quotas(&unlimited, temp_dir.path())
+        .unwrap();
+        assert_eq!(template.disk_quotas.len(), 2);
+        assert!(template.disk_quotas.iter().all(|quota| !quota.is_limited()));
+    }
+
+    #[test]
+    fn test_extract_storage_mounts() {
+        let temp_dir = TempDir::new().unwrap();
+        let plugin_dir = temp_dir.path();
+        let data_dir = TempDir::new().unwrap();
+
+        let yaml_content = format!(
+            r#"
+version: "1.0"
+permissions:
+  storage:
+    allow:
+      - uri: "fs://{}"
+        access: ["read"]
+        mount: "/data"
+      - uri: "fs://work"
+        access: ["read", "write"]
+      - uri: "fs://~/notes"
+        access: ["read"]
+"#,
+            data_dir.path().display()
+        );
+        let policy = PolicyParser::parse_str(&yaml_content).unwrap();
+
+        let preopened_dirs = extract_storage_permissions(&policy, plugin_dir).unwrap();
+        assert_eq!(preopened_dirs.len(), 3);
+        // Absolute paths are used as written and mounted where the grant says
+        assert_eq!(preopened_dirs[0].host_path, data_dir.path());
+        assert_eq!(preopened_dirs[0].guest_path, "/data");
+        assert_eq!(preopened_dirs[1].host_path, plugin_dir.join("work"));
+        assert_eq!(preopened_dirs[1].guest_path, "work");
+        let notes = etcetera::home_dir().unwrap().join("notes");
+        assert_eq!(preopened_dirs[2].host_path, notes);
+        assert_eq!(preopened_dirs[2].guest_path, notes.to_string_lossy());
+
+        let clashing = PolicyParser::parse_str(
+            r#"
+version: "1.0"
+permissions:
+  storage:
+    allow:
+      - uri: "fs://a"
+        access: ["read"]
+        mount: "/data"
+      - uri: "fs://b"
+        access: ["read"]
+        mount: "/data"
+"#,
+        )
+        .unwrap();
+        let err = extract_storage_permissions(&clashing, plugin_dir).unwrap_err();
+        assert!(err.to_string().contains("both mounted at /data"));
+    }
+
+    #[test]
+    fn test_extract_glob_storage_permissions() {
+        let temp_dir = TempDir::new().unwrap();
+        let plugin_dir = temp_dir.path();
+
+        let yaml_content = r#"
+version: "1.0"
+description: "Policy with glob storage grants"
+permissions:
+  storage:
+    allow:
+      - uri: "fs://projects/*/docs/**"
+        access: ["read"]
+      - uri: "fs://projects/*/out/*.json"
+        access: ["write"]
+      - uri: "fs://cache"
+        access: ["read", "write"]
+"#;
+        let policy = PolicyParser::parse_str(yaml_content).unwrap();
+
+        // Both globs preopen `projects`, with the permissions of both grants
+        let preopened_dirs = extract_storage_permissions(&policy, plugin_dir).unwrap();
+        assert_eq!(preopened_dirs.len(), 2);
+        assert_eq!(preopened_dirs[0].guest_path, "projects");
+        assert_eq!(preopened_dirs[0].host_path, plugin_dir.join("projects"));
+        assert_eq!(
+            preopened_dirs[0].file_perms,
+            wasmtime_wasi::FilePerms::READ | wasmtime_wasi::FilePerms::WRITE
+        );
+        assert_eq!(preopened_dirs[1].guest_path, "cache");
+
+        let allowed_paths = extract_allowed_paths(&policy, plugin_dir).unwrap();
+        assert_eq!(allowed_paths.len(), 3);
+        assert_eq!(
+            allowed_paths[0].pattern,
+            Some(vec!["*".to_string(), "docs".to_string(), "**".to_string()])
+        );
+        assert!(allowed_paths[0].read && !allowed_paths[0].write);
+        assert!(!allowed_paths[1].read && allowed_paths[1].write);
+        assert_eq!(allowed_paths[2].pattern, None);
+
+        let invalid = PolicyParser::parse_str(
+            r#"
+version: "1.0"
+permissions:
+  storage:
+    allow:
+      - uri: "fs://**/docs"
+        access: ["read"]
+"#,
+        )
+        .unwrap();
+        assert!(extract_storage_permissions(&invalid, plugin_dir).is_err());
+
+        // Policy files can't reach other components' private data either
+        let reserved = PolicyParser::parse_str(
+            r#"
+version: "1.0"
+permissions:
+  storage:
+    allow:
+      - uri: "fs://data/*/secrets/**"
+        access: ["read"]
+"#,
+        )
+        .unwrap();
+        let err = extract_storage_permissions(&reserved, plugin_dir).unwrap_err();
+        assert!(err.to_string().contains("Wassette manages"), "{err}");
+    }
+
     #[test]
     fn test_extract_network_permissions_empty_allow_list() {
         let yaml_content = r#"
@@ -562,7 +1767,7 @@ permissions:
     allow: []
 "#;
         let policy = PolicyParser::parse_str(yaml_content).unwrap();
-        let network_perms = extract_network_perms(&policy);
+        let network_perms = extract_network_perms(&policy).unwrap();
 
         assert!(!network_perms.allow_tcp);
         assert!(!network_perms.allow_udp);
@@ -682,9 +1887,10 @@ permissions:
         let template =
             create_wasi_state_template_from_policy(&policy, plugin_dir, &env_vars, None).unwrap();
 
-        assert!(template.network_perms.allow_tcp);
-        assert!(template.network_perms.allow_udp);
-        assert!(template.network_perms.allow_ip_name_lookup);
+        assert!(!template.network_perms.allow_tcp);
+        assert!(!template.network_perms.allow_udp);
+        assert!(!template.network_perms.allow_ip_name_lookup);
+        assert_eq!(template.allowed_hosts.len(), 1);
         assert_eq!(template.preopened_dirs.len(), 3);
     }
 
@@ -705,10 +1911,67 @@ permissions:
         assert!(template.preopened_dirs.is_empty());
         assert!(template.allow_stdout);
         assert!(template.allow_stderr);
-        assert!(template.allow_args);
+        assert!(!template.expose_output);
+        assert!(!template.allow_args);
+        assert!(template.args.is_empty());
         assert_eq!(template.memory_limit, None);
     }
 
+    #[test]
+    fn test_expose_output_from_policy() {
+        let temp_dir = TempDir::new().unwrap();
+        let yaml_content = r#"
+version: "1.0"
+description: "Policy exposing captured output"
+permissions:
+  process:
+    expose_output: true
+"#;
+        let policy = PolicyParser::parse_str(yaml_content).unwrap();
+        let template =
+            create_wasi_state_template_from_policy(&policy, temp_dir.path(), &HashMap::new(), None)
+                .unwrap();
+        assert!(template.expose_output);
+
+        let wasi_state = template.build().unwrap();
+        assert!(wasi_state.expose_output);
+        assert!(wasi_state.stdout.contents().is_empty());
+        assert!(wasi_state.stderr.contents().is_empty());
+    }
+
+    #[test]
+    fn test_process_args_from_policy() {
+        let temp_dir = TempDir::new().unwrap();
+        let yaml_content = r#"
+version: "1.0"
+description: "Policy with static arguments"
+permissions:
+  process:
+    args: ["tool", "--verbose"]
+"#;
+        let policy = PolicyParser::parse_str(yaml_content).unwrap();
+        let template =
+            create_wasi_state_template_from_policy(&policy, temp_dir.path(), &HashMap::new(), None)
+                .unwrap();
+        assert_eq!(template.args, vec!["tool", "--verbose"]);
+        assert!(!template.allow_args);
+        assert!(template.build().is_ok());
+
+        let yaml_content = r#"
+version: "1.0"
+description: "Policy forwarding host arguments"
+permissions:
+  process:
+    inherit_args: true
+"#;
+        let policy = PolicyParser::parse_str(yaml_content).unwrap();
+        let template =
+            create_wasi_state_template_from_policy(&policy, temp_dir.path(), &HashMap::new(), None)
+                .unwrap();
+        assert!(template.allow_args);
+        assert!(template.args.is_empty());
+    }
+
     #[test]
     fn test_extract_memory_limit() {
         // Test with k8s-style memory limit
@@ -798,6 +2061,122 @@ permissions:
         Ok(())
     }
 
+    #[test]
+    fn test_extract_cpu_limit() {
+        // Test with k8s-style millicore limit
+        let yaml_content = r#"
+version: "1.0"
+description: "Policy with cpu limit"
+permissions:
+  resources:
+    limits:
+      cpu: "500m"
+"#;
+        let policy = PolicyParser::parse_str(yaml_content).unwrap();
+        assert_eq!(extract_cpu_limit(&policy).unwrap(), Some(0.5));
+
+        // Test with legacy cpu limit
+        let yaml_content_legacy = r#"
+version: "1.0"
+description: "Policy with legacy cpu limit"
+permissions:
+  resources:
+    cpu: 2.0
+"#;
+        let policy_legacy = PolicyParser::parse_str(yaml_content_legacy).unwrap();
+        assert_eq!(extract_cpu_limit(&policy_legacy).unwrap(), Some(2.0));
+
+        // Test with no cpu limit
+        let policy_no_cpu = create_zero_permission_policy();
+        assert_eq!(extract_cpu_limit(&policy_no_cpu).unwrap(), None);
+    }
+
+    #[test]
+    fn test_cpu_limit_fuel_budget() {
+        let temp_dir = TempDir::new().unwrap();
+        let yaml_content = r#"
+version: "1.0"
+description: "Policy with cpu limit"
+permissions:
+  resources:
+    limits:
+      cpu: "250m"
+"#;
+        let policy = PolicyParser::parse_str(yaml_content).unwrap();
+        let template =
+            create_wasi_state_template_from_policy(&policy, temp_dir.path(), &HashMap::new(), None)
+                .unwrap();
+        assert_eq!(template.cpu_limit, Some(0.25));
+
+        let wasi_state = template.build().unwrap();
+        assert_eq!(wasi_state.fuel_budget(), FUEL_PER_CPU_CORE / 4);
+
+        assert_eq!(fuel_for_cpu_limit(None), u64::MAX);
+        assert_eq!(fuel_for_cpu_limit(Some(2.0)), FUEL_PER_CPU_CORE * 2);
+        assert_eq!(fuel_for_cpu_limit(Some(f64::MAX)), u64::MAX);
+    }
+
+    #[test]
+    fn test_extract_call_timeout() {
+        let temp_dir = TempDir::new().unwrap();
+        let yaml_content = r#"
+version: "1.0"
+description: "Policy with call timeout"
+permissions:
+  resources:
+    timeout: "45s"
+"#;
+        let policy = PolicyParser::parse_str(yaml_content).unwrap();
+        assert_eq!(
+            extract_call_timeout(&policy).unwrap(),
+            Some(Duration::from_secs(45))
+        );
+
+        let template =
+            create_wasi_state_template_from_policy(&policy, temp_dir.path(), &HashMap::new(), None)
+                .unwrap();
+        let wasi_state = template.build().unwrap();
+        assert_eq!(wasi_state.call_timeout, Some(Duration::from_secs(45)));
+
+        let policy_no_timeout = create_zero_permission_policy();
+        assert_eq!(extract_call_timeout(&policy_no_timeout).unwrap(), None);
+    }
+
+    #[test]
+    fn test_extract_outbound_limits() {
+        let yaml_content = r#"
+version: "1.0"
+description: "Policy with outbound limits"
+permissions:
+  resources:
+    network:
+      connect_timeout: "5s"
+      between_bytes_timeout: 10
+      max_request_body: "64Ki"
+      max_response_body: 1048576
+      max_requests: 20
+"#;
+        let policy = PolicyParser::parse_str(yaml_content).unwrap();
+        assert_eq!(
+            extract_outbound_limits(&policy).unwrap(),
+            OutboundLimits {
+                connect_timeout: Some(Duration::from_secs(5)),
+                first_byte_timeout: None,
+                between_bytes_timeout: Some(Duration::from_secs(10)),
+                max_request_body: Some(64 * 1024),
+                max_response_body: Some(1024 * 1024),
+                max_requests: Some(20),
+                rate_limits: Vec::new(),
+            }
+        );
+
+        let policy_no_limits = create_zero_permission_policy();
+        assert_eq!(
+            extract_outbound_limits(&policy_no_limits).unwrap(),
+            OutboundLimits::default()
+        );
+    }
+
     #[test]
     fn test_wasi_state_template_injects_env_vars() {
         let temp_dir = TempDir::new().unwrap();
@@ -1050,6 +2429,81 @@ permissions:
         // was properly implemented and the WASI context was created successfully.
     }
 
+    #[test]
+    fn test_separate_config_keeps_secrets_out_of_env() {
+        let temp_dir = TempDir::new().unwrap();
+        let yaml_content = r#"
+version: "1.0"
+permissions:
+  environment:
+    separate_config: true
+    allow:
+      - key: "LOG_LEVEL"
+      - key: "MISSING"
+"#;
+        let policy = PolicyParser::parse_str(yaml_content).unwrap();
+        let env_vars = HashMap::from([
+            ("LOG_LEVEL".to_string(), "debug".to_string()),
+            ("HOME".to_string(), "/home/user".to_string()),
+        ]);
+        let secrets = HashMap::from([("API_KEY".to_string(), "secret123".to_string())]);
+
+        let vars = extract_env_vars(&policy, &env_vars, Some(&secrets)).unwrap();
+        assert_eq!(
+            vars.env,
+            HashMap::from([("LOG_LEVEL".to_string(), "debug".to_string())])
+        );
+        assert_eq!(
+            vars.config,
+            HashMap::from([
+                ("LOG_LEVEL".to_string(), "debug".to_string()),
+                ("API_KEY".to_string(), "secret123".to_string()),
+            ])
+        );
+
+        let template = create_wasi_state_template_from_policy(
+            &policy,
+            temp_dir.path(),
+            &env_vars,
+            Some(&secrets),
+        )
+        .unwrap();
+        assert_eq!(template.env_vars, Some(vars.env));
+        assert_eq!(template.config_vars, vars.config);
+        assert!(template.build().is_ok());
+
+        // Without the option every variable is also set in the environment
+        let shared = PolicyParser::parse_str(yaml_content.replace("true", "false")).unwrap();
+        let vars = extract_env_vars(&shared, &env_vars, Some(&secrets)).unwrap();
+        assert_eq!(vars.env, vars.config);
+
+        // A secret with the same key as an allow entry isn't set in the environment
+        // when the host doesn't have the variable
+        let same_key = PolicyParser::parse_str(
+            r#"
+version: "1.0"
+permissions:
+  environment:
+    separate_config: true
+    allow:
+      - key: "API_KEY"
+"#,
+        )
+        .unwrap();
+        let vars = extract_env_vars(&same_key, &env_vars, Some(&secrets)).unwrap();
+        assert!(vars.env.is_empty());
+        assert_eq!(vars.config["API_KEY"], "secret123");
+
+        // With the host variable set, the environment gets the host's value
+        let host_vars = HashMap::from([("API_KEY".to_string(), "from-host".to_string())]);
+        let vars = extract_env_vars(&same_key, &host_vars, Some(&secrets)).unwrap();
+        assert_eq!(
+            vars.env,
+            HashMap::from([("API_KEY".to_string(), "from-host".to_string())])
+        );
+        assert_eq!(vars.config["API_KEY"], "from-host");
+    }
+
     proptest! {
         #[test]
         fn test_calculate_permissions_union_property(
@@ -1133,4 +2587,39 @@ permissions:
             prop_assert_eq!(dir_perms1, dir_perms2);
         }
     }
+
+    #[test]
+    fn test_injected_secrets_stay_out_of_guest_config() {
+        let yaml = r#"
+version: "1.0"
+description: "Credential injection"
+permissions:
+  network:
+    allow:
+      - host: "api.example.com"
+      - inject:
+          host: "api.example.com"
+          header: Authorization
+          secret: X_TOKEN
+          format: "Bearer {}"
+"#;
+        let policy = PolicyParser::parse_str(yaml).unwrap();
+        let secrets = HashMap::from([
+            ("X_TOKEN".to_string(), "s3cret".to_string()),
+            ("OTHER".to_string(), "visible".to_string()),
+        ]);
+        let template = create_wasi_state_template_from_policy(
+            &policy,
+            Path::new("/tmp"),
+            &HashMap::new(),
+            Some(&secrets),
+        )
+        .unwrap();
+
+        assert_eq!(template.header_injections.len(), 1);
+        assert_eq!(template.header_injections[0].secret(), "X_TOKEN");
+        assert!(!template.config_vars.contains_key("X_TOKEN"));
+        assert_eq!(template.config_vars.get("OTHER").unwrap(), "visible");
+        assert!(template.allowed_hosts.contains("api.example.com"));
+    }
 }
@@ -0,0 +1,61 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+use wasi::exports::http::incoming_handler::Guest;
+use wasi::http::types::{
+    Fields, IncomingRequest, Method, OutgoingBody, OutgoingResponse, ResponseOutparam,
+};
+
+struct Component;
+
+wasi::http::proxy::export!(Component);
+
+impl Guest for Component {
+    /// Acknowledges a webhook delivery by echoing its method, path and body size
+    fn handle(request: IncomingRequest, response_out: ResponseOutparam) {
+        let method = match request.method() {
+            Method::Get => "GET".to_string(),
+            Method::Post => "POST".to_string(),
+            Method::Put => "PUT".to_string(),
+            Method::Delete => "DELETE".to_string(),
+            Method::Patch => "PATCH".to_string(),
+            Method::Head => "HEAD".to_string(),
+            Method::Options => "OPTIONS".to_string(),
+            Method::Connect => "CONNECT".to_string(),
+            Method::Trace => "TRACE".to_string(),
+            Method::Other(other) => other,
+        };
+        let path = request.path_with_query().unwrap_or_default();
+        let received = read_body(&request);
+
+        let headers = Fields::new();
+        let _ = headers.set("content-type", &[b"application/json".to_vec()]);
+        let response = OutgoingResponse::new(headers);
+        let body = response.body().expect("response body is only taken once");
+        ResponseOutparam::set(response_out, Ok(response));
+
+        let message = format!(
+            r#"{{"received":true,"method":"{method}","path":"{}","bytes":{received}}}"#,
+            path.replace('\\', "\\\\").replace('"', "\\\"")
+        );
+        let stream = body.write().expect("body stream is only taken once");
+        let _ = stream.blocking_write_and_flush(message.as_bytes());
+        drop(stream);
+        let _ = OutgoingBody::finish(body, None);
+    }
+}
+
+/// Reads the request body and returns its length
+fn read_body(request: &IncomingRequest) -> usize {
+    let Ok(body) = request.consume() else {
+        return 0;
+    };
+    let Ok(stream) = body.stream() else {
+        return 0;
+    };
+    let mut received = 0;
+    while let Ok(chunk) = stream.blocking_read(64 * 1024) {
+        received += chunk.len();
+    }
+    received
+}
@@ -3,6 +3,7 @@
 
 //! CLI command definitions for wassette
 
+use std::num::NonZeroU64;
 use std::path::PathBuf;
 
 use clap::{Args, Parser, Subcommand};
@@ -21,6 +22,12 @@ pub struct Cli {
     #[arg(long)]
     pub plugin_dir: Option<std::path::PathBuf>,
 
+    /// Where the key that encrypts secrets comes from, as for `serve --secrets-key`.
+    /// Used by the component, policy and permission commands, which load components
+    /// and their secrets. Defaults to the configured key.
+    #[arg(long)]
+    pub secrets_key: Option<String>,
+
     #[command(subcommand)]
     pub command: Option<Commands>,
 }
@@ -49,6 +56,11 @@ pub enum Commands {
         #[command(subcommand)]
         command: SecretCommands,
     },
+    /// Inspect the audit logs of component activity.
+    Audit {
+        #[command(subcommand)]
+        command: AuditCommands,
+    },
 }
 
 #[derive(Parser, Debug, Clone, Serialize, Deserialize)]
@@ -75,6 +87,78 @@ pub struct Serve {
     #[arg(long)]
     #[serde(default)]
     pub disable_builtin_tools: bool,
+
+    /// Default timeout in seconds for each component call
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub call_timeout_secs: Option<NonZeroU64>,
+
+    /// Let components reach loopback, private and link-local addresses through host
+    /// name grants. By default these need an explicit CIDR rule in the policy.
+    #[arg(long)]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub allow_private_network: bool,
+
+    /// Forward proxy for components' HTTP requests (e.g. http://proxy.example.com:3128).
+    /// Defaults to the HTTPS_PROXY or HTTP_PROXY environment variable.
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+
+    /// Hosts components reach directly instead of through the proxy (comma-separated).
+    /// Defaults to the NO_PROXY environment variable.
+    #[arg(long, value_delimiter = ',')]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub no_proxy: Vec<String>,
+
+    /// Send components' requests for host names that don't resolve locally to the
+    /// proxy anyway. Their addresses can't be checked against denied ranges or
+    /// private network protection, so such requests are refused by default.
+    #[arg(long)]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub trust_proxy_dns: bool,
+
+    /// PEM file with CA certificates to trust for components' HTTPS requests, in
+    /// addition to the built-in roots. Can be specified multiple times.
+    #[arg(long = "ca-bundle")]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub ca_bundles: Vec<PathBuf>,
+
+    /// Serve components that export wasi:http/incoming-handler at <ROUTE>/<component-id>
+    /// (e.g. /hooks). Requires the streamable HTTP transport.
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub http_route: Option<String>,
+
+    /// Components served under --http-route (comma-separated component IDs, or * for
+    /// all components)
+    #[arg(long, value_delimiter = ',')]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub http_components: Vec<String>,
+
+    /// Where the key that encrypts secrets comes from: file:<PATH>, env:<VAR> (a
+    /// passphrase) or keyring. Defaults to the OS keyring, or a key file in the data
+    /// directory when there is no keyring.
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secrets_key: Option<String>,
+
+    /// Replace the secret values of these components with [REDACTED:KEY] in tool
+    /// results and logs (comma-separated component IDs, or * for all components)
+    #[arg(long, value_delimiter = ',')]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub redact_secrets: Vec<String>,
+
+    /// Don't record components' outbound network access in the network audit log
+    #[arg(long)]
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub disable_network_audit: bool,
+
+    /// Directory for the network audit log. Defaults to the audit directory under
+    /// the plugin directory.
+    #[arg(long)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network_audit_dir: Option<PathBuf>,
 }
 
 #[derive(Args, Debug, Clone, Serialize, Deserialize, Default)]
@@ -141,6 +225,17 @@ pub enum ComponentCommands {
         #[arg(short = 'o', long = "output-format", default_value = "json")]
         output_format: OutputFormat,
     },
+    /// Show how much a component stores in its writable directories and its quotas.
+    Usage {
+        /// Component ID to show storage usage for
+        id: String,
+        /// Directory where plugins are stored. Defaults to $XDG_DATA_HOME/wassette/components
+        #[arg(long)]
+        plugin_dir: Option<PathBuf>,
+        /// Output format
+        #[arg(short = 'o', long = "output-format", default_value = "json")]
+        output_format: OutputFormat,
+    },
 }
 
 #[derive(Subcommand, Debug)]
@@ -170,6 +265,17 @@ pub enum PermissionCommands {
         #[command(subcommand)]
         permission: RevokePermissionCommands,
     },
+    /// Deny access for a component, overriding any matching grant.
+    Deny {
+        #[command(subcommand)]
+        permission: DenyPermissionCommands,
+    },
+    /// Remove deny rules from a component.
+    #[command(name = "revoke-deny")]
+    RevokeDeny {
+        #[command(subcommand)]
+        permission: RevokeDenyPermissionCommands,
+    },
     /// Reset all permissions for a component.
     Reset {
         /// Component ID to reset permissions for
@@ -191,6 +297,9 @@ pub enum GrantPermissionCommands {
         /// Access level (read, write, or read,write)
         #[arg(long, value_delimiter = ',')]
         access: Vec<String>,
+        /// Path the component sees the directory at (e.g. /notes)
+        #[arg(long)]
+        mount: Option<String>,
         /// Directory where plugins are stored. Defaults to $XDG_DATA_HOME/wassette/components
         #[arg(long)]
         plugin_dir: Option<PathBuf>,
@@ -199,8 +308,23 @@ pub enum GrantPermissionCommands {
     Network {
         /// Component ID to grant permission to
         component_id: String,
-        /// Host to grant access to
-        host: String,
+        /// Host to grant access to, optionally with a method, port and path
+        /// (e.g. "GET https://api.github.com/repos/our-org/**")
+        #[arg(required_unless_present = "cidr", conflicts_with = "cidr")]
+        host: Option<String>,
+        /// IP address range to grant access to instead of a host (e.g. 10.0.0.0/8).
+        /// Private and loopback addresses can only be reached this way
+        #[arg(long, conflicts_with_all = ["methods", "ports", "paths"])]
+        cidr: Option<String>,
+        /// HTTP methods the host may be called with (e.g. GET,HEAD)
+        #[arg(long = "method", value_delimiter = ',')]
+        methods: Vec<String>,
+        /// Ports the host may be reached on
+        #[arg(long = "port", value_delimiter = ',')]
+        ports: Vec<u16>,
+        /// URL path prefixes or globs (e.g. /repos/our-org/**)
+        #[arg(long = "path")]
+        paths: Vec<String>,
         /// Directory where plugins are stored. Defaults to $XDG_DATA_HOME/wassette/components
         #[arg(long)]
         plugin_dir: Option<PathBuf>,
@@ -245,7 +369,11 @@ pub enum RevokePermissionCommands {
         /// Component ID to revoke permission from
         component_id: String,
         /// Host to revoke access from
-        host: String,
+        #[arg(required_unless_present = "cidr", conflicts_with = "cidr")]
+        host: Option<String>,
+        /// IP address range to revoke access from instead of a host
+        #[arg(long)]
+        cidr: Option<String>,
         /// Directory where plugins are stored. Defaults to $XDG_DATA_HOME/wassette/components
         #[arg(long)]
         plugin_dir: Option<PathBuf>,
@@ -263,6 +391,57 @@ pub enum RevokePermissionCommands {
     },
 }
 
+#[derive(Subcommand, Debug)]
+pub enum DenyPermissionCommands {
+    /// Deny storage access below a path, even inside a granted directory.
+    Storage {
+        /// Component ID to deny access for
+        component_id: String,
+        /// URI of the storage resource (e.g., fs:///path/to/directory)
+        uri: String,
+        /// Access to deny (read, write, or read,write). Defaults to both
+        #[arg(long, value_delimiter = ',')]
+        access: Vec<String>,
+        /// Directory where plugins are stored. Defaults to $XDG_DATA_HOME/wassette/components
+        #[arg(long)]
+        plugin_dir: Option<PathBuf>,
+    },
+    /// Deny network access to a host, even if a wildcard grant matches it.
+    Network {
+        /// Component ID to deny access for
+        component_id: String,
+        /// Host or host pattern to deny access to, `*` denies every host
+        host: String,
+        /// Directory where plugins are stored. Defaults to $XDG_DATA_HOME/wassette/components
+        #[arg(long)]
+        plugin_dir: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum RevokeDenyPermissionCommands {
+    /// Remove a storage deny rule from a component.
+    Storage {
+        /// Component ID to remove the deny rule from
+        component_id: String,
+        /// URI of the storage resource (e.g., fs:///path/to/directory)
+        uri: String,
+        /// Directory where plugins are stored. Defaults to $XDG_DATA_HOME/wassette/components
+        #[arg(long)]
+        plugin_dir: Option<PathBuf>,
+    },
+    /// Remove a network deny rule from a component.
+    Network {
+        /// Component ID to remove the deny rule from
+        component_id: String,
+        /// Host or host pattern the deny rule was added for
+        host: String,
+        /// Directory where plugins are stored. Defaults to $XDG_DATA_HOME/wassette/components
+        #[arg(long)]
+        plugin_dir: Option<PathBuf>,
+    },
+}
+
 #[derive(Subcommand, Debug)]
 pub enum SecretCommands {
     /// List secrets for a component.
@@ -278,6 +457,10 @@ pub enum SecretCommands {
         /// Directory where plugins are stored. Defaults to $XDG_DATA_HOME/wassette/components
         #[arg(long)]
         plugin_dir: Option<PathBuf>,
+        /// Where the key that encrypts secrets comes from, as for `serve --secrets-key`.
+        /// Defaults to the configured key.
+        #[arg(long)]
+        secrets_key: Option<String>,
         /// Output format
         #[arg(short = 'o', long = "output-format", default_value = "json")]
         output_format: OutputFormat,
@@ -292,6 +475,10 @@ pub enum SecretCommands {
         /// Directory where plugins are stored. Defaults to $XDG_DATA_HOME/wassette/components
         #[arg(long)]
         plugin_dir: Option<PathBuf>,
+        /// Where the key that encrypts secrets comes from, as for `serve --secrets-key`.
+        /// Defaults to the configured key.
+        #[arg(long)]
+        secrets_key: Option<String>,
     },
     /// Delete secrets for a component.
     Delete {
@@ -302,5 +489,34 @@ pub enum SecretCommands {
         /// Directory where plugins are stored. Defaults to $XDG_DATA_HOME/wassette/components
         #[arg(long)]
         plugin_dir: Option<PathBuf>,
+        /// Where the key that encrypts secrets comes from, as for `serve --secrets-key`.
+        /// Defaults to the configured key.
+        #[arg(long)]
+        secrets_key: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum AuditCommands {
+    /// Show the outbound HTTP requests and socket connections components attempted.
+    Network {
+        /// Only show events of this component
+        #[arg(long)]
+        component: Option<String>,
+        /// Only show events made while calling this tool
+        #[arg(long)]
+        tool: Option<String>,
+        /// Only show attempts the policy denied
+        #[arg(long)]
+        denied: bool,
+        /// Number of most recent events to show
+        #[arg(long, default_value_t = 100)]
+        limit: usize,
+        /// Directory where plugins are stored. Defaults to $XDG_DATA_HOME/wassette/components
+        #[arg(long)]
+        plugin_dir: Option<PathBuf>,
+        /// Output format
+        #[arg(short = 'o', long = "output-format", default_value = "json")]
+        output_format: OutputFormat,
     },
 }
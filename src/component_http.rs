@@ -0,0 +1,151 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Serves components that export `wasi:http/incoming-handler` as HTTP endpoints
+
+use std::sync::Arc;
+
+use anyhow::{bail, Result};
+use axum::body::Body;
+use axum::extract::{Request, State};
+use axum::http::{StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use axum::routing::any;
+use axum::Router;
+use wassette::LifecycleManager;
+
+/// Largest request body passed to a component
+const MAX_REQUEST_BODY_BYTES: usize = 10 * 1024 * 1024;
+
+/// Shared state of the component routes
+#[derive(Clone)]
+struct ComponentRoutes {
+    lifecycle_manager: LifecycleManager,
+    /// Component IDs that may be served, `*` for all of them
+    components: Arc<[String]>,
+}
+
+/// Whether `components` opts the component into being served
+fn exposes(components: &[String], component_id: &str) -> bool {
+    components.iter().any(|id| id == "*" || id == component_id)
+}
+
+/// Routes `<route>/<component-id>/...` to the incoming handler of the listed
+/// components. Other components are never reachable, even if they export a handler.
+pub fn router(
+    route: &str,
+    lifecycle_manager: LifecycleManager,
+    components: Vec<String>,
+) -> Result<Router> {
+    let route = normalize_route(route);
+    if route == "/" || route == "/mcp" {
+        bail!("The HTTP component route can't be the server root or the MCP endpoint");
+    }
+    let components = Router::new()
+        .route("/{component_id}", any(handle))
+        .route("/{component_id}/{*path}", any(handle))
+        .with_state(ComponentRoutes {
+            lifecycle_manager,
+            components: components.into(),
+        });
+    Ok(Router::new().nest(&route, components))
+}
+
+/// Makes `route` start with a single `/` and not end with one
+fn normalize_route(route: &str) -> String {
+    format!("/{}", route.trim_matches('/'))
+}
+
+/// Splits the component ID off a path below the route, leaving the URI the
+/// component sees: the rest of the path and the query
+fn component_request_uri(uri: &Uri) -> Option<(String, Uri)> {
+    let path = uri.path().strip_prefix('/')?;
+    let (component_id, rest) = match path.split_once('/') {
+        Some((component_id, rest)) => (component_id, rest),
+        None => (path, ""),
+    };
+    let path_and_query = match uri.query() {
+        Some(query) => format!("/{rest}?{query}"),
+        None => format!("/{rest}"),
+    };
+    Some((component_id.to_string(), path_and_query.parse().ok()?))
+}
+
+async fn handle(State(routes): State<ComponentRoutes>, request: Request) -> Response {
+    let Some((component_id, uri)) = component_request_uri(request.uri()) else {
+        return StatusCode::BAD_REQUEST.into_response();
+    };
+    let lifecycle_manager = &routes.lifecycle_manager;
+
+    // Components load in the background, so a request may arrive first
+    if !exposes(&routes.components, &component_id)
+        || lifecycle_manager
+            .ensure_component_loaded(&component_id)
+            .await
+            .is_err()
+        || !lifecycle_manager.serves_http(&component_id).await
+    {
+        return (
+            StatusCode::NOT_FOUND,
+            format!("No HTTP component named '{component_id}'"),
+        )
+            .into_response();
+    }
+
+    let (mut parts, body) = request.into_parts();
+    let body = match axum::body::to_bytes(body, MAX_REQUEST_BODY_BYTES).await {
+        Ok(body) => body,
+        Err(_) => return StatusCode::PAYLOAD_TOO_LARGE.into_response(),
+    };
+    parts.uri = uri;
+
+    match lifecycle_manager
+        .handle_http_request(&component_id, Request::from_parts(parts, body))
+        .await
+    {
+        Ok(response) => response.map(Body::new),
+        Err(e) => {
+            // The error can name host paths or policy details, so it stays in the log
+            tracing::error!(%component_id, error = %e, "Component failed to handle HTTP request");
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_route() {
+        assert_eq!(normalize_route("hooks"), "/hooks");
+        assert_eq!(normalize_route("/hooks/"), "/hooks");
+        assert_eq!(normalize_route("/api/hooks"), "/api/hooks");
+    }
+
+    #[test]
+    fn test_exposes() {
+        let components = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+
+        assert!(!exposes(&[], "github-webhook"));
+        assert!(!exposes(&components(&["other"]), "github-webhook"));
+        assert!(exposes(
+            &components(&["other", "github-webhook"]),
+            "github-webhook"
+        ));
+        assert!(exposes(&components(&["*"]), "github-webhook"));
+    }
+
+    #[test]
+    fn test_component_request_uri() {
+        let split = |uri: &str| component_request_uri(&uri.parse().unwrap()).unwrap();
+
+        let (id, uri) = split("/github-webhook/events/push?delivery=1");
+        assert_eq!(id, "github-webhook");
+        assert_eq!(uri, "/events/push?delivery=1");
+
+        let (id, uri) = split("/github-webhook");
+        assert_eq!(id, "github-webhook");
+        assert_eq!(uri, "/");
+    }
+}
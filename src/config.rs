@@ -1,8 +1,11 @@
 // Copyright (c) Microsoft Corporation.
 // Licensed under the MIT license.
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::num::NonZeroU64;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::Context;
 use etcetera::BaseStrategy;
@@ -46,9 +49,169 @@ pub struct Config {
     #[serde(default = "default_secrets_dir")]
     pub secrets_dir: PathBuf,
 
+    /// Where the key that encrypts secrets comes from: `file:<path>`, `env:<VAR>` for a
+    /// passphrase, or `keyring`. Defaults to the OS keyring, or to a key file outside
+    /// the secrets directory when there is no usable keyring.
+    #[serde(default)]
+    pub secrets_key: Option<String>,
+
+    /// Secret backends for individual components, by component ID. Components that
+    /// aren't listed keep their secrets in `secrets_dir`.
+    #[serde(default)]
+    pub secret_sources: HashMap<String, SecretSourceConfig>,
+
+    /// Components whose secret values are replaced with `[REDACTED:KEY]` in tool
+    /// results and logs, or `*` for all of them
+    #[serde(default)]
+    pub redact_secrets: Vec<String>,
+
     /// Environment variables to be made available to components
     #[serde(default)]
     pub environment_vars: HashMap<String, String>,
+
+    /// Default wall-clock timeout in seconds for each component call. Components can
+    /// override this in their policy `resources.timeout`.
+    #[serde(default)]
+    pub call_timeout_secs: Option<NonZeroU64>,
+
+    /// Allow components to reach loopback, private and link-local addresses through
+    /// host name grants instead of requiring an explicit CIDR rule.
+    #[serde(default)]
+    pub allow_private_network: bool,
+
+    /// Proxy and TLS settings for the HTTP requests components make
+    #[serde(flatten)]
+    pub egress: EgressConfig,
+
+    /// Route under which components exporting `wasi:http/incoming-handler` are served
+    #[serde(default)]
+    pub http_route: Option<String>,
+
+    /// Components served under `http_route`, or `*` for all of them
+    #[serde(default)]
+    pub http_components: Vec<String>,
+
+    /// Stop recording components' outbound network access in the audit log
+    #[serde(default)]
+    pub disable_network_audit: bool,
+
+    /// Directory for the network audit log. Defaults to `audit` under `plugin_dir`.
+    #[serde(default)]
+    pub network_audit_dir: Option<PathBuf>,
+}
+
+/// Where a component's secrets come from
+#[derive(Debug, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum SecretSourceConfig {
+    /// Files in the secrets directory, the default
+    File,
+    /// A command for each secret, e.g. `["pass", "show", "weather/api-key"]`, whose
+    /// first line of output is the value. The values are reused for `ttl_secs`
+    /// (5 minutes by default), and `0` runs the commands every time.
+    Exec {
+        keys: BTreeMap<String, Vec<String>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        ttl_secs: Option<u64>,
+    },
+    /// The server's environment variables starting with `prefix`, without the prefix
+    Env { prefix: String },
+}
+
+impl SecretSourceConfig {
+    /// Creates the backend, or `None` for the default file backend
+    pub fn into_provider(self) -> Result<Option<Arc<dyn wassette::SecretProvider>>, anyhow::Error> {
+        Ok(match self {
+            Self::File => None,
+            Self::Exec { keys, ttl_secs } => {
+                let mut provider = wassette::ExecSecretProvider::new(keys)?;
+                if let Some(secs) = ttl_secs {
+                    provider = provider.with_ttl(Duration::from_secs(secs));
+                }
+                Some(Arc::new(provider))
+            }
+            Self::Env { prefix } => Some(Arc::new(wassette::EnvSecretProvider::new(prefix)?)),
+        })
+    }
+}
+
+/// Proxy and TLS settings for the HTTP requests components make
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct EgressConfig {
+    /// Forward proxy, e.g. `http://proxy.example.com:3128`. Falls back to the
+    /// `HTTPS_PROXY` and `HTTP_PROXY` environment variables.
+    #[serde(default)]
+    pub proxy: Option<String>,
+
+    /// Hosts reached directly instead of through the proxy. Falls back to `NO_PROXY`.
+    #[serde(default)]
+    pub no_proxy: Vec<String>,
+
+    /// Send requests for host names that only the proxy can resolve without
+    /// checking their addresses
+    #[serde(default)]
+    pub trust_proxy_dns: bool,
+
+    /// PEM files with CA certificates to trust in addition to the built-in roots
+    #[serde(default)]
+    pub ca_bundles: Vec<PathBuf>,
+
+    /// Client certificates presented to matching hosts for mutual TLS
+    #[serde(default)]
+    pub client_certificates: Vec<ClientCertificateConfig>,
+}
+
+/// A client certificate for mutual TLS with the hosts matching `host`
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ClientCertificateConfig {
+    pub host: String,
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+impl EgressConfig {
+    /// Converts to the lifecycle manager's settings, reading the proxy from the
+    /// standard environment variables when the configuration doesn't set one
+    pub fn into_settings(self) -> wassette::EgressSettings {
+        self.into_settings_with_env(|key| std::env::var(key).ok())
+    }
+
+    fn into_settings_with_env(
+        self,
+        env: impl Fn(&str) -> Option<String>,
+    ) -> wassette::EgressSettings {
+        let env_var = |keys: &[&str]| {
+            keys.iter()
+                .find_map(|key| env(key))
+                .filter(|value| !value.is_empty())
+        };
+        let proxy = self
+            .proxy
+            .or_else(|| env_var(&["HTTPS_PROXY", "https_proxy", "HTTP_PROXY", "http_proxy"]));
+        let no_proxy = if self.no_proxy.is_empty() {
+            env_var(&["NO_PROXY", "no_proxy"])
+                .map(|value| value.split(',').map(str::to_string).collect())
+                .unwrap_or_default()
+        } else {
+            self.no_proxy
+        };
+
+        wassette::EgressSettings {
+            proxy,
+            no_proxy,
+            trust_proxy_dns: self.trust_proxy_dns,
+            ca_bundles: self.ca_bundles,
+            client_certificates: self
+                .client_certificates
+                .into_iter()
+                .map(|client| wassette::ClientCertificateSettings {
+                    host: client.host,
+                    cert: client.cert,
+                    key: client.key,
+                })
+                .collect(),
+        }
+    }
 }
 
 impl Config {
@@ -135,6 +298,18 @@ mod tests {
             env_vars: vec![],
             env_file: None,
             disable_builtin_tools: false,
+            call_timeout_secs: None,
+            allow_private_network: false,
+            proxy: None,
+            no_proxy: vec![],
+            trust_proxy_dns: false,
+            ca_bundles: vec![],
+            http_route: None,
+            http_components: vec![],
+            secrets_key: None,
+            redact_secrets: vec![],
+            disable_network_audit: false,
+            network_audit_dir: None,
         }
     }
 
@@ -145,6 +320,18 @@ mod tests {
             env_vars: vec![],
             env_file: None,
             disable_builtin_tools: false,
+            call_timeout_secs: None,
+            allow_private_network: false,
+            proxy: None,
+            no_proxy: vec![],
+            trust_proxy_dns: false,
+            ca_bundles: vec![],
+            http_route: None,
+            http_components: vec![],
+            secrets_key: None,
+            redact_secrets: vec![],
+            disable_network_audit: false,
+            network_audit_dir: None,
         }
     }
 
@@ -304,4 +491,150 @@ policy_file = "custom_policy.yaml"
 
         assert_eq!(config.plugin_dir, PathBuf::from("/custom/plugin/dir"));
     }
+
+    #[test]
+    fn test_call_timeout_from_config_file_and_cli() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_file = temp_dir.path().join("config.toml");
+
+        let toml_content = r#"
+call_timeout_secs = 60
+"#;
+        fs::write(&config_file, toml_content).unwrap();
+
+        let config = Config::new_from_path(&empty_test_cli_config(), &config_file)
+            .expect("Failed to create config");
+        assert_eq!(config.call_timeout_secs, NonZeroU64::new(60));
+
+        let mut serve_config = empty_test_cli_config();
+        serve_config.call_timeout_secs = NonZeroU64::new(5);
+        let config =
+            Config::new_from_path(&serve_config, &config_file).expect("Failed to create config");
+        assert_eq!(config.call_timeout_secs, NonZeroU64::new(5));
+
+        // A zero timeout would fail every call, like `resources.timeout: 0` in a policy
+        fs::write(&config_file, "call_timeout_secs = 0\n").unwrap();
+        assert!(Config::new_from_path(&empty_test_cli_config(), &config_file).is_err());
+    }
+
+    #[test]
+    fn test_allow_private_network_from_config_file_and_cli() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_file = temp_dir.path().join("config.toml");
+
+        let config = Config::new_from_path(&empty_test_cli_config(), &config_file)
+            .expect("Failed to create config");
+        assert!(!config.allow_private_network);
+
+        let mut serve_config = empty_test_cli_config();
+        serve_config.allow_private_network = true;
+        let config =
+            Config::new_from_path(&serve_config, &config_file).expect("Failed to create config");
+        assert!(config.allow_private_network);
+
+        // An unset CLI flag does not override the config file
+        fs::write(&config_file, "allow_private_network = true\n").unwrap();
+        let config = Config::new_from_path(&empty_test_cli_config(), &config_file)
+            .expect("Failed to create config");
+        assert!(config.allow_private_network);
+    }
+
+    #[test]
+    fn test_egress_from_config_file_cli_and_env() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_file = temp_dir.path().join("config.toml");
+        fs::write(
+            &config_file,
+            r#"
+proxy = "http://file-proxy:3128"
+trust_proxy_dns = true
+ca_bundles = ["/etc/corp/ca.pem"]
+
+[[client_certificates]]
+host = "*.internal.example.com"
+cert = "/etc/corp/client.pem"
+key = "/etc/corp/client-key.pem"
+"#,
+        )
+        .unwrap();
+
+        let mut serve_config = empty_test_cli_config();
+        serve_config.proxy = Some("http://cli-proxy:3128".to_string());
+        serve_config.no_proxy = vec!["localhost".to_string(), ".corp".to_string()];
+        let config =
+            Config::new_from_path(&serve_config, &config_file).expect("Failed to create config");
+        let settings = config.egress.into_settings_with_env(|_| None);
+        assert_eq!(settings.proxy.as_deref(), Some("http://cli-proxy:3128"));
+        assert_eq!(settings.no_proxy, vec!["localhost", ".corp"]);
+        assert!(settings.trust_proxy_dns);
+        assert_eq!(settings.ca_bundles, vec![PathBuf::from("/etc/corp/ca.pem")]);
+        assert_eq!(settings.client_certificates.len(), 1);
+        assert_eq!(
+            settings.client_certificates[0].host,
+            "*.internal.example.com"
+        );
+
+        // The standard proxy environment variables apply when nothing is configured
+        let env = |key: &str| match key {
+            "HTTPS_PROXY" => Some("http://env-proxy:8080".to_string()),
+            "no_proxy" => Some("localhost,127.0.0.1".to_string()),
+            _ => None,
+        };
+        let settings = EgressConfig::default().into_settings_with_env(env);
+        assert_eq!(settings.proxy.as_deref(), Some("http://env-proxy:8080"));
+        assert_eq!(settings.no_proxy, vec!["localhost", "127.0.0.1"]);
+    }
+
+    #[test]
+    fn test_secret_sources_from_config_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_file = temp_dir.path().join("config.toml");
+        fs::write(
+            &config_file,
+            r#"
+[secret_sources.weather]
+type = "exec"
+keys = { API_KEY = ["pass", "show", "weather/api-key"] }
+ttl_secs = 60
+
+[secret_sources.github]
+type = "env"
+prefix = "GITHUB_"
+
+[secret_sources.fetch]
+type = "file"
+"#,
+        )
+        .unwrap();
+
+        let config = Config::new_from_path(&empty_test_cli_config(), &config_file)
+            .expect("Failed to create config");
+        assert_eq!(config.secret_sources.len(), 3);
+        assert_eq!(
+            config.secret_sources["weather"],
+            SecretSourceConfig::Exec {
+                keys: BTreeMap::from([(
+                    "API_KEY".to_string(),
+                    vec![
+                        "pass".to_string(),
+                        "show".to_string(),
+                        "weather/api-key".to_string()
+                    ]
+                )]),
+                ttl_secs: Some(60),
+            }
+        );
+        assert_eq!(
+            config.secret_sources["github"],
+            SecretSourceConfig::Env {
+                prefix: "GITHUB_".to_string()
+            }
+        );
+        assert!(SecretSourceConfig::File.into_provider().unwrap().is_none());
+        assert!(SecretSourceConfig::Env {
+            prefix: String::new()
+        }
+        .into_provider()
+        .is_err());
+    }
 }
@@ -54,6 +54,40 @@ pub fn format_as_table(value: &Value) -> Result<String> {
             }
             return Ok(table);
         }
+
+        if let Some(events) = obj.get("network_events").and_then(|v| v.as_array()) {
+            let mut table = String::new();
+            table.push_str(
+                "Component            | Tool                 | Decision | Status | Target\n",
+            );
+            table.push_str(
+                "---------------------|----------------------|----------|--------|-------\n",
+            );
+
+            for event in events {
+                let field = |key: &str| {
+                    event
+                        .get(key)
+                        .map(|v| match v {
+                            Value::String(s) => s.clone(),
+                            other => other.to_string(),
+                        })
+                        .unwrap_or_else(|| "-".to_string())
+                };
+                let target = match event.get("method").and_then(|v| v.as_str()) {
+                    Some(method) => format!("{method} {}", field("target")),
+                    None => field("target"),
+                };
+                table.push_str(&format!(
+                    "{:<20} | {:<20} | {:<8} | {:<6} | {target}\n",
+                    field("component_id"),
+                    field("tool"),
+                    field("decision"),
+                    field("status"),
+                ));
+            }
+            return Ok(table);
+        }
     }
 
     // Default generic table format
@@ -0,0 +1,72 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+//! Log writer that hides component secrets in formatted log lines.
+
+use std::io::Write;
+use std::sync::Arc;
+
+use tracing_subscriber::fmt::MakeWriter;
+use wassette::SecretRedactor;
+
+/// Wraps a [`MakeWriter`] so every log line passes through a [`SecretRedactor`]
+pub struct RedactingMakeWriter<M> {
+    inner: M,
+    redactor: Arc<SecretRedactor>,
+}
+
+impl<M> RedactingMakeWriter<M> {
+    pub fn new(inner: M, redactor: Arc<SecretRedactor>) -> Self {
+        Self { inner, redactor }
+    }
+}
+
+impl<'a, M: MakeWriter<'a>> MakeWriter<'a> for RedactingMakeWriter<M> {
+    type Writer = RedactingWriter<'a, M::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        RedactingWriter {
+            inner: self.inner.make_writer(),
+            redactor: &self.redactor,
+            buf: Vec::new(),
+        }
+    }
+}
+
+/// Collects one formatted event and writes it redacted when flushed or dropped,
+/// so a secret split across several `write` calls is still found
+pub struct RedactingWriter<'a, W: Write> {
+    inner: W,
+    redactor: &'a SecretRedactor,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> RedactingWriter<'_, W> {
+    fn emit(&mut self) -> std::io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        let text = String::from_utf8_lossy(&self.buf);
+        self.inner
+            .write_all(self.redactor.redact_all(&text).as_bytes())?;
+        self.buf.clear();
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Write for RedactingWriter<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.emit()
+    }
+}
+
+impl<W: Write> Drop for RedactingWriter<'_, W> {
+    fn drop(&mut self) {
+        let _ = self.emit();
+    }
+}
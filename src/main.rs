@@ -10,6 +10,7 @@ use std::future::Future;
 use std::path::PathBuf;
 use std::pin::Pin;
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use anyhow::{bail, Context, Result};
 use clap::Parser;
@@ -33,16 +34,21 @@ use rmcp::ServerHandler;
 use serde_json::{json, Map, Value};
 use tracing_subscriber::layer::SubscriberExt as _;
 use tracing_subscriber::util::SubscriberInitExt as _;
+use wassette::{LifecycleBuilder, SecretRedactor, SecretsKey};
 
 mod commands;
+mod component_http;
 mod config;
 mod format;
+mod log_redaction;
 
 use commands::{
-    Cli, Commands, ComponentCommands, GrantPermissionCommands, PermissionCommands, PolicyCommands,
+    AuditCommands, Cli, Commands, ComponentCommands, DenyPermissionCommands,
+    GrantPermissionCommands, PermissionCommands, PolicyCommands, RevokeDenyPermissionCommands,
     RevokePermissionCommands, SecretCommands, Serve, Transport,
 };
 use format::{print_result, OutputFormat};
+use log_redaction::RedactingMakeWriter;
 
 /// Represents the different types of tools available in the MCP server
 #[derive(Debug, Clone, PartialEq)]
@@ -58,6 +64,10 @@ enum ToolName {
     RevokeStoragePermission,
     RevokeNetworkPermission,
     RevokeEnvironmentVariablePermission,
+    DenyStoragePermission,
+    DenyNetworkPermission,
+    RevokeDenyStoragePermission,
+    RevokeDenyNetworkPermission,
     ResetPermission,
 }
 
@@ -79,6 +89,10 @@ impl TryFrom<&str> for ToolName {
             "revoke-environment-variable-permission" => {
                 Ok(Self::RevokeEnvironmentVariablePermission)
             }
+            "deny-storage-permission" => Ok(Self::DenyStoragePermission),
+            "deny-network-permission" => Ok(Self::DenyNetworkPermission),
+            "revoke-deny-storage-permission" => Ok(Self::RevokeDenyStoragePermission),
+            "revoke-deny-network-permission" => Ok(Self::RevokeDenyNetworkPermission),
             "reset-permission" => Ok(Self::ResetPermission),
             _ => Err(anyhow::anyhow!("Unknown tool name: {}", value)),
         }
@@ -107,6 +121,10 @@ impl AsRef<str> for ToolName {
             Self::RevokeStoragePermission => "revoke-storage-permission",
             Self::RevokeNetworkPermission => "revoke-network-permission",
             Self::RevokeEnvironmentVariablePermission => "revoke-environment-variable-permission",
+            Self::DenyStoragePermission => "deny-storage-permission",
+            Self::DenyNetworkPermission => "deny-network-permission",
+            Self::RevokeDenyStoragePermission => "revoke-deny-storage-permission",
+            Self::RevokeDenyNetworkPermission => "revoke-deny-network-permission",
             Self::ResetPermission => "reset-permission",
         }
     }
@@ -237,6 +255,18 @@ async fn handle_tool_cli_command(
         ToolName::RevokeEnvironmentVariablePermission => {
             handle_revoke_environment_variable_permission(&req, lifecycle_manager).await?
         }
+        ToolName::DenyStoragePermission => {
+            handle_deny_storage_permission(&req, lifecycle_manager).await?
+        }
+        ToolName::DenyNetworkPermission => {
+            handle_deny_network_permission(&req, lifecycle_manager).await?
+        }
+        ToolName::RevokeDenyStoragePermission => {
+            handle_revoke_deny_storage_permission(&req, lifecycle_manager).await?
+        }
+        ToolName::RevokeDenyNetworkPermission => {
+            handle_revoke_deny_network_permission(&req, lifecycle_manager).await?
+        }
         ToolName::ResetPermission => handle_reset_permission(&req, lifecycle_manager).await?,
     };
 
@@ -256,8 +286,33 @@ async fn handle_tool_cli_command(
 /// For CLI responsiveness, we create an unloaded lifecycle manager which
 /// initializes engine/linker without compiling/scanning all components.
 /// Component metadata or lazy loads are used by individual handlers.
-async fn create_lifecycle_manager(plugin_dir: Option<PathBuf>) -> Result<LifecycleManager> {
+async fn create_lifecycle_manager(
+    plugin_dir: Option<PathBuf>,
+    secrets_key: Option<String>,
+) -> Result<LifecycleManager> {
+    let serve = crate::Serve {
+        plugin_dir: None,
+        transport: Default::default(),
+        env_vars: vec![],
+        env_file: None,
+        disable_builtin_tools: false,
+        call_timeout_secs: None,
+        allow_private_network: false,
+        proxy: None,
+        no_proxy: vec![],
+        trust_proxy_dns: false,
+        ca_bundles: vec![],
+        http_route: None,
+        http_components: vec![],
+        secrets_key,
+        redact_secrets: vec![],
+        disable_network_audit: false,
+        network_audit_dir: None,
+    };
     let config = if let Some(dir) = plugin_dir {
+        // Secrets and network audit settings still come from the config file, or
+        // what `serve` wrote couldn't be read the same way here
+        let configured = config::Config::new(&serve).context("Failed to load configuration")?;
         config::Config {
             plugin_dir: dir,
             secrets_dir: config::get_secrets_dir().unwrap_or_else(|_| {
@@ -265,33 +320,80 @@ async fn create_lifecycle_manager(plugin_dir: Option<PathBuf>) -> Result<Lifecyc
                 PathBuf::from("./secrets")
             }),
             environment_vars: std::collections::HashMap::new(),
+            call_timeout_secs: None,
+            allow_private_network: false,
+            egress: Default::default(),
+            http_route: None,
+            http_components: vec![],
+            secrets_key: configured.secrets_key,
+            secret_sources: configured.secret_sources,
+            redact_secrets: configured.redact_secrets,
+            disable_network_audit: configured.disable_network_audit,
+            network_audit_dir: configured.network_audit_dir,
         }
     } else {
-        config::Config::from_serve(&crate::Serve {
-            plugin_dir: None,
-            transport: Default::default(),
-            env_vars: vec![],
-            env_file: None,
-            disable_builtin_tools: false,
-        })
-        .context("Failed to load configuration")?
+        config::Config::from_serve(&serve).context("Failed to load configuration")?
     };
 
     // Use unloaded manager for fast CLI startup, but preserve custom secrets dir
+    let secret_redactor = Arc::new(SecretRedactor::new(config.redact_secrets.clone()));
+    lifecycle_manager_builder(config, secret_redactor)?
+        .build()
+        .await
+}
+
+/// Configure a lazily loading LifecycleManager from the resolved configuration
+///
+/// `secret_redactor` is passed in rather than built from `config.redact_secrets`
+/// so `serve` can share it with its log writer.
+fn lifecycle_manager_builder(
+    config: config::Config,
+    secret_redactor: Arc<SecretRedactor>,
+) -> Result<LifecycleBuilder> {
     let config::Config {
         plugin_dir,
         secrets_dir,
+        secrets_key,
+        secret_sources,
+        redact_secrets: _,
         environment_vars,
+        call_timeout_secs,
+        allow_private_network,
+        egress,
+        http_route: _,
+        http_components: _,
+        disable_network_audit,
+        network_audit_dir,
     } = config;
 
-    LifecycleManager::builder(plugin_dir)
+    let mut builder = LifecycleManager::builder(plugin_dir)
         .with_environment_vars(environment_vars)
         .with_secrets_dir(secrets_dir)
         .with_oci_client(oci_client::Client::default())
         .with_http_client(reqwest::Client::default())
         .with_eager_loading(false)
-        .build()
-        .await
+        .with_ssrf_protection(!allow_private_network)
+        .with_egress(egress.into_settings())
+        .with_secret_redactor(secret_redactor)
+        .with_network_audit(!disable_network_audit);
+    if let Some(dir) = network_audit_dir {
+        builder = builder.with_network_audit_dir(dir);
+    }
+    if let Some(secs) = call_timeout_secs {
+        builder = builder.with_call_timeout(Duration::from_secs(secs.get()));
+    }
+    // The server and CLI keep the key in the OS keyring unless configured otherwise
+    let secrets_key = match secrets_key {
+        Some(key) => key.parse()?,
+        None => SecretsKey::keyring_or_default_file(),
+    };
+    builder = builder.with_secrets_key(secrets_key);
+    for (component_id, source) in secret_sources {
+        if let Some(provider) = source.into_provider()? {
+            builder = builder.with_secret_provider(component_id, provider);
+        }
+    }
+    Ok(builder)
 }
 
 impl McpServer {
@@ -483,6 +585,19 @@ async fn main() -> Result<()> {
         return Ok(());
     }
 
+    if !matches!(cli.command, Some(Commands::Serve(_))) {
+        // Other commands print their results on stdout; warnings, such as a secrets key
+        // that had to be kept in a file, go to stderr
+        tracing_subscriber::fmt()
+            .with_env_filter(
+                tracing_subscriber::EnvFilter::try_from_default_env()
+                    .unwrap_or_else(|_| "warn".into()),
+            )
+            .with_writer(std::io::stderr)
+            .with_ansi(false)
+            .init();
+    }
+
     match &cli.command {
         Some(command) => match command {
             Commands::Serve(cfg) => {
@@ -496,6 +611,12 @@ async fn main() -> Result<()> {
 
                 let registry = tracing_subscriber::registry().with(env_filter);
 
+                let config =
+                    config::Config::from_serve(cfg).context("Failed to load configuration")?;
+
+                // Shared with the lifecycle manager, which tells it the secret values to hide
+                let secret_redactor = Arc::new(SecretRedactor::new(config.redact_secrets.clone()));
+
                 // Initialize logging based on transport type
                 let transport: Transport = (&cfg.transport).into();
                 match transport {
@@ -503,31 +624,26 @@ async fn main() -> Result<()> {
                         registry
                             .with(
                                 tracing_subscriber::fmt::layer()
-                                    .with_writer(std::io::stderr)
+                                    .with_writer(RedactingMakeWriter::new(
+                                        std::io::stderr,
+                                        secret_redactor.clone(),
+                                    ))
                                     .with_ansi(false),
                             )
                             .init();
                     }
-                    _ => registry.with(tracing_subscriber::fmt::layer()).init(),
+                    _ => registry
+                        .with(tracing_subscriber::fmt::layer().with_writer(
+                            RedactingMakeWriter::new(std::io::stdout, secret_redactor.clone()),
+                        ))
+                        .init(),
                 }
 
-                let config =
-                    config::Config::from_serve(cfg).context("Failed to load configuration")?;
-
                 // Build the lifecycle manager without eagerly loading components so the
                 // background loader is the single source of tool registration.
-                let config::Config {
-                    plugin_dir,
-                    secrets_dir,
-                    environment_vars,
-                } = config;
-
-                let lifecycle_manager = LifecycleManager::builder(plugin_dir)
-                    .with_environment_vars(environment_vars)
-                    .with_secrets_dir(secrets_dir)
-                    .with_oci_client(oci_client::Client::default())
-                    .with_http_client(reqwest::Client::default())
-                    .with_eager_loading(false)
+                let http_route = config.http_route.clone();
+                let http_components = config.http_components.clone();
+                let lifecycle_manager = lifecycle_manager_builder(config, secret_redactor)?
                     .build()
                     .await?;
 
@@ -557,6 +673,17 @@ async fn main() -> Result<()> {
                     }
                 });
 
+                if http_route.is_some() && !matches!(transport, Transport::StreamableHttp) {
+                    tracing::warn!(
+                        "HTTP components are only served with the streamable HTTP transport"
+                    );
+                }
+                if http_route.is_some() && http_components.is_empty() {
+                    tracing::warn!(
+                        "No components are served over HTTP, list them with http_components"
+                    );
+                }
+
                 match transport {
                     Transport::Stdio => {
                         tracing::info!("Starting MCP server with stdio transport. Components will load in the background.");
@@ -577,7 +704,15 @@ async fn main() -> Result<()> {
                             Default::default(),
                         );
 
-                        let router = axum::Router::new().nest_service("/mcp", service);
+                        let mut router = axum::Router::new().nest_service("/mcp", service);
+                        if let Some(route) = &http_route {
+                            tracing::info!(route, "Serving HTTP components");
+                            router = router.merge(component_http::router(
+                                route,
+                                lifecycle_manager.clone(),
+                                http_components,
+                            )?);
+                        }
                         let tcp_listener = tokio::net::TcpListener::bind(BIND_ADDRESS).await?;
                         let _ = axum::serve(tcp_listener, router)
                             .with_graceful_shutdown(async {
@@ -604,7 +739,8 @@ async fn main() -> Result<()> {
             Commands::Component { command } => match command {
                 ComponentCommands::Load { path, plugin_dir } => {
                     let plugin_dir = plugin_dir.clone().or_else(|| cli.plugin_dir.clone());
-                    let lifecycle_manager = create_lifecycle_manager(plugin_dir).await?;
+                    let lifecycle_manager =
+                        create_lifecycle_manager(plugin_dir, cli.secrets_key.clone()).await?;
                     let mut args = Map::new();
                     args.insert("path".to_string(), json!(path));
                     handle_tool_cli_command(
@@ -617,7 +753,8 @@ async fn main() -> Result<()> {
                 }
                 ComponentCommands::Unload { id, plugin_dir } => {
                     let plugin_dir = plugin_dir.clone().or_else(|| cli.plugin_dir.clone());
-                    let lifecycle_manager = create_lifecycle_manager(plugin_dir).await?;
+                    let lifecycle_manager =
+                        create_lifecycle_manager(plugin_dir, cli.secrets_key.clone()).await?;
                     let mut args = Map::new();
                     args.insert("id".to_string(), json!(id));
                     handle_tool_cli_command(
@@ -633,7 +770,8 @@ async fn main() -> Result<()> {
                     output_format,
                 } => {
                     let plugin_dir = plugin_dir.clone().or_else(|| cli.plugin_dir.clone());
-                    let lifecycle_manager = create_lifecycle_manager(plugin_dir).await?;
+                    let lifecycle_manager =
+                        create_lifecycle_manager(plugin_dir, cli.secrets_key.clone()).await?;
                     let args = Map::new();
                     handle_tool_cli_command(
                         &lifecycle_manager,
@@ -643,6 +781,31 @@ async fn main() -> Result<()> {
                     )
                     .await?;
                 }
+                ComponentCommands::Usage {
+                    id,
+                    plugin_dir,
+                    output_format,
+                } => {
+                    let plugin_dir = plugin_dir.clone().or_else(|| cli.plugin_dir.clone());
+                    let lifecycle_manager =
+                        create_lifecycle_manager(plugin_dir, cli.secrets_key.clone()).await?;
+                    lifecycle_manager.ensure_component_loaded(id).await?;
+                    let usage = lifecycle_manager.storage_usage(id).await?;
+
+                    print_result(
+                        &rmcp::model::CallToolResult {
+                            content: Some(vec![rmcp::model::Content::text(
+                                serde_json::to_string_pretty(&json!({
+                                    "component_id": id,
+                                    "storage_usage": usage
+                                }))?,
+                            )]),
+                            structured_content: None,
+                            is_error: None,
+                        },
+                        *output_format,
+                    )?;
+                }
             },
             Commands::Policy { command } => match command {
                 PolicyCommands::Get {
@@ -651,7 +814,8 @@ async fn main() -> Result<()> {
                     output_format,
                 } => {
                     let plugin_dir = plugin_dir.clone().or_else(|| cli.plugin_dir.clone());
-                    let lifecycle_manager = create_lifecycle_manager(plugin_dir).await?;
+                    let lifecycle_manager =
+                        create_lifecycle_manager(plugin_dir, cli.secrets_key.clone()).await?;
                     let mut args = Map::new();
                     args.insert("component_id".to_string(), json!(component_id));
                     handle_tool_cli_command(&lifecycle_manager, "get-policy", args, *output_format)
@@ -664,19 +828,22 @@ async fn main() -> Result<()> {
                         component_id,
                         uri,
                         access,
+                        mount,
                         plugin_dir,
                     } => {
                         let plugin_dir = plugin_dir.clone().or_else(|| cli.plugin_dir.clone());
-                        let lifecycle_manager = create_lifecycle_manager(plugin_dir).await?;
+                        let lifecycle_manager =
+                            create_lifecycle_manager(plugin_dir, cli.secrets_key.clone()).await?;
                         let mut args = Map::new();
                         args.insert("component_id".to_string(), json!(component_id));
-                        args.insert(
-                            "details".to_string(),
-                            json!({
-                                "uri": uri,
-                                "access": access
-                            }),
-                        );
+                        let mut details = json!({
+                            "uri": uri,
+                            "access": access
+                        });
+                        if let Some(mount) = mount {
+                            details["mount"] = json!(mount);
+                        }
+                        args.insert("details".to_string(), details);
                         handle_tool_cli_command(
                             &lifecycle_manager,
                             "grant-storage-permission",
@@ -688,18 +855,34 @@ async fn main() -> Result<()> {
                     GrantPermissionCommands::Network {
                         component_id,
                         host,
+                        cidr,
+                        methods,
+                        ports,
+                        paths,
                         plugin_dir,
                     } => {
                         let plugin_dir = plugin_dir.clone().or_else(|| cli.plugin_dir.clone());
-                        let lifecycle_manager = create_lifecycle_manager(plugin_dir).await?;
+                        let lifecycle_manager =
+                            create_lifecycle_manager(plugin_dir, cli.secrets_key.clone()).await?;
                         let mut args = Map::new();
                         args.insert("component_id".to_string(), json!(component_id));
-                        args.insert(
-                            "details".to_string(),
-                            json!({
-                                "host": host
-                            }),
-                        );
+                        let mut details = Map::new();
+                        if let Some(host) = host {
+                            details.insert("host".to_string(), json!(host));
+                        }
+                        if let Some(cidr) = cidr {
+                            details.insert("cidr".to_string(), json!(cidr));
+                        }
+                        if !methods.is_empty() {
+                            details.insert("methods".to_string(), json!(methods));
+                        }
+                        if !ports.is_empty() {
+                            details.insert("ports".to_string(), json!(ports));
+                        }
+                        if !paths.is_empty() {
+                            details.insert("paths".to_string(), json!(paths));
+                        }
+                        args.insert("details".to_string(), Value::Object(details));
                         handle_tool_cli_command(
                             &lifecycle_manager,
                             "grant-network-permission",
@@ -714,7 +897,8 @@ async fn main() -> Result<()> {
                         plugin_dir,
                     } => {
                         let plugin_dir = plugin_dir.clone().or_else(|| cli.plugin_dir.clone());
-                        let lifecycle_manager = create_lifecycle_manager(plugin_dir).await?;
+                        let lifecycle_manager =
+                            create_lifecycle_manager(plugin_dir, cli.secrets_key.clone()).await?;
                         let mut args = Map::new();
                         args.insert("component_id".to_string(), json!(component_id));
                         args.insert(
@@ -737,7 +921,8 @@ async fn main() -> Result<()> {
                         plugin_dir,
                     } => {
                         let plugin_dir = plugin_dir.clone().or_else(|| cli.plugin_dir.clone());
-                        let lifecycle_manager = create_lifecycle_manager(plugin_dir).await?;
+                        let lifecycle_manager =
+                            create_lifecycle_manager(plugin_dir, cli.secrets_key.clone()).await?;
                         let mut args = Map::new();
                         args.insert("component_id".to_string(), json!(component_id));
                         args.insert(
@@ -766,7 +951,8 @@ async fn main() -> Result<()> {
                         plugin_dir,
                     } => {
                         let plugin_dir = plugin_dir.clone().or_else(|| cli.plugin_dir.clone());
-                        let lifecycle_manager = create_lifecycle_manager(plugin_dir).await?;
+                        let lifecycle_manager =
+                            create_lifecycle_manager(plugin_dir, cli.secrets_key.clone()).await?;
                         let mut args = Map::new();
                         args.insert("component_id".to_string(), json!(component_id));
                         args.insert(
@@ -786,18 +972,19 @@ async fn main() -> Result<()> {
                     RevokePermissionCommands::Network {
                         component_id,
                         host,
+                        cidr,
                         plugin_dir,
                     } => {
                         let plugin_dir = plugin_dir.clone().or_else(|| cli.plugin_dir.clone());
-                        let lifecycle_manager = create_lifecycle_manager(plugin_dir).await?;
+                        let lifecycle_manager =
+                            create_lifecycle_manager(plugin_dir, cli.secrets_key.clone()).await?;
                         let mut args = Map::new();
                         args.insert("component_id".to_string(), json!(component_id));
-                        args.insert(
-                            "details".to_string(),
-                            json!({
-                                "host": host
-                            }),
-                        );
+                        let details = match cidr {
+                            Some(cidr) => json!({ "cidr": cidr }),
+                            None => json!({ "host": host }),
+                        };
+                        args.insert("details".to_string(), details);
                         handle_tool_cli_command(
                             &lifecycle_manager,
                             "revoke-network-permission",
@@ -812,7 +999,8 @@ async fn main() -> Result<()> {
                         plugin_dir,
                     } => {
                         let plugin_dir = plugin_dir.clone().or_else(|| cli.plugin_dir.clone());
-                        let lifecycle_manager = create_lifecycle_manager(plugin_dir).await?;
+                        let lifecycle_manager =
+                            create_lifecycle_manager(plugin_dir, cli.secrets_key.clone()).await?;
                         let mut args = Map::new();
                         args.insert("component_id".to_string(), json!(component_id));
                         args.insert(
@@ -830,12 +1018,89 @@ async fn main() -> Result<()> {
                         .await?;
                     }
                 },
+                PermissionCommands::Deny { permission } => {
+                    let (tool_name, component_id, details, plugin_dir) = match permission {
+                        DenyPermissionCommands::Storage {
+                            component_id,
+                            uri,
+                            access,
+                            plugin_dir,
+                        } => {
+                            let mut details = json!({ "uri": uri });
+                            if !access.is_empty() {
+                                details["access"] = json!(access);
+                            }
+                            ("deny-storage-permission", component_id, details, plugin_dir)
+                        }
+                        DenyPermissionCommands::Network {
+                            component_id,
+                            host,
+                            plugin_dir,
+                        } => (
+                            "deny-network-permission",
+                            component_id,
+                            json!({ "host": host }),
+                            plugin_dir,
+                        ),
+                    };
+                    let plugin_dir = plugin_dir.clone().or_else(|| cli.plugin_dir.clone());
+                    let lifecycle_manager =
+                        create_lifecycle_manager(plugin_dir, cli.secrets_key.clone()).await?;
+                    let mut args = Map::new();
+                    args.insert("component_id".to_string(), json!(component_id));
+                    args.insert("details".to_string(), details);
+                    handle_tool_cli_command(
+                        &lifecycle_manager,
+                        tool_name,
+                        args,
+                        OutputFormat::Json,
+                    )
+                    .await?;
+                }
+                PermissionCommands::RevokeDeny { permission } => {
+                    let (tool_name, component_id, details, plugin_dir) = match permission {
+                        RevokeDenyPermissionCommands::Storage {
+                            component_id,
+                            uri,
+                            plugin_dir,
+                        } => (
+                            "revoke-deny-storage-permission",
+                            component_id,
+                            json!({ "uri": uri }),
+                            plugin_dir,
+                        ),
+                        RevokeDenyPermissionCommands::Network {
+                            component_id,
+                            host,
+                            plugin_dir,
+                        } => (
+                            "revoke-deny-network-permission",
+                            component_id,
+                            json!({ "host": host }),
+                            plugin_dir,
+                        ),
+                    };
+                    let plugin_dir = plugin_dir.clone().or_else(|| cli.plugin_dir.clone());
+                    let lifecycle_manager =
+                        create_lifecycle_manager(plugin_dir, cli.secrets_key.clone()).await?;
+                    let mut args = Map::new();
+                    args.insert("component_id".to_string(), json!(component_id));
+                    args.insert("details".to_string(), details);
+                    handle_tool_cli_command(
+                        &lifecycle_manager,
+                        tool_name,
+                        args,
+                        OutputFormat::Json,
+                    )
+                    .await?;
+                }
                 PermissionCommands::Reset {
                     component_id,
                     plugin_dir,
                 } => {
                     let plugin_dir = plugin_dir.clone().or_else(|| cli.plugin_dir.clone());
-                    let lifecycle_manager = create_lifecycle_manager(plugin_dir).await?;
+                    let lifecycle_manager =
+                        create_lifecycle_manager(plugin_dir, cli.secrets_key.clone()).await?;
                     let mut args = Map::new();
                     args.insert("component_id".to_string(), json!(component_id));
                     handle_tool_cli_command(
@@ -853,9 +1118,14 @@ async fn main() -> Result<()> {
                     show_values,
                     yes,
                     plugin_dir,
+                    secrets_key,
                     output_format,
                 } => {
-                    let lifecycle_manager = create_lifecycle_manager(plugin_dir.clone()).await?;
+                    let lifecycle_manager = create_lifecycle_manager(
+                        plugin_dir.clone(),
+                        secrets_key.clone().or_else(|| cli.secrets_key.clone()),
+                    )
+                    .await?;
 
                     // Prompt for confirmation if showing values
                     if *show_values && !*yes {
@@ -908,8 +1178,13 @@ async fn main() -> Result<()> {
                     component_id,
                     secrets,
                     plugin_dir,
+                    secrets_key,
                 } => {
-                    let lifecycle_manager = create_lifecycle_manager(plugin_dir.clone()).await?;
+                    let lifecycle_manager = create_lifecycle_manager(
+                        plugin_dir.clone(),
+                        secrets_key.clone().or_else(|| cli.secrets_key.clone()),
+                    )
+                    .await?;
                     lifecycle_manager
                         .set_component_secrets(component_id, secrets)
                         .await?;
@@ -935,8 +1210,13 @@ async fn main() -> Result<()> {
                     component_id,
                     keys,
                     plugin_dir,
+                    secrets_key,
                 } => {
-                    let lifecycle_manager = create_lifecycle_manager(plugin_dir.clone()).await?;
+                    let lifecycle_manager = create_lifecycle_manager(
+                        plugin_dir.clone(),
+                        secrets_key.clone().or_else(|| cli.secrets_key.clone()),
+                    )
+                    .await?;
                     lifecycle_manager
                         .delete_component_secrets(component_id, keys)
                         .await?;
@@ -959,6 +1239,40 @@ async fn main() -> Result<()> {
                     )?;
                 }
             },
+            Commands::Audit { command } => match command {
+                AuditCommands::Network {
+                    component,
+                    tool,
+                    denied,
+                    limit,
+                    plugin_dir,
+                    output_format,
+                } => {
+                    let plugin_dir = plugin_dir.clone().or_else(|| cli.plugin_dir.clone());
+                    let lifecycle_manager =
+                        create_lifecycle_manager(plugin_dir, cli.secrets_key.clone()).await?;
+                    let events =
+                        lifecycle_manager.network_audit_events(&wassette::NetworkAuditQuery {
+                            component_id: component.clone(),
+                            tool: tool.clone(),
+                            decision: denied.then_some(wassette::AuditDecision::Deny),
+                            limit: Some(*limit),
+                        })?;
+
+                    print_result(
+                        &rmcp::model::CallToolResult {
+                            content: Some(vec![rmcp::model::Content::text(
+                                serde_json::to_string_pretty(&json!({
+                                    "network_events": events
+                                }))?,
+                            )]),
+                            structured_content: None,
+                            is_error: None,
+                        },
+                        *output_format,
+                    )?;
+                }
+            },
         },
         None => {
             eprintln!("No command provided. Use --help for usage information.");
@@ -1085,6 +1399,22 @@ mod cli_tests {
             ToolName::try_from("revoke-environment-variable-permission").unwrap(),
             ToolName::RevokeEnvironmentVariablePermission
         );
+        assert_eq!(
+            ToolName::try_from("deny-storage-permission").unwrap(),
+            ToolName::DenyStoragePermission
+        );
+        assert_eq!(
+            ToolName::try_from("deny-network-permission").unwrap(),
+            ToolName::DenyNetworkPermission
+        );
+        assert_eq!(
+            ToolName::try_from("revoke-deny-storage-permission").unwrap(),
+            ToolName::RevokeDenyStoragePermission
+        );
+        assert_eq!(
+            ToolName::try_from("revoke-deny-network-permission").unwrap(),
+            ToolName::RevokeDenyNetworkPermission
+        );
         assert_eq!(
             ToolName::try_from("reset-permission").unwrap(),
             ToolName::ResetPermission
@@ -1128,6 +1458,22 @@ mod cli_tests {
             ToolName::RevokeEnvironmentVariablePermission.as_str(),
             "revoke-environment-variable-permission"
         );
+        assert_eq!(
+            ToolName::DenyStoragePermission.as_str(),
+            "deny-storage-permission"
+        );
+        assert_eq!(
+            ToolName::DenyNetworkPermission.as_str(),
+            "deny-network-permission"
+        );
+        assert_eq!(
+            ToolName::RevokeDenyStoragePermission.as_str(),
+            "revoke-deny-storage-permission"
+        );
+        assert_eq!(
+            ToolName::RevokeDenyNetworkPermission.as_str(),
+            "revoke-deny-network-permission"
+        );
         assert_eq!(ToolName::ResetPermission.as_str(), "reset-permission");
     }
 
@@ -1145,6 +1491,10 @@ mod cli_tests {
             ToolName::RevokeStoragePermission,
             ToolName::RevokeNetworkPermission,
             ToolName::RevokeEnvironmentVariablePermission,
+            ToolName::DenyStoragePermission,
+            ToolName::DenyNetworkPermission,
+            ToolName::RevokeDenyStoragePermission,
+            ToolName::RevokeDenyNetworkPermission,
             ToolName::ResetPermission,
         ];
 
@@ -1222,6 +1572,209 @@ mod cli_tests {
         }
     }
 
+    #[test]
+    fn test_permission_grant_network_scoped_parsing() {
+        let args = vec![
+            "wassette",
+            "permission",
+            "grant",
+            "network",
+            "test-component",
+            "https://api.github.com",
+            "--method",
+            "GET,HEAD",
+            "--port",
+            "443",
+            "--path",
+            "/repos/our-org/**",
+        ];
+        let cli = Cli::try_parse_from(args).unwrap();
+
+        if let Some(Commands::Permission {
+            command:
+                PermissionCommands::Grant {
+                    permission:
+                        GrantPermissionCommands::Network {
+                            host,
+                            methods,
+                            ports,
+                            paths,
+                            ..
+                        },
+                },
+        }) = cli.command
+        {
+            assert_eq!(host.as_deref(), Some("https://api.github.com"));
+            assert_eq!(methods, vec!["GET", "HEAD"]);
+            assert_eq!(ports, vec![443]);
+            assert_eq!(paths, vec!["/repos/our-org/**"]);
+        } else {
+            panic!("Expected network grant command");
+        }
+    }
+
+    #[test]
+    fn test_permission_grant_network_cidr_parsing() {
+        let args = vec![
+            "wassette",
+            "permission",
+            "grant",
+            "network",
+            "test-component",
+            "--cidr",
+            "127.0.0.1/32",
+        ];
+        let cli = Cli::try_parse_from(args).unwrap();
+
+        if let Some(Commands::Permission {
+            command:
+                PermissionCommands::Grant {
+                    permission: GrantPermissionCommands::Network { host, cidr, .. },
+                },
+        }) = cli.command
+        {
+            assert_eq!(host, None);
+            assert_eq!(cidr.as_deref(), Some("127.0.0.1/32"));
+        } else {
+            panic!("Expected network grant command");
+        }
+
+        // A grant names either a host or a range, and scoping only applies to hosts
+        let missing = vec![
+            "wassette",
+            "permission",
+            "grant",
+            "network",
+            "test-component",
+        ];
+        assert!(Cli::try_parse_from(missing).is_err());
+        let both = vec![
+            "wassette",
+            "permission",
+            "grant",
+            "network",
+            "test-component",
+            "api.example.com",
+            "--cidr",
+            "10.0.0.0/8",
+        ];
+        assert!(Cli::try_parse_from(both).is_err());
+        let scoped = vec![
+            "wassette",
+            "permission",
+            "grant",
+            "network",
+            "test-component",
+            "--cidr",
+            "10.0.0.0/8",
+            "--port",
+            "443",
+        ];
+        assert!(Cli::try_parse_from(scoped).is_err());
+    }
+
+    #[test]
+    fn test_permission_deny_storage_parsing() {
+        let args = vec![
+            "wassette",
+            "permission",
+            "deny",
+            "storage",
+            "test-component",
+            "fs://work/secrets",
+        ];
+        let cli = Cli::try_parse_from(args).unwrap();
+
+        if let Some(Commands::Permission {
+            command:
+                PermissionCommands::Deny {
+                    permission:
+                        DenyPermissionCommands::Storage {
+                            component_id,
+                            uri,
+                            access,
+                            ..
+                        },
+                },
+        }) = cli.command
+        {
+            assert_eq!(component_id, "test-component");
+            assert_eq!(uri, "fs://work/secrets");
+            assert!(access.is_empty());
+        } else {
+            panic!("Expected storage deny command");
+        }
+
+        let args = vec![
+            "wassette",
+            "permission",
+            "revoke-deny",
+            "network",
+            "test-component",
+            "metadata.internal",
+        ];
+        let cli = Cli::try_parse_from(args).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Permission {
+                command: PermissionCommands::RevokeDeny {
+                    permission: RevokeDenyPermissionCommands::Network { .. }
+                }
+            })
+        ));
+    }
+
+    #[test]
+    fn test_secret_set_secrets_key_parsing() {
+        let args = vec![
+            "wassette",
+            "secret",
+            "set",
+            "test-component",
+            "API_KEY=value",
+            "--plugin-dir",
+            "/custom/components",
+            "--secrets-key",
+            "keyring",
+        ];
+        let cli = Cli::try_parse_from(args).unwrap();
+
+        if let Some(Commands::Secret {
+            command:
+                SecretCommands::Set {
+                    plugin_dir,
+                    secrets_key,
+                    ..
+                },
+        }) = cli.command
+        {
+            assert_eq!(plugin_dir, Some(PathBuf::from("/custom/components")));
+            assert_eq!(secrets_key.as_deref(), Some("keyring"));
+        } else {
+            panic!("Expected secret set command");
+        }
+    }
+
+    #[test]
+    fn test_component_command_secrets_key_parsing() {
+        let args = vec![
+            "wassette",
+            "--secrets-key",
+            "env:WASSETTE_PASSPHRASE",
+            "component",
+            "load",
+            "file:///tmp/tool.wasm",
+        ];
+        let cli = Cli::try_parse_from(args).unwrap();
+        assert_eq!(cli.secrets_key.as_deref(), Some("env:WASSETTE_PASSPHRASE"));
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Component {
+                command: ComponentCommands::Load { .. }
+            })
+        ));
+    }
+
     #[test]
     fn test_permission_revoke_network_parsing() {
         let args = vec![
@@ -1245,9 +1798,32 @@ mod cli_tests {
         }) = cli.command
         {
             assert_eq!(component_id, "test-component");
-            assert_eq!(host, "example.com");
+            assert_eq!(host.as_deref(), Some("example.com"));
         } else {
             panic!("Expected network revoke command");
         }
+
+        let args = vec![
+            "wassette",
+            "permission",
+            "revoke",
+            "network",
+            "test-component",
+            "--cidr",
+            "127.0.0.1/32",
+        ];
+        let cli = Cli::try_parse_from(args).unwrap();
+        assert!(matches!(
+            cli.command,
+            Some(Commands::Permission {
+                command: PermissionCommands::Revoke {
+                    permission: RevokePermissionCommands::Network {
+                        host: None,
+                        cidr: Some(_),
+                        ..
+                    }
+                }
+            })
+        ));
     }
 }
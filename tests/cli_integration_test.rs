@@ -78,6 +78,14 @@ impl CliTestContext {
     /// Execute a wassette CLI command
     async fn run_command(&self, args: &[&str]) -> Result<(String, String, i32)> {
         let mut cmd = AsyncCommand::new(&self.wassette_bin);
+        // Keep secrets, their key and config files in the temp directory instead of
+        // the user's own directories and OS keyring
+        cmd.arg("--secrets-key").arg(format!(
+            "file:{}",
+            self.temp_dir.path().join("secrets.key").display()
+        ));
+        cmd.env("XDG_CONFIG_HOME", self.temp_dir.path().join("config"));
+        cmd.env("XDG_DATA_HOME", self.temp_dir.path().join("data"));
         cmd.args(args);
         cmd.arg("--plugin-dir").arg(&self.plugin_dir);
 
@@ -8,6 +8,7 @@ use anyhow::{Context, Result};
 
 static FETCH_COMPONENT_BUILD: Once = Once::new();
 static FILESYSTEM_COMPONENT_BUILD: Once = Once::new();
+static WEBHOOK_COMPONENT_BUILD: Once = Once::new();
 
 /// Ensure fetch-rs component is built exactly once for all tests
 fn ensure_fetch_component_built() -> Result<()> {
@@ -106,3 +107,52 @@ pub async fn build_filesystem_component() -> Result<PathBuf> {
 
     Ok(component_path)
 }
+
+/// Ensure webhook-rs component is built exactly once for all tests
+fn ensure_webhook_component_built() -> Result<()> {
+    WEBHOOK_COMPONENT_BUILD.call_once(|| {
+        let result = std::panic::catch_unwind(|| {
+            let top_level = PathBuf::from(
+                std::env::var("CARGO_MANIFEST_DIR").expect("CARGO_MANIFEST_DIR not set"),
+            );
+
+            // Use std::process::Command instead of tokio::process::Command to avoid runtime issues
+            let status = std::process::Command::new("cargo")
+                .current_dir(top_level.join("examples/webhook-rs"))
+                .args(["build", "--release", "--target", "wasm32-wasip2"])
+                .status()
+                .expect("Failed to execute cargo component build");
+
+            if !status.success() {
+                panic!("Failed to compile webhook-rs component");
+            }
+        });
+
+        if result.is_err() {
+            panic!("Failed to build webhook-rs component in Once block");
+        }
+    });
+
+    Ok(())
+}
+
+#[allow(dead_code)]
+pub async fn build_webhook_component() -> Result<PathBuf> {
+    let top_level =
+        PathBuf::from(std::env::var("CARGO_MANIFEST_DIR").context("CARGO_MANIFEST_DIR not set")?);
+
+    let component_path =
+        top_level.join("examples/webhook-rs/target/wasm32-wasip2/release/webhook_rs.wasm");
+
+    // Ensure component is built exactly once across all tests
+    ensure_webhook_component_built()?;
+
+    if !component_path.exists() {
+        anyhow::bail!(
+            "Component file not found after build: {}",
+            component_path.display()
+        );
+    }
+
+    Ok(component_path)
+}
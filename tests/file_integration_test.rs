@@ -458,3 +458,75 @@ async fn test_filesystem_component_lifecycle_manager() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+#[test(tokio::test)]
+async fn test_filesystem_component_storage_deny() -> Result<()> {
+    let (manager, _tempdir) = setup_lifecycle_manager().await?;
+    let component_path = build_filesystem_component().await?;
+    let id = manager
+        .load_component(&format!("file://{}", component_path.to_str().unwrap()))
+        .await?
+        .component_id;
+
+    let data_dir = tempfile::tempdir()?;
+    let data_path = data_dir.path().canonicalize()?;
+    tokio::fs::write(data_path.join("public.txt"), "public contents").await?;
+    tokio::fs::create_dir(data_path.join("secret")).await?;
+    tokio::fs::write(data_path.join("secret/key.txt"), "secret contents").await?;
+
+    manager
+        .grant_permission(
+            &id,
+            "storage",
+            &serde_json::json!({"uri": format!("fs://{}", data_path.display()), "access": ["read"]}),
+        )
+        .await?;
+    manager
+        .deny_permission(
+            &id,
+            "storage",
+            &serde_json::json!({"uri": format!("fs://{}/secret", data_path.display())}),
+        )
+        .await?;
+
+    let public = manager
+        .execute_component_call(
+            &id,
+            "read-file",
+            &serde_json::json!({"path": data_path.join("public.txt")}).to_string(),
+        )
+        .await?;
+    assert!(public.contains("public contents"), "{public}");
+
+    let secret = manager
+        .execute_component_call(
+            &id,
+            "read-file",
+            &serde_json::json!({"path": data_path.join("secret/key.txt")}).to_string(),
+        )
+        .await;
+    let secret_output = format!("{secret:?}");
+    assert!(
+        !secret_output.contains("secret contents"),
+        "denied file was readable: {secret_output}"
+    );
+
+    manager
+        .revoke_deny_permission(
+            &id,
+            "storage",
+            &serde_json::json!({"uri": format!("fs://{}/secret", data_path.display())}),
+        )
+        .await?;
+    let secret = manager
+        .execute_component_call(
+            &id,
+            "read-file",
+            &serde_json::json!({"path": data_path.join("secret/key.txt")}).to_string(),
+        )
+        .await?;
+    assert!(secret.contains("secret contents"), "{secret}");
+
+    Ok(())
+}
@@ -0,0 +1,69 @@
+// Copyright (c) Microsoft Corporation.
+// Licensed under the MIT license.
+
+use anyhow::{Context, Result};
+use http_body_util::BodyExt;
+use hyper::body::Bytes;
+use tempfile::TempDir;
+use wassette::LifecycleManager;
+
+mod common;
+use common::{build_fetch_component, build_webhook_component};
+
+async fn setup_lifecycle_manager() -> Result<(LifecycleManager, TempDir)> {
+    let tempdir = tempfile::tempdir().context("Failed to create temporary directory")?;
+    let manager = LifecycleManager::new(&tempdir).await?;
+    Ok((manager, tempdir))
+}
+
+#[tokio::test]
+async fn test_http_component_handles_requests() -> Result<()> {
+    let (manager, _tempdir) = setup_lifecycle_manager().await?;
+    let component_path = build_webhook_component().await?;
+
+    let component_id = manager
+        .load_component(&format!("file://{}", component_path.to_str().unwrap()))
+        .await?
+        .component_id;
+
+    assert!(manager.serves_http(&component_id).await);
+
+    // The incoming handler is not listed as a tool
+    let tools = manager.list_tools().await;
+    assert!(tools.is_empty(), "Unexpected tools: {tools:?}");
+
+    let request = hyper::Request::post("http://localhost/github?delivery=1")
+        .body(Bytes::from_static(b"{\"action\":\"opened\"}"))?;
+    let response = manager.handle_http_request(&component_id, request).await?;
+    assert_eq!(response.status(), hyper::StatusCode::OK);
+
+    let body = response.into_body().collect().await?.to_bytes();
+    let body: serde_json::Value = serde_json::from_slice(&body)?;
+    assert_eq!(body["method"], "POST");
+    assert_eq!(body["path"], "/github?delivery=1");
+    assert_eq!(body["bytes"], 19);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_tool_component_does_not_serve_http() -> Result<()> {
+    let (manager, _tempdir) = setup_lifecycle_manager().await?;
+    let component_path = build_fetch_component().await?;
+
+    let component_id = manager
+        .load_component(&format!("file://{}", component_path.to_str().unwrap()))
+        .await?
+        .component_id;
+
+    assert!(!manager.serves_http(&component_id).await);
+
+    let request = hyper::Request::get("http://localhost/").body(Bytes::new())?;
+    let err = manager
+        .handle_http_request(&component_id, request)
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("wasi:http/incoming-handler"));
+
+    Ok(())
+}
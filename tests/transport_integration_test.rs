@@ -137,12 +137,13 @@ async fn test_fetch_component_workflow() -> Result<()> {
         .iter()
         .any(|t| t["name"] == "fetch"));
 
-    // Grant permission for the local mock server (use IP address or localhost)
+    // Grant permission for the local mock server. Loopback addresses need a CIDR
+    // rule, since a host grant alone is blocked by SSRF protection.
     let grant_result = manager
         .grant_permission(
             &id,
             "network",
-            &serde_json::json!({"host": addr.ip().to_string()}),
+            &serde_json::json!({"cidr": format!("{}/32", addr.ip())}),
         )
         .await;
     assert!(grant_result.is_ok(), "Failed to grant network permission");
@@ -1199,9 +1200,8 @@ async fn test_disable_builtin_tools() -> Result<()> {
     assert_eq!(call_response["id"], 3);
     assert!(call_response["result"].is_object());
     let result = &call_response["result"];
-    assert_eq!(
+    assert!(
         result["isError"].as_bool().unwrap_or(false),
-        true,
         "Tool call should have failed"
     );
 